@@ -0,0 +1,267 @@
+//! A minimal, allocation-free reader for 32-bit little-endian RISC-V ELF
+//! files.  Only the parts needed to load a program are parsed.
+
+use core::convert::TryInto;
+use core::fmt;
+use minielf::{SectionFlags, RELA_ENTRY_SIZE, SHT_NOBITS, SHT_RELA};
+
+const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+const ELFCLASS32: u8 = 1;
+const ELFDATA2LSB: u8 = 1;
+const ET_EXEC: u16 = 2;
+const ET_DYN: u16 = 3;
+const EM_RISCV: u16 = 0xf3;
+
+const ELF_HEADER_SIZE: usize = 52;
+const SECTION_HEADER_SIZE: usize = 40;
+
+#[derive(Debug, PartialEq)]
+pub enum ElfError {
+    /// The file ended before a header or section did
+    Truncated,
+
+    /// The file doesn't start with the ELF magic number
+    NotElf,
+
+    /// The file isn't a 32-bit little-endian RISC-V ELF
+    WrongArchitecture,
+
+    /// The file isn't an executable
+    NotExecutable,
+
+    /// A relocation wasn't `R_RISCV_RELATIVE`
+    UnsupportedRelocation(u8),
+}
+
+impl fmt::Display for ElfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ElfError::Truncated => write!(f, "elf file is truncated"),
+            ElfError::NotElf => write!(f, "not an elf file"),
+            ElfError::WrongArchitecture => write!(f, "elf file is not 32-bit little-endian RISC-V"),
+            ElfError::NotExecutable => write!(f, "elf file is not an executable"),
+            ElfError::UnsupportedRelocation(kind) => {
+                write!(f, "elf file has unsupported relocation type {}", kind)
+            }
+        }
+    }
+}
+
+/// A section that occupies memory when the program runs.
+#[derive(Debug, PartialEq)]
+pub struct Section<'a> {
+    /// Virtual address of the section
+    pub virt: u32,
+
+    /// Size of the section in memory
+    pub size: u32,
+
+    pub flags: SectionFlags,
+
+    /// Contents of the section.  This is empty for `NOCOPY` sections.
+    pub data: &'a [u8],
+}
+
+impl<'a> Section<'a> {
+    /// The virtual address just past the end of this section
+    pub fn end(&self) -> usize {
+        (self.virt as usize).saturating_add(self.size as usize)
+    }
+}
+
+/// A word that must have the load offset added to it.
+#[derive(Debug, PartialEq)]
+pub struct Relocation {
+    /// Link-time address of the word
+    pub offset: u32,
+
+    /// Link-time value of the word
+    pub addend: u32,
+}
+
+pub struct ElfFile<'a> {
+    data: &'a [u8],
+
+    /// Virtual address of the entrypoint
+    pub entry_point: u32,
+
+    /// `true` if this is a position-independent executable, which may be
+    /// loaded anywhere as long as its relocations are applied
+    pub relocatable: bool,
+
+    section_offset: usize,
+    section_count: usize,
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, ElfError> {
+    let bytes = data.get(offset..offset + 2).ok_or(ElfError::Truncated)?;
+    Ok(u16::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, ElfError> {
+    let bytes = data.get(offset..offset + 4).ok_or(ElfError::Truncated)?;
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+impl<'a> ElfFile<'a> {
+    pub fn new(data: &'a [u8]) -> Result<ElfFile<'a>, ElfError> {
+        if data.len() < ELF_HEADER_SIZE {
+            return Err(ElfError::Truncated);
+        }
+        if data[0..4] != ELF_MAGIC {
+            return Err(ElfError::NotElf);
+        }
+        if data[4] != ELFCLASS32 || data[5] != ELFDATA2LSB || read_u16(data, 18)? != EM_RISCV {
+            return Err(ElfError::WrongArchitecture);
+        }
+        let kind = read_u16(data, 16)?;
+        if kind != ET_EXEC && kind != ET_DYN {
+            return Err(ElfError::NotExecutable);
+        }
+        if read_u16(data, 46)? as usize != SECTION_HEADER_SIZE {
+            return Err(ElfError::Truncated);
+        }
+
+        Ok(ElfFile {
+            data,
+            entry_point: read_u32(data, 24)?,
+            relocatable: kind == ET_DYN,
+            section_offset: read_u32(data, 32)? as usize,
+            section_count: read_u16(data, 48)? as usize,
+        })
+    }
+
+    /// Iterate over every section that gets loaded into memory.  As with
+    /// `create-image`, sections without an address are skipped.
+    pub fn sections(&self) -> impl Iterator<Item = Result<Section<'a>, ElfError>> + '_ {
+        (0..self.section_count).filter_map(move |index| {
+            self.section(self.section_offset + index * SECTION_HEADER_SIZE)
+                .transpose()
+        })
+    }
+
+    fn section(&self, header: usize) -> Result<Option<Section<'a>>, ElfError> {
+        let data = self.data;
+        let kind = read_u32(data, header + 4)?;
+        let elf_flags = read_u32(data, header + 8)?;
+        let virt = read_u32(data, header + 12)?;
+        let offset = read_u32(data, header + 16)? as usize;
+        let size = read_u32(data, header + 20)?;
+
+        let flags = match SectionFlags::from_section(virt, elf_flags, kind == SHT_NOBITS) {
+            Some(flags) => flags,
+            None => return Ok(None),
+        };
+        let contents = if flags.contains(SectionFlags::NOCOPY) {
+            &[]
+        } else {
+            offset
+                .checked_add(size as usize)
+                .and_then(|end| data.get(offset..end))
+                .ok_or(ElfError::Truncated)?
+        };
+
+        Ok(Some(Section {
+            virt,
+            size,
+            flags,
+            data: contents,
+        }))
+    }
+
+    /// Iterate over every relocation in the file.  Only `R_RISCV_RELATIVE`
+    /// relocations are supported, which is all a static PIE needs.
+    pub fn relocations<'b>(&'b self) -> Relocations<'a, 'b> {
+        Relocations {
+            elf: self,
+            section: 0,
+            next: 0,
+            end: 0,
+        }
+    }
+
+    /// Return the file offsets of the entries of a `SHT_RELA` section.
+    fn rela_entries(&self, header: usize) -> Result<Option<(usize, usize)>, ElfError> {
+        if read_u32(self.data, header + 4)? != SHT_RELA {
+            return Ok(None);
+        }
+        let offset = read_u32(self.data, header + 16)? as usize;
+        let size = read_u32(self.data, header + 20)? as usize;
+        let end = offset
+            .checked_add(size - size % RELA_ENTRY_SIZE)
+            .filter(|end| *end <= self.data.len())
+            .ok_or(ElfError::Truncated)?;
+        Ok(Some((offset, end)))
+    }
+
+    fn relocation(&self, entry: usize) -> Result<Option<Relocation>, ElfError> {
+        let bytes = self
+            .data
+            .get(entry..entry + RELA_ENTRY_SIZE)
+            .ok_or(ElfError::Truncated)?;
+        let (offset, kind, addend) = minielf::read_rela(bytes.try_into().unwrap());
+        match minielf::is_relative(kind) {
+            Ok(false) => Ok(None),
+            Ok(true) => Ok(Some(Relocation { offset, addend })),
+            Err(other) => Err(ElfError::UnsupportedRelocation(other)),
+        }
+    }
+}
+
+/// An iterator over the relocations in an ELF file.
+pub struct Relocations<'a, 'b> {
+    elf: &'b ElfFile<'a>,
+
+    /// Index of the next section header to examine
+    section: usize,
+
+    /// File offsets of the remaining entries in the current section
+    next: usize,
+    end: usize,
+}
+
+impl<'a, 'b> Iterator for Relocations<'a, 'b> {
+    type Item = Result<Relocation, ElfError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.next < self.end {
+                let entry = self.next;
+                self.next += RELA_ENTRY_SIZE;
+                match self.elf.relocation(entry) {
+                    Ok(None) => continue,
+                    Ok(Some(relocation)) => return Some(Ok(relocation)),
+                    Err(e) => {
+                        self.stop();
+                        return Some(Err(e));
+                    }
+                }
+            }
+
+            if self.section >= self.elf.section_count {
+                return None;
+            }
+            let header = self.elf.section_offset + self.section * SECTION_HEADER_SIZE;
+            self.section += 1;
+            match self.elf.rela_entries(header) {
+                Ok(Some((next, end))) => {
+                    self.next = next;
+                    self.end = end;
+                }
+                Ok(None) => (),
+                Err(e) => {
+                    self.stop();
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+impl<'a, 'b> Relocations<'a, 'b> {
+    /// End the iteration early, after an error.
+    fn stop(&mut self) {
+        self.section = self.elf.section_count;
+        self.next = self.end;
+    }
+}
@@ -0,0 +1,479 @@
+//! Start new processes from ELF files at runtime.
+//!
+//! On hardware, the only processes that exist at boot are the ones the
+//! loader placed from `IniE` tags.  This crate lets a running process, such
+//! as a shell or an installer, launch programs stored elsewhere.  Sections
+//! are read the same way `create-image` reads them: everything read-only is
+//! gathered into the text region, and everything writable into the data
+//! region.  Each region is copied into freshly-mapped pages, which are then
+//! moved into the new process by `CreateProcess`.
+//!
+//! Position-independent executables are loaded `PIE_LOAD_OFFSET` bytes
+//! above the addresses they were linked at, and their relocations are
+//! applied while the regions are being copied.
+#![cfg_attr(target_os = "none", no_std)]
+
+mod elf;
+pub use elf::{ElfError, ElfFile, Relocation, Relocations, Section};
+pub use minielf::{SectionFlags, PIE_LOAD_OFFSET};
+
+use core::fmt;
+use core::ops::Range;
+
+const PAGE_SIZE: usize = 4096;
+
+/// Programs must lie below this address, as the rest belongs to the kernel.
+const USER_AREA_END: usize = 0xff00_0000;
+
+#[derive(Debug, PartialEq)]
+pub enum LoadError {
+    /// The ELF file couldn't be read
+    Elf(ElfError),
+
+    /// The program has no read-only sections
+    NoText,
+
+    /// A section lies outside of userspace
+    BadAddress,
+
+    /// A relocation doesn't point inside the program
+    BadRelocation,
+
+    /// The text and data regions share a page, so they can't be given
+    /// different permissions
+    Overlap,
+
+    /// A syscall failed
+    Xous(xous::Error),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Elf(e) => write!(f, "{}", e),
+            LoadError::NoText => write!(f, "program has no text sections"),
+            LoadError::BadAddress => write!(f, "program has a section outside of userspace"),
+            LoadError::BadRelocation => write!(f, "program has a relocation outside of itself"),
+            LoadError::Overlap => write!(f, "text and data sections share a page"),
+            LoadError::Xous(e) => write!(f, "couldn't create process: {:?}", e),
+        }
+    }
+}
+
+impl From<ElfError> for LoadError {
+    fn from(e: ElfError) -> Self {
+        LoadError::Elf(e)
+    }
+}
+
+impl From<xous::Error> for LoadError {
+    fn from(e: xous::Error) -> Self {
+        LoadError::Xous(e)
+    }
+}
+
+/// The pages a program occupies once loaded.
+#[derive(Debug, PartialEq)]
+pub struct ProgramLayout {
+    /// Virtual address of the entrypoint
+    pub entry_point: usize,
+
+    /// Page-aligned virtual addresses of the read-only sections
+    pub text: Range<usize>,
+
+    /// Page-aligned virtual addresses of the writable sections, if any
+    pub data: Option<Range<usize>>,
+}
+
+/// Expand `region` so that it covers `start..end`, rounding out to whole
+/// pages.
+fn cover(region: &mut Option<Range<usize>>, start: usize, end: usize) {
+    let start = start & !(PAGE_SIZE - 1);
+    let end = (end + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+    *region = match region.take() {
+        Some(range) => Some(range.start.min(start)..range.end.max(end)),
+        None => Some(start..end),
+    };
+}
+
+/// Where a program should be loaded, relative to the addresses it was
+/// linked at.
+pub fn load_offset(elf: &ElfFile) -> usize {
+    if elf.relocatable {
+        PIE_LOAD_OFFSET
+    } else {
+        0
+    }
+}
+
+impl ProgramLayout {
+    /// Work out which pages the program occupies when it is loaded
+    /// `load_offset` bytes above the addresses it was linked at.
+    pub fn new(elf: &ElfFile, load_offset: usize) -> Result<ProgramLayout, LoadError> {
+        let mut text = None;
+        let mut data = None;
+        for section in elf.sections() {
+            let section = section?;
+            if section.size == 0 {
+                continue;
+            }
+            let end = section
+                .end()
+                .checked_add(load_offset)
+                .filter(|end| *end <= USER_AREA_END)
+                .ok_or(LoadError::BadAddress)?;
+            let start = section.virt as usize + load_offset;
+            if section.flags.contains(SectionFlags::WRITE) {
+                cover(&mut data, start, end);
+            } else {
+                cover(&mut text, start, end);
+            }
+        }
+
+        let text = text.ok_or(LoadError::NoText)?;
+        if let Some(data) = &data {
+            if data.start < text.end && text.start < data.end {
+                return Err(LoadError::Overlap);
+            }
+        }
+
+        let contains = |region: &Range<usize>, word: usize| {
+            word >= region.start && word.saturating_add(4) <= region.end
+        };
+        for relocation in elf.relocations() {
+            let word = (relocation?.offset as usize).saturating_add(load_offset);
+            let in_data = match &data {
+                Some(data) => contains(data, word),
+                None => false,
+            };
+            if !contains(&text, word) && !in_data {
+                return Err(LoadError::BadRelocation);
+            }
+        }
+
+        Ok(ProgramLayout {
+            entry_point: elf.entry_point as usize + load_offset,
+            text,
+            data,
+        })
+    }
+}
+
+/// Copy every section that lies in `region` into `dest`, which will be
+/// mapped at the start of `region`, and apply any relocations that fall
+/// within it.  `dest` must already be zeroed.
+pub fn copy_sections(
+    elf: &ElfFile,
+    load_offset: usize,
+    region: &Range<usize>,
+    dest: &mut [u8],
+) -> Result<(), LoadError> {
+    for section in elf.sections() {
+        let section = section?;
+        let start = (section.virt as usize).saturating_add(load_offset);
+        if start < region.start || section.end().saturating_add(load_offset) > region.end {
+            continue;
+        }
+        let offset = start - region.start;
+        dest[offset..offset + section.data.len()].copy_from_slice(section.data);
+    }
+
+    for relocation in elf.relocations() {
+        let relocation = relocation?;
+        let word = (relocation.offset as usize).saturating_add(load_offset);
+        if word < region.start || word.saturating_add(4) > region.end {
+            continue;
+        }
+        let offset = word - region.start;
+        let value = minielf::relocate(relocation.addend, load_offset);
+        dest[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+    Ok(())
+}
+
+/// Map zeroed pages in this process covering `region`, and fill them with
+/// the program's sections.
+#[cfg(target_os = "none")]
+fn stage(
+    elf: &ElfFile,
+    load_offset: usize,
+    region: &Range<usize>,
+) -> Result<xous::MemoryRange, LoadError> {
+    let staging = xous::map_memory(
+        None,
+        None,
+        region.end - region.start,
+        xous::MemoryFlags::R | xous::MemoryFlags::W,
+    )?;
+    let dest = unsafe { core::slice::from_raw_parts_mut(staging.as_mut_ptr(), staging.len()) };
+    if let Err(e) = copy_sections(elf, load_offset, region, dest) {
+        xous::unmap_memory(staging).ok();
+        return Err(e);
+    }
+    Ok(staging)
+}
+
+/// Start a new process running the ELF file in `elf`, returning its PID.
+#[cfg(target_os = "none")]
+pub fn load(elf: &[u8]) -> Result<xous::PID, LoadError> {
+    let elf = ElfFile::new(elf)?;
+    let load_offset = load_offset(&elf);
+    let layout = ProgramLayout::new(&elf, load_offset)?;
+
+    let text = stage(&elf, load_offset, &layout.text)?;
+    let data = match &layout.data {
+        Some(region) => match stage(&elf, load_offset, region) {
+            Ok(data) => Some(data),
+            Err(e) => {
+                xous::unmap_memory(text).ok();
+                return Err(e);
+            }
+        },
+        None => None,
+    };
+
+    let text_destination = xous::MemoryAddress::new(layout.text.start);
+    let start = xous::MemoryAddress::new(layout.entry_point);
+    let result = match (text_destination, start) {
+        (Some(text_destination), Some(start)) => xous::create_process_raw(xous::ProcessInit {
+            text,
+            text_destination,
+            data,
+            data_destination: layout
+                .data
+                .as_ref()
+                .and_then(|region| xous::MemoryAddress::new(region.start)),
+            start,
+        }),
+        _ => Err(xous::Error::BadAddress),
+    };
+
+    // The staged pages only move to the new process if it gets created.
+    // Otherwise they're still ours.
+    if result.is_err() {
+        xous::unmap_memory(text).ok();
+        if let Some(data) = data {
+            xous::unmap_memory(data).ok();
+        }
+    }
+    Ok(result?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use minielf::{SHT_NOBITS, SHT_RELA};
+
+    const SHT_PROGBITS: u32 = 1;
+
+    /// Build a RISC-V executable with the given sections, each described as
+    /// `(type, flags, address, contents)`.  NOBITS sections get a size of
+    /// 64 bytes.
+    fn build_elf(entry_point: u32, sections: &[(u32, u32, u32, &[u8])]) -> Vec<u8> {
+        let mut elf = vec![0u8; 52];
+        elf[0..4].copy_from_slice(b"\x7fELF");
+        elf[4] = 1;
+        elf[5] = 1;
+        elf[6] = 1;
+        elf[16..18].copy_from_slice(&2u16.to_le_bytes());
+        elf[18..20].copy_from_slice(&0xf3u16.to_le_bytes());
+        elf[24..28].copy_from_slice(&entry_point.to_le_bytes());
+        elf[46..48].copy_from_slice(&40u16.to_le_bytes());
+        elf[48..50].copy_from_slice(&(sections.len() as u16 + 1).to_le_bytes());
+
+        let mut offsets = vec![];
+        for (_, _, _, contents) in sections {
+            offsets.push(elf.len() as u32);
+            elf.extend_from_slice(contents);
+        }
+
+        let section_offset = elf.len() as u32;
+        elf[32..36].copy_from_slice(&section_offset.to_le_bytes());
+        elf.extend_from_slice(&[0; 40]);
+        for ((kind, flags, addr, contents), offset) in sections.iter().zip(offsets) {
+            let size = if *kind == SHT_NOBITS {
+                64
+            } else {
+                contents.len() as u32
+            };
+            for word in &[0, *kind, *flags, *addr, offset, size, 0, 0, 4, 0] {
+                elf.extend_from_slice(&word.to_le_bytes());
+            }
+        }
+        elf
+    }
+
+    #[test]
+    fn text_and_data() {
+        let elf = build_elf(
+            0x1000_0010,
+            &[
+                (SHT_PROGBITS, 0x6, 0x1000_0000, &[0x13; 0x1800]),
+                (SHT_PROGBITS, 0x2, 0x1000_1800, b"rodata!!"),
+                (SHT_PROGBITS, 0x3, 0x1000_3000, b"data"),
+                (SHT_NOBITS, 0x3, 0x1000_3004, &[]),
+                (SHT_PROGBITS, 0x0, 0x0000_0000, b"comment"),
+            ],
+        );
+        let elf = ElfFile::new(&elf).unwrap();
+        let sections: Vec<Section> = elf.sections().map(|s| s.unwrap()).collect();
+        assert_eq!(sections.len(), 4);
+        assert_eq!(sections[0].flags, SectionFlags::EXECUTE);
+        assert_eq!(
+            sections[3].flags,
+            SectionFlags::WRITE | SectionFlags::NOCOPY
+        );
+        assert_eq!(sections[3].size, 64);
+
+        let layout = ProgramLayout::new(&elf, load_offset(&elf)).unwrap();
+        assert_eq!(
+            layout,
+            ProgramLayout {
+                entry_point: 0x1000_0010,
+                text: 0x1000_0000..0x1000_2000,
+                data: Some(0x1000_3000..0x1000_4000),
+            }
+        );
+
+        let mut text = vec![0; 0x2000];
+        copy_sections(&elf, 0, &layout.text, &mut text).unwrap();
+        assert!(text[..0x1800].iter().all(|b| *b == 0x13));
+        assert_eq!(&text[0x1800..0x1808], b"rodata!!");
+        assert!(text[0x1808..].iter().all(|b| *b == 0));
+
+        let mut data = vec![0; 0x1000];
+        copy_sections(&elf, 0, layout.data.as_ref().unwrap(), &mut data).unwrap();
+        assert_eq!(&data[..4], b"data");
+        assert!(data[4..].iter().all(|b| *b == 0));
+    }
+
+    /// Encode `R_RISCV_RELATIVE` relocations as a `SHT_RELA` section.
+    fn relative_relocations(relocations: &[(u32, u32)]) -> Vec<u8> {
+        let mut section = vec![];
+        for (offset, addend) in relocations {
+            for word in &[*offset, 3, *addend] {
+                section.extend_from_slice(&word.to_le_bytes());
+            }
+        }
+        section
+    }
+
+    #[test]
+    fn relocatable() {
+        let relocations = relative_relocations(&[(0x2004, 0x120), (0x100, 0x2000)]);
+        let mut elf = build_elf(
+            0x120,
+            &[
+                (SHT_PROGBITS, 0x6, 0x100, &[0x13; 0x100]),
+                (SHT_PROGBITS, 0x3, 0x2000, b"data\0\0\0\0"),
+                (SHT_RELA, 0x0, 0x0, &relocations),
+            ],
+        );
+        elf[16] = 3;
+        let elf = ElfFile::new(&elf).unwrap();
+        assert!(elf.relocatable);
+        assert_eq!(load_offset(&elf), PIE_LOAD_OFFSET);
+
+        let layout = ProgramLayout::new(&elf, PIE_LOAD_OFFSET).unwrap();
+        assert_eq!(
+            layout,
+            ProgramLayout {
+                entry_point: PIE_LOAD_OFFSET + 0x120,
+                text: PIE_LOAD_OFFSET..PIE_LOAD_OFFSET + 0x1000,
+                data: Some(PIE_LOAD_OFFSET + 0x2000..PIE_LOAD_OFFSET + 0x3000),
+            }
+        );
+
+        let mut text = vec![0; 0x1000];
+        copy_sections(&elf, PIE_LOAD_OFFSET, &layout.text, &mut text).unwrap();
+        assert_eq!(
+            &text[0x100..0x104],
+            &(PIE_LOAD_OFFSET as u32 + 0x2000).to_le_bytes()
+        );
+        assert!(text[0x104..0x200].iter().all(|b| *b == 0x13));
+
+        let mut data = vec![0; 0x1000];
+        copy_sections(
+            &elf,
+            PIE_LOAD_OFFSET,
+            layout.data.as_ref().unwrap(),
+            &mut data,
+        )
+        .unwrap();
+        assert_eq!(&data[..4], b"data");
+        assert_eq!(&data[4..8], &(PIE_LOAD_OFFSET as u32 + 0x120).to_le_bytes());
+    }
+
+    #[test]
+    fn bad_relocations() {
+        let outside = relative_relocations(&[(0x1800, 0)]);
+        let mut elf = build_elf(
+            0x100,
+            &[
+                (SHT_PROGBITS, 0x6, 0x100, &[0x13; 0x100]),
+                (SHT_RELA, 0x0, 0x0, &outside),
+            ],
+        );
+        elf[16] = 3;
+        let elf = ElfFile::new(&elf).unwrap();
+        assert_eq!(
+            ProgramLayout::new(&elf, PIE_LOAD_OFFSET),
+            Err(LoadError::BadRelocation)
+        );
+
+        let mut unsupported = relative_relocations(&[(0x100, 0)]);
+        unsupported[4] = 2;
+        let mut elf = build_elf(
+            0x100,
+            &[
+                (SHT_PROGBITS, 0x6, 0x100, &[0x13; 0x100]),
+                (SHT_RELA, 0x0, 0x0, &unsupported),
+            ],
+        );
+        elf[16] = 3;
+        let elf = ElfFile::new(&elf).unwrap();
+        assert_eq!(
+            ProgramLayout::new(&elf, PIE_LOAD_OFFSET),
+            Err(LoadError::Elf(ElfError::UnsupportedRelocation(2)))
+        );
+    }
+
+    #[test]
+    fn shared_page() {
+        let elf = build_elf(
+            0x1000_0000,
+            &[
+                (SHT_PROGBITS, 0x6, 0x1000_0000, &[0x13; 0x100]),
+                (SHT_PROGBITS, 0x3, 0x1000_0100, b"data"),
+            ],
+        );
+        let elf = ElfFile::new(&elf).unwrap();
+        assert_eq!(ProgramLayout::new(&elf, 0), Err(LoadError::Overlap));
+
+        let elf = build_elf(
+            0xff00_0000,
+            &[(SHT_PROGBITS, 0x6, 0xff00_0000, &[0x13; 0x100])],
+        );
+        let elf = ElfFile::new(&elf).unwrap();
+        assert_eq!(ProgramLayout::new(&elf, 0), Err(LoadError::BadAddress));
+    }
+
+    #[test]
+    fn bad_files() {
+        assert_eq!(ElfFile::new(&[0; 16]).err(), Some(ElfError::Truncated));
+        assert_eq!(ElfFile::new(&[0; 64]).err(), Some(ElfError::NotElf));
+
+        let mut elf = build_elf(0x1000_0000, &[]);
+        elf[18] = 0x3e;
+        assert_eq!(ElfFile::new(&elf).err(), Some(ElfError::WrongArchitecture));
+
+        let mut elf = build_elf(0x1000_0000, &[(SHT_PROGBITS, 0x6, 0x1000_0000, b"code")]);
+        elf[16] = 1;
+        assert_eq!(ElfFile::new(&elf).err(), Some(ElfError::NotExecutable));
+
+        let elf = build_elf(0x1000_0000, &[(SHT_PROGBITS, 0x6, 0x1000_0000, b"code")]);
+        let truncated = elf.len() - 30;
+        let elf = ElfFile::new(&elf[..truncated]).unwrap();
+        assert_eq!(elf.sections().next(), Some(Err(ElfError::Truncated)));
+    }
+}
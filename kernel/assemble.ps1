@@ -1,5 +1,6 @@
 # remove existing blobs because otherwise this will append object files to the old blobs
-Remove-Item -Force bin/*.a
+New-Item -ItemType Directory -Force bin/qemu-virt | Out-Null
+Remove-Item -Force bin/*.a, bin/qemu-virt/*.a
 
 $crate = "xous-kernel"
 
@@ -14,4 +15,8 @@ riscv64-unknown-elf-gcc -ggdb3 -c -mabi=lp64 -march=rv64imac src/asm.S -o bin/$c
 riscv64-unknown-elf-ar crs bin/riscv64imac-unknown-none-elf.a bin/$crate.o
 riscv64-unknown-elf-ar crs bin/riscv64gc-unknown-none-elf.a bin/$crate.o
 
+# The QEMU `virt` machine needs Sstc timer routines
+riscv64-unknown-elf-gcc -ggdb3 -c -mabi=ilp32 -march=rv32imac src/asm.S -DQEMU_VIRT -o bin/$crate.o
+riscv64-unknown-elf-ar crs bin/qemu-virt/riscv32imac-unknown-none-elf.a bin/$crate.o
+
 Remove-Item bin/$crate.o
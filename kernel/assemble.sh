@@ -5,8 +5,8 @@ set -euxo pipefail
 crate=xous-kernel
 
 # remove existing blobs because otherwise this will append object files to the old blobs
-mkdir -p bin
-rm -f bin/*.a
+mkdir -p bin bin/qemu-virt
+rm -f bin/*.a bin/qemu-virt/*.a
 
 riscv64-unknown-elf-gcc -ggdb3 -c -mabi=ilp32 -march=rv32imac src/asm.S -o bin/$crate.o
 ar crs bin/riscv32imac-unknown-none-elf.a bin/$crate.o
@@ -19,4 +19,8 @@ riscv64-unknown-elf-gcc -ggdb3 -c -mabi=lp64 -march=rv64imac src/asm.S -o bin/$c
 ar crs bin/riscv64imac-unknown-none-elf.a bin/$crate.o
 ar crs bin/riscv64gc-unknown-none-elf.a bin/$crate.o
 
+# The QEMU `virt` machine needs Sstc timer routines
+riscv64-unknown-elf-gcc -ggdb3 -c -mabi=ilp32 -march=rv32imac src/asm.S -DQEMU_VIRT -o bin/$crate.o
+ar crs bin/qemu-virt/riscv32imac-unknown-none-elf.a bin/$crate.o
+
 rm bin/$crate.o
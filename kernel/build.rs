@@ -21,17 +21,19 @@ fn main() {
         println!("Target {} is NOT bare metal", target);
     }
 
-    // For RISC-V, link in the startup library.
+    // For RISC-V, link in the startup library.  QEMU `virt` builds have
+    // extra routines for the timer, so they have blobs of their own.
     if target.starts_with("riscv") {
-        fs::copy(
-            format!("bin/{}.a", target),
-            out_dir.join(format!("lib{}.a", name)),
-        )
-        .unwrap();
+        let blob = if env::var("CARGO_FEATURE_QEMU_VIRT").is_ok() {
+            format!("bin/qemu-virt/{}.a", target)
+        } else {
+            format!("bin/{}.a", target)
+        };
+        fs::copy(&blob, out_dir.join(format!("lib{}.a", name))).unwrap();
 
         println!("cargo:rustc-link-lib=static={}", name);
         println!("cargo:rustc-link-search={}", out_dir.display());
-        println!("cargo:rerun-if-changed=bin/{}.a", target);
+        println!("cargo:rerun-if-changed={}", blob);
 
         // Put the linker script somewhere the linker can find it
         fs::File::create(out_dir.join("link.x"))
@@ -3,28 +3,47 @@ pub mod mem;
 pub mod process;
 pub mod rand;
 pub mod syscall;
+pub mod trace;
 
 use std::cell::RefCell;
+use std::collections::VecDeque;
 use std::convert::TryInto;
 use std::env;
-use std::io::Read;
-use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
 use std::thread_local;
 
 use crate::arch::process::Process;
 use crate::services::SystemServices;
 
+use xous_kernel::arch::protocol;
+use xous_kernel::arch::transport::{self, Transport, XousAddress};
 use xous_kernel::{MemoryAddress, ProcessInit, ProcessKey, Result, SysCall, ThreadInit, PID, TID};
 
+/// How long a new client has to complete each step of the handshake. Clients
+/// that stall are dropped, so that they can't stop others from connecting.
+const HANDSHAKE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
 enum ThreadMessage {
     SysCall(PID, TID, SysCall),
-    NewConnection(TcpStream, ProcessKey),
+    NewConnection(Box<dyn Transport>, Credentials),
+    /// A connection from a trace being replayed, which was authenticated when recording.
+    ReplayConnection(PID, Box<dyn Transport>),
+}
+
+/// What a new client sent to prove that it holds a process key.
+struct Credentials {
+    client_nonce: protocol::Nonce,
+    kernel_nonce: protocol::Nonce,
+    proof: protocol::Proof,
 }
 
-#[derive(Debug)]
 enum NewPidMessage {
-    NewPid(PID),
+    /// The client was assigned a PID. Includes the proof that the kernel issued its key.
+    NewPid(PID, protocol::Proof),
+    /// No waiting process has the key the client claimed to hold.
+    AccessDenied,
 }
 
 #[derive(Debug)]
@@ -32,8 +51,8 @@ enum ExitMessage {
     Exit,
 }
 
-thread_local!(static NETWORK_LISTEN_ADDRESS: RefCell<SocketAddr> = RefCell::new(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 0)));
-thread_local!(static SEND_ADDR: RefCell<Option<Sender<SocketAddr>>> = RefCell::new(None));
+thread_local!(static NETWORK_LISTEN_ADDRESS: RefCell<XousAddress> = RefCell::new(XousAddress::Tcp(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 0))));
+thread_local!(static SEND_ADDR: RefCell<Option<Sender<XousAddress>>> = RefCell::new(None));
 thread_local!(static PID1_KEY: RefCell<[u8; 16]> = RefCell::new([0u8; 16]));
 
 #[cfg(test)]
@@ -43,16 +62,16 @@ pub fn set_pid1_key(new_key: [u8; 16]) {
 
 /// Set the network address for this particular thread.
 #[cfg(test)]
-pub fn set_listen_address(new_address: &SocketAddr) {
+pub fn set_listen_address(new_address: &XousAddress) {
     NETWORK_LISTEN_ADDRESS.with(|nla| {
         let mut address = nla.borrow_mut();
-        *address = *new_address;
+        *address = new_address.clone();
     });
 }
 
 /// Set the network address for this particular thread.
 #[allow(dead_code)]
-pub fn set_send_addr(send_addr: Sender<SocketAddr>) {
+pub fn set_send_addr(send_addr: Sender<XousAddress>) {
     SEND_ADDR.with(|sa| {
         *sa.borrow_mut() = Some(send_addr);
     });
@@ -76,10 +95,11 @@ pub fn current_pid() -> PID {
 
 /// Each client gets its own connection and its own thread, which is handled here.
 fn handle_connection(
-    conn: TcpStream,
+    conn: Box<dyn Transport>,
     pid: PID,
     chn: Sender<ThreadMessage>,
     should_exit: std::sync::Arc<core::sync::atomic::AtomicBool>,
+    tracing: Option<trace::ConnectionTrace>,
 ) {
     enum ServerMessage {
         Exit,
@@ -87,53 +107,33 @@ fn handle_connection(
         ServerPacketWithData([usize; 9], Vec<u8>),
     }
 
-    fn conn_thread(mut conn: TcpStream, sender: Sender<ServerMessage>, _pid: PID) {
+    fn conn_thread(mut conn: Box<dyn Transport>, sender: Sender<ServerMessage>, _pid: PID) {
         loop {
-            let mut raw_data = [0u8; 9 * std::mem::size_of::<usize>()];
-            if let Err(_e) = conn.read_exact(&mut raw_data) {
-                #[cfg(not(test))]
-                eprintln!(
-                    "KERNEL({}): client disconnected: {} -- shutting down virtual process",
-                    _pid, _e
-                );
-                sender.send(ServerMessage::Exit).ok();
-                return;
-            }
+            // Each packet carries its own length, so memory is read along with the call
+            // regardless of what kind of call it is.
+            let packet = match protocol::read_packet(&mut *conn) {
+                Ok(packet) => packet,
+                Err(_e) => {
+                    #[cfg(not(test))]
+                    eprintln!(
+                        "KERNEL({}): client disconnected: {} -- shutting down virtual process",
+                        _pid, _e
+                    );
+                    sender.send(ServerMessage::Exit).ok();
+                    return;
+                }
+            };
 
             let mut packet_data = [0usize; 9];
-            for (bytes, word) in raw_data
-                .chunks_exact(std::mem::size_of::<usize>())
-                .zip(packet_data.iter_mut())
-            {
-                *word = usize::from_le_bytes(bytes.try_into().unwrap());
-            }
+            packet_data[0] = packet.thread_id;
+            packet_data[1..].copy_from_slice(&packet.args);
 
             sender
-                .send(
-                    if (packet_data[1] == xous_kernel::syscall::SysCallNumber::SendMessage as _
-                        || packet_data[1]
-                            == xous_kernel::syscall::SysCallNumber::TrySendMessage as _)
-                        && (packet_data[3] == 1 || packet_data[3] == 2 || packet_data[3] == 3)
-                    {
-                        let mut v = vec![0; packet_data[6]];
-                        if conn.read_exact(&mut v).is_err() {
-                            sender.send(ServerMessage::Exit).ok();
-                            return;
-                        }
-                        ServerMessage::ServerPacketWithData(packet_data, v)
-                    } else if packet_data[1]
-                        == xous_kernel::syscall::SysCallNumber::ReturnMemory as _
-                    {
-                        let mut v = vec![0; packet_data[4]];
-                        if conn.read_exact(&mut v).is_err() {
-                            sender.send(ServerMessage::Exit).ok();
-                            return;
-                        }
-                        ServerMessage::ServerPacketWithData(packet_data, v)
-                    } else {
-                        ServerMessage::ServerPacket(packet_data)
-                    },
-                )
+                .send(if packet.data.is_empty() {
+                    ServerMessage::ServerPacket(packet_data)
+                } else {
+                    ServerMessage::ServerPacketWithData(packet_data, packet.data)
+                })
                 .unwrap();
         }
     }
@@ -159,7 +159,31 @@ fn handle_connection(
         })
         .unwrap();
 
+    // Syscalls are traced as they're handed to the kernel, so that a trace lists
+    // calls from all processes in the order the kernel handles them.
+    let send_call = |thread_id: TID, call: SysCall, packet: Option<Vec<u8>>| {
+        let send = || {
+            chn.send(ThreadMessage::SysCall(pid, thread_id, call))
+                .expect("couldn't make syscall")
+        };
+        match &tracing {
+            Some(tracing) => tracing.syscall(packet, send),
+            None => send(),
+        }
+    };
+    let recording = matches!(tracing, Some(trace::ConnectionTrace::Record(_, _)));
+
     for msg in receiver {
+        // Keep a copy of the packet as it was received, since the call may be modified below.
+        let packet = match &msg {
+            ServerMessage::ServerPacket(pkt) if recording => {
+                Some(protocol::encode_packet(pkt[0], pkt[1..].try_into().unwrap(), &[]))
+            }
+            ServerMessage::ServerPacketWithData(pkt, data) if recording => {
+                Some(protocol::encode_packet(pkt[0], pkt[1..].try_into().unwrap(), data))
+            }
+            _ => None,
+        };
         match msg {
             ServerMessage::Exit => {
                 #[cfg(not(test))]
@@ -179,9 +203,7 @@ fn handle_connection(
                             pkt[0], pkt[1], pkt[2], pkt[3], pkt[4], pkt[5], pkt[6], pkt[7]
                         );
                     }
-                    Ok(call) => chn
-                        .send(ThreadMessage::SysCall(pid, thread_id, call))
-                        .expect("couldn't make syscall"),
+                    Ok(call) => send_call(thread_id, call, packet),
                 }
             }
             ServerMessage::ServerPacketWithData(pkt, data) => {
@@ -256,8 +278,7 @@ fn handle_connection(
                             }
                             _ => panic!("unsupported message type"),
                         }
-                        chn.send(ThreadMessage::SysCall(pid, thread_id, call))
-                            .expect("couldn't make syscall");
+                        send_call(thread_id, call, packet);
                     }
                 }
             }
@@ -268,114 +289,243 @@ fn handle_connection(
         "KERNEL({}): Finished the thread so sending TerminateProcess",
         pid
     );
-    chn.send(ThreadMessage::SysCall(
-        pid,
-        1,
-        xous_kernel::SysCall::TerminateProcess,
-    ))
-    .unwrap();
+    let terminate = || {
+        chn.send(ThreadMessage::SysCall(
+            pid,
+            1,
+            xous_kernel::SysCall::TerminateProcess,
+        ))
+        .unwrap()
+    };
+    match &tracing {
+        Some(tracing) => tracing.disconnect(terminate),
+        None => terminate(),
+    }
 }
 
 fn listen_thread(
-    listen_addr: SocketAddr,
+    listen_addr: XousAddress,
     chn: Sender<ThreadMessage>,
-    mut local_addr_sender: Option<Sender<SocketAddr>>,
+    mut local_addr_sender: Option<Sender<XousAddress>>,
     new_pid_channel: Receiver<NewPidMessage>,
     exit_channel: Receiver<ExitMessage>,
+    trace: trace::Trace,
 ) {
     let should_exit = std::sync::Arc::new(core::sync::atomic::AtomicBool::new(false));
 
     // println!("KERNEL(1): Starting Xous server on {}...", listen_addr);
-    let listener = TcpListener::bind(listen_addr).unwrap_or_else(|e| {
+    let listener = transport::bind(&listen_addr).unwrap_or_else(|e| {
         panic!("Unable to create server: {}", e);
     });
     // Notify the host what our kernel address is, if a listener exists.
     if let Some(las) = local_addr_sender.take() {
-        las.send(listener.local_addr().unwrap()).unwrap();
+        las.send(listener.local_address().unwrap()).unwrap();
     }
 
     let mut clients = vec![];
 
+    /// Make sure a new client speaks our protocol, then challenge it to prove
+    /// that it holds a process key. This runs on a thread of its own, so a
+    /// client that stalls partway through can't hold up anyone else.
+    fn handshake(mut conn: Box<dyn Transport>) -> Option<(Box<dyn Transport>, Credentials)> {
+        if let Err(e) = conn.set_read_timeout(Some(HANDSHAKE_TIMEOUT)) {
+            eprintln!("KERNEL: couldn't set handshake timeout: {}", e);
+            conn.close().ok();
+            return None;
+        }
+        let hello = match protocol::read_hello(&mut *conn) {
+            Ok(hello) => hello,
+            Err(e) => {
+                eprintln!("KERNEL: rejecting client with invalid handshake: {}", e);
+                conn.close().ok();
+                return None;
+            }
+        };
+        let kernel_nonce = protocol::generate_nonce().expect("couldn't generate nonce");
+        let status = hello.status();
+        if protocol::write_hello_response(&mut *conn, status, &kernel_nonce).is_err() {
+            return None;
+        }
+        if status != protocol::STATUS_OK {
+            eprintln!(
+                "KERNEL: rejecting client speaking protocol version {} with {}-byte words",
+                hello.version, hello.word_size
+            );
+            conn.close().ok();
+            return None;
+        }
+        let proof = match protocol::read_proof(&mut *conn) {
+            Ok(proof) => proof,
+            Err(e) => {
+                eprintln!("KERNEL: client disconnected during handshake: {}", e);
+                conn.close().ok();
+                return None;
+            }
+        };
+        // Once connected, a process may go quiet for as long as it likes.
+        if conn.set_read_timeout(None).is_err() {
+            conn.close().ok();
+            return None;
+        }
+        Some((
+            conn,
+            Credentials {
+                client_nonce: hello.nonce,
+                kernel_nonce,
+                proof,
+            },
+        ))
+    }
+
+    /// Run the handshake for a new connection, passing the connection back to
+    /// the listen thread once the client has proven who it is.
+    fn spawn_handshake(
+        conn: Box<dyn Transport>,
+        sender: &Sender<ClientMessage>,
+        clients: &mut Vec<(std::thread::JoinHandle<()>, Box<dyn Transport>)>,
+    ) {
+        let sender = sender.clone();
+        let conn_copy = conn.duplicate().expect("couldn't duplicate connection");
+        let jh = std::thread::Builder::new()
+            .name("kernel handshake".to_owned())
+            .spawn(move || {
+                if let Some((conn, credentials)) = handshake(conn) {
+                    sender
+                        .send(ClientMessage::Authenticated(conn, credentials))
+                        .ok();
+                }
+            })
+            .expect("couldn't spawn handshake thread");
+        clients.push((jh, conn_copy));
+    }
+
     fn accept_new_connection(
-        mut conn: TcpStream,
+        mut conn: Box<dyn Transport>,
+        credentials: Credentials,
         chn: &Sender<ThreadMessage>,
         new_pid_channel: &Receiver<NewPidMessage>,
-        clients: &mut Vec<(std::thread::JoinHandle<()>, TcpStream)>,
+        clients: &mut Vec<(std::thread::JoinHandle<()>, Box<dyn Transport>)>,
         should_exit: &std::sync::Arc<core::sync::atomic::AtomicBool>,
+        trace: &trace::Trace,
     ) -> bool {
-        let thr_chn = chn.clone();
-
-        // Read the challenge access key from the client
-        let mut access_key = [0u8; 16];
-        conn.read_exact(&mut access_key).unwrap();
-
         // Spawn a new process. This process will start out in the "Allocated" state.
         chn.send(ThreadMessage::NewConnection(
-            conn.try_clone()
+            conn.duplicate()
                 .expect("couldn't make a copy of the network connection for the kernel"),
-            ProcessKey::new(access_key),
+            credentials,
         ))
         .expect("couldn't request a new PID");
 
         // The kernel will immediately respond with a new PID.
-        let NewPidMessage::NewPid(new_pid) = new_pid_channel
+        let (new_pid, tracing) = match new_pid_channel
             .recv()
-            .expect("couldn't receive message from main thread");
+            .expect("couldn't receive message from main thread")
+        {
+            NewPidMessage::NewPid(new_pid, kernel_proof) => {
+                // Record the connection before the client can make any calls over it.
+                let tracing = match trace {
+                    trace::Trace::Record(recorder) => Some(trace::ConnectionTrace::Record(
+                        recorder.clone(),
+                        recorder.connect(new_pid),
+                    )),
+                    _ => None,
+                };
+                // If this fails, the connection thread will notice the disconnection
+                // and terminate the process.
+                protocol::write_auth_response(&mut *conn, protocol::STATUS_OK, &kernel_proof)
+                    .ok();
+                (new_pid, tracing)
+            }
+            NewPidMessage::AccessDenied => {
+                eprintln!("KERNEL: rejecting client with an unrecognized process key");
+                protocol::write_auth_response(&mut *conn, protocol::STATUS_ACCESS_DENIED, &[0; 32])
+                    .ok();
+                conn.close().ok();
+                return false;
+            }
+        };
         // println!("KERNEL({}): New client connected from {}", new_pid, _addr);
-        let conn_copy = conn.try_clone().expect("couldn't duplicate connection");
+        spawn_client(conn, new_pid, chn, clients, should_exit, tracing);
+        false
+    }
+
+    /// Hand a connection from the trace being replayed to the kernel. This skips the
+    /// handshake, since the process was authenticated when the trace was recorded.
+    fn attach_replayed_connection(
+        conn: Box<dyn Transport>,
+        pid: PID,
+        tracing: trace::ConnectionTrace,
+        chn: &Sender<ThreadMessage>,
+        clients: &mut Vec<(std::thread::JoinHandle<()>, Box<dyn Transport>)>,
+        should_exit: &std::sync::Arc<core::sync::atomic::AtomicBool>,
+    ) {
+        chn.send(ThreadMessage::ReplayConnection(
+            pid,
+            conn.duplicate()
+                .expect("couldn't make a copy of the replayed connection for the kernel"),
+        ))
+        .expect("couldn't register replayed connection");
+        spawn_client(conn, pid, chn, clients, should_exit, Some(tracing));
+    }
+
+    fn spawn_client(
+        conn: Box<dyn Transport>,
+        pid: PID,
+        chn: &Sender<ThreadMessage>,
+        clients: &mut Vec<(std::thread::JoinHandle<()>, Box<dyn Transport>)>,
+        should_exit: &std::sync::Arc<core::sync::atomic::AtomicBool>,
+        tracing: Option<trace::ConnectionTrace>,
+    ) {
+        let thr_chn = chn.clone();
+        let conn_copy = conn.duplicate().expect("couldn't duplicate connection");
         let should_exit = should_exit.clone();
         let jh = std::thread::Builder::new()
-            .name(format!("kernel PID {} listener", new_pid))
-            .spawn(move || handle_connection(conn, new_pid, thr_chn, should_exit))
+            .name(format!("kernel PID {} listener", pid))
+            .spawn(move || handle_connection(conn, pid, thr_chn, should_exit, tracing))
             .expect("couldn't spawn listen thread");
         clients.push((jh, conn_copy));
-        false
     }
 
     fn exit_server(
         should_exit: std::sync::Arc<core::sync::atomic::AtomicBool>,
-        clients: Vec<(std::thread::JoinHandle<()>, TcpStream)>,
+        clients: Vec<(std::thread::JoinHandle<()>, Box<dyn Transport>)>,
     ) {
         should_exit.store(true, core::sync::atomic::Ordering::Relaxed);
         for (jh, conn) in clients {
-            use std::net::Shutdown;
-            conn.shutdown(Shutdown::Both).ok();
+            conn.close().ok();
             jh.join().expect("couldn't join client thread");
         }
     }
 
-    // Use `listener` in a nonblocking setup so that we can exit when doing tests
+    // Accept connections with a timeout so that we can exit when doing tests
     enum ClientMessage {
-        NewConnection(TcpStream),
+        NewConnection(Box<dyn Transport>),
+        /// A new connection whose client has completed the handshake
+        Authenticated(Box<dyn Transport>, Credentials),
+        ReplayConnection(PID, Box<dyn Transport>, trace::ConnectionTrace),
         Exit,
     };
     let (sender, receiver) = channel();
-    let tcp_sender = sender.clone();
+    let conn_sender = sender.clone();
+    let replay_sender = sender.clone();
+    let handshake_sender = sender.clone();
     let exit_sender = sender;
 
     let (shutdown_listener, shutdown_listener_receiver) = channel();
 
-    // `listener.accept()` has no way to break, so we must put it in nonblocking mode
-    listener.set_nonblocking(true).unwrap();
-
-    std::thread::Builder::new()
+    let accept_thread = std::thread::Builder::new()
         .name("kernel accept thread".to_owned())
         .spawn(move || loop {
-            match listener.accept() {
-                Ok((conn, _addr)) => {
-                    conn.set_nonblocking(false).unwrap();
-                    tcp_sender.send(ClientMessage::NewConnection(conn)).unwrap();
+            match listener.accept_timeout(std::time::Duration::from_millis(100)) {
+                Ok(Some(conn)) => {
+                    conn_sender.send(ClientMessage::NewConnection(conn)).unwrap();
                 }
-                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                    match shutdown_listener_receiver
-                        .recv_timeout(std::time::Duration::from_millis(500))
-                    {
-                        Err(std::sync::mpsc::RecvTimeoutError::Timeout) => continue,
-                        Ok(()) | Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
-                            return;
-                        }
+                Ok(None) => match shutdown_listener_receiver.try_recv() {
+                    Err(std::sync::mpsc::TryRecvError::Empty) => continue,
+                    Ok(()) | Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                        return;
                     }
-                }
+                },
                 Err(e) => {
                     // Windows generates this error -- WSACancelBlockingCall -- when a
                     // connection is shut down while `accept()` is running. This should
@@ -397,6 +547,30 @@ fn listen_thread(
         })
         .unwrap();
 
+    // When replaying a trace, hand each recorded connection to the kernel as the
+    // replay reaches it.
+    let replay_thread = match &trace {
+        trace::Trace::Replay(replay) => {
+            let replay = replay.clone();
+            Some(
+                std::thread::Builder::new()
+                    .name("kernel replay thread".to_owned())
+                    .spawn(move || {
+                        while let Some((pid, conn, tracing)) = replay.next_connection() {
+                            if replay_sender
+                                .send(ClientMessage::ReplayConnection(pid, conn, tracing))
+                                .is_err()
+                            {
+                                return;
+                            }
+                        }
+                    })
+                    .unwrap(),
+            )
+        }
+        _ => None,
+    };
+
     // Spawn a thread to listen for the `exit` command, and relay that
     // to the main thread. This prevents us from needing to poll, since
     // all messages are coalesced into a single channel.
@@ -411,17 +585,55 @@ fn listen_thread(
     for msg in receiver {
         match msg {
             ClientMessage::NewConnection(conn) => {
-                if accept_new_connection(conn, &chn, &new_pid_channel, &mut clients, &should_exit) {
+                spawn_handshake(conn, &handshake_sender, &mut clients)
+            }
+            ClientMessage::Authenticated(conn, credentials) => {
+                if accept_new_connection(
+                    conn,
+                    credentials,
+                    &chn,
+                    &new_pid_channel,
+                    &mut clients,
+                    &should_exit,
+                    &trace,
+                ) {
                     break;
                 }
             }
+            ClientMessage::ReplayConnection(pid, conn, tracing) => {
+                attach_replayed_connection(conn, pid, tracing, &chn, &mut clients, &should_exit)
+            }
             ClientMessage::Exit => break,
         }
     }
     shutdown_listener.send(()).unwrap();
+    // Wait for the accept thread to drop the listener, which releases its address.
+    accept_thread.join().expect("couldn't join accept thread");
+    if let trace::Trace::Replay(replay) = &trace {
+        // Let any connections still being replayed come to an end.
+        replay.stop();
+        match replay.result() {
+            Ok(()) => println!("KERNEL: replay finished without diverging from the trace"),
+            Err(e) => eprintln!("KERNEL: replay failed: {}", e),
+        }
+    }
+    if let Some(replay_thread) = replay_thread {
+        replay_thread.join().expect("couldn't join replay thread");
+    }
     exit_server(should_exit, clients);
 }
 
+/// Switch to a process that has just connected, which moves it from `Setup(_)` to `Running(0)`.
+/// Note that in this system, multiple processes can be active at once. This is
+/// similar to having one core for each process.
+fn start_connected_process(pid: PID) {
+    SystemServices::with_mut(|ss| {
+        ss.create_thread(pid, ThreadInit {})?;
+        ss.switch_to_thread(pid, None)
+    })
+    .unwrap();
+}
+
 /// The idle function is run when there are no directly-runnable processes
 /// that kmain can activate. In a hosted environment,this is the primary
 /// thread that handles network communications, and this function never returns.
@@ -431,6 +643,18 @@ pub fn idle() -> bool {
     let (new_pid_sender, new_pid_receiver) = channel();
     let (exit_sender, exit_receiver) = channel();
 
+    // Record or replay syscall traffic, if requested.
+    if let Ok(path) = env::var("XOUS_TRACE_RECORD") {
+        let recorder = trace::Recorder::create(path.as_ref())
+            .unwrap_or_else(|e| panic!("couldn't create trace file {}: {}", path, e));
+        trace::set_trace(trace::Trace::Record(Arc::new(recorder)));
+    } else if let Ok(path) = env::var("XOUS_TRACE_REPLAY") {
+        let replay = trace::Replay::load(path.as_ref())
+            .unwrap_or_else(|e| panic!("couldn't load trace file {}: {}", path, e));
+        trace::set_trace(trace::Trace::Replay(Arc::new(replay)));
+    }
+    let trace = trace::trace();
+
     // Allocate PID1 with the key we were passed.
     let pid1_key = PID1_KEY.with(|p1k| *p1k.borrow());
     let pid1_init = ProcessInit {
@@ -440,13 +664,8 @@ pub fn idle() -> bool {
     assert_eq!(pid1.get(), 1);
 
     let listen_addr = env::var("XOUS_LISTEN_ADDR")
-        .map(|s| {
-            s.to_socket_addrs()
-                .expect("invalid server address")
-                .next()
-                .expect("unable to resolve server address")
-        })
-        .unwrap_or_else(|_| NETWORK_LISTEN_ADDRESS.with(|nla| *nla.borrow()));
+        .map(|s| s.parse().expect("invalid server address"))
+        .unwrap_or_else(|_| NETWORK_LISTEN_ADDRESS.with(|nla| nla.borrow().clone()));
 
     #[cfg(not(test))]
     let address_receiver = {
@@ -459,15 +678,24 @@ pub fn idle() -> bool {
         let sa = sa.borrow_mut().take();
         std::thread::Builder::new()
             .name("kernel network listener".to_owned())
-            .spawn(move || listen_thread(listen_addr, sender, sa, new_pid_receiver, exit_receiver))
+            .spawn(move || {
+                listen_thread(
+                    listen_addr,
+                    sender,
+                    sa,
+                    new_pid_receiver,
+                    exit_receiver,
+                    trace,
+                )
+            })
             .expect("couldn't spawn listen thread")
     });
 
     #[cfg(not(test))]
     {
         let address = address_receiver.recv().unwrap();
-        xous_kernel::arch::set_xous_address(address);
         println!("KERNEL: Xous server listening on {}", address);
+        xous_kernel::arch::set_xous_address(address);
         println!("KERNEL: Starting initial processes:");
         let mut args = std::env::args();
         args.next();
@@ -476,6 +704,8 @@ pub fn idle() -> bool {
         // are owned by PID1.
         crate::arch::process::set_current_pid(pid1);
 
+        let replaying = matches!(trace::trace(), trace::Trace::Replay(_));
+
         // Go through each arg and spawn it as a new process. Failures here will
         // halt the entire system.
         println!("  PID  |  Command");
@@ -487,116 +717,182 @@ pub fn idle() -> bool {
             };
             let new_pid = SystemServices::with_mut(|ss| ss.create_process(init)).unwrap();
             println!(" {:^5} |  {}", new_pid, arg);
+            // When replaying a trace, the process's calls come from the trace instead.
+            if replaying {
+                continue;
+            }
             let process_args = xous_kernel::ProcessArgs::new("program", arg);
             xous_kernel::arch::create_process_post(process_args, init, new_pid)
                 .expect("couldn't spawn");
         }
+
+        // Reserve a process for each key in `XOUS_REMOTE_KEYS`. These processes are started
+        // by hand, possibly on another machine, with `XOUS_SERVER` set to this kernel's
+        // address and `XOUS_PROCESS_KEY` set to one of these keys.
+        if let Ok(keys) = env::var("XOUS_REMOTE_KEYS") {
+            for key in keys.split(',').map(str::trim).filter(|k| !k.is_empty()) {
+                let mut process_key = [0u8; 16];
+                hex::decode_to_slice(key, &mut process_key)
+                    .expect("XOUS_REMOTE_KEYS must contain 32-digit hex keys");
+                let init = xous_kernel::ProcessInit {
+                    key: ProcessKey::new(process_key),
+                };
+                let new_pid = SystemServices::with_mut(|ss| ss.create_process(init)).unwrap();
+                println!(" {:^5} |  (remote process)", new_pid);
+            }
+        }
     }
 
-    while let Ok(msg) = message_receiver.recv() {
+    // Calls that returned `WouldBlock`, along with their arguments so that they may be retried.
+    let mut blocked_calls: Vec<(PID, TID, [usize; 8])> = vec![];
+
+    'kernel: while let Ok(msg) = message_receiver.recv() {
         match msg {
-            ThreadMessage::NewConnection(conn, access_key) => {
-                // The new process should already have a PID registered. Convert its access key
-                // into a PID, and register the connection with the server.
-                let new_pid =
-                    crate::arch::process::register_connection_for_key(conn, access_key).unwrap();
-                // println!(
-                //     "KERNEL: Access key {:?} mapped to PID {}",
-                //     access_key, new_pid
-                // );
+            ThreadMessage::NewConnection(conn, credentials) => {
+                // The new process should already have a PID registered. Find the process whose
+                // key the client proved it holds, and register the connection with the server.
+                let registration = crate::arch::process::register_connection(conn, |_, key| {
+                    let expected = protocol::client_proof(
+                        key.as_bytes(),
+                        &credentials.client_nonce,
+                        &credentials.kernel_nonce,
+                    );
+                    protocol::proofs_match(&credentials.proof, &expected)
+                });
+                let (new_pid, key) = match registration {
+                    Ok(registration) => registration,
+                    Err(_) => {
+                        new_pid_sender
+                            .send(NewPidMessage::AccessDenied)
+                            .expect("couldn't reject new connection");
+                        continue;
+                    }
+                };
 
                 // Inform the backchannel of the new process ID.
+                let kernel_proof = protocol::kernel_proof(
+                    key.as_bytes(),
+                    &credentials.client_nonce,
+                    &credentials.kernel_nonce,
+                );
                 new_pid_sender
-                    .send(NewPidMessage::NewPid(new_pid))
+                    .send(NewPidMessage::NewPid(new_pid, kernel_proof))
                     .expect("couldn't send new pid to new connection");
 
                 // conn.write_all(&new_pid.get().to_le_bytes())
                 //     .expect("couldn't send pid to new process");
 
-                // Switch to this process immediately, which moves it from `Setup(_)` to `Running(0)`.
-                // Note that in this system, multiple processes can be active at once. This is
-                // similar to having one core for each process
-                SystemServices::with_mut(|ss| {
-                    ss.create_thread(new_pid, ThreadInit {})?;
-                    ss.switch_to_thread(new_pid, None)
-                })
-                .unwrap();
+                start_connected_process(new_pid);
+            }
+            ThreadMessage::ReplayConnection(pid, conn) => {
+                // The process was created by an earlier call in the trace, and is
+                // waiting for a connection just as it was when recording.
+                let registration =
+                    crate::arch::process::register_connection(conn, |candidate, _| candidate == pid);
+                if registration.is_err() {
+                    eprintln!("KERNEL: trace connects to PID {}, which isn't waiting for a connection", pid);
+                    continue;
+                }
+                start_connected_process(pid);
             }
             ThreadMessage::SysCall(pid, thread_id, call) => {
-                // println!("KERNEL({}): Received syscall {:?}", pid, call);
-                crate::arch::process::set_current_pid(pid);
-                // println!("KERNEL({}): Now running as the new process", pid);
-
-                // If the call being made is to terminate the current process, we need to know
-                // because we won't be able to send a response.
-                let is_terminate = call == SysCall::TerminateProcess;
-                let is_shutdown = call == SysCall::Shutdown;
-
-                // For a "Shutdown" command, send the response before we issue the shutdown.
-                // This is because the "process" will be "terminated" (the network socket will be closed),
-                // and we won't be able to send the response after we're done.
-                if is_shutdown {
-                    // println!("KERNEL: Detected shutdown -- sending final \"Ok\" to the client");
-                    let mut process = Process::current();
-                    let mut response_vec = Vec::new();
-                    response_vec.extend_from_slice(&thread_id.to_le_bytes());
-                    for word in Result::Ok.to_args().iter_mut() {
-                        response_vec.extend_from_slice(&word.to_le_bytes());
+                // Handle this call, along with any blocked calls that it unblocks.
+                let mut runnable = VecDeque::new();
+                runnable.push_back((pid, thread_id, call));
+                while let Some((pid, thread_id, call)) = runnable.pop_front() {
+                    // println!("KERNEL({}): Received syscall {:?}", pid, call);
+                    crate::arch::process::set_current_pid(pid);
+                    // println!("KERNEL({}): Now running as the new process", pid);
+
+                    // If the call being made is to terminate the current process, we need to know
+                    // because we won't be able to send a response.
+                    let is_terminate = call == SysCall::TerminateProcess;
+                    let is_shutdown = call == SysCall::Shutdown;
+
+                    // For a "Shutdown" command, send the response before we issue the shutdown.
+                    // This is because the "process" will be "terminated" (the network socket will be closed),
+                    // and we won't be able to send the response after we're done.
+                    if is_shutdown {
+                        // println!("KERNEL: Detected shutdown -- sending final \"Ok\" to the client");
+                        let mut process = Process::current();
+                        let response_vec =
+                            protocol::encode_packet(thread_id, &Result::Ok.to_args(), &[]);
+                        process.send(&response_vec).unwrap_or_else(|_e| {
+                            // If we're unable to send data to the process, assume it's dead and terminate it.
+                            println!(
+                                "Unable to send response to process: {:?} -- terminating",
+                                _e
+                            );
+                            crate::syscall::handle(pid, thread_id, false, SysCall::TerminateProcess)
+                                .ok();
+                        });
+                        // println!("KERNEL: Done sending");
                     }
-                    process.send(&response_vec).unwrap_or_else(|_e| {
-                        // If we're unable to send data to the process, assume it's dead and terminate it.
-                        println!(
-                            "Unable to send response to process: {:?} -- terminating",
-                            _e
-                        );
-                        crate::syscall::handle(pid, thread_id, false, SysCall::TerminateProcess)
-                            .ok();
-                    });
-                    // println!("KERNEL: Done sending");
-                }
 
-                // Handle the syscall within the Xous kernel
-                let response = crate::syscall::handle(pid, thread_id, false, call)
-                    .unwrap_or_else(Result::Error);
-
-                // println!("KERNEL({}): Syscall response {:?}", pid, response);
-                // There's a response if it wasn't a blocked process and we're not terminating.
-                // Send the response back to the target.
-                if response != Result::BlockedProcess && !is_terminate && !is_shutdown {
-                    // The syscall may change what the current process is, but we always
-                    // want to send a response to the process where the request came from.
-                    // For this block, switch to the original PID, send the message, then
-                    // switch back.
-                    let existing_pid = crate::arch::process::current_pid();
-                    crate::arch::process::set_current_pid(pid);
+                    // Handle the syscall within the Xous kernel
+                    let call_args = call.as_args();
+                    let response = crate::syscall::handle(pid, thread_id, false, call)
+                        .unwrap_or_else(Result::Error);
+
+                    // `WouldBlock` means the call can't complete yet, for example because
+                    // the server it's connecting to doesn't exist. Rather than having the
+                    // client poll, park the call and retry it after the next call completes.
+                    if response == Result::WouldBlock {
+                        blocked_calls.push((pid, thread_id, call_args));
+                        continue;
+                    }
+                    if is_terminate {
+                        blocked_calls.retain(|(blocked_pid, _, _)| *blocked_pid != pid);
+                    }
 
-                    let mut process = Process::current();
-                    let mut response_vec = Vec::new();
-                    response_vec.extend_from_slice(&thread_id.to_le_bytes());
-                    for word in response.to_args().iter_mut() {
-                        response_vec.extend_from_slice(&word.to_le_bytes());
+                    // println!("KERNEL({}): Syscall response {:?}", pid, response);
+                    // There's a response if it wasn't a blocked process and we're not terminating.
+                    // Send the response back to the target.
+                    if response != Result::BlockedProcess && !is_terminate && !is_shutdown {
+                        // The syscall may change what the current process is, but we always
+                        // want to send a response to the process where the request came from.
+                        // For this block, switch to the original PID, send the message, then
+                        // switch back.
+                        let existing_pid = crate::arch::process::current_pid();
+                        crate::arch::process::set_current_pid(pid);
+
+                        let mut process = Process::current();
+                        let data: &[u8] = match response.memory() {
+                            Some(mem) => unsafe {
+                                core::slice::from_raw_parts(mem.as_ptr(), mem.len())
+                            },
+                            None => &[],
+                        };
+                        let response_vec =
+                            protocol::encode_packet(thread_id, &response.to_args(), data);
+                        process.send(&response_vec).unwrap_or_else(|_e| {
+                            // If we're unable to send data to the process, assume it's dead and terminate it.
+                            eprintln!(
+                                "KERNEL({}): Unable to send response to process: {:?} -- terminating",
+                                pid, _e
+                            );
+                            crate::syscall::handle(pid, thread_id, false, SysCall::TerminateProcess)
+                                .ok();
+                        });
+                        crate::arch::process::set_current_pid(existing_pid);
                     }
-                    if let Some(mem) = response.memory() {
-                        let s = unsafe { core::slice::from_raw_parts(mem.as_ptr(), mem.len()) };
-                        response_vec.extend_from_slice(s);
+
+                    if is_shutdown {
+                        exit_sender
+                            .send(ExitMessage::Exit)
+                            .expect("couldn't send shutdown signal");
+                        break 'kernel;
                     }
-                    process.send(&response_vec).unwrap_or_else(|_e| {
-                        // If we're unable to send data to the process, assume it's dead and terminate it.
-                        eprintln!(
-                            "KERNEL({}): Unable to send response to process: {:?} -- terminating",
-                            pid, _e
-                        );
-                        crate::syscall::handle(pid, thread_id, false, SysCall::TerminateProcess)
-                            .ok();
-                    });
-                    crate::arch::process::set_current_pid(existing_pid);
-                }
 
-                if is_shutdown {
-                    exit_sender
-                        .send(ExitMessage::Exit)
-                        .expect("couldn't send shutdown signal");
-                    break;
+                    // This call may have unblocked one of the parked calls, so retry them.
+                    runnable.extend(blocked_calls.drain(..).map(|(pid, tid, args)| {
+                        let call = SysCall::from_args(
+                            args[0], args[1], args[2], args[3], args[4], args[5], args[6],
+                            args[7],
+                        )
+                        .expect("couldn't reconstruct blocked syscall");
+                        (pid, tid, call)
+                    }));
                 }
             }
         }
@@ -2,8 +2,8 @@ pub const MAX_THREAD: TID = 31;
 use crate::services::ProcessInner;
 use core::cell::RefCell;
 use std::io::Write;
-use std::net::TcpStream;
 use std::thread_local;
+use xous_kernel::arch::{protocol, Transport};
 use xous_kernel::{ProcessInit, ProcessKey, ThreadInit, PID, TID};
 
 pub const INITIAL_TID: usize = 1;
@@ -22,7 +22,7 @@ struct ProcessImpl {
     key: ProcessKey,
 
     /// The network connection to the client process.
-    conn: Option<TcpStream>,
+    conn: Option<Box<dyn Transport>>,
 
     /// Memory that may need to be returned to the caller for each thread
     memory_to_return: [Option<Vec<u8>>; MAX_THREAD + 1],
@@ -87,17 +87,23 @@ pub fn set_current_pid(pid: PID) {
     });
 }
 
-pub fn register_connection_for_key(
-    conn: TcpStream,
-    key: ProcessKey,
-) -> Result<PID, xous_kernel::Error> {
+/// Attach `conn` to the first process that is still waiting for a connection
+/// and that satisfies `matches`, which is given the PID and key of each candidate.
+pub fn register_connection<F>(
+    conn: Box<dyn Transport>,
+    matches: F,
+) -> Result<(PID, ProcessKey), xous_kernel::Error>
+where
+    F: Fn(PID, &ProcessKey) -> bool,
+{
     PROCESS_TABLE.with(|pt| {
         let mut process_table = pt.borrow_mut();
         for (pid_minus_1, process) in process_table.table.iter_mut().enumerate() {
+            let pid = PID::new(pid_minus_1 as u8 + 1).unwrap();
             if let Some(process) = process.as_mut() {
-                if process.key == key && process.conn.is_none() {
+                if process.conn.is_none() && matches(pid, &process.key) {
                     process.conn = Some(conn);
-                    return Ok(PID::new(pid_minus_1 as u8 + 1).unwrap());
+                    return Ok((pid, process.key));
                 }
             }
         }
@@ -280,19 +286,11 @@ impl Process {
                 tid,
             );
 
-            let mut response = vec![];
-            // Add the destination thread ID to the start of the packet.
-            response.extend_from_slice(&tid.to_le_bytes());
-
-            // Append the contents of the response packet.
-            for word in result.to_args().iter_mut() {
-                response.extend_from_slice(&word.to_le_bytes());
-            }
-
+            let mut data = vec![];
             if let Some(mem) = result.memory() {
                 let s = unsafe { core::slice::from_raw_parts(mem.as_ptr(), mem.len()) };
                 klog!("adding {} additional bytes from result", s.len());
-                response.extend_from_slice(&s);
+                data.extend_from_slice(s);
             }
 
             // If there is memory to return for this thread, also return that.
@@ -305,13 +303,18 @@ impl Process {
                     buf.len()
                 );
                 klog!("data: {:?}", buf);
-                response.extend_from_slice(&buf);
+                data.extend_from_slice(&buf);
             }
 
+            // The packet is addressed to the destination thread, and carries the
+            // contents of the response followed by any memory.
+            let response = protocol::encode_packet(tid, &result.to_args(), &data);
+
             klog!("setting thread return value to {} bytes", response.len());
             let conn = process.conn.as_mut().unwrap();
             conn.write_all(&response).expect("Disconnection");
             conn.flush().expect("Disconnection");
+            super::trace::record_response(process_table.current, &response);
         });
     }
 
@@ -334,7 +337,7 @@ impl Process {
             let pid_idx = (pid.get() - 1) as usize;
             use crate::filled_array;
             let process = ProcessImpl {
-                inner: Default::default(),
+                inner: ProcessInner::new(),
                 conn: None,
                 key: init_data.key,
                 memory_to_return: filled_array![None; 32 /* MAX_THREAD */],
@@ -362,12 +365,10 @@ impl Process {
                 panic!("attempted to destroy PID that exceeds table index: {}", pid);
             }
             let process = process_table.table[pid_idx].as_mut().unwrap();
-            process
-                .conn
-                .as_mut()
-                .unwrap()
-                .shutdown(std::net::Shutdown::Both)
-                .unwrap();
+            // The kernel's own process never connects, so it has nothing to close.
+            if let Some(conn) = process.conn.as_mut() {
+                conn.close().unwrap();
+            }
             process_table.table[pid_idx] = None;
             process_table.total -= 1;
             Ok(())
@@ -383,6 +384,7 @@ impl Process {
             let conn = process.conn.as_mut().unwrap();
             conn.write_all(bytes).unwrap();
             conn.flush().unwrap();
+            super::trace::record_response(process_table.current, bytes);
         });
         Ok(())
     }
@@ -1,3 +1,3 @@
 pub fn get_u32() -> u32 {
-    ::rand::random()
+    super::trace::random_u32()
 }
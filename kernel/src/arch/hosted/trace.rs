@@ -0,0 +1,576 @@
+//! Recording and replaying the syscall traffic of hosted processes.
+//!
+//! When the kernel is started with `XOUS_TRACE_RECORD` set to a path, it writes
+//! every connection, syscall, response, and random number it generates to that
+//! file. Starting the kernel with `XOUS_TRACE_REPLAY` set to such a file feeds the
+//! recorded syscalls back to the kernel in place of the original processes, in the
+//! order they were originally received, and checks that the kernel responds exactly
+//! as it did before.
+//!
+//! A trace begins with `TRACE_MAGIC` followed by the trace version, the protocol
+//! version, and the size of `usize`, each a 32-bit little-endian value. Each record
+//! after that is a one-byte kind, a 32-bit little-endian ID, a 32-bit little-endian
+//! length, and that many bytes of payload. Syscalls and responses carry the packet
+//! as it appeared on the wire, except that host addresses in responses are zeroed.
+//! These point to buffers the kernel allocated, and differ from one run to the next.
+
+use std::cell::RefCell;
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::mem::size_of;
+use std::path::Path;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread_local;
+use std::time::Duration;
+
+use xous_kernel::arch::protocol;
+use xous_kernel::arch::Transport;
+use xous_kernel::PID;
+
+/// Bytes that open every trace file.
+pub const TRACE_MAGIC: [u8; 4] = *b"XTRC";
+
+/// Increment this whenever the trace format changes.
+pub const TRACE_VERSION: u32 = 2;
+
+const KIND_CONNECT: u8 = 0;
+const KIND_REQUEST: u8 = 1;
+const KIND_RESPONSE: u8 = 2;
+const KIND_DISCONNECT: u8 = 3;
+const KIND_RANDOM: u8 = 4;
+
+/// Connections are numbered in the order they were made, which lets a trace tell
+/// apart two processes that were assigned the same PID at different times.
+pub type ConnectionId = u32;
+
+/// A single record in a trace.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// A process connected and was assigned the given PID.
+    Connect(PID),
+
+    /// A process sent a syscall packet over the given connection.
+    Request(ConnectionId, Vec<u8>),
+
+    /// The kernel sent a response packet to the given process.
+    Response(PID, Vec<u8>),
+
+    /// The given connection was closed.
+    Disconnect(ConnectionId),
+
+    /// The kernel generated a random number.
+    Random(u32),
+}
+
+impl Event {
+    fn encode(&self) -> Vec<u8> {
+        let random;
+        let (kind, id, payload): (u8, u32, &[u8]) = match self {
+            Event::Connect(pid) => (KIND_CONNECT, pid.get() as u32, &[]),
+            Event::Request(conn, packet) => (KIND_REQUEST, *conn, packet),
+            Event::Response(pid, packet) => (KIND_RESPONSE, pid.get() as u32, packet),
+            Event::Disconnect(conn) => (KIND_DISCONNECT, *conn, &[]),
+            Event::Random(value) => {
+                random = value.to_le_bytes();
+                (KIND_RANDOM, 0, &random)
+            }
+        };
+        let mut record = Vec::with_capacity(9 + payload.len());
+        record.push(kind);
+        record.extend_from_slice(&id.to_le_bytes());
+        record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        record.extend_from_slice(payload);
+        record
+    }
+
+    /// Decode the record at the start of `bytes`, returning it and its length.
+    fn decode(bytes: &[u8]) -> io::Result<(Event, usize)> {
+        if bytes.len() < 9 {
+            return Err(invalid_data("trace ends in the middle of a record"));
+        }
+        let kind = bytes[0];
+        let id = u32::from_le_bytes(bytes[1..5].try_into().unwrap());
+        let len = u32::from_le_bytes(bytes[5..9].try_into().unwrap()) as usize;
+        let payload = bytes
+            .get(9..9 + len)
+            .ok_or_else(|| invalid_data("trace ends in the middle of a record"))?;
+        let pid = || {
+            if id > 255 {
+                return Err(invalid_data("trace contains an invalid PID"));
+            }
+            PID::new(id as u8).ok_or_else(|| invalid_data("trace contains an invalid PID"))
+        };
+        let event = match kind {
+            KIND_CONNECT => Event::Connect(pid()?),
+            KIND_REQUEST => Event::Request(id, payload.to_vec()),
+            KIND_RESPONSE => Event::Response(pid()?, payload.to_vec()),
+            KIND_DISCONNECT => Event::Disconnect(id),
+            KIND_RANDOM => Event::Random(u32::from_le_bytes(
+                payload
+                    .try_into()
+                    .map_err(|_| invalid_data("random number is the wrong size"))?,
+            )),
+            _ => return Err(invalid_data("trace contains an unknown record")),
+        };
+        Ok((event, 9 + len))
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Copy a response packet with any host addresses it contains zeroed, so that it
+/// can be compared against one from another run.
+fn normalize_response(packet: &[u8]) -> Vec<u8> {
+    let mut packet = packet.to_vec();
+    let word_size = size_of::<usize>();
+    // Skip the length and the thread ID to reach the result's arguments.
+    let arg_offset = |index: usize| 4 + word_size + index * word_size;
+    let arg = |packet: &[u8], index: usize| {
+        packet
+            .get(arg_offset(index)..arg_offset(index) + word_size)
+            .map(|bytes| usize::from_le_bytes(bytes.try_into().unwrap()))
+    };
+    let address = match (arg(&packet, 0), arg(&packet, 2)) {
+        // `Result::MemoryAddress` and `Result::MemoryRange`
+        (Some(2), _) | (Some(3), _) => Some(1),
+        // `Result::Message` carrying a borrowed or moved buffer
+        (Some(8), Some(kind)) if kind <= 2 => Some(4),
+        _ => None,
+    };
+    if let Some(index) = address {
+        if let Some(word) = packet.get_mut(arg_offset(index)..arg_offset(index) + word_size) {
+            word.iter_mut().for_each(|byte| *byte = 0);
+        }
+    }
+    packet
+}
+
+/// Writes a trace as the kernel runs.
+pub struct Recorder {
+    /// The trace file, along with the number of connections recorded so far.
+    file: Mutex<(File, ConnectionId)>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> io::Result<Recorder> {
+        let mut file = File::create(path)?;
+        let mut header = Vec::with_capacity(16);
+        header.extend_from_slice(&TRACE_MAGIC);
+        header.extend_from_slice(&TRACE_VERSION.to_le_bytes());
+        header.extend_from_slice(&protocol::PROTOCOL_VERSION.to_le_bytes());
+        header.extend_from_slice(&(size_of::<usize>() as u32).to_le_bytes());
+        file.write_all(&header)?;
+        Ok(Recorder {
+            file: Mutex::new((file, 0)),
+        })
+    }
+
+    /// Append `event` to the trace. Each record is written in one piece and
+    /// is not buffered, so the trace is usable even if the kernel crashes.
+    pub fn record(&self, event: &Event) {
+        self.record_then(event, || ())
+    }
+
+    /// Append `event` to the trace, then call `f` before anything else may be
+    /// recorded. This keeps the trace in the same order as the kernel's queue.
+    pub fn record_then<F, R>(&self, event: &Event, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        let mut file = self.file.lock().unwrap();
+        file.0
+            .write_all(&event.encode())
+            .expect("couldn't write to trace file");
+        f()
+    }
+
+    /// Record that a process connected, returning the ID of its connection.
+    pub fn connect(&self, pid: PID) -> ConnectionId {
+        let mut file = self.file.lock().unwrap();
+        file.0
+            .write_all(&Event::Connect(pid).encode())
+            .expect("couldn't write to trace file");
+        file.1 += 1;
+        file.1 - 1
+    }
+}
+
+struct ReplayState {
+    /// The index of the next event to be replayed.
+    cursor: usize,
+
+    /// How much of the syscall at `cursor` has been read so far.
+    offset: usize,
+
+    /// Set once the syscall or disconnection at `cursor` has been read in full.
+    /// The replay moves on once it has been handed to the kernel.
+    read_complete: bool,
+
+    /// The number of connections that have been replayed.
+    connections: ConnectionId,
+
+    /// Set once the kernel does something other than what was recorded.
+    divergence: Option<String>,
+
+    /// Set when the kernel shuts down.
+    stopped: bool,
+}
+
+/// Feeds a recorded trace back into the kernel.
+///
+/// Each event is replayed only once every event before it has been, so syscalls
+/// reach the kernel in exactly the order they were recorded. Syscalls are read by
+/// the connection threads through a `ReplayTransport`, and the kernel's responses
+/// and random numbers are checked against the trace as they are produced.
+pub struct Replay {
+    events: Vec<Event>,
+    state: Mutex<ReplayState>,
+    advanced: Condvar,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> io::Result<Replay> {
+        let mut bytes = vec![];
+        File::open(path)?.read_to_end(&mut bytes)?;
+        Self::parse(&bytes)
+    }
+
+    fn parse(bytes: &[u8]) -> io::Result<Replay> {
+        if bytes.len() < 16 || bytes[0..4] != TRACE_MAGIC {
+            return Err(invalid_data("file is not a Xous trace"));
+        }
+        let word =
+            |index: usize| u32::from_le_bytes(bytes[index * 4..index * 4 + 4].try_into().unwrap());
+        if word(1) != TRACE_VERSION {
+            return Err(invalid_data(
+                "trace was written by a different kernel version",
+            ));
+        }
+        if word(2) != protocol::PROTOCOL_VERSION || word(3) != size_of::<usize>() as u32 {
+            return Err(invalid_data(
+                "trace was recorded with a different wire protocol",
+            ));
+        }
+
+        let mut events = vec![];
+        let mut remaining = &bytes[16..];
+        while !remaining.is_empty() {
+            let (event, len) = Event::decode(remaining)?;
+            events.push(event);
+            remaining = &remaining[len..];
+        }
+        Ok(Replay {
+            events,
+            state: Mutex::new(ReplayState {
+                cursor: 0,
+                offset: 0,
+                read_complete: false,
+                connections: 0,
+                divergence: None,
+                stopped: false,
+            }),
+            advanced: Condvar::new(),
+        })
+    }
+
+    fn is_running(&self, state: &ReplayState) -> bool {
+        !state.stopped && state.divergence.is_none() && state.cursor < self.events.len()
+    }
+
+    fn advance(&self, state: &mut ReplayState) {
+        state.cursor += 1;
+        state.offset = 0;
+        state.read_complete = false;
+        self.advanced.notify_all();
+    }
+
+    fn diverge(&self, state: &mut ReplayState, reason: String) {
+        let reason = format!("record {}: {}", state.cursor, reason);
+        eprintln!("KERNEL: replay diverged from trace at {}", reason);
+        state.divergence = Some(reason);
+        self.advanced.notify_all();
+    }
+
+    /// Wait for the next recorded connection, returning a transport that replays
+    /// its syscalls. Returns `None` once the replay is over.
+    pub fn next_connection(self: &Arc<Self>) -> Option<(PID, Box<dyn Transport>, ConnectionTrace)> {
+        let mut state = self.state.lock().unwrap();
+        let pid = loop {
+            if !self.is_running(&state) {
+                return None;
+            }
+            match self.events[state.cursor] {
+                Event::Connect(pid) => break pid,
+                _ => state = self.advanced.wait(state).unwrap(),
+            }
+        };
+        let conn_id = state.connections;
+        let conn = ReplayTransport {
+            replay: self.clone(),
+            pid,
+            conn: conn_id,
+        };
+        state.connections += 1;
+        self.advance(&mut state);
+        Some((
+            pid,
+            Box::new(conn),
+            ConnectionTrace::Replay(self.clone(), conn_id),
+        ))
+    }
+
+    /// Check a response that the kernel is sending to `pid` against the trace.
+    fn respond(&self, pid: PID, packet: &[u8]) {
+        let packet = normalize_response(packet);
+        let mut state = self.state.lock().unwrap();
+        while self.is_running(&state) {
+            match &self.events[state.cursor] {
+                Event::Response(expected_pid, expected) => {
+                    if *expected_pid == pid && *expected == packet {
+                        self.advance(&mut state);
+                    } else {
+                        let reason = format!(
+                            "kernel sent {:02x?} to PID {}, but {:02x?} was sent to PID {} when recording",
+                            packet, pid, expected, expected_pid
+                        );
+                        self.diverge(&mut state, reason);
+                    }
+                    return;
+                }
+                Event::Random(_) => {
+                    let reason = format!(
+                        "kernel sent a response to PID {} where it generated a random number when recording",
+                        pid
+                    );
+                    self.diverge(&mut state, reason);
+                    return;
+                }
+                _ => state = self.advanced.wait(state).unwrap(),
+            }
+        }
+    }
+
+    /// Produce the random number the kernel generated at this point when recording.
+    fn random(&self) -> u32 {
+        let mut state = self.state.lock().unwrap();
+        while self.is_running(&state) {
+            match &self.events[state.cursor] {
+                Event::Random(value) => {
+                    let value = *value;
+                    self.advance(&mut state);
+                    return value;
+                }
+                Event::Response(pid, _) => {
+                    let reason = format!(
+                        "kernel generated a random number where it sent a response to PID {} when recording",
+                        pid
+                    );
+                    self.diverge(&mut state, reason);
+                }
+                _ => state = self.advanced.wait(state).unwrap(),
+            }
+        }
+        ::rand::random()
+    }
+
+    /// Read the next part of a syscall recorded on connection `conn`, waiting until
+    /// every event before it has been replayed. Returns 0 once the connection was
+    /// closed in the trace, or once the replay is over.
+    fn read(&self, conn: ConnectionId, buf: &mut [u8]) -> usize {
+        let mut state = self.state.lock().unwrap();
+        while self.is_running(&state) {
+            match &self.events[state.cursor] {
+                Event::Request(id, packet) if *id == conn && !state.read_complete => {
+                    let remaining = &packet[state.offset..];
+                    let len = remaining.len().min(buf.len());
+                    buf[..len].copy_from_slice(&remaining[..len]);
+                    state.offset += len;
+                    state.read_complete = state.offset == packet.len();
+                    return len;
+                }
+                Event::Disconnect(id) if *id == conn && !state.read_complete => {
+                    state.read_complete = true;
+                    return 0;
+                }
+                _ => state = self.advanced.wait(state).unwrap(),
+            }
+        }
+        0
+    }
+
+    /// Move past the syscall or disconnection that was just read from connection
+    /// `conn`, now that it has been handed to the kernel.
+    fn delivered(&self, conn: ConnectionId) {
+        let mut state = self.state.lock().unwrap();
+        if !self.is_running(&state) || !state.read_complete {
+            return;
+        }
+        match self.events[state.cursor] {
+            Event::Request(id, _) | Event::Disconnect(id) if id == conn => self.advance(&mut state),
+            _ => (),
+        }
+    }
+
+    /// End the replay, for example because the kernel is shutting down. Any
+    /// connections that are still open will see their stream end.
+    pub fn stop(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.stopped = true;
+        self.advanced.notify_all();
+    }
+
+    /// Determine whether the kernel behaved exactly as it did when the trace
+    /// was recorded. Connections closing after the last syscall are ignored.
+    pub fn result(&self) -> Result<(), String> {
+        let state = self.state.lock().unwrap();
+        if let Some(divergence) = &state.divergence {
+            return Err(divergence.clone());
+        }
+        let unreplayed = self.events[state.cursor..]
+            .iter()
+            .filter(|event| !matches!(event, Event::Connect(_) | Event::Disconnect(_)))
+            .count();
+        if unreplayed > 0 {
+            return Err(format!(
+                "replay stopped with {} records left to replay",
+                unreplayed
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Stands in for the connection of a process that is being replayed. Reads
+/// produce the syscalls that the process made, and writes are checked against
+/// the responses it received.
+struct ReplayTransport {
+    replay: Arc<Replay>,
+    pid: PID,
+    conn: ConnectionId,
+}
+
+impl Read for ReplayTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Ok(self.replay.read(self.conn, buf))
+    }
+}
+
+impl Write for ReplayTransport {
+    /// The kernel writes each response packet with a single call to `write_all()`,
+    /// so each write is a complete packet.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.replay.respond(self.pid, buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn duplicate(&self) -> io::Result<Box<dyn Transport>> {
+        Ok(Box::new(ReplayTransport {
+            replay: self.replay.clone(),
+            pid: self.pid,
+            conn: self.conn,
+        }))
+    }
+
+    /// The stream ends when the trace says it does, so there's nothing to close.
+    fn close(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Replayed connections skip the handshake, so reads are never timed.
+    fn set_read_timeout(&self, _timeout: Option<Duration>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Traces the syscalls that arrive over a single connection.
+pub enum ConnectionTrace {
+    Record(Arc<Recorder>, ConnectionId),
+    Replay(Arc<Replay>, ConnectionId),
+}
+
+impl ConnectionTrace {
+    /// Hand a syscall to the kernel by calling `send`. When recording, `packet` must
+    /// hold the syscall as it arrived, and it is recorded in the same order as the
+    /// kernel receives it. When replaying, the replay moves on to the next event.
+    pub fn syscall<F>(&self, packet: Option<Vec<u8>>, send: F)
+    where
+        F: FnOnce(),
+    {
+        match self {
+            ConnectionTrace::Record(recorder, conn_id) => {
+                let packet = packet.expect("syscall packet is required when recording");
+                recorder.record_then(&Event::Request(*conn_id, packet), send)
+            }
+            ConnectionTrace::Replay(replay, conn_id) => {
+                send();
+                replay.delivered(*conn_id);
+            }
+        }
+    }
+
+    /// Tell the kernel that the connection closed by calling `send`.
+    pub fn disconnect<F>(&self, send: F)
+    where
+        F: FnOnce(),
+    {
+        match self {
+            ConnectionTrace::Record(recorder, conn_id) => {
+                recorder.record_then(&Event::Disconnect(*conn_id), send)
+            }
+            ConnectionTrace::Replay(replay, conn_id) => {
+                send();
+                replay.delivered(*conn_id);
+            }
+        }
+    }
+}
+
+/// Whether the kernel running on this thread is recording or replaying a trace.
+#[derive(Clone)]
+pub enum Trace {
+    Off,
+    Record(Arc<Recorder>),
+    Replay(Arc<Replay>),
+}
+
+thread_local!(static TRACE: RefCell<Trace> = RefCell::new(Trace::Off));
+
+/// Set the trace mode of the kernel running on this thread.
+pub fn set_trace(trace: Trace) {
+    TRACE.with(|t| *t.borrow_mut() = trace);
+}
+
+pub fn trace() -> Trace {
+    TRACE.with(|t| t.borrow().clone())
+}
+
+/// Record a response packet being sent to `pid`, if a recording is in progress.
+pub fn record_response(pid: PID, packet: &[u8]) {
+    TRACE.with(|t| {
+        if let Trace::Record(recorder) = &*t.borrow() {
+            recorder.record(&Event::Response(pid, normalize_response(packet)));
+        }
+    })
+}
+
+/// Generate a random number, recording it or taking it from the trace being replayed.
+pub fn random_u32() -> u32 {
+    match trace() {
+        Trace::Off => ::rand::random(),
+        Trace::Record(recorder) => {
+            let value = ::rand::random();
+            recorder.record(&Event::Random(value));
+            value
+        }
+        Trace::Replay(replay) => replay.random(),
+    }
+}
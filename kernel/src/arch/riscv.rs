@@ -2,11 +2,17 @@ use riscv::register::{satp, sie, sstatus};
 use xous_kernel::PID;
 
 pub mod exception;
+#[cfg(feature = "qemu-virt")]
+pub mod finisher;
 pub mod irq;
 pub mod mem;
+#[cfg(feature = "qemu-virt")]
+pub mod plic;
 pub mod process;
 pub mod rand;
 pub mod syscall;
+#[cfg(feature = "qemu-virt")]
+pub mod timer;
 
 pub use process::Thread;
 
@@ -15,6 +21,12 @@ pub fn current_pid() -> PID {
 }
 
 pub fn init() {
+    #[cfg(feature = "qemu-virt")]
+    {
+        plic::init();
+        timer::init();
+        finisher::init();
+    }
     unsafe {
         sstatus::set_sie();
         sie::set_ssoft();
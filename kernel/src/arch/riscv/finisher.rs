@@ -0,0 +1,36 @@
+//! The `sifive_test` finisher on the QEMU `virt` machine, which stops QEMU
+//! with an exit status of our choosing.
+
+use crate::mem::MemoryManager;
+use utralib::generated::*;
+use xous_kernel::{MemoryFlags, MemoryType, PID};
+
+/// Where the finisher is mapped, just above the PLIC pages in the top 4 MiB
+/// that is shared among all processes.
+const VIRT: usize = 0xffcf_4000;
+
+const STATUS_PASS: u32 = 0x5555;
+
+pub fn init() {
+    MemoryManager::with_mut(|memory_manager| {
+        memory_manager
+            .map_range(
+                utra::test::HW_TEST_BASE as *mut u8,
+                VIRT as *mut u8,
+                4096,
+                PID::new(1).unwrap(),
+                MemoryFlags::R | MemoryFlags::W,
+                MemoryType::Default,
+            )
+            .expect("unable to map test finisher")
+    });
+}
+
+/// Exit QEMU with a status of 0.
+pub fn pass() -> ! {
+    CSR::new(VIRT as *mut u32).wfo(utra::test::FINISHER_STATUS, STATUS_PASS);
+    // QEMU stops as soon as the write lands
+    loop {
+        unsafe { riscv::asm::wfi() };
+    }
+}
@@ -4,7 +4,9 @@ use crate::arch::process::Process as ArchProcess;
 use crate::arch::process::{Thread, RETURN_FROM_ISR};
 use crate::mem::{MemoryManager, PAGE_SIZE};
 use crate::services::SystemServices;
-use riscv::register::{scause, sepc, sie, sstatus, stval, vexriscv::sim, vexriscv::sip};
+use riscv::register::{scause, sepc, sie, sstatus, stval};
+#[cfg(not(feature = "qemu-virt"))]
+use riscv::register::{vexriscv::sim, vexriscv::sip};
 use xous_kernel::{SysCall, PID, TID};
 
 extern "Rust" {
@@ -18,24 +20,78 @@ extern "C" {
 /// Disable external interrupts
 pub fn disable_all_irqs() {
     unsafe { sie::clear_sext() };
+    #[cfg(feature = "qemu-virt")]
+    unsafe {
+        sie::clear_stimer()
+    };
 }
 
 /// Enable external interrupts
 pub fn enable_all_irqs() {
     unsafe { sie::set_sext() };
+    #[cfg(feature = "qemu-virt")]
+    {
+        if super::timer::running() {
+            unsafe { sie::set_stimer() };
+        }
+    }
 }
 
+#[cfg(not(feature = "qemu-virt"))]
 pub fn enable_irq(irq_no: usize) {
     // Note that the vexriscv "IRQ Mask" register is inverse-logic --
     // that is, setting a bit in the "mask" register unmasks (i.e. enables) it.
     sim::write(sim::read() | (1 << irq_no));
 }
 
+#[cfg(not(feature = "qemu-virt"))]
 pub fn disable_irq(irq_no: usize) -> Result<(), xous_kernel::Error> {
     sim::write(sim::read() & !(1 << irq_no));
     Ok(())
 }
 
+/// The vexriscv reports every pending IRQ at once.
+#[cfg(not(feature = "qemu-virt"))]
+fn pending_irqs(_cause: scause::Scause) -> usize {
+    sip::read()
+}
+
+#[cfg(feature = "qemu-virt")]
+pub fn enable_irq(irq_no: usize) {
+    if irq_no == super::timer::TIMER_IRQ {
+        super::timer::start();
+    } else {
+        super::plic::enable(irq_no);
+    }
+}
+
+#[cfg(feature = "qemu-virt")]
+pub fn disable_irq(irq_no: usize) -> Result<(), xous_kernel::Error> {
+    if irq_no == super::timer::TIMER_IRQ {
+        super::timer::stop();
+    } else {
+        super::plic::disable(irq_no);
+    }
+    Ok(())
+}
+
+/// On QEMU `virt` the timer has its own cause, and external interrupts are
+/// claimed from the PLIC one at a time.
+#[cfg(feature = "qemu-virt")]
+fn pending_irqs(cause: scause::Scause) -> usize {
+    use scause::{Interrupt, Trap};
+    match cause.cause() {
+        Trap::Interrupt(Interrupt::SupervisorTimer) => {
+            super::timer::rearm();
+            1 << super::timer::TIMER_IRQ
+        }
+        Trap::Interrupt(Interrupt::SupervisorExternal) => {
+            super::plic::claim().map(|irq_no| 1 << irq_no).unwrap_or(0)
+        }
+        _ => 0,
+    }
+}
+
 static mut PREVIOUS_PAIR: Option<(PID, TID)> = None;
 
 pub unsafe fn set_isr_return_pair(pid: PID, tid: TID) {
@@ -208,7 +264,7 @@ pub extern "C" fn trap_handler(
         MemoryMapping::current().print_map();
         loop {}
     } else {
-        let irqs_pending = sip::read();
+        let irqs_pending = pending_irqs(sc);
         // Safe to access globals since interrupts are disabled
         // when this function runs.
         unsafe {
@@ -1,9 +1,9 @@
 use crate::mem::MemoryManager;
 use core::fmt;
 use riscv::register::satp;
-use xous_kernel::{MemoryFlags, PID};
+use xous_kernel::{MemoryFlags, MemoryRange, PID};
 
-// pub const DEFAULT_STACK_TOP: usize = 0x8000_0000;
+pub const DEFAULT_STACK_TOP: usize = 0x8000_0000;
 pub const DEFAULT_HEAP_BASE: usize = 0x2000_0000;
 pub const DEFAULT_MESSAGE_BASE: usize = 0x4000_0000;
 pub const DEFAULT_BASE: usize = 0x6000_0000;
@@ -12,6 +12,7 @@ pub const USER_AREA_END: usize = 0xff00_0000;
 pub const PAGE_SIZE: usize = 4096;
 const PAGE_TABLE_OFFSET: usize = 0xff40_0000;
 const PAGE_TABLE_ROOT_OFFSET: usize = 0xff80_0000;
+const CONTEXT_OFFSET: usize = 0xff80_1000;
 
 extern "C" {
     fn flush_mmu();
@@ -92,6 +93,79 @@ impl MemoryMapping {
         self.satp = satp;
     }
 
+    /// Create a new, empty address space for `pid`.  The kernel is mapped into
+    /// it, as are its root pagetable and an empty context page, so it can be
+    /// activated immediately.
+    pub fn allocate(mm: &mut MemoryManager, pid: PID) -> Result<MemoryMapping, xous_kernel::Error> {
+        // Every page allocated here belongs to `pid`, so a failure partway
+        // through is undone by releasing all of them.
+        Self::allocate_pages(mm, pid).map_err(|e| {
+            mm.release_all(pid);
+            e
+        })
+    }
+
+    fn allocate_pages(
+        mm: &mut MemoryManager,
+        pid: PID,
+    ) -> Result<MemoryMapping, xous_kernel::Error> {
+        let root_phys = mm.alloc_page(pid)?;
+        let pagetables_phys = mm.alloc_page(pid)?;
+        let context_table_phys = mm.alloc_page(pid)?;
+        let context_phys = mm.alloc_page(pid)?;
+
+        let table_entry = |phys: usize| ((phys >> 12) << 10) | MMUFlags::VALID.bits();
+        let leaf_entry = |phys: usize| {
+            ((phys >> 12) << 10)
+                | (MMUFlags::VALID | MMUFlags::R | MMUFlags::W | MMUFlags::D | MMUFlags::A).bits()
+        };
+        let vpn1 = |virt: usize| (virt >> 22) & ((1 << 10) - 1);
+        let vpn0 = |virt: usize| (virt >> 12) & ((1 << 10) - 1);
+
+        // Every process shares the kernel's pagetable for the top 4 MB.
+        let l1_pt = unsafe { &(*(PAGE_TABLE_ROOT_OFFSET as *const RootPageTable)) };
+        let kernel_entry = l1_pt.entries[1023];
+
+        write_page_table(
+            mm,
+            root_phys,
+            &[
+                (vpn1(PAGE_TABLE_OFFSET), table_entry(pagetables_phys)),
+                (
+                    vpn1(PAGE_TABLE_ROOT_OFFSET),
+                    table_entry(context_table_phys),
+                ),
+                (1023, kernel_entry),
+            ],
+        )?;
+
+        // Leaf pagetables are mapped at `PAGE_TABLE_OFFSET`, including the
+        // leaf pagetable that does this mapping.
+        write_page_table(
+            mm,
+            pagetables_phys,
+            &[
+                (vpn1(PAGE_TABLE_OFFSET), leaf_entry(pagetables_phys)),
+                (vpn1(PAGE_TABLE_ROOT_OFFSET), leaf_entry(context_table_phys)),
+            ],
+        )?;
+
+        // The root pagetable and the process context sit next to each other.
+        write_page_table(
+            mm,
+            context_table_phys,
+            &[
+                (vpn0(PAGE_TABLE_ROOT_OFFSET), leaf_entry(root_phys)),
+                (vpn0(CONTEXT_OFFSET), leaf_entry(context_phys)),
+            ],
+        )?;
+        write_page_table(mm, context_phys, &[])?;
+
+        Ok(MemoryMapping {
+            satp: 0x8000_0000 | ((pid.get() as usize) << 22) | (root_phys >> 12),
+        })
+    }
+
     /// Get the currently active memory mapping.  Note that the actual root pages
     /// may be found at virtual address `PAGE_TABLE_ROOT_OFFSET`.
     pub fn current() -> MemoryMapping {
@@ -174,7 +248,9 @@ impl MemoryMapping {
         // println!("Reserving memory address {:08x} with flags {:?}", addr, flags);
         // Allocate a new level 1 pagetable entry if one doesn't exist.
         if l1_pt.entries[vpn1] & MMUFlags::VALID.bits() == 0 {
-            let pid = crate::arch::current_pid();
+            // The pagetable belongs to the process that owns this address
+            // space, which may not be the one that's running.
+            let pid = self.get_pid();
             // Allocate a fresh page
             let l0pt_phys = mm.alloc_page(pid)?;
 
@@ -210,6 +286,40 @@ impl MemoryMapping {
 
 pub const DEFAULT_MEMORY_MAPPING: MemoryMapping = MemoryMapping { satp: 0 };
 
+/// Zero out the physical page `phys` and fill in the given `(index, entry)`
+/// pairs.  The page is mapped into the current process just long enough
+/// to write it.
+fn write_page_table(
+    mm: &mut MemoryManager,
+    phys: usize,
+    entries: &[(usize, usize)],
+) -> Result<(), xous_kernel::Error> {
+    let virt = mm.find_virtual_address(
+        core::ptr::null_mut(),
+        PAGE_SIZE,
+        xous_kernel::MemoryType::Default,
+    )? as usize;
+    map_page_inner(
+        mm,
+        crate::arch::current_pid(),
+        phys,
+        virt,
+        MemoryFlags::R | MemoryFlags::W,
+        false,
+    )?;
+
+    let table = unsafe { &mut (*(virt as *mut LeafPageTable)) };
+    for entry in table.entries.iter_mut() {
+        *entry = 0;
+    }
+    for (index, entry) in entries {
+        table.entries[*index] = *entry;
+    }
+
+    unmap_page_inner(mm, virt)?;
+    Ok(())
+}
+
 /// A single RISC-V page table entry.  In order to resolve an address,
 /// we need two entries: the top level, followed by the lower level.
 struct RootPageTable {
@@ -450,6 +560,128 @@ pub fn move_page_inner(
     result
 }
 
+/// Move the pages in `src` out of the current address space and into
+/// `dest_space` starting at `dest_addr`, giving them the permissions in
+/// `flags`.  Pages that have only been reserved in the current address space
+/// are reserved in `dest_space`, and get allocated when they are first used.
+/// If any page can't be moved, the ones that were are returned, and the
+/// current address space is left as it was.
+pub fn move_pages_to(
+    mm: &mut MemoryManager,
+    src: MemoryRange,
+    dest_pid: PID,
+    dest_space: &MemoryMapping,
+    dest_addr: usize,
+    flags: MemoryFlags,
+) -> Result<(), xous_kernel::Error> {
+    let src_addr = src.as_ptr() as usize;
+    for offset in (0..src.len()).step_by(PAGE_SIZE) {
+        if let Err(e) = move_page_to(
+            mm,
+            src_addr + offset,
+            dest_pid,
+            dest_space,
+            dest_addr + offset,
+            flags,
+        ) {
+            return_pages(mm, src_addr, offset, dest_pid, dest_space, dest_addr);
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+/// Move a single page for `move_pages_to()`.  Nothing is changed if this fails.
+fn move_page_to(
+    mm: &mut MemoryManager,
+    src_addr: usize,
+    dest_pid: PID,
+    dest_space: &MemoryMapping,
+    dest_addr: usize,
+    flags: MemoryFlags,
+) -> Result<(), xous_kernel::Error> {
+    let src_space = MemoryMapping::current();
+    let src_pid = src_space.get_pid();
+    let previous_entry = *pagetable_entry(src_addr)?;
+    if previous_entry == 0 {
+        return Err(xous_kernel::Error::BadAddress);
+    }
+    if previous_entry & MMUFlags::S.bits() != 0 {
+        return Err(xous_kernel::Error::ShareViolation);
+    }
+
+    let valid = previous_entry & MMUFlags::VALID.bits() != 0;
+    let phys = previous_entry >> 10 << 12;
+    if valid {
+        mm.transfer_page(phys, src_pid, dest_pid)?;
+    }
+
+    let result = dest_space.activate().and_then(|_| {
+        if valid {
+            map_page_inner(mm, dest_pid, phys, dest_addr, flags, true)
+        } else {
+            let mut dest_space = *dest_space;
+            dest_space.reserve_address(mm, dest_addr, flags)
+        }
+    });
+    src_space.activate().unwrap();
+    if let Err(e) = result {
+        if valid {
+            mm.transfer_page(phys, dest_pid, src_pid)
+                .expect("couldn't take back page");
+        }
+        return Err(e);
+    }
+
+    // The pagetable is only reachable once the current address space is
+    // active again.
+    *pagetable_entry(src_addr)? = 0;
+    unsafe { flush_mmu() };
+    Ok(())
+}
+
+/// Undo `move_pages_to()`, moving the pages at `dest_addr` in `dest_space`
+/// back to `src_addr` in the current address space.  Pages that were never
+/// moved are skipped.  Pages come back readable and writable, as they were
+/// when they were staged.
+pub fn return_pages(
+    mm: &mut MemoryManager,
+    src_addr: usize,
+    len: usize,
+    dest_pid: PID,
+    dest_space: &MemoryMapping,
+    dest_addr: usize,
+) {
+    let src_space = MemoryMapping::current();
+    let src_pid = src_space.get_pid();
+    for offset in (0..len).step_by(PAGE_SIZE) {
+        dest_space.activate().unwrap();
+        let moved_entry = match pagetable_entry(dest_addr + offset) {
+            Ok(entry) => core::mem::replace(entry, 0),
+            Err(_) => 0,
+        };
+        unsafe { flush_mmu() };
+        src_space.activate().unwrap();
+        if moved_entry == 0 {
+            continue;
+        }
+
+        let flags = translate_flags(MemoryFlags::R | MemoryFlags::W);
+        let entry = if moved_entry & MMUFlags::VALID.bits() != 0 {
+            let phys = moved_entry >> 10 << 12;
+            mm.transfer_page(phys, dest_pid, src_pid)
+                .expect("couldn't return page");
+            (moved_entry & !((1 << 10) - 1))
+                | (flags | MMUFlags::USER | MMUFlags::VALID | MMUFlags::D | MMUFlags::A).bits()
+        } else {
+            flags.bits()
+        };
+        // The page was moved out of here, so its pagetable must still exist.
+        *pagetable_entry(src_addr + offset).expect("staged page has no pagetable") = entry;
+        unsafe { flush_mmu() };
+    }
+}
+
 /// Mark the given virtual address as being lent.  If `writable`, clear the
 /// `valid` bit so that this process can't accidentally write to this page while
 /// it is lent.
@@ -0,0 +1,76 @@
+//! The platform-level interrupt controller on the QEMU `virt` machine.
+//! PLIC sources 1-31 are delivered as the IRQ with the same number.
+
+use crate::mem::MemoryManager;
+use utralib::generated::*;
+use xous_kernel::{MemoryFlags, MemoryType, PID};
+
+/// The PLIC pages the kernel uses, as their offset within the PLIC and the
+/// address they are mapped to. These sit just above the debug UART, in the
+/// top 4 MiB that is shared among all processes.
+const PAGES: [(usize, usize); 3] = [
+    // Source priorities
+    (0x0000_0000, 0xffcf_1000),
+    // Supervisor enables
+    (0x0000_2000, 0xffcf_2000),
+    // Supervisor threshold and claim
+    (0x0020_1000, 0xffcf_3000),
+];
+
+/// Access the page holding `register` as if the whole PLIC were mapped.
+fn plic<A>(register: Register<A>) -> CSR<u32> {
+    let offset = register.offset() * 4;
+    let (page, virt) = PAGES
+        .iter()
+        .find(|(page, _)| *page == offset & !4095)
+        .expect("PLIC register is not mapped");
+    CSR::new(virt.wrapping_sub(*page) as *mut u32)
+}
+
+/// Map the PLIC and mask every source.
+pub fn init() {
+    MemoryManager::with_mut(|memory_manager| {
+        for (page, virt) in PAGES.iter() {
+            memory_manager
+                .map_range(
+                    (utra::plic::HW_PLIC_BASE + page) as *mut u8,
+                    *virt as *mut u8,
+                    4096,
+                    PID::new(1).unwrap(),
+                    MemoryFlags::R | MemoryFlags::W,
+                    MemoryType::Default,
+                )
+                .expect("unable to map PLIC");
+        }
+    });
+    plic(utra::plic::SENABLE).wo(utra::plic::SENABLE, 0);
+    plic(utra::plic::STHRESHOLD).wfo(utra::plic::STHRESHOLD_STHRESHOLD, 0);
+}
+
+pub fn enable(irq_no: usize) {
+    plic(utra::plic::PRIORITY[irq_no]).wfo(utra::plic::PRIORITY_PRIORITY[irq_no], 1);
+    let mut enable = plic(utra::plic::SENABLE);
+    let enabled = enable.r(utra::plic::SENABLE);
+    enable.wo(utra::plic::SENABLE, enabled | (1 << irq_no));
+}
+
+pub fn disable(irq_no: usize) {
+    let mut enable = plic(utra::plic::SENABLE);
+    let enabled = enable.r(utra::plic::SENABLE);
+    enable.wo(utra::plic::SENABLE, enabled & !(1 << irq_no));
+    plic(utra::plic::PRIORITY[irq_no]).wfo(utra::plic::PRIORITY_PRIORITY[irq_no], 0);
+}
+
+/// Claim the highest-priority pending source and complete it straight away.
+/// The source will be raised again once the handler has returned if the
+/// device still needs attention.
+pub fn claim() -> Option<usize> {
+    let mut claim = plic(utra::plic::SCLAIM);
+    match claim.r(utra::plic::SCLAIM) as usize {
+        0 => None,
+        irq_no => {
+            claim.wo(utra::plic::SCLAIM, irq_no as u32);
+            Some(irq_no)
+        }
+    }
+}
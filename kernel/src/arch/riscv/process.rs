@@ -3,9 +3,12 @@ static mut PROCESS: *mut ProcessImpl = 0xff80_1000 as *mut ProcessImpl;
 pub const MAX_THREAD: TID = 31;
 pub const INITIAL_TID: TID = 1;
 pub const IRQ_TID: TID = 0;
-use crate::arch::mem::PAGE_SIZE;
+use crate::arch::mem::{
+    move_pages_to, return_pages, MemoryMapping, DEFAULT_STACK_TOP, PAGE_SIZE, USER_AREA_END,
+};
+use crate::mem::MemoryManager;
 use crate::services::ProcessInner;
-use xous_kernel::{ProcessInit, ThreadInit, PID, TID};
+use xous_kernel::{MemoryFlags, MemoryRange, ProcessInit, ThreadInit, PID, TID};
 
 // use crate::args::KernelArguments;
 pub const DEFAULT_STACK_SIZE: usize = 131072;
@@ -83,7 +86,8 @@ static mut PROCESS_TABLE: ProcessTable = ProcessTable {
 #[repr(C)]
 #[cfg(baremetal)]
 /// The stage1 bootloader sets up some initial processes.  These are reported
-/// to us as (satp, entrypoint, sp) tuples, which can be turned into a structure.
+/// to us as (satp, entrypoint, sp, stack_size, heap_max, name) tuples, which
+/// can be turned into a structure.
 /// The first element is always the kernel.
 pub struct InitialProcess {
     /// The RISC-V SATP value, which includes the offset of the root page
@@ -95,6 +99,15 @@ pub struct InitialProcess {
 
     /// Address of the top of the stack
     pub sp: usize,
+
+    /// Size of the main thread's stack, or 0 for `DEFAULT_STACK_SIZE`
+    pub stack_size: usize,
+
+    /// Largest size the heap may grow to, or 0 for `DEFAULT_HEAP_MAX`
+    pub heap_max: usize,
+
+    /// Name of the process, padded with zeroes
+    pub name: [u8; crate::services::PROCESS_NAME_LENGTH],
 }
 
 #[repr(C)]
@@ -309,7 +322,7 @@ impl Process {
             }
         }
 
-        process.inner = Default::default();
+        process.inner = ProcessInner::new();
 
         // Mark the stack as "unallocated-but-free"
         let init_sp = (thread_init.stack.as_ptr() as usize) & !0xfff;
@@ -385,8 +398,118 @@ impl Process {
         );
     }
 
-    pub fn create(_pid: PID, _init_data: ProcessInit) -> PID {
-        todo!();
+    /// Create a new address space for `pid` and move the program described by
+    /// `init_data` into it.  Returns the new address space, along with the
+    /// initial thread that gets started when the process is first scheduled.
+    pub fn create(
+        pid: PID,
+        init_data: ProcessInit,
+    ) -> Result<(MemoryMapping, ThreadInit), xous_kernel::Error> {
+        let pid_idx = pid.get() as usize - 1;
+        if unsafe { PROCESS_TABLE.table[pid_idx] } {
+            return Err(xous_kernel::Error::InternalError);
+        }
+
+        #[cfg(feature = "aslr")]
+        let stack_top = DEFAULT_STACK_TOP - crate::services::aslr_offset();
+        #[cfg(not(feature = "aslr"))]
+        let stack_top = DEFAULT_STACK_TOP;
+        let stack_base = stack_top - DEFAULT_STACK_SIZE;
+        let stack = MemoryRange::new(stack_base, DEFAULT_STACK_SIZE)?;
+        let regions = [
+            (
+                Some(init_data.text),
+                Some(init_data.text_destination),
+                MemoryFlags::R | MemoryFlags::X,
+            ),
+            (
+                init_data.data,
+                init_data.data_destination,
+                MemoryFlags::R | MemoryFlags::W,
+            ),
+        ];
+
+        // Ensure the program fits in userspace without overlapping the stack
+        // or itself, before anything gets moved.
+        let mut destinations = [(stack_base, stack_top), (0, 0), (0, 0)];
+        for (index, (src, dest, _)) in regions.iter().enumerate() {
+            let (src, dest) = match (src, dest) {
+                (Some(src), Some(dest)) => (src, dest.get()),
+                (None, None) => continue,
+                _ => return Err(xous_kernel::Error::InvalidSyscall),
+            };
+            if (src.as_ptr() as usize | src.len() | dest) & (PAGE_SIZE - 1) != 0 {
+                return Err(xous_kernel::Error::BadAlignment);
+            }
+            let src_end = src.as_ptr() as usize + src.len();
+            let dest_end = dest
+                .checked_add(src.len())
+                .ok_or(xous_kernel::Error::BadAddress)?;
+            if src_end > USER_AREA_END || dest_end > USER_AREA_END {
+                return Err(xous_kernel::Error::BadAddress);
+            }
+            for (start, end) in destinations.iter() {
+                if dest < *end && *start < dest_end {
+                    return Err(xous_kernel::Error::BadAddress);
+                }
+            }
+            destinations[index + 1] = (dest, dest_end);
+        }
+
+        // If anything goes wrong from here on, the program is handed back to
+        // the caller and everything the new process was given is freed.
+        let abandon = |mm: &mut MemoryManager, mapping: &MemoryMapping| {
+            for (src, dest, _) in regions.iter() {
+                if let (Some(src), Some(dest)) = (src, dest) {
+                    return_pages(mm, src.as_ptr() as usize, src.len(), pid, mapping, dest.get());
+                }
+            }
+            mm.release_all(pid);
+        };
+
+        let mapping = MemoryManager::with_mut(|mm| {
+            let mapping = MemoryMapping::allocate(mm, pid)?;
+            for (src, dest, flags) in regions.iter() {
+                if let (Some(src), Some(dest)) = (src, dest) {
+                    if let Err(e) = move_pages_to(mm, *src, pid, &mapping, dest.get(), *flags) {
+                        abandon(mm, &mapping);
+                        return Err(e);
+                    }
+                }
+            }
+            Ok(mapping)
+        })?;
+
+        // Prepare the context and the stack from within the new process.
+        let current_mapping = MemoryMapping::current();
+        let stack_result = mapping.activate().and_then(|_| {
+            let process = unsafe { &mut *PROCESS };
+            process.inner = ProcessInner::new();
+            process.inner.pid = pid;
+            MemoryManager::with_mut(|mm| {
+                let mut mapping = mapping;
+                for addr in (stack_base..stack_top).step_by(PAGE_SIZE) {
+                    mapping.reserve_address(mm, addr, MemoryFlags::R | MemoryFlags::W)?;
+                }
+                Ok(())
+            })
+        });
+        current_mapping.activate().unwrap();
+        if let Err(e) = stack_result {
+            MemoryManager::with_mut(|mm| abandon(mm, &mapping));
+            return Err(e);
+        }
+
+        unsafe { PROCESS_TABLE.table[pid_idx] = true };
+        Ok((
+            mapping,
+            ThreadInit::new(
+                unsafe { core::mem::transmute::<usize, _>(init_data.start.get()) },
+                stack,
+                None,
+                [0u8; 12],
+            ),
+        ))
     }
 
     pub fn destroy(_pid: PID) -> Result<(), xous_kernel::Error> {
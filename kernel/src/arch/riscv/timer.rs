@@ -0,0 +1,55 @@
+//! The supervisor timer on the QEMU `virt` machine, driven through the
+//! Sstc `stimecmp` CSR.  While IRQ 0 is claimed it fires every `TICK_MS`.
+
+use riscv::register::sie;
+use utralib::generated::*;
+
+/// The IRQ number that timer ticks are delivered on
+pub const TIMER_IRQ: usize = utra::clint::TIMER_IRQ;
+
+/// Frequency of the `time` CSR
+const TIMEBASE_HZ: u64 = 10_000_000;
+
+/// Milliseconds between ticks
+const TICK_MS: u64 = 10;
+
+extern "C" {
+    fn read_time() -> u64;
+    fn write_stimecmp(lo: u32, hi: u32);
+}
+
+static mut RUNNING: bool = false;
+
+fn set_compare(value: u64) {
+    unsafe { write_stimecmp(value as u32, (value >> 32) as u32) };
+}
+
+/// Push the compare value out of reach so the timer never fires.
+pub fn init() {
+    set_compare(u64::MAX);
+}
+
+pub fn start() {
+    unsafe { RUNNING = true };
+    rearm();
+    unsafe { sie::set_stimer() };
+}
+
+pub fn stop() {
+    unsafe {
+        sie::clear_stimer();
+        RUNNING = false;
+    }
+    set_compare(u64::MAX);
+}
+
+/// Whether a tick handler is installed
+pub fn running() -> bool {
+    unsafe { RUNNING }
+}
+
+/// Schedule the next tick, which also clears the pending one.
+pub fn rearm() {
+    let now = unsafe { read_time() };
+    set_compare(now + TIMEBASE_HZ * TICK_MS / 1000);
+}
@@ -1,6 +1,6 @@
 #[cfg(baremetal)]
 use core::fmt::{Error, Write};
-#[cfg(baremetal)]
+#[cfg(all(baremetal, not(feature = "qemu-virt")))]
 use utralib::generated::*;
 
 #[macro_use]
@@ -52,7 +52,7 @@ pub struct Uart {
     pub base: *mut usize,
 }
 
-#[cfg(baremetal)]
+#[cfg(all(baremetal, not(feature = "qemu-virt")))]
 impl Uart {
     #[allow(dead_code)]
     pub fn enable_rx(self) {
@@ -85,6 +85,47 @@ impl Uart {
     }
 }
 
+/// The QEMU `virt` UART is a 16550, whose registers are one byte apart.
+#[cfg(all(baremetal, feature = "qemu-virt"))]
+impl Uart {
+    const RBR_THR: usize = 0;
+    const IER: usize = 1;
+    const LSR: usize = 5;
+    const IER_ERBFI: u8 = 1 << 0;
+    const LSR_DR: u8 = 1 << 0;
+    const LSR_THRE: u8 = 1 << 5;
+
+    fn reg(&self, offset: usize) -> *mut u8 {
+        (self.base as usize + offset) as *mut u8
+    }
+
+    #[allow(dead_code)]
+    pub fn enable_rx(self) {
+        unsafe { self.reg(Self::IER).write_volatile(Self::IER_ERBFI) };
+    }
+
+    pub fn putc(&self, c: u8) {
+        unsafe {
+            // Wait until the transmit holding register is empty
+            while self.reg(Self::LSR).read_volatile() & Self::LSR_THRE == 0 {
+                ()
+            }
+            self.reg(Self::RBR_THR).write_volatile(c);
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn getc(&self) -> Option<u8> {
+        unsafe {
+            if self.reg(Self::LSR).read_volatile() & Self::LSR_DR == 0 {
+                None
+            } else {
+                Some(self.reg(Self::RBR_THR).read_volatile())
+            }
+        }
+    }
+}
+
 #[cfg(all(not(test), baremetal, any(feature = "debug-print", feature = "print-panics")))]
 pub fn irq(_irq_number: usize, _arg: *mut usize) {
     println!(
@@ -7,6 +7,11 @@ use crate::arch::process::Process;
 
 use xous_kernel::{MemoryFlags, MemoryRange, PID};
 
+/// Owner the loader gives to pages of RAM that failed its memory test, so
+/// that they're never allocated.  No process can have this ID.
+#[cfg(baremetal)]
+pub const BAD_PAGE_PID: u8 = 255;
+
 #[derive(Debug)]
 enum ClaimOrRelease {
     Claim,
@@ -50,6 +55,25 @@ pub struct MemoryManager {
     ram_name: u32,
     #[allow(dead_code)]
     last_ram_page: usize,
+
+    /// Number of RAM pages the loader tested, and the number that failed,
+    /// as reported in the `MemT` tag
+    #[allow(dead_code)]
+    tested_pages: usize,
+    #[allow(dead_code)]
+    bad_pages: usize,
+
+    /// Physical address and size of the loader's boot log, as reported in
+    /// the `BLog` tag, or 0 if there is no log
+    #[allow(dead_code)]
+    boot_log_start: usize,
+    #[allow(dead_code)]
+    boot_log_size: usize,
+
+    /// Physical address of the booted slot's `confirmed` word, as reported
+    /// in the `BSlt` tag, or 0 if the image didn't come from a boot slot
+    #[allow(dead_code)]
+    boot_slot_confirmed: usize,
 }
 
 impl Default for MemoryManager {
@@ -80,6 +104,11 @@ impl MemoryManager {
             ram_size: 0,
             ram_name: 0,
             last_ram_page: 0,
+            tested_pages: 0,
+            bad_pages: 0,
+            boot_log_start: 0,
+            boot_log_size: 0,
+            boot_slot_confirmed: 0,
         }
     }
 
@@ -135,8 +164,21 @@ impl MemoryManager {
         self.ram_name = xarg_def.data[4];
 
         let mut mem_size = self.ram_size / PAGE_SIZE;
+        let mut reported_bad_pages: &[u32] = &[];
         for tag in args_iter {
-            if tag.name == make_type!("MREx") {
+            if tag.name == make_type!("MemT") {
+                assert!(tag.data.len() >= 2, "mm: MemT tag is too short");
+                self.tested_pages = tag.data[0] as usize;
+                self.bad_pages = tag.data[1] as usize;
+                reported_bad_pages = &tag.data[2..];
+            } else if tag.name == make_type!("BLog") {
+                assert!(tag.data.len() >= 2, "mm: BLog tag is too short");
+                self.boot_log_start = tag.data[0] as usize;
+                self.boot_log_size = tag.data[1] as usize;
+            } else if tag.name == make_type!("BSlt") {
+                assert!(!tag.data.is_empty(), "mm: BSlt tag is too short");
+                self.boot_slot_confirmed = tag.data[0] as usize;
+            } else if tag.name == make_type!("MREx") {
                 unsafe {
                     assert!(
                         EXTRA_REGIONS.is_empty(),
@@ -161,6 +203,18 @@ impl MemoryManager {
         unsafe {
             MEMORY_ALLOCATIONS = slice::from_raw_parts_mut(base as *mut Option<PID>, mem_size)
         };
+
+        // The loader should already have reserved these, but make sure
+        // that pages known to be bad are never handed out.
+        for addr in reported_bad_pages {
+            let addr = *addr as usize;
+            if addr >= self.ram_start && addr < self.ram_start + self.ram_size {
+                unsafe {
+                    MEMORY_ALLOCATIONS[(addr - self.ram_start) / PAGE_SIZE] =
+                        PID::new(BAD_PAGE_PID);
+                }
+            }
+        }
         Ok(())
     }
 
@@ -169,6 +223,12 @@ impl MemoryManager {
         println!("Ownership ({} bytes in all):", unsafe {
             MEMORY_ALLOCATIONS.len()
         });
+        if self.tested_pages != 0 {
+            println!(
+                "    {} of {} tested pages failed the memory test",
+                self.bad_pages, self.tested_pages
+            );
+        }
 
         let mut offset = 0;
         unsafe {
@@ -410,6 +470,75 @@ impl MemoryManager {
         Ok(virt)
     }
 
+    /// Map the loader's boot log read-only into process `pid`, which must
+    /// be the current process.  The pages remain owned by the kernel, so
+    /// the process can't write, free or lend them.
+    #[cfg(baremetal)]
+    pub fn map_boot_log(&mut self, pid: PID) -> Result<MemoryRange, xous_kernel::Error> {
+        let phys = self.boot_log_start;
+        let size = self.boot_log_size;
+        if phys == 0 || size == 0 {
+            return Err(xous_kernel::Error::BadAddress);
+        }
+        let virt = self.find_virtual_address(
+            core::ptr::null_mut(),
+            size,
+            xous_kernel::MemoryType::Default,
+        )? as usize;
+
+        for offset in (0..size).step_by(PAGE_SIZE) {
+            if let Err(e) = crate::arch::mem::map_page_inner(
+                self,
+                pid,
+                offset + phys,
+                offset + virt,
+                MemoryFlags::R,
+                true,
+            ) {
+                for unmap_offset in (0..offset).step_by(PAGE_SIZE) {
+                    crate::arch::mem::unmap_page_inner(self, unmap_offset + virt).ok();
+                }
+                return Err(e);
+            }
+        }
+
+        Ok(MemoryRange::new(virt, size)?)
+    }
+
+    /// There is no boot log when running hosted.
+    #[cfg(not(baremetal))]
+    pub fn map_boot_log(&mut self, _pid: PID) -> Result<MemoryRange, xous_kernel::Error> {
+        Err(xous_kernel::Error::BadAddress)
+    }
+
+    /// Map the page holding the booted slot's `confirmed` word read-write
+    /// into process `pid`, which must be the current process, and return
+    /// the range covering just that word.  The page is claimed by `pid`,
+    /// so no other process can map it.
+    #[cfg(baremetal)]
+    pub fn map_boot_slot(&mut self, pid: PID) -> Result<MemoryRange, xous_kernel::Error> {
+        let confirmed = self.boot_slot_confirmed;
+        if confirmed == 0 {
+            return Err(xous_kernel::Error::BadAddress);
+        }
+        let range = self.map_range(
+            (confirmed & !(PAGE_SIZE - 1)) as *mut u8,
+            core::ptr::null_mut(),
+            PAGE_SIZE,
+            pid,
+            MemoryFlags::R | MemoryFlags::W,
+            xous_kernel::MemoryType::Default,
+        )?;
+        crate::arch::mem::hand_page_to_user(range.as_mut_ptr())?;
+        MemoryRange::new(range.as_ptr() as usize + (confirmed & (PAGE_SIZE - 1)), 4)
+    }
+
+    /// There are no boot slots when running hosted.
+    #[cfg(not(baremetal))]
+    pub fn map_boot_slot(&mut self, _pid: PID) -> Result<MemoryRange, xous_kernel::Error> {
+        Err(xous_kernel::Error::BadAddress)
+    }
+
     pub fn is_main_memory(&self, phys: *mut u8) -> bool {
         (phys as usize) >= self.ram_start && (phys as usize) < self.ram_start + self.ram_size
     }
@@ -630,6 +759,26 @@ impl MemoryManager {
         Err(xous_kernel::Error::BadAddress)
     }
 
+    /// Hand the physical page `phys` from one process to another.
+    #[allow(dead_code)]
+    pub fn transfer_page(&mut self, phys: usize, from: PID, to: PID) -> Result<(), xous_kernel::Error> {
+        self.release_page(phys as *mut usize, from)?;
+        self.claim_page(phys as *mut usize, to)
+    }
+
+    /// Release every page owned by `pid`.  Used to tear down a process that
+    /// failed to start, once anything it was given has been handed back.
+    #[cfg(baremetal)]
+    pub fn release_all(&mut self, pid: PID) {
+        unsafe {
+            for owner in MEMORY_ALLOCATIONS.iter_mut() {
+                if *owner == Some(pid) {
+                    *owner = None;
+                }
+            }
+        }
+    }
+
     /// Mark a given address as being owned by the specified process ID
     fn claim_page(&mut self, addr: *mut usize, pid: PID) -> Result<(), xous_kernel::Error> {
         self.claim_or_release(addr, pid, ClaimOrRelease::Claim)
@@ -15,6 +15,26 @@ use xous_kernel::{
 
 const MAX_SERVER_COUNT: usize = 32;
 
+/// Largest size a process' heap may grow to, unless the loader was told
+/// otherwise.
+pub const DEFAULT_HEAP_MAX: usize = 524_288;
+
+/// Length of a process name, in bytes
+pub const PROCESS_NAME_LENGTH: usize = 16;
+
+/// With the `aslr` feature, each process' heap, message, default, and stack
+/// areas are moved by a random number of pages below this amount.  This is
+/// small enough that the areas can never overlap one another.
+#[cfg(feature = "aslr")]
+pub const ASLR_WINDOW: usize = 0x0400_0000;
+
+/// Return a random, page-aligned offset that is less than `ASLR_WINDOW`.
+#[cfg(feature = "aslr")]
+pub fn aslr_offset() -> usize {
+    let pages = ASLR_WINDOW / arch::mem::PAGE_SIZE;
+    (arch::rand::get_u32() as usize % pages) * arch::mem::PAGE_SIZE
+}
+
 pub use crate::arch::process::{INITIAL_TID, MAX_PROCESS_COUNT};
 
 /// A big unifying struct containing all of the system state.
@@ -88,6 +108,14 @@ pub struct Process {
     /// The context number that was active before this process was switched
     /// away.
     previous_thread: TID,
+
+    /// Largest size the heap may grow to.  This is applied when the
+    /// process is set up, since the heap limit lives in `ProcessInner`.
+    heap_max: usize,
+
+    /// Name of the process, padded with zeroes.  Processes without a name
+    /// are all zeroes.
+    name: [u8; PROCESS_NAME_LENGTH],
 }
 
 impl Default for Process {
@@ -146,7 +174,7 @@ impl Default for ProcessInner {
             mem_message_last: arch::mem::DEFAULT_MESSAGE_BASE,
             mem_heap_base: arch::mem::DEFAULT_HEAP_BASE,
             mem_heap_size: 0,
-            mem_heap_max: 524_288,
+            mem_heap_max: DEFAULT_HEAP_MAX,
             connection_map: [None; 32],
             pid: unsafe { PID::new_unchecked(1) },
             _reserved: [0; 1],
@@ -154,7 +182,45 @@ impl Default for ProcessInner {
     }
 }
 
+impl ProcessInner {
+    /// Create the state of a new process.  With the `aslr` feature, the
+    /// heap, message, and default areas are moved up by random amounts.
+    #[cfg(not(feature = "aslr"))]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Create the state of a new process.  With the `aslr` feature, the
+    /// heap, message, and default areas are moved up by random amounts.
+    #[cfg(feature = "aslr")]
+    pub fn new() -> Self {
+        let mem_default_base = arch::mem::DEFAULT_BASE + aslr_offset();
+        let mem_message_base = arch::mem::DEFAULT_MESSAGE_BASE + aslr_offset();
+        ProcessInner {
+            mem_default_base,
+            mem_default_last: mem_default_base,
+            mem_message_base,
+            mem_message_last: mem_message_base,
+            mem_heap_base: arch::mem::DEFAULT_HEAP_BASE + aslr_offset(),
+            ..Default::default()
+        }
+    }
+}
+
 impl Process {
+    /// The name given to this process by the loader, if it has one
+    pub fn name(&self) -> Option<&str> {
+        let length = self
+            .name
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(PROCESS_NAME_LENGTH);
+        match core::str::from_utf8(&self.name[..length]) {
+            Ok(name) if !name.is_empty() => Some(name),
+            _ => None,
+        }
+    }
+
     /// This process has at least one context that may be run
     pub fn runnable(&self) -> bool {
         match self.state {
@@ -203,6 +269,8 @@ std::thread_local!(static SYSTEM_SERVICES: core::cell::RefCell<SystemServices> =
         mapping: arch::mem::DEFAULT_MEMORY_MAPPING,
         current_thread: 0 as TID,
         previous_thread: INITIAL_TID as TID,
+        heap_max: DEFAULT_HEAP_MAX,
+        name: [0; PROCESS_NAME_LENGTH],
     }; MAX_PROCESS_COUNT],
     // Note we can't use MAX_SERVER_COUNT here because of how Rust's
     // macro tokenization works
@@ -220,6 +288,8 @@ static mut SYSTEM_SERVICES: SystemServices = SystemServices {
         mapping: arch::mem::DEFAULT_MEMORY_MAPPING,
         current_thread: 0 as TID,
         previous_thread: INITIAL_TID as TID,
+        heap_max: DEFAULT_HEAP_MAX,
+        name: [0; PROCESS_NAME_LENGTH],
     }; MAX_PROCESS_COUNT],
     // Note we can't use MAX_SERVER_COUNT here because of how Rust's
     // macro tokenization works
@@ -232,8 +302,9 @@ impl core::fmt::Debug for Process {
     fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::result::Result<(), core::fmt::Error> {
         write!(
             fmt,
-            "Process {} state: {:?}  Memory mapping: {:?}",
+            "Process {} ({}) state: {:?}  Memory mapping: {:?}",
             self.pid.get(),
+            self.name().unwrap_or("unnamed"),
             self.state,
             self.mapping
         )
@@ -311,9 +382,20 @@ impl SystemServices {
             if pid == 1 {
                 process.state = ProcessState::Running(0);
             } else {
+                // A stack size or heap maximum of 0 means the default.  The
+                // stack grows down from the page holding the initial `sp`.
+                let stack_size = match init.stack_size {
+                    0 => crate::arch::process::DEFAULT_STACK_SIZE,
+                    stack_size => stack_size,
+                };
+                let stack_top = (init.sp & !(arch::mem::PAGE_SIZE - 1)) + arch::mem::PAGE_SIZE;
+                if init.heap_max != 0 {
+                    process.heap_max = init.heap_max;
+                }
+                process.name = init.name;
                 process.state = ProcessState::Setup(ThreadInit::new(
                     unsafe { core::mem::transmute::<usize, _>(init.entrypoint) },
-                    MemoryRange::new(init.sp, crate::arch::process::DEFAULT_STACK_SIZE).unwrap(),
+                    MemoryRange::new(stack_top - stack_size, stack_size).unwrap(),
                     None,
                     [0u8; 12],
                 ));
@@ -335,10 +417,23 @@ impl SystemServices {
                 continue;
             }
             let new_pid = pid_from_usize(idx + 1)?;
-            arch::process::Process::create(new_pid, init_process);
+            // On hardware, the program is moved into a new address space and
+            // the process is ready to run.  Hosted processes connect later.
+            #[cfg(baremetal)]
+            {
+                let (mapping, setup) = arch::process::Process::create(new_pid, init_process)?;
+                entry.mapping = mapping;
+                entry.state = ProcessState::Setup(setup);
+            }
+            #[cfg(not(baremetal))]
+            {
+                arch::process::Process::create(new_pid, init_process);
+                entry.state = ProcessState::Allocated;
+            }
+            entry.heap_max = DEFAULT_HEAP_MAX;
+            entry.name = [0; PROCESS_NAME_LENGTH];
             let ppid = crate::arch::process::current_pid();
             // println!("Creating new process for PID {} with PPID {}", new_pid, ppid);
-            entry.state = ProcessState::Allocated;
             entry.ppid = ppid;
             entry.pid = new_pid;
             return Ok(new_pid);
@@ -859,7 +954,11 @@ impl SystemServices {
                     // println!("Setting up new process...");
                     ArchProcess::setup_process(new_pid, thread_init)
                         .expect("couldn't set up new process");
-                    ArchProcess::with_inner_mut(|process_inner| process_inner.pid = new_pid);
+                    let heap_max = new.heap_max;
+                    ArchProcess::with_inner_mut(|process_inner| {
+                        process_inner.pid = new_pid;
+                        process_inner.mem_heap_max = heap_max;
+                    });
 
                     ProcessState::Running(0)
                 }
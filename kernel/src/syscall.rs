@@ -647,6 +647,29 @@ pub fn handle_inner(pid: PID, tid: TID, in_irq: bool, call: SysCall) -> SysCallR
             }
             result
         }),
+        SysCall::MapBootLog => {
+            // The log describes the whole system, so only the service
+            // that is meant to publish it may read it.
+            SystemServices::with(|ss| match ss.get_process(pid)?.name() {
+                Some("bootlog") => Ok(()),
+                _ => Err(xous_kernel::Error::ShareViolation),
+            })?;
+            MemoryManager::with_mut(|mm| {
+                mm.map_boot_log(pid)
+                    .map(xous_kernel::Result::MemoryRange)
+            })
+        }
+        SysCall::MapBootSlot => {
+            // Confirming a boot decides which image the loader starts
+            // next time, so it's limited in the same way as the log.
+            SystemServices::with(|ss| match ss.get_process(pid)?.name() {
+                Some("bootlog") => Ok(()),
+                _ => Err(xous_kernel::Error::ShareViolation),
+            })?;
+            MemoryManager::with_mut(|mm| {
+                mm.map_boot_slot(pid).map(xous_kernel::Result::MemoryRange)
+            })
+        }
         SysCall::IncreaseHeap(delta, flags) => {
             if delta & 0xfff != 0 {
                 return Err(xous_kernel::Error::BadAlignment);
@@ -772,8 +795,13 @@ pub fn handle_inner(pid: PID, tid: TID, in_irq: bool, call: SysCall) -> SysCallR
                 Ok(xous_kernel::Result::Ok)
             }
         }),
+        #[allow(unreachable_code)]
         SysCall::Shutdown => {
-            SystemServices::with_mut(|ss| ss.shutdown().map(|_| xous_kernel::Result::Ok))
+            SystemServices::with_mut(|ss| ss.shutdown())?;
+            // Stop QEMU, so that a test run finishes instead of idling forever
+            #[cfg(all(baremetal, feature = "qemu-virt"))]
+            crate::arch::finisher::pass();
+            Ok(xous_kernel::Result::Ok)
         }
 
         SysCall::Connect(sid) => {
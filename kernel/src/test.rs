@@ -1,7 +1,6 @@
 use crate::kmain;
 use std::thread::JoinHandle;
 
-use std::net::ToSocketAddrs;
 use std::sync::mpsc::channel;
 use xous_kernel::{rsyscall, SysCall};
 
@@ -13,9 +12,23 @@ use stats_alloc::{Region, Stats, StatsAlloc, INSTRUMENTED_SYSTEM};
 #[global_allocator]
 static GLOBAL: &StatsAlloc<std::alloc::System> = &INSTRUMENTED_SYSTEM;
 
-const SERVER_SPEC: &str = "127.0.0.1:0";
+/// Run tests over an in-process transport, so they don't compete for ports.
+const SERVER_SPEC: &str = "local:";
 
 fn start_kernel(server_spec: &str) -> JoinHandle<()> {
+    start_kernel_at(server_spec).0
+}
+
+/// Start the kernel, and also return the address it is listening on.
+fn start_kernel_at(server_spec: &str) -> (JoinHandle<()>, xous_kernel::arch::XousAddress) {
+    start_kernel_traced(server_spec, crate::arch::trace::Trace::Off)
+}
+
+/// Start the kernel, recording or replaying its syscall traffic.
+fn start_kernel_traced(
+    server_spec: &str,
+    trace: crate::arch::trace::Trace,
+) -> (JoinHandle<()>, xous_kernel::arch::XousAddress) {
     assert!(
         std::env::var("XOUS_LISTEN_ADDR").is_err(),
         "XOUS_LISTEN_ADDR environment variable must be unset to run tests"
@@ -33,11 +46,8 @@ fn start_kernel(server_spec: &str) -> JoinHandle<()> {
     }
     xous_kernel::arch::set_process_key(&pid1_key);
 
-    let server_addr = server_spec
-        .to_socket_addrs()
-        .expect("invalid server address")
-        .next()
-        .expect("unable to resolve server address");
+    let server_addr: xous_kernel::arch::XousAddress =
+        server_spec.parse().expect("invalid server address");
     // Attempt to bind. This will fail if the port is in use.
     // let temp_server = TcpListener::bind(server_addr).unwrap();
     // let server_addr = temp_server.local_addr().unwrap();
@@ -54,11 +64,12 @@ fn start_kernel(server_spec: &str) -> JoinHandle<()> {
             crate::arch::set_pid1_key(pid1_key);
             crate::arch::set_send_addr(send_addr);
             crate::arch::set_listen_address(&server_spec_server);
+            crate::arch::trace::set_trace(trace);
             kmain()
         })
         .expect("couldn't start kernel thread");
     let server_addr = recv_addr.recv().unwrap();
-    xous_kernel::arch::set_xous_address(server_addr);
+    xous_kernel::arch::set_xous_address(server_addr.clone());
 
     // Connect to server. This first instance needs to make sure the kernel is listening.
     // let mut server_conn = None;
@@ -73,7 +84,7 @@ fn start_kernel(server_spec: &str) -> JoinHandle<()> {
     // }
     // // Convert the Option<conn> into conn
     // assert!(connected, "unable to connect to server");
-    main_thread
+    (main_thread, server_addr)
 }
 
 fn shutdown_kernel() {
@@ -118,6 +129,90 @@ fn shutdown() {
     main_thread.join().expect("couldn't join main thread");
 }
 
+#[test]
+fn shutdown_tcp() {
+    let main_thread = start_kernel("127.0.0.1:0");
+    shutdown_kernel();
+    main_thread.join().expect("couldn't join main thread");
+}
+
+#[cfg(unix)]
+#[test]
+fn shutdown_unix() {
+    let socket_path = std::env::temp_dir().join(format!("xous-test-{}.sock", std::process::id()));
+    let main_thread = start_kernel(&format!("unix:{}", socket_path.display()));
+    shutdown_kernel();
+    main_thread.join().expect("couldn't join main thread");
+    assert!(!socket_path.exists(), "socket file was not removed");
+}
+
+#[test]
+fn reject_protocol_mismatch() {
+    use xous_kernel::arch::{protocol, transport};
+    let (main_thread, server_addr) = start_kernel_at(SERVER_SPEC);
+
+    // A client from a newer build should be turned away before it gets a PID.
+    let mut conn = transport::connect(&server_addr).expect("couldn't connect to kernel");
+    let mut hello = protocol::Hello::new().unwrap();
+    hello.version = protocol::PROTOCOL_VERSION + 1;
+    protocol::write_hello(&mut *conn, &hello).unwrap();
+    let err = protocol::read_hello_response(&mut *conn).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::ConnectionRefused);
+
+    // The kernel should still be usable afterwards.
+    shutdown_kernel();
+    main_thread.join().expect("couldn't join main thread");
+}
+
+#[test]
+fn reject_unknown_process_key() {
+    use xous_kernel::arch::{protocol, transport};
+    let (main_thread, server_addr) = start_kernel_at(SERVER_SPEC);
+
+    // A client that can't prove it holds the key of a waiting process gets no PID.
+    let mut conn = transport::connect(&server_addr).expect("couldn't connect to kernel");
+    let key = protocol::generate_nonce().unwrap();
+    let err = protocol::client_handshake(&mut *conn, &key).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+
+    // The kernel should still be usable afterwards.
+    shutdown_kernel();
+    main_thread.join().expect("couldn't join main thread");
+}
+
+#[test]
+fn stalled_handshake() {
+    use std::time::{Duration, Instant};
+    use xous_kernel::arch::{protocol, transport};
+    let (main_thread, server_addr) = start_kernel_at(SERVER_SPEC);
+
+    // A client that connects and then says nothing doesn't keep everyone
+    // after it from connecting.
+    let mut conn = transport::connect(&server_addr).expect("couldn't connect to kernel");
+    let start = Instant::now();
+    xous_kernel::wait_process_as_thread(
+        xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+            "second client",
+            || {
+                rsyscall(SysCall::Yield).expect("couldn't yield");
+            },
+        ))
+        .expect("couldn't start the second client"),
+    )
+    .expect("couldn't wait for the second client to end");
+    let waited = start.elapsed();
+    assert!(
+        waited < Duration::from_secs(1),
+        "second client waited {:?} to connect",
+        waited
+    );
+
+    // The stalled client is dropped when the kernel exits.
+    shutdown_kernel();
+    main_thread.join().expect("couldn't join main thread");
+    assert!(protocol::read_hello_response(&mut *conn).is_err());
+}
+
 #[test]
 fn connect_for_process() {
     use xous_kernel::SID;
@@ -327,6 +422,141 @@ fn send_scalar_message() {
     main_thread.join().expect("couldn't join kernel process");
 }
 
+/// Record the kernel while `run` starts and waits for some processes, then
+/// check that replaying the trace without them makes the kernel behave
+/// exactly the same way, all the way through to shutting down.
+fn record_and_replay_with<F: FnOnce()>(name: &str, run: F) {
+    use crate::arch::trace::{Recorder, Replay, Trace};
+    use std::sync::Arc;
+
+    let trace_path =
+        std::env::temp_dir().join(format!("xous-{}-{}.trace", name, std::process::id()));
+
+    let recorder = Arc::new(Recorder::create(&trace_path).expect("couldn't create trace"));
+    let (main_thread, _) = start_kernel_traced(SERVER_SPEC, Trace::Record(recorder));
+    run();
+    shutdown_kernel();
+    main_thread.join().expect("couldn't join kernel process");
+
+    let replay = Arc::new(Replay::load(&trace_path).expect("couldn't load trace"));
+    let (main_thread, _) = start_kernel_traced(SERVER_SPEC, Trace::Replay(replay.clone()));
+    main_thread.join().expect("couldn't join kernel process");
+    std::fs::remove_file(&trace_path).ok();
+    assert_eq!(replay.result(), Ok(()));
+}
+
+#[test]
+fn record_and_replay() {
+    // Record a server and client exchanging messages. The server is given a
+    // random SID, so this also records the kernel's random numbers.
+    record_and_replay_with("record-and-replay", || {
+        let (server_addr_send, server_addr_recv) = channel();
+        let xous_server = xous_kernel::create_process_as_thread(
+            xous_kernel::ProcessArgsAsThread::new("record_and_replay server", move || {
+                let sid = xous_kernel::create_server().expect("couldn't create test server");
+                server_addr_send.send(sid).unwrap();
+                let envelope =
+                    xous_kernel::receive_message(sid).expect("couldn't receive messages");
+                if let xous_kernel::Message::BlockingScalar(msg) = envelope.body {
+                    xous_kernel::return_scalar(envelope.sender, msg.arg1 + msg.arg2)
+                        .expect("couldn't return scalar");
+                } else {
+                    panic!("unexpected message type");
+                }
+            }),
+        )
+        .expect("couldn't spawn server process");
+
+        let xous_client = xous_kernel::create_process_as_thread(
+            xous_kernel::ProcessArgsAsThread::new("record_and_replay client", move || {
+                let sid = server_addr_recv.recv().unwrap();
+                let conn = xous_kernel::connect(sid).expect("couldn't connect to server");
+                let result = xous_kernel::send_message(
+                    conn,
+                    xous_kernel::Message::BlockingScalar(xous_kernel::ScalarMessage {
+                        id: 1,
+                        arg1: 2,
+                        arg2: 3,
+                        arg3: 0,
+                        arg4: 0,
+                    }),
+                )
+                .expect("couldn't send message");
+                assert_eq!(result, xous_kernel::Result::Scalar1(5));
+            }),
+        )
+        .expect("couldn't spawn client process");
+
+        crate::wait_process_as_thread(xous_server).expect("couldn't join server process");
+        crate::wait_process_as_thread(xous_client).expect("couldn't join client process");
+    });
+}
+
+#[test]
+fn record_and_replay_memory_messages() {
+    // The kernel hands the server a buffer at a host address that changes
+    // from run to run, which the replay must not mistake for a divergence.
+    record_and_replay_with("record-and-replay-memory", || {
+        let test_bytes = b"Hello, world!";
+        let (server_addr_send, server_addr_recv) = channel();
+        let xous_server = xous_kernel::create_process_as_thread(
+            xous_kernel::ProcessArgsAsThread::new("record_and_replay_memory server", move || {
+                let sid = xous_kernel::create_server_with_address(b"record_replay_mm")
+                    .expect("couldn't create test server");
+                server_addr_send.send(sid).unwrap();
+
+                let envelope =
+                    xous_kernel::receive_message(sid).expect("couldn't receive messages");
+                if let xous_kernel::Message::Move(m) = &envelope.body {
+                    let bt = unsafe { core::slice::from_raw_parts(m.buf.as_ptr(), m.buf.len()) };
+                    assert_eq!(*test_bytes, *bt, "message was changed by the kernel");
+                } else {
+                    panic!("unexpected message type");
+                }
+
+                let envelope =
+                    xous_kernel::receive_message(sid).expect("couldn't receive messages");
+                if let xous_kernel::Message::MutableBorrow(m) = envelope.body {
+                    let bt =
+                        unsafe { core::slice::from_raw_parts_mut(m.buf.as_mut_ptr(), m.buf.len()) };
+                    for letter in bt.iter_mut() {
+                        *letter += 1;
+                    }
+                    xous_kernel::return_memory(envelope.sender, m.buf).unwrap();
+                } else {
+                    panic!("unexpected message type");
+                }
+            }),
+        )
+        .expect("couldn't spawn server process");
+
+        let xous_client = xous_kernel::create_process_as_thread(
+            xous_kernel::ProcessArgsAsThread::new("record_and_replay_memory client", move || {
+                let sid = server_addr_recv.recv().unwrap();
+                let conn = xous_kernel::connect(sid).expect("couldn't connect to server");
+
+                let msg = xous_kernel::carton::Carton::from_bytes(test_bytes);
+                xous_kernel::try_send_message(
+                    conn,
+                    xous_kernel::Message::Move(msg.into_message(0)),
+                )
+                .expect("couldn't send a message");
+
+                let mut carton = xous_kernel::carton::Carton::from_bytes(test_bytes);
+                carton
+                    .lend_mut(conn, 1)
+                    .expect("couldn't mutably lend data");
+                let modified_bytes: &[u8] = carton.as_ref();
+                assert_eq!(modified_bytes, b"Ifmmp-!xpsme\"");
+            }),
+        )
+        .expect("couldn't spawn client process");
+
+        crate::wait_process_as_thread(xous_server).expect("couldn't join server process");
+        crate::wait_process_as_thread(xous_client).expect("couldn't join client process");
+    });
+}
+
 #[test]
 fn try_receive_message() {
     // Start the server in another thread
@@ -1168,3 +1398,45 @@ fn process_restart_server() {
 
     main_thread.join().expect("couldn't join kernel process");
 }
+
+#[test]
+fn map_boot_slot_restricted() {
+    let main_thread = start_kernel(SERVER_SPEC);
+
+    // Only the process named `bootlog` may confirm the boot
+    let xous_process = xous_kernel::create_process_as_thread(
+        xous_kernel::ProcessArgsAsThread::new("map_boot_slot_restricted", || {
+            assert_eq!(
+                xous_kernel::map_boot_slot(),
+                Err(xous_kernel::Error::ShareViolation)
+            );
+        }),
+    )
+    .expect("couldn't start process");
+    xous_kernel::wait_process_as_thread(xous_process).expect("couldn't join process");
+
+    shutdown_kernel();
+
+    main_thread.join().expect("couldn't join kernel process");
+}
+
+#[test]
+fn map_boot_log_restricted() {
+    let main_thread = start_kernel(SERVER_SPEC);
+
+    // Only the process named `bootlog` may see the loader's log
+    let xous_process = xous_kernel::create_process_as_thread(
+        xous_kernel::ProcessArgsAsThread::new("map_boot_log_restricted", || {
+            assert_eq!(
+                xous_kernel::map_boot_log(),
+                Err(xous_kernel::Error::ShareViolation)
+            );
+        }),
+    )
+    .expect("couldn't start process");
+    xous_kernel::wait_process_as_thread(xous_process).expect("couldn't join process");
+
+    shutdown_kernel();
+
+    main_thread.join().expect("couldn't join kernel process");
+}
@@ -8,25 +8,79 @@ fn main() {
     let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
     let target = env::var("TARGET").unwrap();
     let name = env::var("CARGO_PKG_NAME").unwrap();
+    let qemu_virt = env::var("CARGO_FEATURE_QEMU_VIRT").is_ok();
 
+    // QEMU `virt` builds start in Machine mode and have to set it up
+    // themselves, so they have blobs of their own.
     if target.starts_with("riscv") {
-        fs::copy(
-            format!("bin/{}.a", target),
-            out_dir.join(format!("lib{}.a", name)),
-        ).unwrap();
+        let blob = if qemu_virt {
+            format!("bin/qemu-virt/{}.a", target)
+        } else {
+            format!("bin/{}.a", target)
+        };
+        fs::copy(&blob, out_dir.join(format!("lib{}.a", name))).unwrap();
 
         println!("cargo:rustc-link-lib=static={}", name);
         println!("cargo:rustc-link-search={}", out_dir.display());
-        println!("cargo:rerun-if-changed=bin/{}.a", target);
+        println!("cargo:rerun-if-changed={}", blob);
     }
 
-    // Put the linker script somewhere the linker can find it
+    // Put the linker script and the platform's memory layout somewhere the
+    // linker can find them
     fs::File::create(out_dir.join("link.x"))
         .unwrap()
         .write_all(include_bytes!("link.x"))
         .unwrap();
+    let memory: &[u8] = if qemu_virt {
+        include_bytes!("memory-qemu-virt.x")
+    } else {
+        include_bytes!("memory.x")
+    };
+    fs::File::create(out_dir.join("memory.x"))
+        .unwrap()
+        .write_all(memory)
+        .unwrap();
     println!("cargo:rustc-link-search={}", out_dir.display());
 
+    // Embed the public key that boot images must be signed with
+    let key_path = env::var("XOUS_LOADER_PUBLIC_KEY").unwrap_or_else(|_| {
+        // The developer secret key is published, so anyone can sign an
+        // image this loader will boot.  Release builds have to name their
+        // key, even if that key is the developer one.
+        if env::var("PROFILE").as_deref() == Ok("release") {
+            panic!(
+                "XOUS_LOADER_PUBLIC_KEY must be set for release builds; \
+                 point it at keys/developer.pub to accept the developer key"
+            );
+        }
+        println!(
+            "cargo:warning=XOUS_LOADER_PUBLIC_KEY is not set, so this loader \
+             accepts images signed with the published developer key"
+        );
+        "../keys/developer.pub".to_string()
+    });
+    let key_hex = fs::read_to_string(&key_path)
+        .unwrap_or_else(|e| panic!("couldn't read public key {}: {}", key_path, e));
+    let key_hex = key_hex.trim();
+    if key_hex.len() != 64 || !key_hex.is_ascii() {
+        panic!("public key {} must be 64 hex digits", key_path);
+    }
+    let key_bytes: Vec<u8> = (0..key_hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&key_hex[i..i + 2], 16)
+                .unwrap_or_else(|_| panic!("public key {} is not valid hex", key_path))
+        })
+        .collect();
+    fs::File::create(out_dir.join("public_key.rs"))
+        .unwrap()
+        .write_all(format!("pub const PUBLIC_KEY: [u8; 32] = {:?};\n", key_bytes).as_bytes())
+        .unwrap();
+    println!("cargo:rerun-if-env-changed=XOUS_LOADER_PUBLIC_KEY");
+    println!("cargo:rerun-if-changed={}", key_path);
+
     println!("cargo:rerun-if-changed=build.rs");
     println!("cargo:rerun-if-changed=link.x");
+    println!("cargo:rerun-if-changed=memory.x");
+    println!("cargo:rerun-if-changed=memory-qemu-virt.x");
 }
@@ -0,0 +1,16 @@
+/* QEMU `virt` has no flash, so the loader is loaded into the bottom of RAM
+   and runs from there.  Its arguments are loaded 8 MiB in. */
+MEMORY
+{
+  RAM : ORIGIN = 0x80080000, LENGTH = 16M - 512K
+  FLASH : ORIGIN = 0x80000000, LENGTH = 512K
+}
+
+REGION_ALIAS("REGION_TEXT", FLASH);
+REGION_ALIAS("REGION_RODATA", FLASH);
+REGION_ALIAS("REGION_DATA", RAM);
+REGION_ALIAS("REGION_BSS", RAM);
+REGION_ALIAS("REGION_STACK", RAM);
+REGION_ALIAS("REGION_HEAP", RAM);
+
+_xous_args = 0x80800000;
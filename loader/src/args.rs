@@ -43,6 +43,12 @@ impl KernelArguments {
 pub struct KernelArgument {
     pub name: u32,
 
+    // Offset of this tag, in bytes, from the start of the args block
+    pub offset: u32,
+
+    // CRC16 of the data section, as recorded in the tag header
+    pub crc: u16,
+
     // Total number of bytes in the data section
     pub size: u32,
 
@@ -53,6 +59,7 @@ pub struct KernelArgument {
 impl KernelArgument {
     pub fn new(base: *const u32, offset: u32) -> Self {
         let name = unsafe { base.add(offset as usize / 4).read() };
+        let crc = unsafe { (base.add(offset as usize / 4 + 1) as *const u16).read() };
         let size = unsafe {
             (base.add(offset as usize / 4 + 1) as *const u16)
                 .add(1)
@@ -63,10 +70,40 @@ impl KernelArgument {
         };
         KernelArgument {
             name,
+            offset,
+            crc,
             size: size * 4,
             data,
         }
     }
+
+    /// Calculate the CRC16 of the data section.  This should match `crc`.
+    pub fn calculated_crc(&self) -> u16 {
+        crc16(self.data)
+    }
+
+    pub fn crc_valid(&self) -> bool {
+        self.calculated_crc() == self.crc
+    }
+}
+
+/// CRC16 of a tag's data section, using the reflected CCITT polynomial
+/// with an initial value of 0xffff and a final XOR of 0xffff (as in X.25).
+pub fn crc16(data: &[u32]) -> u16 {
+    let mut crc = 0xffffu16;
+    for word in data {
+        for byte in word.to_le_bytes().iter() {
+            crc ^= *byte as u16;
+            for _ in 0..8 {
+                if crc & 1 != 0 {
+                    crc = (crc >> 1) ^ 0x8408;
+                } else {
+                    crc >>= 1;
+                }
+            }
+        }
+    }
+    !crc
 }
 
 impl Iterator for KernelArgumentsIterator {
@@ -0,0 +1,109 @@
+//! A record of what the loader did, kept in a page of its own so that the
+//! kernel can hand it to the `bootlog` service once the system is running.
+//!
+//! The log starts with a two-word header holding `BOOT_LOG_VERSION` and the
+//! number of bytes of entries that follow.  Each entry is a word holding its
+//! kind in the low 16 bits and the number of data words in the high 16
+//! bits, then the cycle count when it was written, then its data.
+
+use crate::{BootConfig, PAGE_SIZE};
+use core::{mem, ptr};
+
+/// Number of bytes set aside for the log
+pub const BOOT_LOG_SIZE: usize = PAGE_SIZE;
+
+pub const BOOT_LOG_VERSION: u32 = 1;
+
+/// Size of the `BLog` tag that describes the log, including its header
+pub const TAG_SIZE: usize = 8 + 8;
+
+#[derive(Clone, Copy)]
+pub enum EntryKind {
+    /// The loader started a phase: 1 and 2 are `phase_1()` and
+    /// `phase_2()`, and 3 is jumping to the kernel
+    Phase = 1,
+
+    /// The image came from a boot slot: index, version and attempts
+    Slot = 2,
+
+    /// RAM was tested: pages tested and pages that failed
+    MemTest = 3,
+
+    /// Pages of RAM taken by the loader: for the kernel and its
+    /// arguments, and for processes and page tables
+    Pages = 4,
+
+    /// A process was set up: PID, entrypoint, stack pointer, SATP, and
+    /// its name as four words
+    Process = 5,
+}
+
+pub struct BootLog {
+    base: *mut u32,
+    used: usize,
+}
+
+/// The cycle count, which is the only clock the loader has.
+#[cfg(target_arch = "riscv32")]
+fn timestamp() -> u32 {
+    riscv::register::mcycle::read() as u32
+}
+
+#[cfg(not(target_arch = "riscv32"))]
+fn timestamp() -> u32 {
+    0
+}
+
+impl BootLog {
+    /// Start an empty log in the `BOOT_LOG_SIZE` bytes at `base`.
+    pub fn new(base: *mut usize) -> BootLog {
+        let base = base as *mut u32;
+        unsafe {
+            ptr::write_volatile(base, BOOT_LOG_VERSION);
+            ptr::write_volatile(base.add(1), 0);
+        }
+        BootLog { base, used: 0 }
+    }
+
+    pub fn addr(&self) -> usize {
+        self.base as usize
+    }
+
+    /// Add an entry to the log.  Entries that don't fit are dropped.
+    pub fn record(&mut self, kind: EntryKind, data: &[u32]) {
+        let words = 2 + data.len();
+        let start = 2 + self.used / mem::size_of::<u32>();
+        if (start + words) * mem::size_of::<u32>() > BOOT_LOG_SIZE {
+            return;
+        }
+        unsafe {
+            let entry = self.base.add(start);
+            ptr::write_volatile(entry, kind as u32 | (data.len() as u32) << 16);
+            ptr::write_volatile(entry.add(1), timestamp());
+            for (index, word) in data.iter().enumerate() {
+                ptr::write_volatile(entry.add(2 + index), *word);
+            }
+            self.used += words * mem::size_of::<u32>();
+            ptr::write_volatile(self.base.add(1), self.used as u32);
+        }
+    }
+}
+
+impl BootConfig {
+    /// Add an entry to the boot log, if there is one yet.
+    pub fn log(&mut self, kind: EntryKind, data: &[u32]) {
+        if let Some(log) = &mut self.boot_log {
+            log.record(kind, data);
+        }
+    }
+}
+
+/// Write the `BLog` tag describing `log` to `tag`, which must have room
+/// for `TAG_SIZE` bytes.  The tag holds the address and size of the log.
+pub fn write_tag(log: &BootLog, tag: &mut [u32]) {
+    tag[0] = u32::from_le_bytes(*b"BLog");
+    tag[2] = log.addr() as u32;
+    tag[3] = BOOT_LOG_SIZE as u32;
+    let crc = crate::args::crc16(&tag[2..4]);
+    tag[1] = 2 << 16 | crc as u32;
+}
@@ -1,8 +1,10 @@
+#[cfg_attr(feature = "qemu-virt", allow(unused_imports))]
 use utralib::generated::*;
 pub struct Uart {
     pub base: *mut u32,
 }
 
+#[cfg(not(feature = "qemu-virt"))]
 impl Uart {
     pub fn putc(&self, c: u8) {
         let mut uart = CSR::new(self.base);
@@ -12,6 +14,19 @@ impl Uart {
     }
 }
 
+/// The QEMU `virt` UART is a 16550, whose registers are one byte apart.
+#[cfg(feature = "qemu-virt")]
+impl Uart {
+    pub fn putc(&self, c: u8) {
+        let base = self.base as *mut u8;
+        unsafe {
+            // Wait until the transmit holding register (LSR.THRE) is empty
+            while base.add(5).read_volatile() & (1 << 5) == 0 {}
+            base.write_volatile(c)
+        }
+    }
+}
+
 use core::fmt::{Error, Write};
 impl Write for Uart {
     fn write_str(&mut self, s: &str) -> Result<(), Error> {
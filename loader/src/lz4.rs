@@ -0,0 +1,131 @@
+//! A minimal decoder for LZ4 block data.  Compressed payloads are
+//! decompressed straight into the pages allocated for them, so there
+//! is no need for a separate output buffer.
+
+use crate::PAGE_SIZE;
+
+#[derive(Debug, PartialEq)]
+pub enum Lz4Error {
+    /// The compressed data ended in the middle of a sequence
+    Truncated,
+
+    /// The decompressed data does not fit in the destination
+    Overrun,
+
+    /// A match refers to data before the start of the output
+    BadOffset,
+
+    /// The decompressed data did not fill the destination
+    ShortOutput,
+}
+
+/// A run of pages that decompressed data is written into.  The pages are
+/// adjacent in RAM, and are either ascending, as with the kernel, or
+/// descending, as with initial processes.
+pub struct Pages {
+    first: *mut u8,
+    descending: bool,
+    len: usize,
+    written: usize,
+}
+
+impl Pages {
+    /// `len` bytes starting at `first`
+    pub fn ascending(first: *mut u8, len: usize) -> Pages {
+        Pages {
+            first,
+            descending: false,
+            len,
+            written: 0,
+        }
+    }
+
+    /// `len` bytes of pages, where `first` is the first page and each
+    /// subsequent page lies immediately below the previous one.
+    pub fn descending(first: *mut u8, len: usize) -> Pages {
+        Pages {
+            first,
+            descending: true,
+            len,
+            written: 0,
+        }
+    }
+
+    fn addr(&self, offset: usize) -> *mut u8 {
+        let page = offset & !(PAGE_SIZE - 1);
+        let page_start = if self.descending {
+            self.first as usize - page
+        } else {
+            self.first as usize + page
+        };
+        (page_start + (offset & (PAGE_SIZE - 1))) as *mut u8
+    }
+
+    fn push(&mut self, byte: u8) -> Result<(), Lz4Error> {
+        if self.written >= self.len {
+            return Err(Lz4Error::Overrun);
+        }
+        unsafe { self.addr(self.written).write_volatile(byte) };
+        self.written += 1;
+        Ok(())
+    }
+}
+
+/// Read an extended length, which continues for as long as each byte is 255.
+fn read_length(initial: u8, src: &[u8], offset: &mut usize) -> Result<usize, Lz4Error> {
+    let mut length = initial as usize;
+    if initial == 15 {
+        loop {
+            let byte = *src.get(*offset).ok_or(Lz4Error::Truncated)?;
+            *offset += 1;
+            length += byte as usize;
+            if byte != 255 {
+                break;
+            }
+        }
+    }
+    Ok(length)
+}
+
+/// Decompress one LZ4 block from `src`, which must exactly fill `dest`.
+pub fn decompress(src: &[u8], dest: &mut Pages) -> Result<(), Lz4Error> {
+    let mut offset = 0;
+    loop {
+        let token = *src.get(offset).ok_or(Lz4Error::Truncated)?;
+        offset += 1;
+
+        // Copy literals straight from the source
+        let literal_count = read_length(token >> 4, src, &mut offset)?;
+        let literals = src
+            .get(offset..offset + literal_count)
+            .ok_or(Lz4Error::Truncated)?;
+        for byte in literals {
+            dest.push(*byte)?;
+        }
+        offset += literal_count;
+
+        // The last sequence consists of literals only
+        if offset == src.len() {
+            break;
+        }
+
+        // Copy a match from earlier in the output.  The match may overlap
+        // the bytes being written, so copy one byte at a time.
+        let distance = src.get(offset..offset + 2).ok_or(Lz4Error::Truncated)?;
+        let distance = u16::from_le_bytes([distance[0], distance[1]]) as usize;
+        offset += 2;
+        if distance == 0 || distance > dest.written {
+            return Err(Lz4Error::BadOffset);
+        }
+        let match_count = read_length(token & 0xf, src, &mut offset)? + 4;
+        for _ in 0..match_count {
+            let byte = unsafe { dest.addr(dest.written - distance).read_volatile() };
+            dest.push(byte)?;
+        }
+    }
+
+    if dest.written != dest.len {
+        return Err(Lz4Error::ShortOutput);
+    }
+    Ok(())
+}
@@ -6,20 +6,26 @@ mod args;
 use args::{KernelArgument, KernelArguments};
 
 use core::{mem, ptr, slice};
+use minielf::{
+    SectionFlags, CONTEXT_OFFSET, EXCEPTION_STACK_TOP, PAGE_TABLE_OFFSET, PAGE_TABLE_ROOT_OFFSET,
+    PIE_LOAD_OFFSET, STACK_PAGE_COUNT, USER_STACK_TOP,
+};
+#[cfg(all(target_arch = "riscv32", not(feature = "qemu-virt")))]
+use utralib::generated::*;
 
 pub type XousPid = u8;
 pub const PAGE_SIZE: usize = 4096;
 const WORD_SIZE: usize = mem::size_of::<usize>();
 
-const USER_STACK_TOP: usize = 0x8000_0000;
-const PAGE_TABLE_OFFSET: usize = 0xff40_0000;
-const PAGE_TABLE_ROOT_OFFSET: usize = 0xff80_0000;
-const CONTEXT_OFFSET: usize = 0xff80_1000;
 const USER_AREA_END: usize = 0xff00_0000;
 
+/// When `ASLR` is set, position-independent programs are moved up, and
+/// stacks are moved down, by a random number of pages below this amount.
+/// This matches the kernel's `aslr` feature.
+const ASLR_WINDOW: usize = 0x0400_0000;
+
 // All of the kernel structures must live within Megapage 1023,
 // and therefore are limited to 4 MB.
-const EXCEPTION_STACK_TOP: usize = 0xffff_0000;
 const KERNEL_LOAD_OFFSET: usize = 0xffd0_0000;
 const KERNEL_ARGUMENT_OFFSET: usize = 0xffc0_0000;
 
@@ -30,9 +36,14 @@ const FLG_R: usize = 0x2;
 const FLG_U: usize = 0x10;
 const FLG_A: usize = 0x40;
 const FLG_D: usize = 0x80;
-const STACK_PAGE_COUNT: usize = 5;
+const PROCESS_NAME_LENGTH: usize = 16;
 
+mod bootlog;
 mod debug;
+mod lz4;
+mod memtest;
+mod signature;
+mod slots;
 
 // Install a panic handler when not running tests.
 #[cfg(not(test))]
@@ -69,6 +80,33 @@ pub struct BootConfig {
     /// kernel to access user memory.
     debug: bool,
 
+    /// `true` if the kernel and Init programs are LZ4-compressed
+    compressed: bool,
+
+    /// `true` if Init programs should be placed at random addresses
+    aslr: bool,
+
+    /// State of the random number generator used for `aslr`, or 0 if it
+    /// hasn't been seeded yet.
+    rng_state: u32,
+
+    /// `true` if main RAM should be tested before it's used
+    memtest: bool,
+
+    /// The results of the memory test, if it was run
+    memtest_summary: Option<memtest::MemTestSummary>,
+
+    /// The boot slot the image was selected from, as its index, version
+    /// and number of attempts, if it came from a `BootSlots` record
+    boot_slot: Option<[u32; 3]>,
+
+    /// Address of the `confirmed` word of that boot slot, which is passed
+    /// to the kernel so that userspace can confirm the image works
+    slot_confirmed: Option<usize>,
+
+    /// The record of what the loader did, once space for it is allocated
+    boot_log: Option<bootlog::BootLog>,
+
     /// Where the tagged args list starts in RAM.
     args: KernelArguments,
 
@@ -109,6 +147,14 @@ impl Default for BootConfig {
         BootConfig {
             no_copy: false,
             debug: false,
+            compressed: false,
+            aslr: false,
+            rng_state: 0,
+            memtest: false,
+            memtest_summary: None,
+            boot_slot: None,
+            slot_confirmed: None,
+            boot_log: None,
             base_addr: core::ptr::null::<usize>(),
             regions: Default::default(),
             sram_start: core::ptr::null_mut::<usize>(),
@@ -141,6 +187,15 @@ pub struct InitialProcess {
 
     /// Address of the top of the stack
     sp: usize,
+
+    /// Size of the main thread's stack, or 0 for the kernel's default
+    stack_size: usize,
+
+    /// Largest size the heap may grow to, or 0 for the kernel's default
+    heap_max: usize,
+
+    /// Name of the process, padded with zeroes
+    name: [u8; PROCESS_NAME_LENGTH],
 }
 
 #[repr(C)]
@@ -163,13 +218,12 @@ impl MiniElfSection {
         false
     }
 
-    pub fn flags(&self) -> usize {
-        let le_bytes = self.size_and_flags >> 24;
-        le_bytes as usize
+    pub fn flags(&self) -> SectionFlags {
+        SectionFlags::from_bits_truncate((self.size_and_flags >> 24) as u8)
     }
 
     pub fn no_copy(&self) -> bool {
-        self.size_and_flags & (1 << 25) != 0
+        self.flags().contains(SectionFlags::NOCOPY)
     }
 }
 
@@ -181,6 +235,15 @@ pub struct MiniElf {
     /// Virtual address of the entrypoint
     pub entry_point: u32,
 
+    /// Size of the main thread's stack, or 0 for the kernel's default
+    pub stack_size: u32,
+
+    /// Largest size the heap may grow to, or 0 for the kernel's default
+    pub heap_max: u32,
+
+    /// Name of the process, padded with zeroes
+    pub name: [u8; PROCESS_NAME_LENGTH],
+
     /// All of the sections inside this file
     pub sections: &'static [MiniElfSection],
 }
@@ -188,32 +251,76 @@ pub struct MiniElf {
 impl MiniElf {
     pub fn new(tag: &KernelArgument) -> Self {
         let ptr = tag.data.as_ptr();
+        let header_size = 16 + PROCESS_NAME_LENGTH;
         unsafe {
             MiniElf {
                 load_offset: ptr.add(0).read(),
                 entry_point: ptr.add(1).read(),
+                stack_size: ptr.add(2).read(),
+                heap_max: ptr.add(3).read(),
+                name: (ptr.add(4) as *const [u8; PROCESS_NAME_LENGTH]).read(),
                 sections: slice::from_raw_parts(
-                    ptr.add(2) as *mut MiniElfSection,
-                    (tag.size as usize - 8) / mem::size_of::<MiniElfSection>(),
+                    ptr.add(header_size / 4) as *mut MiniElfSection,
+                    (tag.size as usize - header_size) / mem::size_of::<MiniElfSection>(),
                 ),
             }
         }
     }
 
+    /// The number of pages this program occupies.  Pages are allocated in
+    /// the same order by both `copy_processes()` and `load()`.
+    pub fn page_count(&self) -> usize {
+        let mut page_count = 0;
+        let mut page_addr = None;
+        for section in self.sections {
+            if Some(section.virt as usize & !(PAGE_SIZE - 1)) != page_addr {
+                page_count += 1;
+            }
+
+            let mut first_chunk_size = PAGE_SIZE - (section.virt as usize & (PAGE_SIZE - 1));
+            if first_chunk_size > section.len() {
+                first_chunk_size = section.len();
+            }
+            let mut bytes_to_copy = section.len() - first_chunk_size;
+            while bytes_to_copy > PAGE_SIZE {
+                page_count += 1;
+                bytes_to_copy -= PAGE_SIZE;
+            }
+            if bytes_to_copy > 0 {
+                page_count += 1;
+            }
+
+            page_addr = Some((section.virt as usize + section.len()) & !(PAGE_SIZE - 1));
+        }
+        page_count
+    }
+
     /// Load the process into its own memory space.
     /// The process will have been already loaded in stage 1.  This simply assigns
-    /// memory maps as necessary.
-    pub fn load(&self, allocator: &mut BootConfig, load_offset: usize, pid: XousPid) -> usize {
+    /// memory maps as necessary.  Position-independent programs, which come
+    /// with `relocations`, are moved up by `PIE_LOAD_OFFSET` and relocated.
+    pub fn load(
+        &self,
+        allocator: &mut BootConfig,
+        load_offset: usize,
+        pid: XousPid,
+        relocations: Option<&[u32]>,
+    ) -> usize {
         println!("Mapping PID {} starting at offset {:08x}", pid, load_offset);
-        let mut allocated_bytes = 0;
 
+        let virt_offset = if relocations.is_some() {
+            PIE_LOAD_OFFSET + allocator.aslr_offset()
+        } else {
+            0
+        };
         let mut page_addr: usize = 0;
         let mut previous_addr: usize = 0;
 
         // The load offset is the end of this process.  Shift it down by one page
-        // so we get the start of the first page.
-        let mut top = load_offset - PAGE_SIZE;
-        let stack_addr = USER_STACK_TOP - 4;
+        // so we get the start of the first page, skipping any pages that failed
+        // the memory test just as `copy_processes()` did.
+        let mut top = allocator.page_below(load_offset);
+        let stack_addr = USER_STACK_TOP - allocator.aslr_offset() - 4;
 
         // Allocate a page to handle the top-level memory translation
         let satp_address = allocator.alloc() as usize;
@@ -231,8 +338,9 @@ impl MiniElf {
         let pt_addr = allocator.alloc() as usize;
         allocator.map_page(satp, pt_addr, PAGE_TABLE_OFFSET, FLG_R | FLG_W);
 
-        // Allocate stack pages.
-        for i in 0..STACK_PAGE_COUNT {
+        // Allocate stack pages.  The kernel allocates the rest of the stack
+        // as it gets used.
+        for i in 0..minielf::stack_page_count(self.stack_size as usize) {
             let sp_page = allocator.alloc() as usize;
             allocator.map_page(
                 satp,
@@ -257,29 +365,35 @@ impl MiniElf {
         // Example: Page starts at oxf0c0 and is 128 bytes long
         // 1. Copy 128 bytes to page 1
         for section in self.sections {
-            let flag_defaults = FLG_U
-                | FLG_R
-                | if section.flags() & 1 == 1 { FLG_W } else { 0 }
-                | if section.flags() & 4 == 4 { FLG_X } else { 0 };
+            let mut flag_defaults = FLG_U | FLG_R;
+            if section.flags().contains(SectionFlags::WRITE) {
+                flag_defaults |= FLG_W;
+            }
+            if section.flags().contains(SectionFlags::EXECUTE) {
+                flag_defaults |= FLG_X;
+            }
 
-            if (section.virt as usize) < previous_addr {
+            let virt = section.virt as usize + virt_offset;
+            if virt < previous_addr {
                 panic!("init section addresses are not strictly increasing");
             }
+            if virt + section.len() > USER_AREA_END {
+                panic!("init section at {:08x} is outside of userspace", virt);
+            }
 
-            let mut this_page = section.virt as usize & !(PAGE_SIZE - 1);
+            let mut this_page = virt & !(PAGE_SIZE - 1);
             let mut bytes_to_copy = section.len();
 
             // If this is not a new page, ensure the uninitialized values from between
             // this section and the previous one are all zeroed out.
             if this_page != page_addr {
                 allocator.map_page(satp, top as usize, this_page, flag_defaults);
-                allocated_bytes += PAGE_SIZE;
-                top -= PAGE_SIZE;
+                top = allocator.page_below(top);
                 this_page += PAGE_SIZE;
             }
 
             // Part 1: Copy the first chunk over.
-            let mut first_chunk_size = PAGE_SIZE - (section.virt as usize & (PAGE_SIZE - 1));
+            let mut first_chunk_size = PAGE_SIZE - (virt & (PAGE_SIZE - 1));
             if first_chunk_size > section.len() {
                 first_chunk_size = section.len();
             }
@@ -288,32 +402,94 @@ impl MiniElf {
             // Part 2: Copy any full pages.
             while bytes_to_copy > PAGE_SIZE {
                 allocator.map_page(satp, top as usize, this_page, flag_defaults);
-                allocated_bytes += PAGE_SIZE;
-                top -= PAGE_SIZE;
+                top = allocator.page_below(top);
                 this_page += PAGE_SIZE;
                 bytes_to_copy -= PAGE_SIZE;
             }
 
             // Part 3: Copy the final residual partial page
             if bytes_to_copy > 0 {
-                let this_page = (section.virt as usize + section.len()) & !(PAGE_SIZE - 1);
+                let this_page = (virt + section.len()) & !(PAGE_SIZE - 1);
                 allocator.map_page(satp, top as usize, this_page, flag_defaults);
-                allocated_bytes += PAGE_SIZE;
-                top -= PAGE_SIZE;
+                top = allocator.page_below(top);
                 // this_page += PAGE_SIZE;
             }
 
-            previous_addr = section.virt as usize + section.len();
+            previous_addr = virt + section.len();
             page_addr = previous_addr & !(PAGE_SIZE - 1);
         }
 
+        // The program was copied in stage 1 as it was linked, so fix up
+        // every pointer now that its final address is known.
+        for offset in relocations.unwrap_or(&[]) {
+            let virt = *offset as usize + virt_offset;
+            let phys = virt_to_phys(satp, virt)
+                .unwrap_or_else(|| panic!("relocation at {:08x} is not mapped", virt));
+            let word = phys as *mut u32;
+            unsafe { word.write(minielf::relocate(word.read(), virt_offset)) };
+        }
+
         let mut process = &mut allocator.processes[pid as usize - 1];
-        process.entrypoint = self.entry_point as usize;
+        process.entrypoint = self.entry_point as usize + virt_offset;
         process.sp = stack_addr;
+        process.stack_size = self.stack_size as usize;
+        process.heap_max = self.heap_max as usize;
+        process.name = self.name;
         process.satp = 0x8000_0000 | ((pid as usize) << 22) | (satp_address >> 12);
 
-        allocated_bytes
+        load_offset - (top + PAGE_SIZE)
+    }
+}
+
+/// Describes the relocation table of a position-independent initial
+/// program.  This comes from an `IniR` tag, which follows the program's
+/// `IniE` tag.
+pub struct Relocations {
+    /// Position of the relocation table relative to the start of the
+    /// arguments block, or an absolute address if `ABSOLUTE` is set.
+    pub load_offset: u32,
+
+    /// The number of entries in the relocation table
+    pub count: u32,
+}
+
+impl Relocations {
+    pub fn new(tag: &KernelArgument) -> Self {
+        Relocations {
+            load_offset: tag.data[0],
+            count: tag.data[1],
+        }
+    }
+
+    /// The link-time address of each word that must have the load offset
+    /// added to it.
+    pub fn offsets(&self, cfg: &BootConfig) -> &'static [u32] {
+        unsafe {
+            slice::from_raw_parts(
+                cfg.base_addr
+                    .add(self.load_offset as usize / mem::size_of::<usize>())
+                    as *const u32,
+                self.count as usize,
+            )
+        }
+    }
+}
+
+/// Look up the physical address that `virt` is mapped to in `root`.
+fn virt_to_phys(root: &PageTable, virt: usize) -> Option<usize> {
+    let vpn1 = (virt >> 22) & ((1 << 10) - 1);
+    let vpn0 = (virt >> 12) & ((1 << 10) - 1);
+
+    let l1_entry = root.entries[vpn1];
+    if l1_entry & FLG_VALID == 0 {
+        return None;
+    }
+    let l0_pt = unsafe { &*(((l1_entry << 2) & !((1 << 12) - 1)) as *const PageTable) };
+    let l0_entry = l0_pt.entries[vpn0];
+    if l0_entry & FLG_VALID == 0 {
+        return None;
     }
+    Some(((l0_entry >> 10) << 12) | (virt & (PAGE_SIZE - 1)))
 }
 
 /// This describes the kernel as well as initially-loaded processes
@@ -360,6 +536,13 @@ extern "C" {
 }
 
 impl ProgramDescription {
+    /// The number of bytes of RAM the program occupies once copied, with
+    /// its text and data each rounded up to a whole page.
+    pub fn load_size_rounded(&self) -> usize {
+        ((self.text_size as usize + PAGE_SIZE - 1) & !(PAGE_SIZE - 1))
+            + (((self.data_size + self.bss_size) as usize + PAGE_SIZE - 1) & !(PAGE_SIZE - 1))
+    }
+
     /// Map this ProgramDescription into RAM.
     /// The program may already have been relocated, and so may be
     /// either on SPI flash or in RAM.  The `load_offset` argument
@@ -524,20 +707,103 @@ where
     }
 }
 
+/// Reasons an arguments block can't be booted
+#[derive(Debug, PartialEq)]
+pub enum ConfigError {
+    /// `XArg` wasn't the first tag, or was the wrong size
+    InvalidXArg,
+
+    /// A tag's CRC16 doesn't match its contents, and debug mode isn't set
+    CorruptTag(u32 /* tag name */, u32 /* tag offset */),
+
+    /// More than one `XKrn` tag was found
+    DuplicateKernel,
+
+    /// The `XKrn` tag is the wrong size
+    InvalidKernelSize,
+
+    /// An `IniE` tag is too small to hold a program
+    InvalidInitSize,
+
+    /// An `IniR` tag doesn't directly follow an `IniE` tag
+    OrphanRelocations,
+
+    /// An `IniR` tag is the wrong size
+    InvalidRelocationsSize,
+
+    /// No `XKrn` tag was found
+    NoKernel,
+
+    /// No `IniE` tag was found
+    NoInitialPrograms,
+
+    /// Compressed programs were left in place
+    CompressedNotCopied,
+}
+
+impl core::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            ConfigError::InvalidXArg => write!(f, "XArg wasn't first tag, or was invalid size"),
+            ConfigError::CorruptTag(name, offset) => write!(
+                f,
+                "refusing to boot: {} tag at offset {:08x} is corrupt",
+                TagName(*name),
+                offset
+            ),
+            ConfigError::DuplicateKernel => write!(f, "kernel appears twice"),
+            ConfigError::InvalidKernelSize => write!(f, "invalid XKrn size"),
+            ConfigError::InvalidInitSize => write!(f, "invalid Init size"),
+            ConfigError::OrphanRelocations => write!(f, "IniR does not follow an IniE"),
+            ConfigError::InvalidRelocationsSize => write!(f, "invalid IniR size"),
+            ConfigError::NoKernel => write!(f, "no kernel definition"),
+            ConfigError::NoInitialPrograms => write!(f, "no initial programs found"),
+            ConfigError::CompressedNotCopied => {
+                write!(f, "compressed programs must be copied to RAM")
+            }
+        }
+    }
+}
+
 pub fn read_initial_config(cfg: &mut BootConfig) {
+    if let Err(e) = parse_initial_config(cfg) {
+        panic!("{}", e);
+    }
+}
+
+/// Fill in `cfg` from its arguments block, checking that the tags
+/// describe something that can be booted.
+pub fn parse_initial_config(cfg: &mut BootConfig) -> Result<(), ConfigError> {
     let args = cfg.args;
     let mut i = args.iter();
-    let xarg = i.next().expect("couldn't read initial tag");
+    let xarg = i.next().ok_or(ConfigError::InvalidXArg)?;
     if xarg.name != u32::from_le_bytes(*b"XArg") || xarg.size != 20 {
-        panic!("XArg wasn't first tag, or was invalid size");
+        return Err(ConfigError::InvalidXArg);
     }
     cfg.sram_start = xarg.data[2] as *mut usize;
     cfg.sram_size = xarg.data[3] as usize;
 
     let mut kernel_seen = false;
     let mut init_seen = false;
+    let mut previous_tag = xarg.name;
+
+    // Corrupt tags are only tolerated in debug mode, which isn't known
+    // until Bflg has been read.  Remember the first one for later.
+    let mut corrupt_tag = None;
+    let mut flags_corrupt = false;
+    if !xarg.crc_valid() {
+        report_corrupt_tag(&xarg);
+        corrupt_tag = Some(xarg);
+    }
 
     for tag in i {
+        if !tag.crc_valid() {
+            report_corrupt_tag(&tag);
+            if tag.name == u32::from_le_bytes(*b"Bflg") {
+                flags_corrupt = true;
+            }
+        }
+
         if tag.name == u32::from_le_bytes(*b"MREx") {
             cfg.regions = unsafe {
                 slice::from_raw_parts(
@@ -556,30 +822,186 @@ pub fn read_initial_config(cfg: &mut BootConfig) {
             if boot_flags & (1 << 2) != 0 {
                 cfg.debug = true;
             }
+            if boot_flags & (1 << 3) != 0 {
+                cfg.compressed = true;
+            }
+            if boot_flags & (1 << 4) != 0 {
+                cfg.aslr = true;
+            }
+            if boot_flags & (1 << 5) != 0 {
+                cfg.memtest = true;
+            }
         } else if tag.name == u32::from_le_bytes(*b"XKrn") {
-            assert!(!kernel_seen, "kernel appears twice");
-            assert!(
-                tag.size as usize == mem::size_of::<ProgramDescription>(),
-                "invalid XKrn size"
-            );
+            if kernel_seen {
+                return Err(ConfigError::DuplicateKernel);
+            }
+            if tag.size as usize != mem::size_of::<ProgramDescription>() {
+                return Err(ConfigError::InvalidKernelSize);
+            }
             kernel_seen = true;
         } else if tag.name == u32::from_le_bytes(*b"IniE") {
-            assert!(tag.size >= 4, "invalid Init size");
+            if (tag.size as usize) < 16 + PROCESS_NAME_LENGTH {
+                return Err(ConfigError::InvalidInitSize);
+            }
             init_seen = true;
             cfg.init_process_count += 1;
+        } else if tag.name == u32::from_le_bytes(*b"IniR") {
+            if previous_tag != u32::from_le_bytes(*b"IniE") {
+                return Err(ConfigError::OrphanRelocations);
+            }
+            if tag.size != 8 {
+                return Err(ConfigError::InvalidRelocationsSize);
+            }
+        }
+        previous_tag = tag.name;
+
+        if !tag.crc_valid() && corrupt_tag.is_none() {
+            corrupt_tag = Some(tag);
+        }
+    }
+
+    if let Some(tag) = corrupt_tag {
+        // A corrupt Bflg can't be trusted to enable debug mode
+        if !cfg.debug || flags_corrupt {
+            return Err(ConfigError::CorruptTag(tag.name, tag.offset));
+        }
+        println!("DEBUG mode is set, so booting anyway");
+    }
+
+    if !kernel_seen {
+        return Err(ConfigError::NoKernel);
+    }
+    if !init_seen {
+        return Err(ConfigError::NoInitialPrograms);
+    }
+    if cfg.no_copy && cfg.compressed {
+        return Err(ConfigError::CompressedNotCopied);
+    }
+    Ok(())
+}
+
+/// Displays a tag code as its four-character name.
+struct TagName(u32);
+
+impl core::fmt::Display for TagName {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        for c in self.0.to_le_bytes().iter() {
+            write!(f, "{}", *c as char)?;
+        }
+        Ok(())
+    }
+}
+
+fn report_corrupt_tag(_tag: &KernelArgument) {
+    println!(
+        "{} tag at offset {:08x} is corrupt: CRC16 is {:04x}, but data has CRC16 {:04x}",
+        TagName(_tag.name),
+        _tag.offset,
+        _tag.crc,
+        _tag.calculated_crc()
+    );
+}
+
+/// Return the compressed stream stored at `offset` bytes from the base
+/// address, along with the offset of whatever follows it.  Each stream
+/// is its length in bytes, followed by LZ4 block data padded to a word.
+fn compressed_stream(cfg: &BootConfig, offset: usize) -> (&'static [u8], usize) {
+    let start = cfg.base_addr as usize + offset;
+    let len = unsafe { (start as *const u32).read() } as usize;
+    let data = unsafe { slice::from_raw_parts((start + 4) as *const u8, len) };
+    (data, offset + 4 + ((len + 3) & !3))
+}
+
+/// Decompress an initial program into newly-allocated RAM.  The stream
+/// contains the contents of every page the program occupies, in the order
+/// the pages are mapped by `MiniElf::load()`.
+fn decompress_process(cfg: &mut BootConfig, tag: &KernelArgument) {
+    let inie = MiniElf::new(tag);
+    let page_count = inie.page_count();
+    println!("Decompressing {} pages of IniE", page_count);
+    if page_count == 0 {
+        return;
+    }
+
+    let first_page = cfg.claim_pages(1) as usize;
+    let mut last_page = first_page;
+    for _ in 1..page_count {
+        last_page = cfg.claim_pages(1) as usize;
+    }
+    let len = page_count * PAGE_SIZE;
+    let (stream, _) = compressed_stream(cfg, inie.load_offset as usize);
+
+    // The pages are adjacent unless some were skipped for failing the memory
+    // test.  In that case, decompress the program into free pages below them,
+    // and then move each page into place.
+    if first_page - last_page == len - PAGE_SIZE {
+        let mut pages = lz4::Pages::descending(first_page as *mut u8, len);
+        lz4::decompress(stream, &mut pages).expect("IniE payload is corrupt");
+        return;
+    }
+    let scratch = cfg.clear_of_bad_pages(cfg.get_top() as usize, len) - PAGE_SIZE;
+    let mut pages = lz4::Pages::descending(scratch as *mut u8, len);
+    lz4::decompress(stream, &mut pages).expect("IniE payload is corrupt");
+
+    let mut page = first_page;
+    for index in 0..page_count {
+        if index != 0 {
+            page = cfg.page_below(page);
         }
+        unsafe {
+            memcpy(
+                page as *mut usize,
+                (scratch - index * PAGE_SIZE) as *const usize,
+                PAGE_SIZE,
+            )
+        };
     }
+}
 
-    assert!(kernel_seen, "no kernel definition");
-    assert!(init_seen, "no initial programs found");
+/// Decompress the kernel into newly-allocated RAM.  The kernel consists
+/// of a stream containing the text section followed by a stream containing
+/// the data section.
+fn decompress_kernel(cfg: &mut BootConfig, tag: &KernelArgument) {
+    let prog = unsafe { &*(tag.data.as_ptr() as *const ProgramDescription) };
+    let (text, data_offset) = compressed_stream(cfg, prog.load_offset as usize);
+    let (data, _) = compressed_stream(cfg, data_offset);
+
+    // The kernel is mapped as one run of pages, so none of them may have
+    // failed the memory test.
+    cfg.skip_bad_pages(prog.load_size_rounded());
+
+    let sections = [
+        (text, prog.text_size as usize, prog.text_size as usize),
+        (
+            data,
+            prog.data_size as usize,
+            (prog.data_size + prog.bss_size) as usize,
+        ),
+    ];
+    for (stream, size, load_size) in sections.iter() {
+        // Round it off to a page boundary
+        let load_size_rounded = (load_size + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+        cfg.extra_pages += load_size_rounded / PAGE_SIZE;
+        let top = cfg.get_top() as *mut u8;
+        println!("    Decompressing {} bytes to {:08x}", size, top as usize);
+        let mut pages = lz4::Pages::ascending(top, *size);
+        lz4::decompress(stream, &mut pages).expect("XKrn payload is corrupt");
+
+        // Zero out the remaining data.
+        unsafe { bzero(top.add(*size), top.add(load_size_rounded)) };
+    }
 }
 
 /// Copy program data from the SPI flash into newly-allocated RAM
 /// located at the end of memory space.
 fn copy_processes(cfg: &mut BootConfig) {
     for tag in cfg.args.iter() {
-        if tag.name == u32::from_le_bytes(*b"IniE") {
-            let mut page_addr: usize = 0;
+        if cfg.compressed && tag.name == u32::from_le_bytes(*b"IniE") {
+            decompress_process(cfg, &tag);
+        } else if cfg.compressed && tag.name == u32::from_le_bytes(*b"XKrn") {
+            decompress_kernel(cfg, &tag);
+        } else if tag.name == u32::from_le_bytes(*b"IniE") {
+            let mut page_addr = None;
             let mut previous_addr: usize = 0;
             let mut top = core::ptr::null_mut::<u8>();
 
@@ -617,7 +1039,7 @@ fn copy_processes(cfg: &mut BootConfig) {
                 );
                 // If this is not a new page, ensure the uninitialized values from between
                 // this section and the previous one are all zeroed out.
-                if this_page != page_addr {
+                if Some(this_page) != page_addr {
                     println!("New page @ {:08x}", this_page);
                     if previous_addr != 0 {
                         println!(
@@ -633,8 +1055,7 @@ fn copy_processes(cfg: &mut BootConfig) {
                     }
 
                     // Allocate a new page.
-                    cfg.extra_pages += 1;
-                    top = cfg.get_top() as *mut u8;
+                    top = cfg.claim_pages(1) as *mut u8;
 
                     // Zero out the page, if necessary.
                     unsafe { bzero(top, top.add(section.virt as usize & (PAGE_SIZE - 1))) };
@@ -676,8 +1097,7 @@ fn copy_processes(cfg: &mut BootConfig) {
 
                 // Part 2: Copy any full pages.
                 while bytes_to_copy > PAGE_SIZE {
-                    cfg.extra_pages += 1;
-                    top = cfg.get_top() as *mut u8;
+                    top = cfg.claim_pages(1) as *mut u8;
                     // println!(
                     //     "Copying next page from {:08x} {:08x}",
                     //     src_addr as usize, top as usize
@@ -696,8 +1116,7 @@ fn copy_processes(cfg: &mut BootConfig) {
                 // Part 3: Copy the final residual partial page
                 if bytes_to_copy > 0 {
                     println!("Copying final section -- {} bytes", bytes_to_copy);
-                    cfg.extra_pages += 1;
-                    top = cfg.get_top() as *mut u8;
+                    top = cfg.claim_pages(1) as *mut u8;
                     if !section.no_copy() {
                         unsafe {
                             memcpy(top, src_addr, bytes_to_copy);
@@ -709,7 +1128,7 @@ fn copy_processes(cfg: &mut BootConfig) {
                 }
 
                 previous_addr = section.virt as usize + section.len();
-                page_addr = previous_addr & !(PAGE_SIZE - 1);
+                page_addr = Some(previous_addr & !(PAGE_SIZE - 1));
                 println!("Looping to the next section");
             }
 
@@ -724,6 +1143,10 @@ fn copy_processes(cfg: &mut BootConfig) {
         } else if tag.name == u32::from_le_bytes(*b"XKrn") {
             let prog = unsafe { &*(tag.data.as_ptr() as *const ProgramDescription) };
 
+            // The kernel is mapped as one run of pages, so none of them may
+            // have failed the memory test.
+            cfg.skip_bad_pages(prog.load_size_rounded());
+
             // TEXT SECTION
             // Round it off to a page boundary
             let load_size_rounded = (prog.text_size as usize + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
@@ -793,7 +1216,71 @@ fn copy_processes(cfg: &mut BootConfig) {
     }
 }
 
+/// How many times to poll the TRNG for a fresh word before giving up
+#[cfg(all(target_arch = "riscv32", not(feature = "qemu-virt")))]
+const TRNG_POLL_LIMIT: usize = 1_000_000;
+
+/// Read a word from the SoC's TRNG, or `None` if it never produces one.
+/// The TRNG is left in the state the loader found it in.
+#[cfg(all(target_arch = "riscv32", not(feature = "qemu-virt")))]
+fn trng_word() -> Option<u32> {
+    let mut trng = CSR::new(utra::trng_osc::HW_TRNG_OSC_BASE as *mut u32);
+    let ctl = trng.r(utra::trng_osc::CTL);
+    trng.rmwf(utra::trng_osc::CTL_ENA, 1);
+    let mut word = None;
+    for _ in 0..TRNG_POLL_LIMIT {
+        if trng.rf(utra::trng_osc::STATUS_FRESH) != 0 {
+            word = Some(trng.r(utra::trng_osc::RAND));
+            break;
+        }
+    }
+    trng.wo(utra::trng_osc::CTL, ctl);
+    word
+}
+
+/// The QEMU `virt` machine has no TRNG.
+#[cfg(all(target_arch = "riscv32", feature = "qemu-virt"))]
+fn trng_word() -> Option<u32> {
+    None
+}
+
+/// Return a value that differs from one boot to the next, for seeding the
+/// random number generator.  This comes from the TRNG, mixed with the cycle
+/// count in case the TRNG is stuck.  The cycle count alone is only used if
+/// there's no TRNG, and is easy to predict.
+#[cfg(target_arch = "riscv32")]
+fn entropy() -> u32 {
+    let cycles = riscv::register::mcycle::read() as u32;
+    match trng_word() {
+        Some(word) => word ^ cycles,
+        None => {
+            println!("WARNING: no TRNG, so ASLR is seeded from the cycle count");
+            cycles
+        }
+    }
+}
+
+#[cfg(not(target_arch = "riscv32"))]
+fn entropy() -> u32 {
+    0x5eed
+}
+
 impl BootConfig {
+    /// Return a random, page-aligned offset less than `ASLR_WINDOW`, or 0
+    /// if `ASLR` isn't set.
+    fn aslr_offset(&mut self) -> usize {
+        if !self.aslr {
+            return 0;
+        }
+        if self.rng_state == 0 {
+            self.rng_state = entropy() | 1;
+        }
+        self.rng_state ^= self.rng_state << 13;
+        self.rng_state ^= self.rng_state >> 17;
+        self.rng_state ^= self.rng_state << 5;
+        (self.rng_state as usize % (ASLR_WINDOW / PAGE_SIZE)) * PAGE_SIZE
+    }
+
     fn get_top(&self) -> *mut usize {
         let val = unsafe {
             self.sram_start.add(
@@ -813,11 +1300,62 @@ impl BootConfig {
         val
     }
 
+    /// Whether the page of RAM holding `addr` failed the memory test.
+    fn is_bad_page(&self, addr: usize) -> bool {
+        let start = self.sram_start as usize;
+        addr >= start
+            && addr < start + self.sram_size
+            && self.runtime_page_tracker.get((addr - start) / PAGE_SIZE)
+                == Some(&memtest::BAD_PAGE_PID)
+    }
+
+    /// Find the highest address, at or below `end`, that `len` bytes can
+    /// end at without touching a page that failed the memory test.
+    fn clear_of_bad_pages(&self, mut end: usize, len: usize) -> usize {
+        while let Some(bad) = ((end - len) & !(PAGE_SIZE - 1)..end)
+            .step_by(PAGE_SIZE)
+            .rev()
+            .find(|addr| self.is_bad_page(*addr))
+        {
+            end = bad;
+        }
+        end
+    }
+
+    /// The highest page below `addr` that passed the memory test.
+    fn page_below(&self, addr: usize) -> usize {
+        self.clear_of_bad_pages(addr, PAGE_SIZE) - PAGE_SIZE
+    }
+
+    /// Grow `init_size` by `len` bytes, skipping past any pages that
+    /// failed the memory test.
+    fn grow_init(&mut self, len: usize) {
+        let top = self.sram_start as usize + self.sram_size - self.init_size;
+        let end = self.clear_of_bad_pages(top, len);
+        self.init_size += top - end + len;
+    }
+
+    /// Skip past any pages that failed the memory test, so that the next
+    /// `len` bytes of pages claimed are all good.
+    fn skip_bad_pages(&mut self, len: usize) {
+        let top = self.sram_start as usize + self.sram_size
+            - self.init_size
+            - self.extra_pages * PAGE_SIZE;
+        self.extra_pages += (top - self.clear_of_bad_pages(top, len)) / PAGE_SIZE;
+    }
+
+    /// Claim the next `count` pages that passed the memory test, and
+    /// return the lowest of them.
+    fn claim_pages(&mut self, count: usize) -> *mut usize {
+        self.skip_bad_pages(count * PAGE_SIZE);
+        self.extra_pages += count;
+        self.get_top()
+    }
+
     /// Zero-alloc a new page, mark it as owned by PID1, and return it.
     /// Decrement the `next_page_offset` (npo) variable by one page.
     pub fn alloc(&mut self) -> *mut usize {
-        self.extra_pages += 1;
-        let pg = self.get_top();
+        let pg = self.claim_pages(1);
         unsafe {
             // Grab the page address and zero it out
             bzero(
@@ -826,9 +1364,8 @@ impl BootConfig {
             );
         }
         // Mark this page as in-use by the kernel
-        let extra_bytes = self.extra_pages * PAGE_SIZE;
-        self.runtime_page_tracker[(self.sram_size - (extra_bytes + self.init_size)) / PAGE_SIZE] =
-            1;
+        let page = (pg as usize - self.sram_start as usize) / PAGE_SIZE;
+        self.runtime_page_tracker[page] = 1;
 
         // Return the address
         pg as *mut usize
@@ -967,7 +1504,7 @@ pub fn allocate_processes(cfg: &mut BootConfig) {
     let process_count = cfg.init_process_count + 1;
     let table_size = process_count * mem::size_of::<InitialProcess>();
     // Allocate the process table
-    cfg.init_size += table_size;
+    cfg.grow_init(table_size);
     let processes = cfg.get_top();
     unsafe {
         bzero(
@@ -981,17 +1518,53 @@ pub fn allocate_processes(cfg: &mut BootConfig) {
 }
 
 pub fn copy_args(cfg: &mut BootConfig) {
-    // Copy the args list to target RAM
-    cfg.init_size += cfg.args.size();
+    // Copy the args list to target RAM, leaving room for a `MemT` tag
+    // if RAM was tested, a `BLog` tag if there is a boot log, and a `BSlt`
+    // tag if the image came from a boot slot.
+    let args_size = cfg.args.size();
+    let memtest_size = match &cfg.memtest_summary {
+        Some(summary) => memtest::tag_size(summary.bad_pages),
+        None => 0,
+    };
+    let bootlog_size = match &cfg.boot_log {
+        Some(_) => bootlog::TAG_SIZE,
+        None => 0,
+    };
+    let slot_size = match cfg.slot_confirmed {
+        Some(_) => slots::TAG_SIZE,
+        None => 0,
+    };
+    let total_size = args_size + memtest_size + bootlog_size + slot_size;
+    cfg.grow_init(total_size);
     let runtime_arg_buffer = cfg.get_top();
     unsafe {
         #[allow(clippy::cast_ptr_alignment)]
         memcpy(
             runtime_arg_buffer,
             cfg.args.base as *const usize,
-            cfg.args.size() as usize,
+            args_size as usize,
         )
     };
+
+    if total_size != args_size {
+        let words =
+            unsafe { slice::from_raw_parts_mut(runtime_arg_buffer as *mut u32, total_size / 4) };
+        if let Some(summary) = &cfg.memtest_summary {
+            memtest::write_tag(cfg, summary, &mut words[args_size / 4..]);
+        }
+        if let Some(log) = &cfg.boot_log {
+            bootlog::write_tag(log, &mut words[(args_size + memtest_size) / 4..]);
+        }
+        if let Some(confirmed) = cfg.slot_confirmed {
+            let offset = args_size + memtest_size + bootlog_size;
+            slots::write_tag(confirmed, &mut words[offset / 4..]);
+        }
+
+        // Grow the arguments block to include the new tags
+        words[2] = words.len() as u32;
+        let crc = args::crc16(&words[2..7]);
+        words[1] = 5 << 16 | crc as u32;
+    }
     cfg.args = KernelArguments::new(runtime_arg_buffer);
 }
 
@@ -1000,26 +1573,39 @@ pub fn copy_args(cfg: &mut BootConfig) {
 /// and copying the arguments to RAM.
 /// Assume the bootloader has already set up the stack to point to the end of RAM.
 ///
+/// `arg_buffer` points either to an arguments block, or to a `BootSlots`
+/// record describing two images to choose between.
+///
 /// # Safety
 ///
 /// This function is safe to call exactly once.
 #[export_name = "rust_entry"]
 pub unsafe extern "C" fn rust_entry(arg_buffer: *const usize, signature: u32) -> ! {
-    let kab = KernelArguments::new(arg_buffer);
-    boot_sequence(kab, signature);
-}
-
-fn boot_sequence(args: KernelArguments, _signature: u32) -> ! {
     // Store the initial boot config on the stack.  We don't know
     // where in heap this memory will go.
-    #[allow(clippy::cast_ptr_alignment)] // This test only works on 32-bit systems
-    let mut cfg = BootConfig {
-        base_addr: args.base as *const usize,
-        args,
-        ..Default::default()
+    let cfg = if slots::BootSlots::is_boot_slots(arg_buffer) {
+        let slots = &mut *(arg_buffer as *mut slots::BootSlots);
+        slots
+            .select(&signature::PUBLIC_KEY)
+            .unwrap_or_else(|e| panic!("refusing to boot: {}", e))
+    } else {
+        let args = KernelArguments::new(arg_buffer);
+        #[allow(clippy::cast_ptr_alignment)] // This test only works on 32-bit systems
+        let mut cfg = BootConfig {
+            base_addr: args.base as *const usize,
+            args,
+            ..Default::default()
+        };
+        read_initial_config(&mut cfg);
+        if let Err(e) = signature::verify(&cfg, &signature::PUBLIC_KEY) {
+            panic!("refusing to boot: {}", e);
+        }
+        cfg
     };
-    read_initial_config(&mut cfg);
+    boot_sequence(cfg, signature);
+}
 
+fn boot_sequence(mut cfg: BootConfig, _signature: u32) -> ! {
     phase_1(&mut cfg);
     phase_2(&mut cfg);
 
@@ -1035,6 +1621,7 @@ fn boot_sequence(args: KernelArguments, _signature: u32) -> ! {
         cfg.processes[0].entrypoint, cfg.processes[0].satp, cfg.processes[0].sp,
         arg_offset, ip_offset, rpt_offset,
     );
+    cfg.log(bootlog::EntryKind::Phase, &[3]);
     unsafe {
         start_kernel(
             arg_offset,
@@ -1056,11 +1643,38 @@ fn phase_1(cfg: &mut BootConfig) {
     // All other allocations will be placed below the stack pointer.
     cfg.init_size += PAGE_SIZE * 2;
 
+    // Start the boot log in a page of its own below the stack, so that it
+    // can be handed to userspace without exposing anything else.
+    cfg.init_size += bootlog::BOOT_LOG_SIZE;
+    let boot_log = cfg.get_top();
+    unsafe { bzero(boot_log, boot_log.add(bootlog::BOOT_LOG_SIZE / WORD_SIZE)) };
+    cfg.boot_log = Some(bootlog::BootLog::new(boot_log));
+    cfg.log(bootlog::EntryKind::Phase, &[1]);
+    if let Some(slot) = cfg.boot_slot {
+        cfg.log(bootlog::EntryKind::Slot, &slot);
+    }
+
     // The first region is defined as being "main RAM", which will be used
     // to keep track of allocations.
     println!("Allocating regions");
     allocate_regions(cfg);
 
+    // Test RAM now, while only the stack and page tracker are in use.
+    if cfg.memtest {
+        memtest::test_ram(cfg);
+        if let Some(summary) = &cfg.memtest_summary {
+            let data = [summary.tested_pages as u32, summary.bad_pages as u32];
+            cfg.log(bootlog::EntryKind::MemTest, &data);
+        }
+    }
+
+    copy_to_ram(cfg);
+}
+
+/// Copy the process table, arguments and programs into RAM below the page
+/// tracker, avoiding any pages that failed the memory test, and mark the
+/// pages they use as belonging to the kernel.
+pub fn copy_to_ram(cfg: &mut BootConfig) {
     // The kernel, as well as initial processes, are all stored in RAM.
     println!("Allocating processes");
     allocate_processes(cfg);
@@ -1089,17 +1703,24 @@ fn phase_1(cfg: &mut BootConfig) {
     // NOTE: This causes the .text section to be owned by the kernel!  This
     // will require us to transfer ownership in `stage3`.
     // Note also that we skip the first index, causing the stack to be
-    // returned to the process pool.
+    // returned to the process pool.  Pages that failed the memory test
+    // were skipped over, and stay with `BAD_PAGE_PID`.
     println!("Marking pages as in-use");
     for i in 1..(cfg.init_size / PAGE_SIZE) {
-        cfg.runtime_page_tracker[cfg.sram_size / PAGE_SIZE - i] = 1;
+        let page = cfg.sram_size / PAGE_SIZE - i;
+        if cfg.runtime_page_tracker[page] != memtest::BAD_PAGE_PID {
+            cfg.runtime_page_tracker[page] = 1;
+        }
     }
+    let pages = [(cfg.init_size / PAGE_SIZE) as u32, cfg.extra_pages as u32];
+    cfg.log(bootlog::EntryKind::Pages, &pages);
 }
 
 /// Stage 2 bootloader
 /// This sets up the MMU and loads both PID1 and the kernel into RAM.
 pub fn phase_2(cfg: &mut BootConfig) {
     let args = cfg.args;
+    cfg.log(bootlog::EntryKind::Phase, &[2]);
 
     // This is the offset in RAM where programs are loaded from.
     let mut process_offset = cfg.sram_start as usize + cfg.sram_size - cfg.init_size;
@@ -1108,20 +1729,27 @@ pub fn phase_2(cfg: &mut BootConfig) {
     // Go through all Init processes and the kernel, setting up their
     // page tables and mapping memory to them.
     let mut pid = 2;
-    for tag in args.iter() {
+    let mut tags = args.iter().peekable();
+    while let Some(tag) = tags.next() {
         if tag.name == u32::from_le_bytes(*b"IniE") {
             let inie = MiniElf::new(&tag);
             println!("Mapping program into memory");
             // let init = unsafe { &*(tag.data.as_ptr() as *const ProgramDescription) };
             // let load_size_rounded = ((init.text_size as usize + PAGE_SIZE - 1) & !(PAGE_SIZE - 1))
             //     + (((init.data_size + init.bss_size) as usize + PAGE_SIZE - 1) & !(PAGE_SIZE - 1));
-            process_offset -= inie.load(cfg, process_offset, pid);
+            let relocations = match tags.peek() {
+                Some(next) if next.name == u32::from_le_bytes(*b"IniR") => {
+                    Some(Relocations::new(next).offsets(cfg))
+                }
+                _ => None,
+            };
+            process_offset -= inie.load(cfg, process_offset, pid, relocations);
             pid += 1;
         } else if tag.name == u32::from_le_bytes(*b"XKrn") {
             println!("Mapping kernel into memory");
             let xkrn = unsafe { &*(tag.data.as_ptr() as *const ProgramDescription) };
-            let load_size_rounded = ((xkrn.text_size as usize + PAGE_SIZE - 1) & !(PAGE_SIZE - 1))
-                + (((xkrn.data_size + xkrn.bss_size) as usize + PAGE_SIZE - 1) & !(PAGE_SIZE - 1));
+            let load_size_rounded = xkrn.load_size_rounded();
+            process_offset = cfg.clear_of_bad_pages(process_offset, load_size_rounded);
             xkrn.load(cfg, process_offset - load_size_rounded, 1);
             process_offset -= load_size_rounded;
         }
@@ -1136,6 +1764,9 @@ pub fn phase_2(cfg: &mut BootConfig) {
     // Map boot-generated kernel structures into the kernel
     let satp = unsafe { &mut *(krn_l1_pt_addr as *mut PageTable) };
     for addr in (0..cfg.init_size).step_by(PAGE_SIZE as usize) {
+        if cfg.is_bad_page(addr + krn_struct_start) {
+            continue;
+        }
         cfg.map_page(
             satp,
             addr + krn_struct_start,
@@ -1170,4 +1801,17 @@ pub fn phase_2(cfg: &mut BootConfig) {
         cfg.runtime_page_tracker.len()
     );
     cfg.runtime_page_tracker[cfg.sram_size / PAGE_SIZE - 1] = 0;
+
+    for index in 0..cfg.processes.len() {
+        let process = &cfg.processes[index];
+        let mut data = [0u32; 4 + PROCESS_NAME_LENGTH / 4];
+        data[0] = index as u32 + 1;
+        data[1] = process.entrypoint as u32;
+        data[2] = process.sp as u32;
+        data[3] = process.satp as u32;
+        for (word, bytes) in data[4..].iter_mut().zip(process.name.chunks(4)) {
+            *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        cfg.log(bootlog::EntryKind::Process, &data);
+    }
 }
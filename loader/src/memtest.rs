@@ -0,0 +1,164 @@
+//! A March C- test of main RAM, run when `MEMTEST` is set in `Bflg`.
+//! Pages that fail are given to `BAD_PAGE_PID` so that the kernel never
+//! hands them out, and a `MemT` tag summarising the results is appended
+//! to the arguments passed to the kernel.
+
+use crate::{println, BootConfig, XousPid, PAGE_SIZE};
+use core::{mem, ptr};
+
+/// Owner of pages that failed the memory test.  This is larger than any
+/// real process ID, so the page is never free and never claimed.
+pub const BAD_PAGE_PID: XousPid = 255;
+
+/// The most bad page addresses listed in the `MemT` tag.  Every bad page
+/// is counted and marked in the page tracker, however many there are.
+pub const MAX_REPORTED_BAD_PAGES: usize = 8;
+
+/// Size of the `MemT` tag, including its header, when it lists `bad_pages`
+/// bad pages.
+pub fn tag_size(bad_pages: usize) -> usize {
+    8 + 8 + 4 * bad_pages.min(MAX_REPORTED_BAD_PAGES)
+}
+
+/// The results of the memory test, kept until the arguments are copied.
+pub struct MemTestSummary {
+    /// The number of pages that were tested
+    pub tested_pages: usize,
+
+    /// The number of pages that failed
+    pub bad_pages: usize,
+}
+
+/// The elements of a March C- test: whether each runs from the top of RAM
+/// down, the value every word is expected to hold, and the value then
+/// written to it.
+const MARCH_C_MINUS: [(bool, Option<usize>, Option<usize>); 6] = [
+    (false, None, Some(0)),
+    (false, Some(0), Some(!0)),
+    (false, Some(!0), Some(0)),
+    (true, Some(0), Some(!0)),
+    (true, Some(!0), Some(0)),
+    (false, Some(0), None),
+];
+
+/// The number of bytes the image occupies from the start of its arguments
+/// block, which is the length that was signed.  Payloads may only lie
+/// inside this area.
+fn image_length(cfg: &BootConfig) -> usize {
+    cfg.args
+        .iter()
+        .find(|tag| tag.name == u32::from_le_bytes(*b"Sign"))
+        .map(|tag| tag.data[0] as usize)
+        .unwrap_or_else(|| cfg.args.size())
+}
+
+/// Test every page of main RAM below the loader's own allocations,
+/// except for those holding the boot image, and give any that fail to
+/// `BAD_PAGE_PID`.  This must run before anything but the page tracker
+/// has been allocated, since the contents of tested pages are lost.
+pub fn test_ram(cfg: &mut BootConfig) {
+    let ram_start = cfg.sram_start as usize;
+    let top_page = (cfg.get_top() as usize - ram_start) / PAGE_SIZE;
+
+    // The image is still needed, so leave alone any page it touches.
+    let image_start = cfg.args.base as usize;
+    let image_end = image_start + image_length(cfg);
+    let is_tested = |page: usize| {
+        let start = ram_start + page * PAGE_SIZE;
+        start + PAGE_SIZE <= image_start || start >= image_end
+    };
+    let tested_pages = (0..top_page).filter(|page| is_tested(*page)).count();
+    println!(
+        "Testing {} pages of RAM, leaving image at {:08x} - {:08x}",
+        tested_pages, image_start, image_end
+    );
+
+    for &(descending, expected, write) in MARCH_C_MINUS.iter() {
+        for index in 0..top_page {
+            let page = if descending {
+                top_page - 1 - index
+            } else {
+                index
+            };
+            if is_tested(page)
+                && !march_page(ram_start + page * PAGE_SIZE, descending, expected, write)
+            {
+                mark_bad_page(cfg, page);
+            }
+        }
+    }
+
+    let bad_pages = cfg
+        .runtime_page_tracker
+        .iter()
+        .take(top_page)
+        .filter(|owner| **owner == BAD_PAGE_PID)
+        .count();
+    println!("{} of {} pages failed", bad_pages, tested_pages);
+    cfg.memtest_summary = Some(MemTestSummary {
+        tested_pages,
+        bad_pages,
+    });
+}
+
+/// Run one element of the march over the page at `addr`, returning
+/// `false` if any word didn't hold the expected value.
+fn march_page(
+    addr: usize,
+    descending: bool,
+    expected: Option<usize>,
+    write: Option<usize>,
+) -> bool {
+    let words = PAGE_SIZE / mem::size_of::<usize>();
+    let base = addr as *mut usize;
+    let mut passed = true;
+    for index in 0..words {
+        let word = if descending { words - 1 - index } else { index };
+        unsafe {
+            let addr = base.add(word);
+            if let Some(expected) = expected {
+                passed &= ptr::read_volatile(addr) == expected;
+            }
+            if let Some(write) = write {
+                ptr::write_volatile(addr, write);
+            }
+        }
+    }
+    passed
+}
+
+/// Give page `page` of main RAM to `BAD_PAGE_PID`.
+pub fn mark_bad_page(cfg: &mut BootConfig, page: usize) {
+    if cfg.runtime_page_tracker[page] != BAD_PAGE_PID {
+        println!(
+            "Page {:08x} failed the memory test",
+            cfg.sram_start as usize + page * PAGE_SIZE
+        );
+        cfg.runtime_page_tracker[page] = BAD_PAGE_PID;
+    }
+}
+
+/// Write the `MemT` tag for `summary` to `tag`, which must have room for
+/// `tag_size(summary.bad_pages)` bytes.  The tag holds the number of pages
+/// tested, the number that failed, and the addresses of the first
+/// `MAX_REPORTED_BAD_PAGES` of them.
+pub fn write_tag(cfg: &BootConfig, summary: &MemTestSummary, tag: &mut [u32]) {
+    let reported = summary.bad_pages.min(MAX_REPORTED_BAD_PAGES);
+    let size = 2 + reported;
+    tag[0] = u32::from_le_bytes(*b"MemT");
+    tag[2] = summary.tested_pages as u32;
+    tag[3] = summary.bad_pages as u32;
+    let bad_pages = cfg
+        .runtime_page_tracker
+        .iter()
+        .take(cfg.sram_size / PAGE_SIZE)
+        .enumerate()
+        .filter(|(_, owner)| **owner == BAD_PAGE_PID)
+        .map(|(page, _)| cfg.sram_start as usize + page * PAGE_SIZE)
+        .take(reported);
+    for (word, addr) in tag[4..4 + reported].iter_mut().zip(bad_pages) {
+        *word = addr as u32;
+    }
+    let crc = crate::args::crc16(&tag[2..2 + size]);
+    tag[1] = (size as u32) << 16 | crc as u32;
+}
@@ -0,0 +1,161 @@
+//! Verification of the `Sign` tag.  Every boot image must carry an Ed25519
+//! signature covering all of its tags and payloads, and the loader refuses
+//! to boot anything that doesn't verify against its built-in public key.
+
+use crate::{BootConfig, MiniElf, ProgramDescription, Relocations};
+use core::{convert::TryFrom, fmt, mem, slice};
+use ed25519_dalek::{PublicKey, Signature};
+use sha2::{Digest, Sha512};
+
+// Defines `PUBLIC_KEY`, which is generated by build.rs
+include!(concat!(env!("OUT_DIR"), "/public_key.rs"));
+
+/// Context string mixed into every image signature.  Must match the one
+/// used by `create-image`.
+const SIGNATURE_CONTEXT: &[u8] = b"xous-boot-image";
+
+/// Size of the `Sign` contents: the number of signed bytes, followed by
+/// the signature itself.
+const SIGN_TAG_SIZE: usize = 4 + 64;
+
+#[derive(Debug, PartialEq)]
+pub enum SignatureError {
+    /// No `Sign` tag was found
+    Unsigned,
+
+    /// The `Sign` tag was not the last tag, or was the wrong size
+    InvalidTag,
+
+    /// A kernel or program payload lies outside of the signed region
+    UnsignedPayload,
+
+    /// The public key is not a valid Ed25519 key
+    InvalidKey,
+
+    /// The signature does not match the image
+    BadSignature,
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SignatureError::Unsigned => write!(f, "image is not signed"),
+            SignatureError::InvalidTag => write!(f, "Sign tag is invalid or is not the last tag"),
+            SignatureError::UnsignedPayload => write!(f, "image contains unsigned payloads"),
+            SignatureError::InvalidKey => write!(f, "public key is invalid"),
+            SignatureError::BadSignature => write!(f, "image signature does not match"),
+        }
+    }
+}
+
+/// Verify that the image described by `cfg` was signed by `public_key`.
+/// This must be called after `read_initial_config()`, and before any
+/// payloads are used.
+pub fn verify(cfg: &BootConfig, public_key: &[u8; 32]) -> Result<(), SignatureError> {
+    let image_start = cfg.args.base as usize;
+    let args_size = cfg.args.size();
+
+    let mut offset = 0;
+    let mut sign = None;
+    for tag in cfg.args.iter() {
+        if tag.name == u32::from_le_bytes(*b"Sign") {
+            if tag.size as usize != SIGN_TAG_SIZE || offset + 8 + SIGN_TAG_SIZE != args_size {
+                return Err(SignatureError::InvalidTag);
+            }
+            sign = Some((offset, tag.data));
+        }
+        offset += tag.size as usize + 8;
+    }
+    let (sign_offset, sign_data) = sign.ok_or(SignatureError::Unsigned)?;
+    let signed_length = sign_data[0] as usize;
+    if signed_length < args_size {
+        return Err(SignatureError::InvalidTag);
+    }
+    let image_end = image_start
+        .checked_add(signed_length)
+        .ok_or(SignatureError::InvalidTag)?;
+
+    // Everything that gets loaded must have been signed.  Payloads are
+    // relative to the base address, which may differ from the image start.
+    let payload_is_signed = |load_offset: usize, length: usize| {
+        let start = (cfg.base_addr as usize).checked_add(load_offset);
+        let end = start.and_then(|start| start.checked_add(length));
+        match (start, end) {
+            (Some(start), Some(end)) => start >= image_start && end <= image_end,
+            _ => false,
+        }
+    };
+
+    // Compressed payloads are made up of streams that each begin with
+    // their length.  Return the offset of whatever follows a stream.
+    let stream_end = |offset: usize| {
+        if !payload_is_signed(offset, 4) {
+            return None;
+        }
+        let len = unsafe { ((cfg.base_addr as usize + offset) as *const u32).read() } as usize;
+        offset.checked_add(4)?.checked_add(len.checked_add(3)? & !3)
+    };
+    let compressed_is_signed = |load_offset: usize, stream_count: usize| {
+        let mut end = load_offset;
+        for _ in 0..stream_count {
+            end = match stream_end(end) {
+                Some(end) => end,
+                None => return false,
+            };
+        }
+        payload_is_signed(load_offset, end - load_offset)
+    };
+
+    for tag in cfg.args.iter() {
+        let signed = if cfg.compressed && tag.name == u32::from_le_bytes(*b"XKrn") {
+            let prog = unsafe { &*(tag.data.as_ptr() as *const ProgramDescription) };
+            compressed_is_signed(prog.load_offset as usize, 2)
+        } else if cfg.compressed && tag.name == u32::from_le_bytes(*b"IniE") {
+            compressed_is_signed(MiniElf::new(&tag).load_offset as usize, 1)
+        } else if tag.name == u32::from_le_bytes(*b"XKrn") {
+            let prog = unsafe { &*(tag.data.as_ptr() as *const ProgramDescription) };
+            payload_is_signed(
+                prog.load_offset as usize,
+                prog.text_size as usize + prog.data_size as usize,
+            )
+        } else if tag.name == u32::from_le_bytes(*b"IniE") {
+            let inie = MiniElf::new(&tag);
+            let length = inie
+                .sections
+                .iter()
+                .filter(|section| !section.no_copy())
+                .map(|section| section.len())
+                .sum();
+            payload_is_signed(inie.load_offset as usize, length)
+        } else if tag.name == u32::from_le_bytes(*b"IniR") {
+            let relocations = Relocations::new(&tag);
+            match (relocations.count as usize).checked_mul(4) {
+                Some(length) => payload_is_signed(relocations.load_offset as usize, length),
+                None => false,
+            }
+        } else {
+            true
+        };
+        if !signed {
+            return Err(SignatureError::UnsignedPayload);
+        }
+    }
+
+    let image = unsafe { slice::from_raw_parts(image_start as *const u8, signed_length) };
+    let mut digest = Sha512::new();
+    digest.update(&image[..sign_offset]);
+    digest.update(&image[sign_offset + 8 + SIGN_TAG_SIZE..]);
+
+    let signature_bytes = unsafe {
+        slice::from_raw_parts(
+            sign_data[1..].as_ptr() as *const u8,
+            (sign_data.len() - 1) * mem::size_of::<u32>(),
+        )
+    };
+    let signature =
+        Signature::try_from(signature_bytes).map_err(|_| SignatureError::BadSignature)?;
+    let public_key = PublicKey::from_bytes(public_key).map_err(|_| SignatureError::InvalidKey)?;
+    public_key
+        .verify_prehashed(digest, Some(SIGNATURE_CONTEXT), &signature)
+        .map_err(|_| SignatureError::BadSignature)
+}
@@ -0,0 +1,245 @@
+//! Selection between two boot images.  A stage 0 loader that keeps an "A"
+//! and a "B" image passes a `BootSlots` record rather than an arguments
+//! block, and the newest image that is intact, and that hasn't repeatedly
+//! failed to confirm it booted, is the one that gets started.
+
+use crate::args::{KernelArgument, KernelArguments};
+use crate::signature::{self, SignatureError};
+use crate::{parse_initial_config, println, BootConfig, ConfigError};
+use core::{fmt, ptr};
+
+/// Identifies a `BootSlots` record, which takes the place of `XArg`
+pub const SLOTS_MAGIC: u32 = u32::from_le_bytes(*b"XSlt");
+
+/// Written to `BootSlot::confirmed` by userspace once an image is known
+/// to work
+pub const BOOT_CONFIRMED: u32 = u32::from_le_bytes(*b"BtOK");
+
+/// The size of the `BSlt` tag, which tells the kernel where the booted
+/// slot's `confirmed` word is
+pub const TAG_SIZE: usize = 8 + 4;
+
+/// The number of times an image may be started without being confirmed
+/// before the other image is preferred
+pub const MAX_BOOT_ATTEMPTS: u32 = 3;
+
+/// The number of images a `BootSlots` record describes
+pub const SLOT_COUNT: usize = 2;
+
+#[derive(Debug, PartialEq)]
+pub enum SlotError {
+    /// The slot has no image
+    Empty,
+
+    /// The image doesn't start with a valid `XArg` tag
+    NotXousImage,
+
+    /// A tag runs past the end of the arguments block
+    Truncated(u32 /* tag offset */),
+
+    /// A tag's CRC16 doesn't match its contents
+    CorruptTag(u32 /* tag offset */),
+
+    /// The image's signature didn't verify
+    BadSignature(SignatureError),
+
+    /// The tags don't describe something that can be booted
+    InvalidConfig(ConfigError),
+
+    /// Neither slot holds an image that can be booted
+    NoBootableImage,
+}
+
+impl fmt::Display for SlotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SlotError::Empty => write!(f, "slot is empty"),
+            SlotError::NotXousImage => write!(f, "slot does not start with an XArg tag"),
+            SlotError::Truncated(offset) => write!(f, "tag at offset {:08x} is truncated", offset),
+            SlotError::CorruptTag(offset) => write!(f, "tag at offset {:08x} is corrupt", offset),
+            SlotError::BadSignature(e) => write!(f, "{}", e),
+            SlotError::InvalidConfig(e) => write!(f, "{}", e),
+            SlotError::NoBootableImage => write!(f, "no slot contains a bootable image"),
+        }
+    }
+}
+
+/// One of the images a `BootSlots` record chooses between.
+#[repr(C)]
+pub struct BootSlot {
+    /// Address of the image's arguments block, or 0 if the slot is empty
+    pub args: usize,
+
+    /// Version of the image.  Higher numbers are newer.
+    pub version: u32,
+
+    /// The number of times the loader has started this image since it was
+    /// written, not counting boots after it was confirmed
+    pub attempts: u32,
+
+    /// `BOOT_CONFIRMED` once userspace has confirmed this image works
+    pub confirmed: u32,
+}
+
+/// A record describing two boot images, which must be kept in memory that
+/// survives a reset and is not part of main RAM.  Whoever writes a new
+/// image into a slot sets its `version`, and clears its `attempts` and
+/// `confirmed` fields.
+#[repr(C)]
+pub struct BootSlots {
+    /// Always `SLOTS_MAGIC`
+    pub magic: u32,
+
+    /// The slot that was started most recently, written by the loader
+    pub booted: u32,
+
+    _reserved: [u32; 2],
+
+    /// The origin of main RAM.  This is at the same offset as in an `XArg`
+    /// tag, since the loader places its stack at the end of RAM before it
+    /// knows which it was given.
+    pub ram_start: u32,
+
+    /// The size of main RAM, in bytes
+    pub ram_length: u32,
+
+    pub slots: [BootSlot; SLOT_COUNT],
+}
+
+impl BootSlot {
+    pub fn is_confirmed(&self) -> bool {
+        unsafe { ptr::read_volatile(&self.confirmed) == BOOT_CONFIRMED }
+    }
+
+    /// The image has been started too many times without being confirmed.
+    pub fn has_failed(&self) -> bool {
+        !self.is_confirmed() && unsafe { ptr::read_volatile(&self.attempts) } >= MAX_BOOT_ATTEMPTS
+    }
+}
+
+impl BootSlots {
+    /// Returns `true` if `base` points to a `BootSlots` record rather than
+    /// an arguments block.
+    ///
+    /// # Safety
+    ///
+    /// `base` must point to at least one readable word.
+    pub unsafe fn is_boot_slots(base: *const usize) -> bool {
+        (base as *const u32).read() == SLOTS_MAGIC
+    }
+
+    /// Pick the image to boot, and count the attempt against it.  The
+    /// newest valid image that hasn't failed is preferred.  If every valid
+    /// image has failed, the newest one is tried anyway rather than leaving
+    /// the device unable to boot.
+    pub fn select(&mut self, public_key: &[u8; 32]) -> Result<BootConfig, SlotError> {
+        let mut best: Option<(usize, BootConfig)> = None;
+        for (index, slot) in self.slots.iter().enumerate() {
+            let cfg = match check_image(slot.args, public_key) {
+                Ok(cfg) => cfg,
+                Err(e) => {
+                    println!("Slot {}: {}", index, e);
+                    continue;
+                }
+            };
+            println!(
+                "Slot {}: version {}, {} attempts{}",
+                index,
+                slot.version,
+                slot.attempts,
+                if slot.is_confirmed() {
+                    ", confirmed"
+                } else {
+                    ""
+                }
+            );
+            let better = match &best {
+                None => true,
+                Some((best_index, _)) => {
+                    let best_slot = &self.slots[*best_index];
+                    match (slot.has_failed(), best_slot.has_failed()) {
+                        (false, true) => true,
+                        (true, false) => false,
+                        _ => slot.version > best_slot.version,
+                    }
+                }
+            };
+            if better {
+                best = Some((index, cfg));
+            }
+        }
+
+        let (index, mut cfg) = best.ok_or(SlotError::NoBootableImage)?;
+        let slot = &mut self.slots[index];
+        if slot.has_failed() {
+            println!("No slot has been confirmed, retrying slot {}", index);
+        }
+        unsafe {
+            if !slot.is_confirmed() {
+                ptr::write_volatile(&mut slot.attempts, slot.attempts.saturating_add(1));
+            }
+            ptr::write_volatile(&mut self.booted, index as u32);
+        }
+        println!("Booting slot {} (version {})", index, slot.version);
+        cfg.boot_slot = Some([index as u32, slot.version, slot.attempts]);
+        cfg.slot_confirmed = Some(&slot.confirmed as *const u32 as usize);
+        Ok(cfg)
+    }
+}
+
+/// Write a `BSlt` tag to `tag`, which must have room for `TAG_SIZE`
+/// bytes.  The tag holds the address of the booted slot's `confirmed`
+/// word, which the kernel lets the `bootlog` service write.
+pub fn write_tag(confirmed: usize, tag: &mut [u32]) {
+    tag[0] = u32::from_le_bytes(*b"BSlt");
+    tag[2] = confirmed as u32;
+    let crc = crate::args::crc16(&tag[2..3]);
+    tag[1] = 1 << 16 | crc as u32;
+}
+
+/// Check that the image at `args` is intact and signed by `public_key`,
+/// returning its configuration if it is.  Unlike when only one image is
+/// given, corrupt tags are never tolerated, even in debug mode.
+pub fn check_image(args: usize, public_key: &[u8; 32]) -> Result<BootConfig, SlotError> {
+    if args == 0 {
+        return Err(SlotError::Empty);
+    }
+    // An erased or half-written slot may hold anything, so the size of
+    // each tag is checked before its CRC16 is calculated.
+    let args = KernelArguments::new(args as *const usize);
+    let xarg = KernelArgument::new(args.base, 0);
+    if xarg.name != u32::from_le_bytes(*b"XArg") || xarg.size != 20 {
+        return Err(SlotError::NotXousImage);
+    }
+    if !xarg.crc_valid() {
+        return Err(SlotError::CorruptTag(0));
+    }
+
+    let args_size = args.size();
+    let mut offset = 0;
+    while offset < args_size {
+        if offset + 8 > args_size {
+            return Err(SlotError::Truncated(offset as u32));
+        }
+        let header = unsafe { args.base.add(offset / 4 + 1).read() };
+        let size = (header >> 16) as usize * 4;
+        if offset + 8 + size > args_size {
+            return Err(SlotError::Truncated(offset as u32));
+        }
+        let tag = KernelArgument::new(args.base, offset as u32);
+        if !tag.crc_valid() {
+            return Err(SlotError::CorruptTag(tag.offset));
+        }
+        offset += 8 + size;
+    }
+
+    #[allow(clippy::cast_ptr_alignment)] // This test only works on 32-bit systems
+    let mut cfg = BootConfig {
+        base_addr: args.base as *const usize,
+        args,
+        ..Default::default()
+    };
+    parse_initial_config(&mut cfg).map_err(SlotError::InvalidConfig)?;
+    signature::verify(&cfg, public_key).map_err(SlotError::BadSignature)?;
+    Ok(cfg)
+}
@@ -58,22 +58,26 @@ impl Drop for FakeMemory {
 struct TestEnvironment {
     pub cfg: BootConfig,
     _mem: FakeMemory,
+
+    /// The image being booted, if the environment owns it
+    _image: Vec<u32>,
 }
 
 impl TestEnvironment {
     pub fn new(idx: usize) -> TestEnvironment {
-        use crate::args::KernelArguments;
+        // `include_bytes!()` doesn't promise to align the image, so boot
+        // from a copy of it.
+        let image = get_args_words(idx);
+        #[allow(clippy::cast_ptr_alignment)] // This test only works on 32-bit systems
+        let mut env = Self::with_args(image.as_ptr() as *const usize);
+        env._image = image;
+        env
+    }
 
-        // Create a fake memory block into which the bootloader will write
-        let fake_memory = FakeMemory::get();
-        // use rand::prelude::*;
-        // for mem in fake_memory.region.iter_mut() {
-        //     *mem = random();
-        // }
+    pub fn with_args(args: *const usize) -> TestEnvironment {
+        use crate::args::KernelArguments;
 
-        let args = get_args_bin(idx);
-        #[allow(clippy::cast_ptr_alignment)] // This test only works on 32-bit systems
-        let ka = KernelArguments::new(args.as_ptr() as *const usize);
+        let ka = KernelArguments::new(args);
         #[allow(clippy::cast_ptr_alignment)] // This test only works on 32-bit systems
         let mut cfg = BootConfig {
             args: ka,
@@ -81,6 +85,18 @@ impl TestEnvironment {
             ..Default::default()
         };
         crate::read_initial_config(&mut cfg);
+        Self::with_config(cfg)
+    }
+
+    /// Boot an image that has already been read, such as one chosen from
+    /// a boot slot.
+    pub fn with_config(mut cfg: BootConfig) -> TestEnvironment {
+        // Create a fake memory block into which the bootloader will write
+        let fake_memory = FakeMemory::get();
+        // use rand::prelude::*;
+        // for mem in fake_memory.region.iter_mut() {
+        //     *mem = random();
+        // }
 
         // Patch up the config memory address.  Ensure the range is on a "page" boundary.
         let raw_ptr = fake_memory.region.as_mut_ptr() as usize;
@@ -98,6 +114,7 @@ impl TestEnvironment {
         TestEnvironment {
             cfg,
             _mem: fake_memory,
+            _image: vec![],
         }
     }
 }
@@ -126,7 +143,7 @@ fn allocate_regions() {
 #[test]
 fn parse_args_bin() {
     use crate::args::KernelArguments;
-    let args = get_args_bin(0);
+    let args = get_args_words(0);
     #[allow(clippy::cast_ptr_alignment)] // This test only works on 32-bit systems
     let ka = KernelArguments::new(args.as_ptr() as *const usize);
 
@@ -159,7 +176,7 @@ fn read_initial_config() {
     use crate::args::KernelArguments;
     use crate::BootConfig;
 
-    let args = get_args_bin(0);
+    let args = get_args_words(0);
     #[allow(clippy::cast_ptr_alignment)] // This test only works on 32-bit systems
     let ka = KernelArguments::new(args.as_ptr() as *const usize);
     #[allow(clippy::cast_ptr_alignment)] // This test only works on 32-bit systems
@@ -171,6 +188,62 @@ fn read_initial_config() {
     crate::read_initial_config(&mut cfg);
 }
 
+fn read_config_words(image: &[u32]) -> BootConfig {
+    use crate::args::KernelArguments;
+    #[allow(clippy::cast_ptr_alignment)] // This test only works on 32-bit systems
+    let ka = KernelArguments::new(image.as_ptr() as *const usize);
+    #[allow(clippy::cast_ptr_alignment)] // This test only works on 32-bit systems
+    let mut cfg = BootConfig {
+        args: ka,
+        base_addr: ka.base as *const usize,
+        ..Default::default()
+    };
+    crate::read_initial_config(&mut cfg);
+    cfg
+}
+
+/// Index of the `IniE` entrypoint within `args-default.bin`
+const DEFAULT_INIE_ENTRYPOINT: usize = 36;
+
+#[test]
+fn tag_crc() {
+    use crate::args::KernelArguments;
+    for idx in 0..2 {
+        let image = get_args_words(idx);
+        #[allow(clippy::cast_ptr_alignment)] // This test only works on 32-bit systems
+        let ka = KernelArguments::new(image.as_ptr() as *const usize);
+        for tag in ka.iter() {
+            assert!(tag.crc_valid(), "tag at offset {} has an invalid CRC", tag.offset);
+        }
+    }
+}
+
+#[test]
+#[should_panic(expected = "IniE tag at offset 00000084 is corrupt")]
+fn corrupt_tag() {
+    let mut image = get_args_words(0);
+    image[DEFAULT_INIE_ENTRYPOINT] ^= 1;
+    read_config_words(&image);
+}
+
+#[test]
+fn corrupt_tag_debug() {
+    let mut image = append_tag(&get_args_words(0), b"Bflg", &[1 << 2]);
+    image[DEFAULT_INIE_ENTRYPOINT] ^= 1;
+    let cfg = read_config_words(&image);
+    assert!(cfg.debug);
+}
+
+#[test]
+#[should_panic(expected = "IniE tag at offset 00000084 is corrupt")]
+fn corrupt_tag_corrupt_debug_flag() {
+    let bflg_offset = get_args_words(0)[2] as usize;
+    let mut image = append_tag(&get_args_words(0), b"Bflg", &[1 << 2]);
+    image[DEFAULT_INIE_ENTRYPOINT] ^= 1;
+    image[bflg_offset + 1] ^= 1;
+    read_config_words(&image);
+}
+
 fn read_word(satp: usize, virt: usize) -> Result<u32, &'static str> {
     if satp & 0x8000_0000 != 0x8000_0000 {
         return Err("satp valid bit isn't set");
@@ -400,6 +473,767 @@ fn tracker_sane() {
     }
 }
 
+/// Secret key used to sign test images.
+const TEST_SECRET_KEY: [u8; 32] = [7; 32];
+
+fn test_public_key() -> [u8; 32] {
+    use ed25519_dalek::{PublicKey, SecretKey};
+    let secret = SecretKey::from_bytes(&TEST_SECRET_KEY).unwrap();
+    PublicKey::from(&secret).to_bytes()
+}
+
+/// One of the test images as a list of words, which keeps it aligned
+/// and allows it to be modified.
+fn get_args_words(idx: usize) -> Vec<u32> {
+    use core::convert::TryInto;
+    get_args_bin(idx)
+        .chunks(4)
+        .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+        .collect()
+}
+
+/// Append a tag to the end of the args block, moving the payloads along
+/// to make room for it and updating the CRC16 of every modified tag.
+fn append_tag(original: &[u32], name: &[u8; 4], data: &[u32]) -> Vec<u32> {
+    use crate::args::crc16;
+
+    let args_words = original[2] as usize;
+    let shift = (data.len() as u32 + 2) * 4;
+
+    let mut image = original[..args_words].to_vec();
+    image[2] += data.len() as u32 + 2;
+    let mut offset = 0;
+    while offset < args_words {
+        let size = (image[offset + 1] >> 16) as usize;
+        let name = image[offset];
+        if name == u32::from_le_bytes(*b"XKrn") || name == u32::from_le_bytes(*b"IniE") {
+            image[offset + 2] += shift;
+        }
+        let crc = crc16(&image[offset + 2..offset + 2 + size]);
+        image[offset + 1] = (size as u32) << 16 | crc as u32;
+        offset += size + 2;
+    }
+    image.push(u32::from_le_bytes(*name));
+    image.push((data.len() as u32) << 16 | crc16(data) as u32);
+    image.extend_from_slice(data);
+    image.extend_from_slice(&original[args_words..]);
+    image
+}
+
+/// Append a `Sign` tag to one of the test images and sign the result
+/// with `TEST_SECRET_KEY`.
+fn get_signed_args_bin(idx: usize) -> Vec<u32> {
+    use core::convert::TryInto;
+    use ed25519_dalek::{Keypair, PublicKey, SecretKey};
+    use sha2::{Digest, Sha512};
+
+    let unsigned = get_args_words(idx);
+    let sign_offset = unsigned[2] as usize;
+    let sign_words = 17;
+    let mut image = append_tag(&unsigned, b"Sign", &[0; 17]);
+    image[sign_offset + 2] = image.len() as u32 * 4;
+
+    let bytes: Vec<u8> = image.iter().flat_map(|word| word.to_le_bytes().to_vec()).collect();
+    let mut digest = Sha512::new();
+    digest.update(&bytes[..sign_offset * 4]);
+    digest.update(&bytes[(sign_offset + 2 + sign_words) * 4..]);
+    let secret = SecretKey::from_bytes(&TEST_SECRET_KEY).unwrap();
+    let public = PublicKey::from(&secret);
+    let signature = Keypair { secret, public }
+        .sign_prehashed(digest, Some(b"xous-boot-image"))
+        .unwrap()
+        .to_bytes();
+    for (word, bytes) in image[sign_offset + 3..].iter_mut().zip(signature.chunks(4)) {
+        *word = u32::from_le_bytes(bytes.try_into().unwrap());
+    }
+    let crc = crate::args::crc16(&image[sign_offset + 2..sign_offset + 2 + sign_words]);
+    image[sign_offset + 1] = (sign_words as u32) << 16 | crc as u32;
+    image
+}
+
+fn verify_signature(image: &[u32]) -> Result<(), crate::signature::SignatureError> {
+    use crate::args::KernelArguments;
+    #[allow(clippy::cast_ptr_alignment)] // This test only works on 32-bit systems
+    let ka = KernelArguments::new(image.as_ptr() as *const usize);
+    #[allow(clippy::cast_ptr_alignment)] // This test only works on 32-bit systems
+    let cfg = BootConfig {
+        args: ka,
+        base_addr: ka.base as *const usize,
+        ..Default::default()
+    };
+    crate::signature::verify(&cfg, &test_public_key())
+}
+
+#[test]
+fn signed_image() {
+    assert_eq!(verify_signature(&get_signed_args_bin(0)), Ok(()));
+    assert_eq!(verify_signature(&get_signed_args_bin(1)), Ok(()));
+}
+
+#[test]
+fn unsigned_image() {
+    use crate::signature::SignatureError;
+    assert_eq!(verify_signature(&get_args_words(0)), Err(SignatureError::Unsigned));
+}
+
+#[test]
+fn tampered_image() {
+    use crate::signature::SignatureError;
+
+    // Modify the last word of the final payload
+    let mut image = get_signed_args_bin(0);
+    *image.last_mut().unwrap() ^= 1;
+    assert_eq!(verify_signature(&image), Err(SignatureError::BadSignature));
+
+    // Modify the RAM size in the XArg tag
+    let mut image = get_signed_args_bin(0);
+    image[5] ^= 0x1000;
+    assert_eq!(verify_signature(&image), Err(SignatureError::BadSignature));
+
+    // Modify the signature itself
+    let mut image = get_signed_args_bin(0);
+    let args_words = image[2] as usize;
+    image[args_words - 1] ^= 1;
+    assert_eq!(verify_signature(&image), Err(SignatureError::BadSignature));
+
+    // Shrink the signed region so it no longer covers the kernel
+    let mut image = get_signed_args_bin(0);
+    image[args_words - 17] = args_words as u32 * 4;
+    assert_eq!(verify_signature(&image), Err(SignatureError::UnsignedPayload));
+}
+
+#[test]
+fn wrong_key() {
+    use crate::signature::SignatureError;
+    let image = get_signed_args_bin(0);
+    #[allow(clippy::cast_ptr_alignment)] // This test only works on 32-bit systems
+    let ka = crate::args::KernelArguments::new(image.as_ptr() as *const usize);
+    #[allow(clippy::cast_ptr_alignment)] // This test only works on 32-bit systems
+    let cfg = BootConfig {
+        args: ka,
+        base_addr: ka.base as *const usize,
+        ..Default::default()
+    };
+    assert_eq!(
+        crate::signature::verify(&cfg, &crate::signature::PUBLIC_KEY),
+        Err(SignatureError::BadSignature)
+    );
+}
+
+/// Set word `index` of the data section of the tag that starts `tag_offset`
+/// words into `image`, and update the tag's CRC16 to match.
+fn set_tag_word(image: &mut [u32], tag_offset: usize, index: usize, value: u32) {
+    let size = (image[tag_offset + 1] >> 16) as usize;
+    image[tag_offset + 2 + index] = value;
+    let crc = crate::args::crc16(&image[tag_offset + 2..tag_offset + 2 + size]);
+    image[tag_offset + 1] = (size as u32) << 16 | crc as u32;
+}
+
+/// Append `data` to `image` as a compressed stream.
+fn push_compressed_stream(image: &mut Vec<u32>, data: &[u8]) {
+    use core::convert::TryInto;
+    let mut compressed = lz4_flex::block::compress(data);
+    image.push(compressed.len() as u32);
+    compressed.resize((compressed.len() + 3) & !3, 0);
+    image.extend(
+        compressed
+            .chunks(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap())),
+    );
+}
+
+/// The address returned by `get_top()` once `page` pages have been allocated.
+fn page_addr(cfg: &BootConfig, page: usize) -> *const u8 {
+    (cfg.sram_start as usize + cfg.sram_size - cfg.init_size - page * crate::PAGE_SIZE) as *const u8
+}
+
+#[test]
+fn lz4_descending_pages() {
+    use crate::lz4::{decompress, Pages};
+    use crate::PAGE_SIZE;
+
+    // Repetitive data, so that matches cross page boundaries
+    let source: Vec<u8> = (0..PAGE_SIZE * 3)
+        .map(|i| (i / 7 % 13) as u8 ^ (i / 5000) as u8)
+        .collect();
+    let compressed = lz4_flex::block::compress(&source);
+    assert!(compressed.len() < source.len());
+
+    let mut ram = vec![0u8; PAGE_SIZE * 3];
+    let first_page = unsafe { ram.as_mut_ptr().add(PAGE_SIZE * 2) };
+    let mut pages = Pages::descending(first_page, source.len());
+    assert_eq!(decompress(&compressed, &mut pages), Ok(()));
+    for page in 0..3 {
+        assert!(
+            ram[(2 - page) * PAGE_SIZE..(3 - page) * PAGE_SIZE]
+                == source[page * PAGE_SIZE..(page + 1) * PAGE_SIZE],
+            "page {} doesn't match",
+            page
+        );
+    }
+}
+
+#[test]
+fn lz4_corrupt() {
+    use crate::lz4::{decompress, Lz4Error, Pages};
+
+    let source = [0x55u8; 1000];
+    let compressed = lz4_flex::block::compress(&source);
+    let mut ram = vec![0u8; 1001];
+
+    let mut pages = Pages::ascending(ram.as_mut_ptr(), 1000);
+    let truncated = &compressed[..compressed.len() - 1];
+    assert_eq!(decompress(truncated, &mut pages), Err(Lz4Error::Truncated));
+
+    let mut pages = Pages::ascending(ram.as_mut_ptr(), 999);
+    assert_eq!(decompress(&compressed, &mut pages), Err(Lz4Error::Overrun));
+
+    let mut pages = Pages::ascending(ram.as_mut_ptr(), 1001);
+    assert_eq!(decompress(&compressed, &mut pages), Err(Lz4Error::ShortOutput));
+
+    // The first match can only refer to the single literal before it
+    let mut bad_offset = compressed.clone();
+    bad_offset[2] = 2;
+    let mut pages = Pages::ascending(ram.as_mut_ptr(), 1000);
+    assert_eq!(decompress(&bad_offset, &mut pages), Err(Lz4Error::BadOffset));
+}
+
+#[test]
+fn compressed_copy_processes() {
+    use crate::args::KernelArgument;
+    use crate::{MiniElf, ProgramDescription, PAGE_SIZE};
+
+    // Copy the uncompressed image.  This provides the expected contents
+    // of each page allocated for initial processes.
+    let original = Box::leak(get_args_words(0).into_boxed_slice());
+    #[allow(clippy::cast_ptr_alignment)] // This test only works on 32-bit systems
+    let mut uncompressed = TestEnvironment::with_args(original.as_ptr() as *const usize);
+    crate::copy_processes(&mut uncompressed.cfg);
+    let original_bytes: Vec<u8> = original
+        .iter()
+        .flat_map(|word| word.to_le_bytes().to_vec())
+        .collect();
+
+    // Build a compressed image with the same programs.  Appending the Bflg
+    // leaves the offsets of all other tags unchanged.
+    let mut image = append_tag(original, b"Bflg", &[1 << 3]);
+    let args_words = image[2] as usize;
+    image.truncate(args_words);
+    let mut expected = vec![];
+    let mut page_count = 0;
+    let mut offset = 0;
+    while offset < args_words {
+        let name = image[offset];
+        if name == u32::from_le_bytes(*b"IniE") {
+            let tag = KernelArgument::new(original.as_ptr(), offset as u32 * 4);
+            let mut pages = vec![];
+            for _ in 0..MiniElf::new(&tag).page_count() {
+                page_count += 1;
+                let page = page_addr(&uncompressed.cfg, page_count);
+                let page = unsafe { core::slice::from_raw_parts(page, PAGE_SIZE) };
+                expected.push((page_count, page.to_vec()));
+                pages.extend_from_slice(page);
+            }
+            let load_offset = image.len() as u32 * 4;
+            set_tag_word(&mut image, offset, 0, load_offset);
+            push_compressed_stream(&mut image, &pages);
+        } else if name == u32::from_le_bytes(*b"XKrn") {
+            let prog = unsafe { &*(original[offset + 2..].as_ptr() as *const ProgramDescription) };
+            let text_start = prog.load_offset as usize;
+            let data_start = text_start + prog.text_size as usize;
+            let text = &original_bytes[text_start..data_start];
+            let data = &original_bytes[data_start..data_start + prog.data_size as usize];
+            for (contents, load_size) in [
+                (text, prog.text_size),
+                (data, prog.data_size + prog.bss_size),
+            ]
+            .iter()
+            {
+                let load_size_rounded =
+                    (*load_size as usize + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+                page_count += load_size_rounded / PAGE_SIZE;
+                let mut contents = contents.to_vec();
+                contents.resize(load_size_rounded, 0);
+                expected.push((page_count, contents));
+            }
+            let load_offset = image.len() as u32 * 4;
+            set_tag_word(&mut image, offset, 0, load_offset);
+            push_compressed_stream(&mut image, text);
+            push_compressed_stream(&mut image, data);
+        }
+        offset += (image[offset + 1] >> 16) as usize + 2;
+    }
+    assert!(image.len() < original.len());
+
+    let image = Box::leak(image.into_boxed_slice());
+    #[allow(clippy::cast_ptr_alignment)] // This test only works on 32-bit systems
+    let mut env = TestEnvironment::with_args(image.as_ptr() as *const usize);
+    assert!(env.cfg.compressed);
+    crate::copy_processes(&mut env.cfg);
+    assert_eq!(env.cfg.extra_pages, page_count);
+
+    for (page, contents) in expected {
+        let ram = unsafe { core::slice::from_raw_parts(page_addr(&env.cfg, page), contents.len()) };
+        assert!(ram == &contents[..], "decompressed data at page {} doesn't match", page);
+    }
+}
+
+#[test]
+#[should_panic(expected = "IniR does not follow an IniE")]
+fn orphan_relocations() {
+    read_config_words(&append_tag(&get_args_words(0), b"IniR", &[0, 0]));
+}
+
+#[test]
+fn inie_process_parameters() {
+    use crate::args::KernelArgument;
+    use crate::MiniElf;
+
+    let inie_offset = 33;
+    let mut image = get_args_words(0);
+    assert_eq!(image[inie_offset], u32::from_le_bytes(*b"IniE"));
+    let tag = KernelArgument::new(image.as_ptr(), inie_offset as u32 * 4);
+    let inie = MiniElf::new(&tag);
+    assert_eq!((inie.stack_size, inie.heap_max, inie.name), (0, 0, [0; 16]));
+
+    set_tag_word(&mut image, inie_offset, 2, 0x4000);
+    set_tag_word(&mut image, inie_offset, 3, 0x10_0000);
+    set_tag_word(&mut image, inie_offset, 4, u32::from_le_bytes(*b"shel"));
+    set_tag_word(&mut image, inie_offset, 5, u32::from_le_bytes(*b"l\0\0\0"));
+    let tag = KernelArgument::new(image.as_ptr(), inie_offset as u32 * 4);
+    assert!(tag.crc_valid());
+    let inie = MiniElf::new(&tag);
+    assert_eq!(inie.entry_point, image[DEFAULT_INIE_ENTRYPOINT]);
+    assert_eq!(inie.stack_size, 0x4000);
+    assert_eq!(inie.heap_max, 0x10_0000);
+    assert_eq!(&inie.name, b"shell\0\0\0\0\0\0\0\0\0\0\0");
+    assert_eq!(inie.sections.len(), 3);
+    assert_eq!(inie.sections[1].virt, 0x12000);
+}
+
+#[test]
+fn virt_to_phys() {
+    use crate::{PageTable, FLG_VALID, PAGE_SIZE};
+
+    // A root table, a leaf table, and a data page, all page-aligned
+    let mut memory = vec![0usize; 4 * PAGE_SIZE / core::mem::size_of::<usize>()];
+    let root = (memory.as_mut_ptr() as usize + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+    let l0 = root + PAGE_SIZE;
+    let page = root + PAGE_SIZE * 2;
+
+    let virt = 0x1000_3000;
+    unsafe {
+        (root as *mut usize)
+            .add(virt >> 22)
+            .write((l0 >> 12) << 10 | FLG_VALID);
+        (l0 as *mut usize)
+            .add((virt >> 12) & 0x3ff)
+            .write((page >> 12) << 10 | FLG_VALID);
+    }
+    let root = unsafe { &*(root as *const PageTable) };
+    assert_eq!(crate::virt_to_phys(root, virt + 0x124), Some(page + 0x124));
+    assert_eq!(crate::virt_to_phys(root, virt + PAGE_SIZE), None);
+    assert_eq!(crate::virt_to_phys(root, 0x2000_0000), None);
+}
+
+#[test]
+fn aslr_offsets() {
+    use crate::{ASLR_WINDOW, PAGE_SIZE};
+
+    let mut cfg = BootConfig::default();
+    assert_eq!(cfg.aslr_offset(), 0);
+
+    cfg.aslr = true;
+    let offsets: Vec<usize> = (0..16).map(|_| cfg.aslr_offset()).collect();
+    for offset in &offsets {
+        assert_eq!(offset & (PAGE_SIZE - 1), 0);
+        assert!(*offset < ASLR_WINDOW);
+    }
+    assert!(offsets.iter().any(|offset| *offset != offsets[0]));
+}
+
+/// A `BootSlots` record holding the two signed test images, with the
+/// second one being newer.
+fn boot_slots(images: &[Vec<u32>; 2]) -> crate::slots::BootSlots {
+    use crate::slots::{BootSlots, SLOTS_MAGIC};
+    // Every field is an integer, so all zeroes is a valid record
+    let mut slots: BootSlots = unsafe { core::mem::zeroed() };
+    slots.magic = SLOTS_MAGIC;
+    for (index, (slot, image)) in slots.slots.iter_mut().zip(images.iter()).enumerate() {
+        slot.args = image.as_ptr() as usize;
+        slot.version = index as u32 + 1;
+    }
+    slots
+}
+
+fn selected_slot(slots: &mut crate::slots::BootSlots) -> Result<usize, crate::slots::SlotError> {
+    let cfg = slots.select(&test_public_key())?;
+    let index = slots
+        .slots
+        .iter()
+        .position(|slot| slot.args == cfg.base_addr as usize)
+        .unwrap();
+    assert_eq!(slots.booted as usize, index);
+    let slot = &slots.slots[index];
+    assert_eq!(
+        cfg.boot_slot,
+        Some([index as u32, slot.version, slot.attempts])
+    );
+    Ok(index)
+}
+
+#[test]
+fn boot_slots_newest() {
+    use crate::slots::{BootSlots, BOOT_CONFIRMED, MAX_BOOT_ATTEMPTS};
+
+    let images = [get_signed_args_bin(0), get_signed_args_bin(1)];
+    let mut slots = boot_slots(&images);
+    assert!(unsafe { BootSlots::is_boot_slots(&slots as *const BootSlots as *const usize) });
+    assert!(!unsafe { BootSlots::is_boot_slots(images[0].as_ptr() as *const usize) });
+
+    // Each unconfirmed boot counts against the image
+    for attempt in 1..=MAX_BOOT_ATTEMPTS {
+        assert_eq!(selected_slot(&mut slots), Ok(1));
+        assert_eq!(slots.slots[1].attempts, attempt);
+    }
+    assert!(slots.slots[1].has_failed());
+    assert_eq!(slots.slots[0].attempts, 0);
+
+    // Once confirmed, boots are no longer counted
+    slots.slots[1].confirmed = BOOT_CONFIRMED;
+    assert_eq!(selected_slot(&mut slots), Ok(1));
+    assert_eq!(slots.slots[1].attempts, MAX_BOOT_ATTEMPTS);
+    assert!(!slots.slots[1].has_failed());
+
+    // An empty slot is skipped
+    slots.slots[1].args = 0;
+    assert_eq!(selected_slot(&mut slots), Ok(0));
+    assert_eq!(slots.slots[0].attempts, 1);
+}
+
+#[test]
+fn boot_slots_confirmed() {
+    use crate::bootlog::{EntryKind, BOOT_LOG_SIZE};
+    use crate::slots::{BOOT_CONFIRMED, MAX_BOOT_ATTEMPTS};
+
+    let images = [get_signed_args_bin(0), get_signed_args_bin(1)];
+    let mut slots = boot_slots(&images);
+    for boot in 1..=MAX_BOOT_ATTEMPTS + 2 {
+        let cfg = slots.select(&test_public_key()).unwrap();
+        assert_eq!(slots.booted, 1);
+        let mut env = TestEnvironment::with_config(cfg);
+        crate::phase_1(&mut env.cfg);
+
+        // The slot is counted once, before it's confirmed
+        let log_addr = env.cfg.boot_log.as_ref().unwrap().addr();
+        let log = unsafe { core::slice::from_raw_parts(log_addr as *const u32, BOOT_LOG_SIZE / 4) };
+        let (_, slot) = boot_log_entries(log)
+            .into_iter()
+            .find(|(kind, _)| *kind == EntryKind::Slot as u32)
+            .expect("no Slot entry");
+        assert_eq!(slot, &[1, 2, 1]);
+        assert_eq!(slots.slots[1].attempts, 1);
+
+        // Confirm the boot the way the `bootlog` service does, through the
+        // address the kernel is given
+        let bslt = env
+            .cfg
+            .args
+            .iter()
+            .find(|tag| tag.name == u32::from_le_bytes(*b"BSlt"))
+            .expect("no BSlt tag");
+        assert!(bslt.crc_valid());
+        let confirmed = &mut slots.slots[1].confirmed;
+        assert_eq!(bslt.data, &[confirmed as *mut u32 as usize as u32]);
+        if boot == 1 {
+            unsafe { (confirmed as *mut u32).write_volatile(BOOT_CONFIRMED) };
+        }
+        assert!(slots.slots[1].is_confirmed());
+    }
+    assert_eq!(slots.slots[0].attempts, 0);
+}
+
+#[test]
+fn boot_slots_fallback() {
+    use crate::slots::MAX_BOOT_ATTEMPTS;
+
+    let images = [get_signed_args_bin(0), get_signed_args_bin(1)];
+    let mut slots = boot_slots(&images);
+
+    // The newer image never confirmed it booted, so the older one is used
+    slots.slots[1].attempts = MAX_BOOT_ATTEMPTS;
+    assert_eq!(selected_slot(&mut slots), Ok(0));
+    assert_eq!(slots.slots[0].attempts, 1);
+    assert_eq!(slots.slots[1].attempts, MAX_BOOT_ATTEMPTS);
+
+    // If both have failed, the newest is tried again
+    slots.slots[0].attempts = MAX_BOOT_ATTEMPTS;
+    assert_eq!(selected_slot(&mut slots), Ok(1));
+    assert_eq!(slots.slots[1].attempts, MAX_BOOT_ATTEMPTS + 1);
+}
+
+#[test]
+fn boot_slots_invalid_image() {
+    use crate::signature::SignatureError;
+    use crate::slots::{check_image, SlotError};
+    use crate::ConfigError;
+
+    let key = test_public_key();
+    let good = get_signed_args_bin(0);
+    assert!(check_image(good.as_ptr() as usize, &key).is_ok());
+    assert_eq!(check_image(0, &key).err(), Some(SlotError::Empty));
+
+    let erased = vec![0xffff_ffffu32; good.len()];
+    assert_eq!(
+        check_image(erased.as_ptr() as usize, &key).err(),
+        Some(SlotError::NotXousImage)
+    );
+
+    // The data of the MREx tag no longer matches its CRC16
+    let mut corrupt = get_signed_args_bin(1);
+    corrupt[9] ^= 1;
+    assert_eq!(
+        check_image(corrupt.as_ptr() as usize, &key).err(),
+        Some(SlotError::CorruptTag(7 * 4))
+    );
+
+    // The final tag claims to be longer than the arguments block
+    let mut truncated = get_signed_args_bin(0);
+    let sign_offset = truncated[2] as usize - 19;
+    truncated[sign_offset + 1] += 1 << 16;
+    assert_eq!(
+        check_image(truncated.as_ptr() as usize, &key).err(),
+        Some(SlotError::Truncated(sign_offset as u32 * 4))
+    );
+
+    let mut tampered = get_signed_args_bin(0);
+    *tampered.last_mut().unwrap() ^= 1;
+    assert_eq!(
+        check_image(tampered.as_ptr() as usize, &key).err(),
+        Some(SlotError::BadSignature(SignatureError::BadSignature))
+    );
+
+    // Every tag is intact, but the kernel is missing
+    let mut no_kernel = get_signed_args_bin(1);
+    let xkrn_offset = no_kernel
+        .iter()
+        .position(|word| *word == u32::from_le_bytes(*b"XKrn"))
+        .unwrap();
+    no_kernel[xkrn_offset] = u32::from_le_bytes(*b"XKrX");
+    assert_eq!(
+        check_image(no_kernel.as_ptr() as usize, &key).err(),
+        Some(SlotError::InvalidConfig(ConfigError::NoKernel))
+    );
+
+    // A newer image that doesn't verify falls back to the older one
+    let images = [good, tampered];
+    let mut slots = boot_slots(&images);
+    assert_eq!(selected_slot(&mut slots), Ok(0));
+    assert_eq!(slots.slots[1].attempts, 0);
+
+    // A newer image that can't be parsed falls back too
+    let images = [images[0].clone(), no_kernel];
+    let mut slots = boot_slots(&images);
+    assert_eq!(selected_slot(&mut slots), Ok(0));
+    assert_eq!(slots.slots[1].attempts, 0);
+
+    // Neither slot can be booted
+    let images = [corrupt, erased];
+    let mut slots = boot_slots(&images);
+    assert_eq!(slots.select(&key).err(), Some(SlotError::NoBootableImage));
+    assert_eq!(slots.slots[0].attempts, 0);
+    assert_eq!(slots.slots[1].attempts, 0);
+}
+
+#[test]
+fn memtest_flag() {
+    assert!(!read_config_words(&get_args_words(0)).memtest);
+    let cfg = read_config_words(&append_tag(&get_args_words(0), b"Bflg", &[1 << 5]));
+    assert!(cfg.memtest);
+}
+
+#[test]
+fn memtest_good_ram() {
+    use crate::args::KernelArguments;
+    use crate::{memtest, PAGE_SIZE};
+
+    let mut env = TestEnvironment::new(0);
+    let ram_start = env.cfg.sram_start as usize;
+
+    // Place the arguments in RAM, where they must survive the test
+    let image = get_args_words(0);
+    let args_words = image[2] as usize;
+    let image_addr = ram_start + 5 * PAGE_SIZE + 0x800;
+    unsafe { core::ptr::copy_nonoverlapping(image.as_ptr(), image_addr as *mut u32, args_words) };
+    #[allow(clippy::cast_ptr_alignment)] // This test only works on 32-bit systems
+    let ka = KernelArguments::new(image_addr as *const usize);
+    env.cfg.args = ka;
+    env.cfg.base_addr = ka.base as *const usize;
+    unsafe { (ram_start as *mut u32).write(0x1234_5678) };
+
+    crate::allocate_regions(&mut env.cfg);
+    memtest::test_ram(&mut env.cfg);
+    let top_page = (env.cfg.get_top() as usize - ram_start) / PAGE_SIZE;
+    let image_pages = (image_addr + args_words * 4 - 1) / PAGE_SIZE - image_addr / PAGE_SIZE + 1;
+    let summary = env.cfg.memtest_summary.as_ref().unwrap();
+    assert_eq!(summary.tested_pages, top_page - image_pages);
+    assert_eq!(summary.bad_pages, 0);
+    assert!(env.cfg.runtime_page_tracker.iter().all(|owner| *owner == 0));
+    let after = unsafe { core::slice::from_raw_parts(image_addr as *const u32, args_words) };
+    assert!(after == &image[..args_words], "image was overwritten");
+    assert_eq!(unsafe { (ram_start as *const u32).read() }, 0);
+
+    // The summary is appended to the copied arguments
+    crate::copy_args(&mut env.cfg);
+    let tags: Vec<_> = env.cfg.args.iter().collect();
+    assert_eq!(env.cfg.args.size(), args_words * 4 + memtest::tag_size(0));
+    assert!(tags[0].crc_valid());
+    let memt = tags.last().unwrap();
+    assert_eq!(memt.name, u32::from_le_bytes(*b"MemT"));
+    assert!(memt.crc_valid());
+    assert_eq!(memt.data, &[(top_page - image_pages) as u32, 0]);
+}
+
+#[test]
+fn memtest_tag_lists_bad_pages() {
+    use crate::memtest::{self, MemTestSummary, BAD_PAGE_PID, MAX_REPORTED_BAD_PAGES};
+    use crate::PAGE_SIZE;
+
+    let mut env = TestEnvironment::new(0);
+    crate::allocate_regions(&mut env.cfg);
+    let bad_pages = [3, 4, 9, 20, 21, 22, 23, 40, 41, 60];
+    for page in bad_pages.iter() {
+        memtest::mark_bad_page(&mut env.cfg, *page);
+    }
+    assert_eq!(env.cfg.runtime_page_tracker[9], BAD_PAGE_PID);
+
+    let summary = MemTestSummary {
+        tested_pages: 100,
+        bad_pages: bad_pages.len(),
+    };
+    let mut tag = vec![0u32; memtest::tag_size(bad_pages.len()) / 4];
+    memtest::write_tag(&env.cfg, &summary, &mut tag);
+    let tag = crate::args::KernelArgument::new(tag.as_ptr(), 0);
+    assert!(tag.crc_valid());
+    assert_eq!(tag.size as usize, 4 * (2 + MAX_REPORTED_BAD_PAGES));
+    assert_eq!(&tag.data[..2], &[100, bad_pages.len() as u32]);
+    for (addr, page) in tag.data[2..].iter().zip(bad_pages.iter()) {
+        assert_eq!(*addr, (env.cfg.sram_start as usize + page * PAGE_SIZE) as u32);
+    }
+}
+
+#[test]
+fn memtest_bad_pages_skipped() {
+    use crate::memtest::{self, BAD_PAGE_PID};
+    use crate::PAGE_SIZE;
+
+    // Lay out RAM as `phase_1()` does up to the memory test, starting the
+    // process table on a fresh page.  Then fail that page, and one that a
+    // program would be copied to.
+    let mut env = TestEnvironment::new(0);
+    env.cfg.init_size += PAGE_SIZE * 2;
+    crate::allocate_regions(&mut env.cfg);
+    env.cfg.init_size = (env.cfg.init_size + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+    let top_page = (env.cfg.get_top() as usize - env.cfg.sram_start as usize) / PAGE_SIZE;
+    let bad_pages = [top_page - 1, top_page - 4];
+    for page in bad_pages.iter() {
+        memtest::mark_bad_page(&mut env.cfg, *page);
+    }
+
+    crate::copy_to_ram(&mut env.cfg);
+    let init_start = (env.cfg.sram_size - env.cfg.init_size) / PAGE_SIZE;
+    assert!(init_start < bad_pages[0], "bad page is past init_size");
+    crate::phase_2(&mut env.cfg);
+
+    for page in bad_pages.iter() {
+        assert_eq!(env.cfg.runtime_page_tracker[*page], BAD_PAGE_PID);
+    }
+    let mut init_index = 0;
+    for arg in env.cfg.args.iter() {
+        if arg.name == u32::from_le_bytes(*b"XKrn") {
+            verify_kernel(&env.cfg, 0, &arg);
+        } else if arg.name == u32::from_le_bytes(*b"IniE") {
+            init_index += 1;
+            verify_program(&env.cfg, init_index, &arg);
+        }
+    }
+}
+
+/// Split a boot log into its entries, as (kind, data) pairs.
+fn boot_log_entries(log: &[u32]) -> Vec<(u32, Vec<u32>)> {
+    assert_eq!(log[0], crate::bootlog::BOOT_LOG_VERSION);
+    let end = 2 + log[1] as usize / 4;
+    let mut entries = vec![];
+    let mut offset = 2;
+    while offset < end {
+        let words = (log[offset] >> 16) as usize;
+        let data = log[offset + 2..offset + 2 + words].to_vec();
+        entries.push((log[offset] & 0xffff, data));
+        offset += 2 + words;
+    }
+    assert_eq!(offset, end);
+    entries
+}
+
+#[test]
+fn boot_log() {
+    use crate::bootlog::{EntryKind, BOOT_LOG_SIZE};
+    use crate::PAGE_SIZE;
+
+    let mut env = TestEnvironment::new(0);
+    crate::phase_1(&mut env.cfg);
+
+    // The log has a page to itself, which belongs to the kernel
+    let log_addr = env.cfg.boot_log.as_ref().unwrap().addr();
+    assert_eq!(log_addr & (PAGE_SIZE - 1), 0);
+    let page = (log_addr - env.cfg.sram_start as usize) / PAGE_SIZE;
+    assert_eq!(env.cfg.runtime_page_tracker[page], 1);
+
+    let blog = env
+        .cfg
+        .args
+        .iter()
+        .find(|tag| tag.name == u32::from_le_bytes(*b"BLog"))
+        .expect("no BLog tag");
+    assert!(blog.crc_valid());
+    assert_eq!(blog.data, &[log_addr as u32, BOOT_LOG_SIZE as u32]);
+    let xarg = env.cfg.args.iter().next().unwrap();
+    assert!(xarg.crc_valid());
+    assert_eq!(xarg.data[0] as usize, env.cfg.args.size() / 4);
+
+    let log = unsafe { core::slice::from_raw_parts(log_addr as *const u32, BOOT_LOG_SIZE / 4) };
+    let entries = boot_log_entries(log);
+    let kinds: Vec<_> = entries.iter().map(|(kind, _)| *kind).collect();
+    assert_eq!(kinds, &[EntryKind::Phase as u32, EntryKind::Pages as u32]);
+    assert_eq!(entries[0].1, &[1]);
+    assert_eq!(
+        entries[1].1,
+        &[
+            (env.cfg.init_size / PAGE_SIZE) as u32,
+            env.cfg.extra_pages as u32
+        ]
+    );
+}
+
+#[test]
+fn boot_log_full() {
+    use crate::bootlog::{BootLog, EntryKind, BOOT_LOG_SIZE};
+
+    let mut page = vec![0usize; BOOT_LOG_SIZE / core::mem::size_of::<usize>()];
+    let mut log = BootLog::new(page.as_mut_ptr());
+    for count in 0..BOOT_LOG_SIZE {
+        log.record(EntryKind::MemTest, &[count as u32, 0]);
+    }
+    let words =
+        unsafe { core::slice::from_raw_parts(page.as_ptr() as *const u32, BOOT_LOG_SIZE / 4) };
+    let entries = boot_log_entries(words);
+    assert_eq!(entries.len(), (BOOT_LOG_SIZE / 4 - 2) / 4);
+    for (count, (kind, data)) in entries.iter().enumerate() {
+        assert_eq!(*kind, EntryKind::MemTest as u32);
+        assert_eq!(data, &[count as u32, 0]);
+    }
+}
+
 // Create a fake "start_kernel" function to allow
 // this module to compile when not running natively.
 #[export_name = "start_kernel"]
@@ -0,0 +1,170 @@
+//! The parts of loading an ELF program that `create-image`, the loader and
+//! `elf-loader` must agree on: which sections get loaded and with what
+//! flags, which relocations are supported, and where position-independent
+//! programs end up.  `size-report` also uses the loader's memory layout to
+//! work out how much RAM each program takes at boot.
+#![no_std]
+
+#[macro_use]
+extern crate bitflags;
+
+const PAGE_SIZE: usize = 4096;
+
+/// Position-independent programs are loaded this far above the addresses
+/// they were linked at.
+pub const PIE_LOAD_OFFSET: usize = 0x1000_0000;
+
+/// The top of the stack the loader gives each program
+pub const USER_STACK_TOP: usize = 0x8000_0000;
+
+/// The top of the kernel's exception stack
+pub const EXCEPTION_STACK_TOP: usize = 0xffff_0000;
+
+/// Where each program's second-level page tables are mapped, one page for
+/// each 4 MiB of address space
+pub const PAGE_TABLE_OFFSET: usize = 0xff40_0000;
+
+/// Where each program's root page table is mapped
+pub const PAGE_TABLE_ROOT_OFFSET: usize = 0xff80_0000;
+
+/// Where each program's context is mapped
+pub const CONTEXT_OFFSET: usize = 0xff80_1000;
+
+/// Stack pages the loader maps for a program, unless it asks for fewer.
+/// The kernel gets this many again for its exception stack.
+pub const STACK_PAGE_COUNT: usize = 5;
+
+/// Section header type of a table of relocations with addends
+pub const SHT_RELA: u32 = 4;
+
+/// Section header type of a section that takes up no space in the file
+pub const SHT_NOBITS: u32 = 8;
+
+const SHF_WRITE: u32 = 1;
+const SHF_ALLOC: u32 = 2;
+const SHF_EXECINSTR: u32 = 4;
+
+/// Size of an entry in a 32-bit `SHT_RELA` section
+pub const RELA_ENTRY_SIZE: usize = 12;
+
+const R_RISCV_NONE: u8 = 0;
+const R_RISCV_RELATIVE: u8 = 3;
+
+bitflags! {
+    /// Flags of a loaded section, as stored in the top byte of each
+    /// section's size in an `IniE` tag.
+    pub struct SectionFlags: u8 {
+        const NONE = 0;
+        const WRITE = 1;
+        const NOCOPY = 2;
+        const EXECUTE = 4;
+    }
+}
+
+impl SectionFlags {
+    /// Classify a section from its header.  Returns `None` for sections
+    /// that aren't loaded, which are those without an address and those
+    /// without `SHF_ALLOC`.  `nobits` is set for `SHT_NOBITS` sections,
+    /// which are zeroed rather than copied.
+    pub fn from_section(virt: u32, sh_flags: u32, nobits: bool) -> Option<SectionFlags> {
+        if virt == 0 || sh_flags & SHF_ALLOC == 0 {
+            return None;
+        }
+        let mut flags = SectionFlags::NONE;
+        if sh_flags & SHF_WRITE != 0 {
+            flags |= SectionFlags::WRITE;
+        }
+        if sh_flags & SHF_EXECINSTR != 0 {
+            flags |= SectionFlags::EXECUTE;
+        }
+        if nobits {
+            flags |= SectionFlags::NOCOPY;
+        }
+        Some(flags)
+    }
+}
+
+/// Check the type of a relocation.  Only `R_RISCV_RELATIVE` relocations,
+/// which are all a static PIE needs, are supported.  Returns whether the
+/// relocation must be applied, or the type if it isn't supported.
+pub fn is_relative(kind: u8) -> Result<bool, u8> {
+    match kind {
+        R_RISCV_NONE => Ok(false),
+        R_RISCV_RELATIVE => Ok(true),
+        other => Err(other),
+    }
+}
+
+/// A `SHT_RELA` entry, as (offset, type, addend).
+pub fn read_rela(entry: &[u8; RELA_ENTRY_SIZE]) -> (u32, u8, u32) {
+    let word = |index: usize| {
+        u32::from_le_bytes([
+            entry[index],
+            entry[index + 1],
+            entry[index + 2],
+            entry[index + 3],
+        ])
+    };
+    (word(0), word(4) as u8, word(8))
+}
+
+/// The value of a word with an `R_RISCV_RELATIVE` relocation once the
+/// program is loaded `load_offset` bytes above where it was linked.
+/// `value` is the relocation's addend.
+pub fn relocate(value: u32, load_offset: usize) -> u32 {
+    value.wrapping_add(load_offset as u32)
+}
+
+/// The number of stack pages the loader maps for a program that asked for
+/// a stack of `stack_size` bytes.  The kernel maps the rest of the stack as
+/// it gets used.
+pub fn stack_page_count(stack_size: usize) -> usize {
+    match stack_size / PAGE_SIZE {
+        0 => STACK_PAGE_COUNT,
+        pages => pages.min(STACK_PAGE_COUNT),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn section_flags() {
+        // .text
+        assert_eq!(
+            SectionFlags::from_section(0x2000_0000, SHF_ALLOC | SHF_EXECINSTR, false),
+            Some(SectionFlags::EXECUTE)
+        );
+        // .bss
+        assert_eq!(
+            SectionFlags::from_section(0x2000_8000, SHF_ALLOC | SHF_WRITE, true),
+            Some(SectionFlags::WRITE | SectionFlags::NOCOPY)
+        );
+        // Debug information has no address, and .comment isn't allocated
+        assert_eq!(SectionFlags::from_section(0, 0, false), None);
+        assert_eq!(SectionFlags::from_section(0x100, 0, false), None);
+    }
+
+    #[test]
+    fn relocations() {
+        assert_eq!(is_relative(R_RISCV_NONE), Ok(false));
+        assert_eq!(is_relative(R_RISCV_RELATIVE), Ok(true));
+        assert_eq!(is_relative(2), Err(2));
+
+        let mut entry = [0u8; RELA_ENTRY_SIZE];
+        entry[0..4].copy_from_slice(&0x2004u32.to_le_bytes());
+        entry[4..8].copy_from_slice(&(0x0500_0000 | R_RISCV_RELATIVE as u32).to_le_bytes());
+        entry[8..12].copy_from_slice(&0x120u32.to_le_bytes());
+        assert_eq!(read_rela(&entry), (0x2004, R_RISCV_RELATIVE, 0x120));
+        assert_eq!(relocate(0x120, PIE_LOAD_OFFSET), 0x1000_0120);
+    }
+
+    #[test]
+    fn stack_pages() {
+        assert_eq!(stack_page_count(0), STACK_PAGE_COUNT);
+        assert_eq!(stack_page_count(PAGE_SIZE - 1), STACK_PAGE_COUNT);
+        assert_eq!(stack_page_count(2 * PAGE_SIZE), 2);
+        assert_eq!(stack_page_count(64 * PAGE_SIZE), STACK_PAGE_COUNT);
+    }
+}
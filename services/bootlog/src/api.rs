@@ -0,0 +1,60 @@
+use xous::{MemoryMessage, MemoryRange, Message, ScalarMessage};
+
+#[derive(Debug)]
+pub enum Opcode {
+    /// Get the size of the boot log, in bytes, including its header
+    Size,
+
+    /// Copy as much of the boot log as fits into the lent buffer
+    Read(MemoryRange),
+
+    /// Mark the boot slot that was started as working, so the loader keeps
+    /// choosing it.  Returns 1 if a slot was confirmed, or 0 if the image
+    /// didn't come from a boot slot.
+    ConfirmBoot,
+}
+
+impl<'a> core::convert::TryFrom<&'a Message> for Opcode {
+    type Error = &'static str;
+    fn try_from(message: &'a Message) -> Result<Self, Self::Error> {
+        match message {
+            Message::BlockingScalar(m) => match m.id {
+                1 => Ok(Opcode::Size),
+                3 => Ok(Opcode::ConfirmBoot),
+                _ => Err("unrecognized opcode"),
+            },
+            Message::MutableBorrow(m) => match m.id {
+                2 => Ok(Opcode::Read(m.buf)),
+                _ => Err("unrecognized opcode"),
+            },
+            _ => Err("unhandled message type"),
+        }
+    }
+}
+
+impl From<Opcode> for Message {
+    fn from(opcode: Opcode) -> Message {
+        match opcode {
+            Opcode::Size => Message::BlockingScalar(ScalarMessage {
+                id: 1,
+                arg1: 0,
+                arg2: 0,
+                arg3: 0,
+                arg4: 0,
+            }),
+            Opcode::ConfirmBoot => Message::BlockingScalar(ScalarMessage {
+                id: 3,
+                arg1: 0,
+                arg2: 0,
+                arg3: 0,
+                arg4: 0,
+            }),
+            Opcode::Read(buf) => Message::MutableBorrow(MemoryMessage {
+                id: 2,
+                buf,
+                offset: None,
+                valid: None,
+            }),
+        }
+    }
+}
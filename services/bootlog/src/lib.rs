@@ -0,0 +1,171 @@
+#![cfg_attr(target_os = "none", no_std)]
+
+pub mod api;
+
+use core::fmt;
+use xous::{send_message, Error, CID};
+
+/// The layout of the log, which must match the loader's `bootlog` module
+pub const BOOT_LOG_VERSION: u32 = 1;
+
+/// Marks a boot slot as working, which must match the loader's `slots`
+/// module
+pub const BOOT_CONFIRMED: u32 = u32::from_le_bytes(*b"BtOK");
+
+/// What a boot log entry records
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EntryKind {
+    /// The loader started a phase: 1, 2, or 3 for jumping to the kernel
+    Phase,
+
+    /// The image came from a boot slot: index, version and attempts
+    Slot,
+
+    /// RAM was tested: pages tested and pages that failed
+    MemTest,
+
+    /// Pages the loader took for the kernel, and for processes
+    Pages,
+
+    /// A process was set up: PID, entrypoint, stack pointer, SATP and name
+    Process,
+
+    /// An entry written by a newer loader
+    Unknown(u16),
+}
+
+impl From<u16> for EntryKind {
+    fn from(kind: u16) -> EntryKind {
+        match kind {
+            1 => EntryKind::Phase,
+            2 => EntryKind::Slot,
+            3 => EntryKind::MemTest,
+            4 => EntryKind::Pages,
+            5 => EntryKind::Process,
+            other => EntryKind::Unknown(other),
+        }
+    }
+}
+
+/// A single record from the boot log
+pub struct Entry<'a> {
+    pub kind: EntryKind,
+
+    /// The loader's cycle count when the entry was written
+    pub timestamp: u32,
+
+    pub data: &'a [u32],
+}
+
+impl<'a> fmt::Display for Entry<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{:10}] ", self.timestamp)?;
+        match (self.kind, self.data) {
+            (EntryKind::Phase, [3]) => write!(f, "starting kernel"),
+            (EntryKind::Phase, [phase]) => write!(f, "starting phase {}", phase),
+            (EntryKind::Slot, [index, version, attempts]) => write!(
+                f,
+                "booting slot {} (version {}, attempt {})",
+                index, version, attempts
+            ),
+            (EntryKind::MemTest, [tested, bad]) => {
+                write!(f, "{} of {} pages failed the memory test", bad, tested)
+            }
+            (EntryKind::Pages, [init, extra]) => write!(
+                f,
+                "loader used {} pages for the kernel and {} for processes",
+                init, extra
+            ),
+            (EntryKind::Process, [pid, entrypoint, sp, satp, name @ ..]) => {
+                let mut bytes = [0u8; 16];
+                for (chunk, word) in bytes.chunks_mut(4).zip(name.iter()) {
+                    chunk.copy_from_slice(&word.to_le_bytes());
+                }
+                let length = bytes.iter().position(|&c| c == 0).unwrap_or(bytes.len());
+                write!(
+                    f,
+                    "PID {} ({}): entrypoint {:08x}, stack {:08x}, satp {:08x}",
+                    pid,
+                    core::str::from_utf8(&bytes[..length]).unwrap_or("?"),
+                    entrypoint,
+                    sp,
+                    satp
+                )
+            }
+            (kind, data) => write!(f, "{:?}: {:08x?}", kind, data),
+        }
+    }
+}
+
+/// Iterates over the entries of a boot log.
+pub struct Entries<'a> {
+    words: &'a [u32],
+}
+
+/// Split the boot log `log` into its entries.  A log with an unknown
+/// version has no entries, and entries that run past the end of the log
+/// are ignored.
+pub fn entries(log: &[u32]) -> Entries<'_> {
+    let words = match log {
+        [BOOT_LOG_VERSION, used, rest @ ..] => {
+            let length = (*used as usize / 4).min(rest.len());
+            &rest[..length]
+        }
+        _ => &[],
+    };
+    Entries { words }
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = Entry<'a>;
+
+    fn next(&mut self) -> Option<Entry<'a>> {
+        if self.words.len() < 2 {
+            return None;
+        }
+        let header = self.words[0];
+        let end = 2 + (header >> 16) as usize;
+        if end > self.words.len() {
+            self.words = &[];
+            return None;
+        }
+        let entry = Entry {
+            kind: EntryKind::from(header as u16),
+            timestamp: self.words[1],
+            data: &self.words[2..end],
+        };
+        self.words = &self.words[end..];
+        Some(entry)
+    }
+}
+
+/// Get the size of the boot log in bytes, or 0 if there is none.
+pub fn size(cid: CID) -> Result<usize, Error> {
+    let response = send_message(cid, api::Opcode::Size.into())?;
+    if let xous::Result::Scalar1(size) = response {
+        Ok(size)
+    } else {
+        panic!("unexpected return value: {:#?}", response);
+    }
+}
+
+/// Copy as much of the boot log as fits into `buf`.
+pub fn read(cid: CID, buf: &mut [u8]) -> Result<(), Error> {
+    let carton = xous::carton::Carton::from_bytes(buf);
+    send_message(cid, api::Opcode::Read(*carton.as_ref()).into())?;
+    let contents: &[u8] = carton.as_ref();
+    buf.copy_from_slice(contents);
+    Ok(())
+}
+
+/// Tell the loader that the image it started works, so that it stops
+/// counting boots of it as failed attempts.  Returns `false` if the image
+/// didn't come from a boot slot, so there was nothing to confirm.
+pub fn confirm_boot(cid: CID) -> Result<bool, Error> {
+    let response = send_message(cid, api::Opcode::ConfirmBoot.into())?;
+    if let xous::Result::Scalar1(confirmed) = response {
+        Ok(confirmed != 0)
+    } else {
+        panic!("unexpected return value: {:#?}", response);
+    }
+}
@@ -0,0 +1,77 @@
+#![cfg_attr(target_os = "none", no_std)]
+#![cfg_attr(target_os = "none", no_main)]
+
+use bootlog::api::Opcode;
+
+use core::convert::TryFrom;
+
+use log::{error, info};
+
+#[xous::xous_main]
+fn xmain() -> ! {
+    log_server::init_wait().unwrap();
+
+    // The kernel only gives the log to this process, and there is none
+    // when running hosted or when the loader didn't write one.
+    let log: &[u32] = match xous::syscall::map_boot_log() {
+        Ok(range) => unsafe {
+            core::slice::from_raw_parts(range.as_ptr() as *const u32, range.len() / 4)
+        },
+        Err(e) => {
+            info!("BootLog: no boot log available: {:?}", e);
+            &[]
+        }
+    };
+    let used = match log {
+        [_, used, ..] => (*used as usize + 8).min(log.len() * 4),
+        _ => 0,
+    };
+
+    for entry in bootlog::entries(log) {
+        info!("BootLog: {}", entry);
+    }
+
+    // Likewise, only this process may confirm the boot slot.
+    let confirmed: Option<*mut u32> = match xous::syscall::map_boot_slot() {
+        Ok(range) => Some(range.as_mut_ptr() as *mut u32),
+        Err(e) => {
+            info!("BootLog: not started from a boot slot: {:?}", e);
+            None
+        }
+    };
+
+    let bootlog_server = xous::create_server_with_address(b"bootlog-server  ")
+        .expect("Couldn't create BootLog server");
+
+    loop {
+        let envelope = xous::receive_message(bootlog_server).unwrap();
+        if let Ok(opcode) = Opcode::try_from(&envelope.body) {
+            match opcode {
+                Opcode::Size => {
+                    xous::return_scalar(envelope.sender, used)
+                        .expect("BootLog: couldn't return size");
+                }
+                Opcode::ConfirmBoot => {
+                    if let Some(confirmed) = confirmed {
+                        unsafe { confirmed.write_volatile(bootlog::BOOT_CONFIRMED) };
+                        info!("BootLog: boot confirmed");
+                    }
+                    xous::return_scalar(envelope.sender, confirmed.is_some() as usize)
+                        .expect("BootLog: couldn't confirm boot");
+                }
+                Opcode::Read(buf) => {
+                    let length = buf.len().min(used);
+                    unsafe {
+                        core::ptr::copy_nonoverlapping(
+                            log.as_ptr() as *const u8,
+                            buf.as_mut_ptr(),
+                            length,
+                        )
+                    };
+                }
+            }
+        } else {
+            error!("couldn't convert opcode");
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use minifb::{Key, Window, WindowOptions};
+use std::path::{Path, PathBuf};
 
 const WIDTH: usize = 336;
 const HEIGHT: usize = 536;
@@ -11,14 +12,29 @@ const MAX_FPS: u64 = 15;
 const DARK_COLOUR: u32 = 0xB5B5AD;
 const LIGHT_COLOUR: u32 = 0x1B1B19;
 
+/// When set, no window is opened and the frame buffer is written to this
+/// file on every redraw instead, as `FB_SIZE` little-endian words.  This
+/// lets `xtask test-system` inspect what was drawn.
+const DUMP_VAR: &str = "XOUS_FRAMEBUFFER_DUMP";
+
 pub struct XousDisplay {
     native_buffer: Vec<u32>, //[u32; WIDTH * HEIGHT],
     emulated_buffer: [u32; FB_SIZE],
-    window: Window,
+    window: Option<Window>,
+    dump_path: Option<PathBuf>,
 }
 
 impl XousDisplay {
     pub fn new() -> XousDisplay {
+        if let Some(dump_path) = std::env::var_os(DUMP_VAR) {
+            return XousDisplay {
+                native_buffer: vec![],
+                window: None,
+                emulated_buffer: [0u32; FB_SIZE],
+                dump_path: Some(dump_path.into()),
+            };
+        }
+
         let mut window = Window::new(
             "Betrusted",
             WIDTH,
@@ -45,8 +61,9 @@ impl XousDisplay {
 
         XousDisplay {
             native_buffer,
-            window,
+            window: Some(window),
             emulated_buffer: [0u32; FB_SIZE],
+            dump_path: None,
         }
     }
 
@@ -61,18 +78,40 @@ impl XousDisplay {
     }
 
     pub fn redraw(&mut self) {
+        if let Some(dump_path) = &self.dump_path {
+            Self::dump(dump_path, &self.emulated_buffer);
+            return;
+        }
         self.emulated_to_native();
-        self.window
-            .update_with_buffer(&self.native_buffer, WIDTH, HEIGHT)
-            .unwrap();
+        if let Some(window) = &mut self.window {
+            window
+                .update_with_buffer(&self.native_buffer, WIDTH, HEIGHT)
+                .unwrap();
+        }
     }
 
     pub fn update(&mut self) {
+        if self.window.is_none() {
+            return;
+        }
         self.emulated_to_native();
-        self.window.update();
-        if !self.window.is_open() || self.window.is_key_down(Key::Escape) {
-            std::process::exit(0);
+        if let Some(window) = &mut self.window {
+            window.update();
+            if !window.is_open() || window.is_key_down(Key::Escape) {
+                std::process::exit(0);
+            }
+        }
+    }
+
+    /// Replace the dump file in one step, so a reader never sees half a frame.
+    fn dump(dump_path: &Path, buffer: &[u32; FB_SIZE]) {
+        let mut bytes = Vec::with_capacity(FB_SIZE * 4);
+        for word in buffer.iter() {
+            bytes.extend_from_slice(&word.to_le_bytes());
         }
+        let tmp_path = dump_path.with_extension("tmp");
+        std::fs::write(&tmp_path, &bytes).expect("couldn't write frame buffer dump");
+        std::fs::rename(&tmp_path, dump_path).expect("couldn't replace frame buffer dump");
     }
 
     fn emulated_to_native(&mut self) {
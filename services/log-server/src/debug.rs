@@ -41,6 +41,16 @@ pub static mut DEFAULT_UART_ADDR: *mut usize = 0x0000_0000 as *mut usize;
 
 pub const DEFAULT: Uart = Uart {};
 
+/// The UART that log output goes to.  QEMU `virt` has only one.
+#[cfg(not(feature = "qemu-virt"))]
+pub const UART_BASE: usize = utra::console::HW_CONSOLE_BASE;
+#[cfg(not(feature = "qemu-virt"))]
+pub const UART_IRQ: usize = utra::console::CONSOLE_IRQ;
+#[cfg(feature = "qemu-virt")]
+pub const UART_BASE: usize = utra::uart::HW_UART_BASE;
+#[cfg(feature = "qemu-virt")]
+pub const UART_IRQ: usize = utra::uart::UART_IRQ;
+
 impl Uart {
     fn map_uart(&self) {
         /*
@@ -48,7 +58,7 @@ impl Uart {
            UART resource. Modify in this function as necessary.
         */
         let uart = xous::syscall::map_memory(
-            xous::MemoryAddress::new(utra::console::HW_CONSOLE_BASE),
+            xous::MemoryAddress::new(UART_BASE),
             None,
             4096,
             xous::MemoryFlags::R | xous::MemoryFlags::W,
@@ -59,10 +69,13 @@ impl Uart {
         // core::mem::forget(uart);
 
         println!("Allocating IRQ...");
-        xous::claim_interrupt(utra::console::CONSOLE_IRQ, handle_irq, core::ptr::null_mut::<usize>()).expect("unable to allocate IRQ");
+        xous::claim_interrupt(UART_IRQ, handle_irq, core::ptr::null_mut::<usize>()).expect("unable to allocate IRQ");
         self.enable_rx();
     }
+}
 
+#[cfg(not(feature = "qemu-virt"))]
+impl Uart {
     pub fn putc(&self, c: u8) {
         if unsafe{DEFAULT_UART_ADDR} as usize == 0 {
             self.map_uart();
@@ -95,6 +108,48 @@ impl Uart {
     }
 }
 
+/// The QEMU `virt` UART is a 16550, whose registers are one byte apart.
+#[cfg(feature = "qemu-virt")]
+impl Uart {
+    const RBR_THR: usize = 0;
+    const IER: usize = 1;
+    const LSR: usize = 5;
+
+    fn reg(&self, offset: usize) -> *mut u8 {
+        unsafe { (DEFAULT_UART_ADDR as *mut u8).add(offset) }
+    }
+
+    pub fn putc(&self, c: u8) {
+        if unsafe{DEFAULT_UART_ADDR} as usize == 0 {
+            self.map_uart();
+        }
+        unsafe {
+            // Wait until the transmit holding register (LSR.THRE) is empty
+            while self.reg(Self::LSR).read_volatile() & (1 << 5) == 0 {}
+            self.reg(Self::RBR_THR).write_volatile(c);
+        }
+    }
+
+    pub fn enable_rx(&self) {
+        // Interrupt when data is received (IER.ERBFI)
+        unsafe { self.reg(Self::IER).write_volatile(1 << 0) };
+    }
+
+    pub fn getc(&self) -> Option<u8> {
+        if unsafe{DEFAULT_UART_ADDR} as usize == 0 {
+            self.map_uart();
+        }
+        unsafe {
+            // Data ready (LSR.DR)
+            if self.reg(Self::LSR).read_volatile() & (1 << 0) == 0 {
+                None
+            } else {
+                Some(self.reg(Self::RBR_THR).read_volatile())
+            }
+        }
+    }
+}
+
 impl Write for Uart {
     fn write_str(&mut self, s: &str) -> Result<(), Error> {
         for c in s.bytes() {
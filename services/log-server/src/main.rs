@@ -96,14 +96,14 @@ mod implementation {
 
 #[cfg(target_os = "none")]
 mod implementation {
+    use crate::debug::{UART_BASE, UART_IRQ};
     use core::fmt::{Error, Write};
-    use utralib::generated::*;
 
     pub struct Output {}
 
     pub fn init() -> Output {
         let uart = xous::syscall::map_memory(
-            xous::MemoryAddress::new(utra::console::HW_CONSOLE_BASE),
+            xous::MemoryAddress::new(UART_BASE),
             None,
             4096,
             xous::MemoryFlags::R | xous::MemoryFlags::W,
@@ -116,13 +116,9 @@ mod implementation {
         crate::debug::DEFAULT.enable_rx();
 
         println!("Allocating IRQ...");
-        xous::syscall::claim_interrupt(
-            utra::console::CONSOLE_IRQ,
-            handle_irq,
-            core::ptr::null_mut::<usize>(),
-        )
-        .expect("couldn't claim interrupt");
-        println!("Claimed IRQ {}", utra::console::CONSOLE_IRQ);
+        xous::syscall::claim_interrupt(UART_IRQ, handle_irq, core::ptr::null_mut::<usize>())
+            .expect("couldn't claim interrupt");
+        println!("Claimed IRQ {}", UART_IRQ);
         Output {}
     }
 
@@ -151,11 +147,7 @@ mod implementation {
 
     impl OutputWriter {
         pub fn putc(&self, c: u8) {
-            let mut uart_csr = CSR::new(unsafe { crate::debug::DEFAULT_UART_ADDR as *mut u32 });
-
-            // Wait until TXFULL is `0`
-            while uart_csr.r(utra::uart::TXFULL) != 0 {}
-            uart_csr.wo(utra::uart::RXTX, c as u32);
+            crate::debug::DEFAULT.putc(c);
         }
     }
 
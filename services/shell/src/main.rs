@@ -193,6 +193,15 @@ fn shell_main() -> ! {
 
     let mut last_time: u64 = 0;
     ticktimer_server::reset(ticktimer_conn).unwrap();
+
+    // The services the shell depends on are all answering, so let the
+    // loader know this image works.
+    let bootlog_id = xous::SID::from_bytes(b"bootlog-server  ").unwrap();
+    let bootlog_conn = xous::connect(bootlog_id).unwrap();
+    if bootlog::confirm_boot(bootlog_conn).expect("couldn't confirm boot") {
+        info!("SHELL: boot confirmed");
+    }
+
     let mut string_buffer = String::new(4096);
     graphics_server::set_glyph_style(graphics_conn, GlyphStyle::Small).expect("unable to set glyph");
     let (_, font_h) = graphics_server::query_glyph(graphics_conn).expect("unable to query glyph");
@@ -0,0 +1,120 @@
+#![cfg_attr(target_os = "none", no_std)]
+#![cfg_attr(target_os = "none", no_main)]
+
+//! Runs a fixed set of scenarios against the other services and reports
+//! each one on the log, then shuts the system down.  `xtask test-system`
+//! checks the log and the frame buffer against what is reported here, so
+//! keep the two in step.
+//!
+//! With the `qemu-virt` feature only the log scenario runs, since QEMU has
+//! no display, COM or ticktimer.
+
+#[cfg(not(feature = "qemu-virt"))]
+use graphics_server::{DrawStyle, PixelColor, Point, Rectangle};
+use log::info;
+
+/// Prefix of every line the harness looks for
+const MARKER: &str = "SYSTEM-TEST:";
+
+/// A filled rectangle in the space between the shell's work area and the
+/// bouncing ball, which the shell never draws over.
+#[cfg(not(feature = "qemu-virt"))]
+const RECTANGLE: (i16, i16, i16, i16) = (104, 304, 232, 356);
+
+/// How much space around `RECTANGLE` is cleared first
+#[cfg(not(feature = "qemu-virt"))]
+const MARGIN: i16 = 8;
+
+/// Time for the log server to write out the last lines before shutdown
+#[cfg(not(feature = "qemu-virt"))]
+const SETTLE_MS: usize = 250;
+
+fn log_lines() {
+    for line in 1..=3 {
+        info!("{} log line {} of 3", MARKER, line);
+    }
+}
+
+#[cfg(not(feature = "qemu-virt"))]
+fn draw_rectangle(graphics_conn: xous::CID) {
+    let (x0, y0, x1, y1) = RECTANGLE;
+    graphics_server::draw_rectangle(
+        graphics_conn,
+        Rectangle::new_coords_with_style(
+            x0 - MARGIN,
+            y0 - MARGIN,
+            x1 + MARGIN,
+            y1 + MARGIN,
+            DrawStyle::new(PixelColor::Light, PixelColor::Light, 1),
+        ),
+    )
+    .expect("unable to clear rectangle area");
+    graphics_server::draw_rectangle(
+        graphics_conn,
+        Rectangle::new_with_style(
+            Point::new(x0, y0),
+            Point::new(x1, y1),
+            DrawStyle::new(PixelColor::Dark, PixelColor::Dark, 1),
+        ),
+    )
+    .expect("unable to draw rectangle");
+    graphics_server::flush(graphics_conn).expect("unable to flush");
+
+    // Flushing doesn't wait for the server.  Messages are handled in
+    // order, so once a blocking request returns the frame is out.
+    graphics_server::screen_size(graphics_conn).expect("unable to get screen size");
+    info!("{} rectangle {} {} {} {}", MARKER, x0, y0, x1, y1);
+}
+
+#[cfg(not(feature = "qemu-virt"))]
+fn battery_stats(com_conn: xous::CID) {
+    let stats = com::get_batt_stats(com_conn).expect("unable to get battery stats");
+    info!(
+        "{} battery {} mV {} mA {}% {} mAh",
+        MARKER, stats.voltage, stats.current, stats.soc, stats.remaining_capacity
+    );
+}
+
+#[cfg(feature = "qemu-virt")]
+#[xous::xous_main]
+fn xmain() -> ! {
+    log_server::init_wait().unwrap();
+
+    // Logging blocks until the log server has written the line, so there
+    // is nothing left to wait for once "done" is out.
+    info!("{} starting", MARKER);
+    log_lines();
+    info!("{} done", MARKER);
+    xous::rsyscall(xous::SysCall::Shutdown).expect("unable to shut down");
+
+    loop {
+        xous::yield_slice();
+    }
+}
+
+#[cfg(not(feature = "qemu-virt"))]
+#[xous::xous_main]
+fn xmain() -> ! {
+    log_server::init_wait().unwrap();
+
+    let graphics_server_id = xous::SID::from_bytes(b"graphics-server ").unwrap();
+    let ticktimer_server_id = xous::SID::from_bytes(b"ticktimer-server").unwrap();
+    let com_id = xous::SID::from_bytes(b"com             ").unwrap();
+
+    let graphics_conn = xous::connect(graphics_server_id).unwrap();
+    let ticktimer_conn = xous::connect(ticktimer_server_id).unwrap();
+    let com_conn = xous::connect(com_id).unwrap();
+
+    info!("{} starting", MARKER);
+    log_lines();
+    draw_rectangle(graphics_conn);
+    battery_stats(com_conn);
+    info!("{} done", MARKER);
+
+    ticktimer_server::sleep_ms(ticktimer_conn, SETTLE_MS).expect("couldn't sleep");
+    xous::rsyscall(xous::SysCall::Shutdown).expect("unable to shut down");
+
+    loop {
+        xous::yield_slice();
+    }
+}
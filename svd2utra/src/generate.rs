@@ -1,4 +1,4 @@
-use quick_xml::events::Event;
+use quick_xml::events::{BytesStart, Event};
 use quick_xml::Reader;
 use std::io::{BufRead, BufReader, Read, Write};
 
@@ -9,24 +9,115 @@ pub enum ParseError {
     ParseIntError,
     NonUTF8,
     WriteError,
+    UnknownAccess,
+    InvalidDim,
+    UnknownPeripheral(String),
 }
 
-#[derive(Default, Debug)]
+/// Whether a register or field may be read, written, or both.  Anything
+/// that doesn't specify its access inherits it from its parent, and the
+/// device defaults to `ReadWrite`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Access {
+    ReadOnly,
+    WriteOnly,
+    ReadWrite,
+}
+
+impl Access {
+    fn parse(value: &str) -> Result<Access, ParseError> {
+        match value.trim() {
+            "read-only" => Ok(Access::ReadOnly),
+            "write-only" | "writeOnce" => Ok(Access::WriteOnly),
+            "read-write" | "read-writeOnce" => Ok(Access::ReadWrite),
+            _ => Err(ParseError::UnknownAccess),
+        }
+    }
+
+    fn readable(self) -> bool {
+        self != Access::WriteOnly
+    }
+
+    fn writable(self) -> bool {
+        self != Access::ReadOnly
+    }
+
+    /// How the SVD spells this access
+    fn svd_name(self) -> &'static str {
+        match self {
+            Access::ReadOnly => "read-only",
+            Access::WriteOnly => "write-only",
+            Access::ReadWrite => "read-write",
+        }
+    }
+
+    /// The marker type the generated code uses for this access
+    fn type_name(self) -> &'static str {
+        match self {
+            Access::ReadOnly => "crate::RO",
+            Access::WriteOnly => "crate::WO",
+            Access::ReadWrite => "crate::RW",
+        }
+    }
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct EnumeratedValue {
+    name: String,
+    description: Option<String>,
+    /// `None` for the `isDefault` entry, which covers every other value
+    value: Option<usize>,
+}
+
+#[derive(Clone, Default, Debug)]
 pub struct Field {
     name: String,
+    description: Option<String>,
     lsb: usize,
     msb: usize,
+    access: Option<Access>,
+    enumerated_values: Vec<EnumeratedValue>,
 }
 
-#[derive(Default, Debug)]
+/// The number of elements in one dimension of an array, and the distance
+/// between them in bytes
+type ArrayDim = (usize, usize);
+
+#[derive(Clone, Default, Debug)]
 pub struct Register {
     name: String,
+    /// Offset of the first register of an array
     offset: usize,
     description: Option<String>,
+    access: Option<Access>,
+    reset_value: Option<usize>,
+    /// The number of elements and the distance between them in bytes for
+    /// each dimension of a register array, outermost first.  Registers in
+    /// an array of clusters have a dimension for the cluster.
+    dims: Vec<ArrayDim>,
     fields: Vec<Field>,
 }
 
+/// The `dim`, `dimIncrement` and `dimIndex` of a register, cluster or
+/// field that is repeated.
 #[derive(Default, Debug)]
+struct Dim {
+    count: Option<usize>,
+    increment: Option<usize>,
+    index: Option<Vec<String>>,
+}
+
+/// How the name of a repeated element is expanded.  A name containing
+/// `[%s]` is an array, and one with `%s` elsewhere is a list of elements
+/// with the index substituted for `%s`.
+enum Expansion {
+    Single(String),
+    Array(String, usize, usize),
+    /// Each name with its offset from the first element
+    List(Vec<(String, usize)>),
+}
+
+#[derive(Clone, Default, Debug)]
 pub struct Interrupt {
     name: String,
     value: usize,
@@ -35,9 +126,14 @@ pub struct Interrupt {
 #[derive(Default, Debug)]
 pub struct Peripheral {
     name: String,
+    description: Option<String>,
     pub base: usize,
     size: usize,
     interrupt: Vec<Interrupt>,
+    access: Option<Access>,
+    reset_value: Option<usize>,
+    /// Name of the peripheral whose registers this one copies
+    derived_from: Option<String>,
     registers: Vec<Register>,
 }
 
@@ -52,6 +148,8 @@ pub struct MemoryRegion {
 pub struct Description {
     pub peripherals: Vec<Peripheral>,
     pub memory_regions: Vec<MemoryRegion>,
+    access: Option<Access>,
+    reset_value: Option<usize>,
 }
 
 impl core::fmt::Display for ParseError {
@@ -63,6 +161,11 @@ impl core::fmt::Display for ParseError {
             ParseIntError => write!(f, "unable to parse number"),
             NonUTF8 => write!(f, "file is not UTF-8"),
             WriteError => write!(f, "unable to write destination file"),
+            UnknownAccess => write!(f, "unrecognized access type"),
+            InvalidDim => write!(f, "repeated element is missing dim or %s"),
+            UnknownPeripheral(ref name) => {
+                write!(f, "derivedFrom names unknown peripheral {}", name)
+            }
         }
     }
 }
@@ -78,6 +181,8 @@ pub fn get_base(value: &str) -> (&str, u32) {
         (value.trim_start_matches("0b"), 2)
     } else if value.starts_with("0B") {
         (value.trim_start_matches("0B"), 2)
+    } else if value.starts_with('#') {
+        (value.trim_start_matches('#'), 2)
     } else if value.starts_with('0') && value != "0" {
         (value.trim_start_matches('0'), 8)
     } else {
@@ -91,24 +196,212 @@ fn parse_usize(value: &[u8]) -> Result<usize, ParseError> {
     usize::from_str_radix(value, base).or(Err(ParseError::ParseIntError))
 }
 
+/// Parse a `bitRange` such as `[7:4]` into its lsb and msb.
+fn parse_bit_range(value: &str) -> Result<(usize, usize), ParseError> {
+    let value = value.trim().trim_start_matches('[').trim_end_matches(']');
+    let mut bits = value.splitn(2, ':');
+    let msb = bits.next().ok_or(ParseError::MissingValue)?;
+    let lsb = bits.next().ok_or(ParseError::MissingValue)?;
+    Ok((
+        parse_usize(lsb.trim().as_bytes())?,
+        parse_usize(msb.trim().as_bytes())?,
+    ))
+}
+
+/// Parse a `dimIndex`, which is either a range such as `0-3` or a list
+/// such as `A,B,C`.
+fn parse_dim_index(value: &str) -> Result<Vec<String>, ParseError> {
+    let value = value.trim();
+    let range: Vec<&str> = value.splitn(2, '-').collect();
+    if range.len() == 2 {
+        let first = parse_usize(range[0].trim().as_bytes())?;
+        let last = parse_usize(range[1].trim().as_bytes())?;
+        return Ok((first..=last).map(|index| index.to_string()).collect());
+    }
+    Ok(value
+        .split(',')
+        .map(|index| index.trim().to_owned())
+        .collect())
+}
+
+impl Dim {
+    /// Record the value of a `dim`, `dimIncrement` or `dimIndex` tag.  Any
+    /// other tag is ignored.
+    fn parse_tag<T: BufRead>(
+        &mut self,
+        tag_name: &str,
+        reader: &mut Reader<T>,
+    ) -> Result<(), ParseError> {
+        match tag_name {
+            "dim" => self.count = Some(parse_usize(extract_contents(reader)?.as_bytes())?),
+            "dimIncrement" => {
+                self.increment = Some(parse_usize(extract_contents(reader)?.as_bytes())?)
+            }
+            "dimIndex" => self.index = Some(parse_dim_index(&extract_contents(reader)?)?),
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn expand(&self, name: &str) -> Result<Expansion, ParseError> {
+        let count = match self.count {
+            Some(count) => count,
+            None => return Ok(Expansion::Single(name.to_owned())),
+        };
+        let increment = self.increment.ok_or(ParseError::InvalidDim)?;
+        if name.contains("[%s]") {
+            return Ok(Expansion::Array(name.replace("[%s]", ""), count, increment));
+        }
+        if !name.contains("%s") {
+            return Err(ParseError::InvalidDim);
+        }
+        let index = match &self.index {
+            Some(index) if index.len() == count => index.clone(),
+            Some(_) => return Err(ParseError::InvalidDim),
+            None => (0..count).map(|index| index.to_string()).collect(),
+        };
+        Ok(Expansion::List(
+            index
+                .iter()
+                .enumerate()
+                .map(|(n, index)| (name.replace("%s", index), n * increment))
+                .collect(),
+        ))
+    }
+}
+
+impl Expansion {
+    /// The name of each element, its offset from the first element, and
+    /// the number of elements and increment if it is an array.
+    fn instances(self) -> Vec<(String, usize, Option<ArrayDim>)> {
+        match self {
+            Expansion::Single(name) => vec![(name, 0, None)],
+            Expansion::Array(name, count, increment) => vec![(name, 0, Some((count, increment)))],
+            Expansion::List(names) => names
+                .into_iter()
+                .map(|(name, offset)| (name, offset, None))
+                .collect(),
+        }
+    }
+}
+
+/// The `derivedFrom` attribute of an element, if it has one
+fn derived_from<T: BufRead>(
+    element: &BytesStart,
+    reader: &Reader<T>,
+) -> Result<Option<String>, ParseError> {
+    for attribute in element.attributes() {
+        let attribute = attribute.map_err(|_| ParseError::UnexpectedTag)?;
+        if attribute.key == b"derivedFrom" {
+            return attribute
+                .unescape_and_decode_value(reader)
+                .map(Some)
+                .map_err(|_| ParseError::NonUTF8);
+        }
+    }
+    Ok(None)
+}
+
 fn extract_contents<T: BufRead>(reader: &mut Reader<T>) -> Result<String, ParseError> {
     let mut buf = Vec::new();
-    let contents = reader
-        .read_event(&mut buf)
-        .map_err(|_| ParseError::UnexpectedTag)?;
-    match contents {
-        Event::Text(t) => t
-            .unescape_and_decode(reader)
-            .map_err(|_| ParseError::NonUTF8),
-        _ => Err(ParseError::UnexpectedTag),
+    let mut contents = String::new();
+    // Text may be split around CDATA sections, so everything up to the end
+    // of the element is collected.
+    loop {
+        match reader
+            .read_event(&mut buf)
+            .map_err(|_| ParseError::UnexpectedTag)?
+        {
+            Event::Text(t) => contents.push_str(
+                &t.unescape_and_decode(reader)
+                    .map_err(|_| ParseError::NonUTF8)?,
+            ),
+            Event::CData(t) => {
+                contents.push_str(std::str::from_utf8(&t).map_err(|_| ParseError::NonUTF8)?)
+            }
+            Event::End(_) => return Ok(contents),
+            _ => return Err(ParseError::UnexpectedTag),
+        }
+        buf.clear();
     }
 }
 
-fn generate_field<T: BufRead>(reader: &mut Reader<T>) -> Result<Field, ParseError> {
+fn generate_enumerated_value<T: BufRead>(
+    reader: &mut Reader<T>,
+) -> Result<EnumeratedValue, ParseError> {
+    let mut buf = Vec::new();
+    let mut name = None;
+    let mut description = None;
+    let mut value = None;
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) => {
+                let tag_name = e
+                    .unescape_and_decode(reader)
+                    .map_err(|_| ParseError::NonUTF8)?;
+                match tag_name.as_str() {
+                    "name" => name = Some(extract_contents(reader)?),
+                    "description" => description = Some(extract_contents(reader)?),
+                    "value" => value = Some(parse_usize(extract_contents(reader)?.as_bytes())?),
+                    _ => (),
+                }
+            }
+            Ok(Event::End(ref e)) => {
+                if let b"enumeratedValue" = e.name() {
+                    break;
+                }
+            }
+            Ok(_) => (),
+            Err(e) => panic!("error parsing: {:?}", e),
+        }
+    }
+
+    Ok(EnumeratedValue {
+        name: name.ok_or(ParseError::MissingValue)?,
+        description,
+        value,
+    })
+}
+
+fn generate_enumerated_values<T: BufRead>(
+    reader: &mut Reader<T>,
+) -> Result<Vec<EnumeratedValue>, ParseError> {
+    let mut buf = Vec::new();
+    let mut values = vec![];
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) => {
+                if let b"enumeratedValue" = e.name() {
+                    values.push(generate_enumerated_value(reader)?);
+                }
+            }
+            Ok(Event::End(ref e)) => {
+                if let b"enumeratedValues" = e.name() {
+                    break;
+                }
+            }
+            Ok(_) => (),
+            Err(e) => panic!("error parsing: {:?}", e),
+        }
+    }
+    Ok(values)
+}
+
+fn generate_field<T: BufRead>(
+    reader: &mut Reader<T>,
+    fields: &mut Vec<Field>,
+) -> Result<(), ParseError> {
     let mut buf = Vec::new();
     let mut name = None;
     let mut lsb = None;
     let mut msb = None;
+    let mut bit_offset = None;
+    let mut bit_width = None;
+    let mut bit_range = None;
+    let mut dim = Dim::default();
+    let mut description = None;
+    let mut access = None;
+    let mut enumerated_values = vec![];
     loop {
         match reader.read_event(&mut buf) {
             Ok(Event::Start(ref e)) => {
@@ -119,7 +412,24 @@ fn generate_field<T: BufRead>(reader: &mut Reader<T>) -> Result<Field, ParseErro
                     "name" => name = Some(extract_contents(reader)?),
                     "lsb" => lsb = Some(parse_usize(extract_contents(reader)?.as_bytes())?),
                     "msb" => msb = Some(parse_usize(extract_contents(reader)?.as_bytes())?),
-                    _ => (),
+                    "description" => description = Some(extract_contents(reader)?),
+                    "bitOffset" => {
+                        bit_offset = Some(parse_usize(extract_contents(reader)?.as_bytes())?)
+                    }
+                    "bitWidth" => {
+                        bit_width = Some(parse_usize(extract_contents(reader)?.as_bytes())?)
+                    }
+                    "bitRange" => bit_range = Some(parse_bit_range(&extract_contents(reader)?)?),
+                    "access" => access = Some(Access::parse(&extract_contents(reader)?)?),
+                    // Fields may have separate sets of values for reading
+                    // and writing.  Only the first set is used.
+                    "enumeratedValues" => {
+                        let values = generate_enumerated_values(reader)?;
+                        if enumerated_values.is_empty() {
+                            enumerated_values = values;
+                        }
+                    }
+                    tag => dim.parse_tag(tag, reader)?,
                 }
             }
             Ok(Event::End(ref e)) => {
@@ -132,11 +442,41 @@ fn generate_field<T: BufRead>(reader: &mut Reader<T>) -> Result<Field, ParseErro
         }
     }
 
-    Ok(Field {
-        name: name.ok_or(ParseError::MissingValue)?,
-        lsb: lsb.ok_or(ParseError::MissingValue)?,
-        msb: msb.ok_or(ParseError::MissingValue)?,
-    })
+    // The bit positions may be given in any of three styles
+    let (lsb, msb) = match (lsb, msb, bit_offset, bit_width, bit_range) {
+        (Some(lsb), Some(msb), _, _, _) => (lsb, msb),
+        (_, _, Some(offset), Some(width), _) if width > 0 => (offset, offset + width - 1),
+        (_, _, _, _, Some(range)) => range,
+        _ => return Err(ParseError::MissingValue),
+    };
+
+    // Repeated fields are always expanded into separately named fields
+    let field = Field {
+        name: String::new(),
+        description,
+        lsb,
+        msb,
+        access,
+        enumerated_values,
+    };
+    let name = name.ok_or(ParseError::MissingValue)?;
+    for (name, offset, array) in dim.expand(&name)?.instances() {
+        let elements = match array {
+            Some((count, increment)) => (0..count)
+                .map(|index| (format!("{}{}", name, index), offset + index * increment))
+                .collect(),
+            None => vec![(name, offset)],
+        };
+        for (name, offset) in elements {
+            fields.push(Field {
+                name,
+                lsb: field.lsb + offset,
+                msb: field.msb + offset,
+                ..field.clone()
+            });
+        }
+    }
+    Ok(())
 }
 
 fn generate_fields<T: BufRead>(
@@ -147,7 +487,7 @@ fn generate_fields<T: BufRead>(
     loop {
         match reader.read_event(&mut buf) {
             Ok(Event::Start(ref e)) => match e.name() {
-                b"field" => fields.push(generate_field(reader)?),
+                b"field" => generate_field(reader, fields)?,
                 _ => panic!("unexpected tag in <field>: {:?}", e),
             },
             Ok(Event::End(ref e)) => match e.name() {
@@ -157,18 +497,24 @@ fn generate_fields<T: BufRead>(
                 }
                 e => panic!("unhandled value: {:?}", e),
             },
-            Ok(Event::Text(_)) => (),
+            Ok(Event::Text(_)) | Ok(Event::Comment(_)) => (),
             e => panic!("unhandled value: {:?}", e),
         }
     }
     Ok(())
 }
 
-fn generate_register<T: BufRead>(reader: &mut Reader<T>) -> Result<Register, ParseError> {
+fn generate_register<T: BufRead>(
+    reader: &mut Reader<T>,
+    registers: &mut Vec<Register>,
+) -> Result<(), ParseError> {
     let mut buf = Vec::new();
     let mut name = None;
     let mut offset = None;
-    let description = None;
+    let mut description = None;
+    let mut dim = Dim::default();
+    let mut access = None;
+    let mut reset_value = None;
     let mut fields = vec![];
     loop {
         match reader.read_event(&mut buf) {
@@ -181,8 +527,13 @@ fn generate_register<T: BufRead>(reader: &mut Reader<T>) -> Result<Register, Par
                     "addressOffset" => {
                         offset = Some(parse_usize(extract_contents(reader)?.as_bytes())?)
                     }
+                    "description" => description = Some(extract_contents(reader)?),
+                    "access" => access = Some(Access::parse(&extract_contents(reader)?)?),
+                    "resetValue" => {
+                        reset_value = Some(parse_usize(extract_contents(reader)?.as_bytes())?)
+                    }
                     "fields" => generate_fields(reader, &mut fields)?,
-                    _ => (),
+                    tag => dim.parse_tag(tag, reader)?,
                 }
             }
             Ok(Event::End(ref e)) => {
@@ -195,12 +546,79 @@ fn generate_register<T: BufRead>(reader: &mut Reader<T>) -> Result<Register, Par
         }
     }
 
-    Ok(Register {
-        name: name.ok_or(ParseError::MissingValue)?,
+    let register = Register {
+        name: String::new(),
         offset: offset.ok_or(ParseError::MissingValue)?,
         description,
+        access,
+        reset_value,
+        dims: vec![],
         fields,
-    })
+    };
+    let name = name.ok_or(ParseError::MissingValue)?;
+    for (name, offset, array) in dim.expand(&name)?.instances() {
+        registers.push(Register {
+            name,
+            offset: register.offset + offset,
+            dims: array.into_iter().collect(),
+            ..register.clone()
+        });
+    }
+    Ok(())
+}
+
+/// Parse a cluster, adding its registers to `registers` with the name of
+/// the cluster as a prefix.  The registers of an array of clusters become
+/// arrays with an extra dimension.
+fn generate_cluster<T: BufRead>(
+    reader: &mut Reader<T>,
+    registers: &mut Vec<Register>,
+) -> Result<(), ParseError> {
+    let mut buf = Vec::new();
+    let mut name = None;
+    let mut offset = None;
+    let mut dim = Dim::default();
+    let mut members = vec![];
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) => {
+                let tag_name = e
+                    .unescape_and_decode(reader)
+                    .map_err(|_| ParseError::NonUTF8)?;
+                match tag_name.as_str() {
+                    "name" => name = Some(extract_contents(reader)?),
+                    "addressOffset" => {
+                        offset = Some(parse_usize(extract_contents(reader)?.as_bytes())?)
+                    }
+                    "register" => generate_register(reader, &mut members)?,
+                    "cluster" => generate_cluster(reader, &mut members)?,
+                    tag => dim.parse_tag(tag, reader)?,
+                }
+            }
+            Ok(Event::End(ref e)) => {
+                if let b"cluster" = e.name() {
+                    break;
+                }
+            }
+            Ok(_) => (),
+            Err(e) => panic!("error parsing: {:?}", e),
+        }
+    }
+
+    let name = name.ok_or(ParseError::MissingValue)?;
+    let cluster_offset = offset.ok_or(ParseError::MissingValue)?;
+    for (prefix, offset, array) in dim.expand(&name)?.instances() {
+        for member in &members {
+            let mut register = member.clone();
+            register.name = format!("{}_{}", prefix, member.name);
+            register.offset += cluster_offset + offset;
+            if let Some(array) = array {
+                register.dims.insert(0, array);
+            }
+            registers.push(register);
+        }
+    }
+    Ok(())
 }
 
 fn generate_interrupts<T: BufRead>(
@@ -251,7 +669,8 @@ fn generate_registers<T: BufRead>(
     loop {
         match reader.read_event(&mut buf) {
             Ok(Event::Start(ref e)) => match e.name() {
-                b"register" => registers.push(generate_register(reader)?),
+                b"register" => generate_register(reader, registers)?,
+                b"cluster" => generate_cluster(reader, registers)?,
                 _ => panic!("unexpected tag in <registers>: {:?}", e),
             },
             Ok(Event::End(ref e)) => match e.name() {
@@ -260,18 +679,24 @@ fn generate_registers<T: BufRead>(
                 }
                 e => panic!("unhandled value: {:?}", e),
             },
-            Ok(Event::Text(_)) => (),
+            Ok(Event::Text(_)) | Ok(Event::Comment(_)) => (),
             e => panic!("unhandled value: {:?}", e),
         }
     }
     Ok(())
 }
 
-fn generate_peripheral<T: BufRead>(reader: &mut Reader<T>) -> Result<Peripheral, ParseError> {
+fn generate_peripheral<T: BufRead>(
+    reader: &mut Reader<T>,
+    derived_from: Option<String>,
+) -> Result<Peripheral, ParseError> {
     let mut buf = Vec::new();
     let mut name = None;
+    let mut description = None;
     let mut base = None;
     let mut size = None;
+    let mut access = None;
+    let mut reset_value = None;
     let mut registers = vec![];
     let mut interrupts = vec![];
     loop {
@@ -282,12 +707,17 @@ fn generate_peripheral<T: BufRead>(reader: &mut Reader<T>) -> Result<Peripheral,
                     .map_err(|_| ParseError::NonUTF8)?;
                 match tag_name.as_str() {
                     "name" => name = Some(extract_contents(reader)?),
+                    "description" => description = Some(extract_contents(reader)?),
                     "baseAddress" => {
                         base = Some(parse_usize(extract_contents(reader)?.as_bytes())?)
                     }
                     "size" => size = Some(parse_usize(extract_contents(reader)?.as_bytes())?),
                     "registers" => generate_registers(reader, &mut registers)?,
                     "interrupt" => generate_interrupts(reader, &mut interrupts)?,
+                    "access" => access = Some(Access::parse(&extract_contents(reader)?)?),
+                    "resetValue" => {
+                        reset_value = Some(parse_usize(extract_contents(reader)?.as_bytes())?)
+                    }
                     _ => (),
                 }
             }
@@ -303,9 +733,18 @@ fn generate_peripheral<T: BufRead>(reader: &mut Reader<T>) -> Result<Peripheral,
 
     Ok(Peripheral {
         name: name.ok_or(ParseError::MissingValue)?,
+        description,
         base: base.ok_or(ParseError::MissingValue)?,
-        size: size.ok_or(ParseError::MissingValue)?,
+        // A derived peripheral may take its size from the one it copies
+        size: match (size, &derived_from) {
+            (Some(size), _) => size,
+            (None, Some(_)) => 0,
+            (None, None) => return Err(ParseError::MissingValue),
+        },
         interrupt: interrupts,
+        access,
+        reset_value,
+        derived_from,
         registers,
     })
 }
@@ -316,7 +755,10 @@ fn generate_peripherals<T: BufRead>(reader: &mut Reader<T>) -> Result<Vec<Periph
     loop {
         match reader.read_event(&mut buf) {
             Ok(Event::Start(ref e)) => match e.name() {
-                b"peripheral" => peripherals.push(generate_peripheral(reader)?),
+                b"peripheral" => {
+                    let derived_from = derived_from(e, reader)?;
+                    peripherals.push(generate_peripheral(reader, derived_from)?)
+                }
                 _ => panic!("unexpected tag in <peripherals>: {:?}", e),
             },
             Ok(Event::End(ref e)) => match e.name() {
@@ -325,7 +767,7 @@ fn generate_peripherals<T: BufRead>(reader: &mut Reader<T>) -> Result<Vec<Periph
                 }
                 e => panic!("unhandled value: {:?}", e),
             },
-            Ok(Event::Text(_)) => (),
+            Ok(Event::Text(_)) | Ok(Event::Comment(_)) => (),
             e => panic!("unhandled value: {:?}", e),
         }
     }
@@ -389,7 +831,7 @@ fn parse_memory_regions<T: BufRead>(
                 }
                 e => panic!("unhandled value: {:?}", e),
             },
-            Ok(Event::Text(_)) => (),
+            Ok(Event::Text(_)) | Ok(Event::Comment(_)) => (),
             e => panic!("unhandled value: {:?}", e),
         }
     }
@@ -413,7 +855,7 @@ fn parse_vendor_extensions<T: BufRead>(
                 }
                 e => panic!("unhandled value: {:?}", e),
             },
-            Ok(Event::Text(_)) => (),
+            Ok(Event::Text(_)) | Ok(Event::Comment(_)) => (),
             e => panic!("unhandled value: {:?}", e),
         }
     }
@@ -423,16 +865,52 @@ fn parse_vendor_extensions<T: BufRead>(
 fn print_header<U: Write>(out: &mut U) -> std::io::Result<()> {
     let s = r####"
 use core::convert::TryInto;
-pub struct Register {
+use core::marker::PhantomData;
+/// Access marker for registers and fields that may only be read
+pub struct RO;
+/// Access marker for registers and fields that may only be written
+pub struct WO;
+/// Access marker for registers and fields that may be read and written
+pub struct RW;
+/// Implemented by access markers that allow reading
+pub trait Readable {}
+/// Implemented by access markers that allow writing
+pub trait Writable {}
+impl Readable for RO {}
+impl Readable for RW {}
+impl Writable for WO {}
+impl Writable for RW {}
+pub struct Register<A = RW> {
     /// Offset of this register within this CSR
     offset: usize,
+    /// Value of this register after the peripheral is reset
+    reset: usize,
+    access: PhantomData<A>,
+}
+impl<A> Register<A> {
+    pub const fn new(offset: usize, reset: usize) -> Register<A> {
+        Register {
+            offset,
+            reset,
+            access: PhantomData,
+        }
+    }
+    /// Offset of this register, in words, from the start of the CSR
+    pub const fn offset(&self) -> usize {
+        self.offset
+    }
+    /// Value of this register after the peripheral is reset
+    pub const fn reset_value(&self) -> usize {
+        self.reset
+    }
 }
-impl Register {
-    pub const fn new(offset: usize) -> Register {
-        Register { offset }
+impl<A> Clone for Register<A> {
+    fn clone(&self) -> Self {
+        *self
     }
 }
-pub struct Field {
+impl<A> Copy for Register<A> {}
+pub struct Field<A = RW> {
     /// A bitmask we use to AND to the value, unshifted.
     /// E.g. for a width of `3` bits, this mask would be 0b111.
     mask: usize,
@@ -441,12 +919,12 @@ pub struct Field {
     /// A copy of the register address that this field
     /// is a member of. Ideally this is optimized out by the
     /// compiler.
-    register: Register,
+    register: Register<A>,
 }
-impl Field {
+impl<A> Field<A> {
     /// Define a new CSR field with the given width at a specified
     /// offset from the start of the register.
-    pub const fn new(width: usize, offset: usize, register: Register) -> Field {
+    pub const fn new(width: usize, offset: usize, register: Register<A>) -> Field<A> {
         // Asserts don't work in const fn yet.
         // assert!(width != 0, "field width cannot be 0");
         // assert!((width + offset) < 32, "field with and offset must fit within a 32-bit value");
@@ -493,6 +971,26 @@ impl Field {
             register,
         }
     }
+    /// Value of this field after the peripheral is reset
+    pub const fn reset_value(&self) -> usize {
+        (self.register.reset >> self.offset) & self.mask
+    }
+}
+impl<A> Clone for Field<A> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<A> Copy for Field<A> {}
+/// Implemented by the enums generated for fields with enumerated values.
+pub trait FieldValue: Sized {
+    type Access;
+    /// The field these are the values of
+    const FIELD: Field<Self::Access>;
+    /// Convert the contents of the field, returning `None` if the value
+    /// isn't one of those listed.
+    fn from_bits(bits: usize) -> Option<Self>;
+    fn bits(self) -> usize;
 }
 pub struct CSR<T> {
     base: *mut T,
@@ -504,63 +1002,98 @@ where
     pub fn new(base: *mut T) -> Self {
         CSR { base }
     }
-    /// Read the contents of this register
-    pub fn r(&self, reg: Register) -> T {
+    /// Read the word `offset` words from the base of this CSR
+    #[cfg(not(feature = "mock"))]
+    fn read_word(&self, offset: usize) -> usize {
         let usize_base: *mut usize = unsafe { core::mem::transmute(self.base) };
-        unsafe { usize_base.add(reg.offset).read_volatile() }
-            .try_into()
-            .unwrap_or_default()
+        unsafe { usize_base.add(offset).read_volatile() }
     }
-    /// Read a field from this CSR
-    pub fn rf(&self, field: Field) -> T {
+    /// Write the word `offset` words from the base of this CSR
+    #[cfg(not(feature = "mock"))]
+    fn write_word(&mut self, offset: usize, value: usize) {
         let usize_base: *mut usize = unsafe { core::mem::transmute(self.base) };
-        ((unsafe { usize_base.add(field.register.offset).read_volatile() } >> field.offset)
-            & field.mask)
+        unsafe { usize_base.add(offset).write_volatile(value) };
+    }
+    #[cfg(feature = "mock")]
+    fn read_word(&self, offset: usize) -> usize {
+        mock::read(self.base as usize, offset)
+    }
+    #[cfg(feature = "mock")]
+    fn write_word(&mut self, offset: usize, value: usize) {
+        mock::write(self.base as usize, offset, value)
+    }
+    /// Read the contents of this register
+    pub fn r<A: Readable>(&self, reg: Register<A>) -> T {
+        self.read_word(reg.offset).try_into().unwrap_or_default()
+    }
+    /// Read a field from this CSR
+    pub fn rf<A: Readable>(&self, field: Field<A>) -> T {
+        ((self.read_word(field.register.offset) >> field.offset) & field.mask)
             .try_into()
             .unwrap_or_default()
     }
     /// Read-modify-write a given field in this CSR
-    pub fn rmwf(&mut self, field: Field, value: T) {
-        let usize_base: *mut usize = unsafe { core::mem::transmute(self.base) };
+    pub fn rmwf<A: Readable + Writable>(&mut self, field: Field<A>, value: T) {
         let value_as_usize: usize = value.try_into().unwrap_or_default() << field.offset;
-        let previous =
-            unsafe { usize_base.add(field.register.offset).read_volatile() } & !field.mask;
-        unsafe {
-            usize_base
-                .add(field.register.offset)
-                .write_volatile(previous | value_as_usize)
-        };
+        let previous = self.read_word(field.register.offset) & !field.mask;
+        self.write_word(field.register.offset, previous | value_as_usize);
     }
     /// Write a given field without reading it first
-    pub fn wfo(&mut self, field: Field, value: T) {
-        let usize_base: *mut usize = unsafe { core::mem::transmute(self.base) };
+    pub fn wfo<A: Writable>(&mut self, field: Field<A>, value: T) {
         let value_as_usize: usize = (value.try_into().unwrap_or_default() & field.mask) << field.offset;
-        unsafe {
-            usize_base
-                .add(field.register.offset)
-                .write_volatile(value_as_usize)
-        };
+        self.write_word(field.register.offset, value_as_usize);
     }
     /// Write the entire contents of a register without reading it first
-    pub fn wo(&mut self, reg: Register, value: T) {
-        let usize_base: *mut usize = unsafe { core::mem::transmute(self.base) };
+    pub fn wo<A: Writable>(&mut self, reg: Register<A>, value: T) {
         let value_as_usize: usize = value.try_into().unwrap_or_default();
-        unsafe { usize_base.add(reg.offset).write_volatile(value_as_usize) };
+        self.write_word(reg.offset, value_as_usize);
     }
     /// Zero a field from a provided value
-    pub fn zf(&mut self, field: Field, value: T) -> T {
+    pub fn zf<A>(&mut self, field: Field<A>, value: T) -> T {
         let value_as_usize: usize = value.try_into().unwrap_or_default();
         (value_as_usize & !(field.mask << field.offset))
             .try_into()
             .unwrap_or_default()
     }
     /// Shift & mask a value to its final field position
-    pub fn ms(&mut self, field: Field, value: T) -> T {
+    pub fn ms<A>(&mut self, field: Field<A>, value: T) -> T {
         let value_as_usize: usize = value.try_into().unwrap_or_default();
         ((value_as_usize & field.mask) << field.offset)
             .try_into()
             .unwrap_or_default()
     }
+    /// Write the value a register has after the peripheral is reset
+    pub fn reset<A: Writable>(&mut self, reg: Register<A>) {
+        self.write_word(reg.offset, reg.reset);
+    }
+    /// Read a field with enumerated values, returning `None` if it holds
+    /// a value that isn't listed
+    pub fn rfv<E: FieldValue>(&self) -> Option<E>
+    where
+        E::Access: Readable,
+    {
+        let field = E::FIELD;
+        E::from_bits((self.read_word(field.register.offset) >> field.offset) & field.mask)
+    }
+    /// Write a field with enumerated values without reading it first
+    pub fn wfov<E: FieldValue>(&mut self, value: E)
+    where
+        E::Access: Writable,
+    {
+        let field = E::FIELD;
+        let value_as_usize = (value.bits() & field.mask) << field.offset;
+        self.write_word(field.register.offset, value_as_usize);
+    }
+    /// Read-modify-write a field with enumerated values
+    pub fn rmwfv<E: FieldValue>(&mut self, value: E)
+    where
+        E::Access: Readable + Writable,
+    {
+        let field = E::FIELD;
+        let value_as_usize = (value.bits() & field.mask) << field.offset;
+        let previous = self.read_word(field.register.offset) & !(field.mask << field.offset);
+        self.write_word(field.register.offset, previous | value_as_usize);
+    }
 }
 "####;
     out.write_all(s.as_bytes())
@@ -598,28 +1131,67 @@ fn print_peripherals<U: Write>(peripherals: &[Peripheral], out: &mut U) -> std::
     writeln!(out, "pub mod utra {{")?;
     for peripheral in peripherals {
         writeln!(out)?;
+        if let Some(description) = &peripheral.description {
+            print_doc(out, "    ", description)?;
+        }
         writeln!(out, "    pub mod {} {{", peripheral.name.to_lowercase())?;
         for register in &peripheral.registers {
             writeln!(out)?;
             if let Some(description) = &register.description {
-                writeln!(out, "        /// {}", description)?;
+                print_doc(out, "        ", description)?;
             }
+            let reg_name = register.name.to_uppercase();
+            let reg_access = register.access.unwrap_or(Access::ReadWrite);
+            let reset_value = register.reset_value.unwrap_or(0);
+            let new_register = |offset: usize| {
+                format!("crate::Register::new({}, 0x{:x})", offset / 4, reset_value)
+            };
             writeln!(
                 out,
-                "        pub const {}: crate::Register = crate::Register::new({});",
-                register.name.to_uppercase(), register.offset / 4
+                "        pub const {}: {} = {};",
+                reg_name,
+                array_type(
+                    &register.dims,
+                    &format!("crate::Register<{}>", reg_access.type_name())
+                ),
+                array_value(&register.dims, register.offset, &new_register)
             )?;
             for field in &register.fields {
+                let field_access = field.access.unwrap_or(reg_access);
+                // A field whose access differs from its register's needs
+                // its own copy of the register with the field's access, as
+                // does each field of an array.
+                let new_field = |offset: usize| {
+                    let field_register = if field_access == reg_access && register.dims.is_empty() {
+                        reg_name.clone()
+                    } else {
+                        new_register(offset)
+                    };
+                    format!(
+                        "crate::Field::new({}, {}, {})",
+                        field.msb + 1 - field.lsb,
+                        field.lsb,
+                        field_register
+                    )
+                };
+                if let Some(description) = &field.description {
+                    print_doc(out, "        ", description)?;
+                }
                 writeln!(
                     out,
-                    "        pub const {}_{}: crate::Field = crate::Field::new({}, {}, {});",
-                    register.name,
+                    "        pub const {}_{}: {} = {};",
+                    reg_name,
                     field.name.to_uppercase(),
-                    field.msb + 1 - field.lsb,
-                    field.lsb,
-                    register.name
+                    array_type(
+                        &register.dims,
+                        &format!("crate::Field<{}>", field_access.type_name())
+                    ),
+                    array_value(&register.dims, register.offset, &new_field)
                 )?;
             }
+            for field in &register.fields {
+                print_field_values(register, field, out)?;
+            }
         }
         writeln!(out)?;
         for interrupt in &peripheral.interrupt {
@@ -637,6 +1209,439 @@ fn print_peripherals<U: Write>(peripherals: &[Peripheral], out: &mut U) -> std::
     Ok(())
 }
 
+/// Write a description from the SVD as a doc comment, one line at a time
+fn print_doc<U: Write>(out: &mut U, indent: &str, description: &str) -> std::io::Result<()> {
+    for line in description.trim().lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            writeln!(out, "{}///", indent)?;
+        } else {
+            writeln!(out, "{}/// {}", indent, line)?;
+        }
+    }
+    Ok(())
+}
+
+/// The type of a constant holding an array of `element` with the given
+/// dimensions, or just `element` if there are none.
+fn array_type(dims: &[ArrayDim], element: &str) -> String {
+    dims.iter()
+        .rev()
+        .fold(element.to_owned(), |inner, (count, _)| {
+            format!("[{}; {}]", inner, count)
+        })
+}
+
+/// The value of a constant holding an array with the given dimensions,
+/// where `element` produces each element from its offset in bytes.
+fn array_value(dims: &[ArrayDim], offset: usize, element: &dyn Fn(usize) -> String) -> String {
+    match dims.split_first() {
+        None => element(offset),
+        Some((&(count, increment), inner)) => format!(
+            "[{}]",
+            (0..count)
+                .map(|index| array_value(inner, offset + index * increment, element))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// Convert an SVD name such as `EV_PENDING` to `EvPending`, for use as
+/// a type or variant name.
+fn camel_case(name: &str) -> String {
+    let mut camel = String::new();
+    for word in name.split(|c: char| !c.is_ascii_alphanumeric()) {
+        // Words that are already mixed case, like `LowPower`, are kept
+        let mixed_case = word.chars().any(|c| c.is_ascii_lowercase())
+            && word.chars().any(|c| c.is_ascii_uppercase());
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            camel.push(first.to_ascii_uppercase());
+            if mixed_case {
+                camel.extend(chars);
+            } else {
+                camel.extend(chars.map(|c| c.to_ascii_lowercase()));
+            }
+        }
+    }
+    // Identifiers can't start with a digit
+    if camel.starts_with(|c: char| c.is_ascii_digit()) {
+        camel.insert(0, 'V');
+    }
+    camel
+}
+
+/// Name of the enum generated for a field's enumerated values.  Fields are
+/// often named after their register, as in `MODE_MODE`, in which case the
+/// register's name isn't repeated.
+fn field_value_name(register: &Register, field: &Field) -> String {
+    let register_name = register.name.to_uppercase();
+    let field_name = field.name.to_uppercase();
+    if field_name == register_name || field_name.starts_with(&format!("{}_", register_name)) {
+        camel_case(&field.name)
+    } else {
+        camel_case(&format!("{}_{}", register.name, field.name))
+    }
+}
+
+/// The const generic parameters that pick an element of an array with
+/// `dims` dimensions, as (declarations, arguments, index)
+fn element_params(dims: &[ArrayDim]) -> (String, String, String) {
+    let names: Vec<String> = match dims.len() {
+        1 => vec!["N".to_owned()],
+        count => (0..count).map(|dim| format!("N{}", dim)).collect(),
+    };
+    let params: Vec<String> = names
+        .iter()
+        .map(|name| format!("const {}: usize", name))
+        .collect();
+    let index: String = names.iter().map(|name| format!("[{}]", name)).collect();
+    (params.join(", "), names.join(", "), index)
+}
+
+/// The enumerated values of a field that can be represented as enum
+/// variants.  The `isDefault` entry has no value, and later entries that
+/// repeat a value or a name are dropped.
+fn field_values(field: &Field) -> Vec<(String, usize, &EnumeratedValue)> {
+    let mut values: Vec<(String, usize, &EnumeratedValue)> = vec![];
+    for value in &field.enumerated_values {
+        let bits = match value.value {
+            Some(bits) => bits,
+            None => continue,
+        };
+        let name = camel_case(&value.name);
+        if name.is_empty() || values.iter().any(|(n, b, _)| *n == name || *b == bits) {
+            continue;
+        }
+        values.push((name, bits, value));
+    }
+    values
+}
+
+fn print_field_values<U: Write>(
+    register: &Register,
+    field: &Field,
+    out: &mut U,
+) -> std::io::Result<()> {
+    let values = field_values(field);
+    if values.is_empty() {
+        return Ok(());
+    }
+    let reg_name = register.name.to_uppercase();
+    let field_name = format!("{}_{}", reg_name, field.name.to_uppercase());
+    let enum_name = field_value_name(register, field);
+    let access = field.access.unwrap_or(Access::ReadWrite);
+    writeln!(out, "        /// Values of `{}`", field_name)?;
+    writeln!(out, "        #[derive(Clone, Copy, Debug, PartialEq, Eq)]")?;
+    writeln!(out, "        pub enum {} {{", enum_name)?;
+    for (name, bits, value) in &values {
+        if let Some(description) = &value.description {
+            print_doc(out, "            ", description)?;
+        }
+        writeln!(out, "            {} = {},", name, bits)?;
+    }
+    writeln!(out, "        }}")?;
+    // `FieldValue` names a single field, so the values of a field in an
+    // array of registers are converted with inherent methods, and each
+    // element gets its own `FieldValue` through a wrapper.
+    let visibility = if register.dims.is_empty() {
+        writeln!(out, "        impl crate::FieldValue for {} {{", enum_name)?;
+        writeln!(out, "            type Access = {};", access.type_name())?;
+        writeln!(
+            out,
+            "            const FIELD: crate::Field<{}> = {};",
+            access.type_name(),
+            field_name
+        )?;
+        ""
+    } else {
+        writeln!(out, "        impl {} {{", enum_name)?;
+        "pub "
+    };
+    writeln!(
+        out,
+        "            {}fn from_bits(bits: usize) -> Option<Self> {{",
+        visibility
+    )?;
+    writeln!(out, "                match bits {{")?;
+    for (name, bits, _) in &values {
+        writeln!(
+            out,
+            "                    {} => Some({}::{}),",
+            bits, enum_name, name
+        )?;
+    }
+    writeln!(out, "                    _ => None,")?;
+    writeln!(out, "                }}")?;
+    writeln!(out, "            }}")?;
+    writeln!(out, "            {}fn bits(self) -> usize {{", visibility)?;
+    writeln!(out, "                self as usize")?;
+    writeln!(out, "            }}")?;
+    writeln!(out, "        }}")?;
+    if register.dims.is_empty() {
+        return Ok(());
+    }
+
+    let (params, names, index) = element_params(&register.dims);
+    writeln!(
+        out,
+        "        /// A value of one element of `{}`, for use with `rfv()`, `wfov()` and `rmwfv()`",
+        field_name
+    )?;
+    writeln!(out, "        #[derive(Clone, Copy, Debug, PartialEq, Eq)]")?;
+    writeln!(
+        out,
+        "        pub struct {}At<{}>(pub {});",
+        enum_name, params, enum_name
+    )?;
+    writeln!(
+        out,
+        "        impl<{}> crate::FieldValue for {}At<{}> {{",
+        params, enum_name, names
+    )?;
+    writeln!(out, "            type Access = {};", access.type_name())?;
+    writeln!(
+        out,
+        "            const FIELD: crate::Field<{}> = {}{};",
+        access.type_name(),
+        field_name,
+        index
+    )?;
+    writeln!(
+        out,
+        "            fn from_bits(bits: usize) -> Option<Self> {{"
+    )?;
+    writeln!(
+        out,
+        "                {}::from_bits(bits).map(Self)",
+        enum_name
+    )?;
+    writeln!(out, "            }}")?;
+    writeln!(out, "            fn bits(self) -> usize {{")?;
+    writeln!(out, "                self.0.bits()")?;
+    writeln!(out, "            }}")?;
+    writeln!(out, "        }}")?;
+    Ok(())
+}
+
+/// The offset in bytes of every element of a register array, or of just
+/// the register if it isn't one.
+fn element_offsets(dims: &[ArrayDim], offset: usize) -> Vec<usize> {
+    match dims.split_first() {
+        None => vec![offset],
+        Some((&(count, increment), inner)) => (0..count)
+            .flat_map(|index| element_offsets(inner, offset + index * increment))
+            .collect(),
+    }
+}
+
+fn print_mock<U: Write>(peripherals: &[Peripheral], out: &mut U) -> std::io::Result<()> {
+    let s = r####"
+/// In-memory stand-ins for peripherals, so that drivers can be tested on a
+/// host.  While a `mock::Peripheral` exists, a `CSR` created on the same
+/// thread with its base address reads and writes the mock rather than
+/// hardware.  Hooks may be attached to registers to model their side
+/// effects, such as sending a byte when `RXTX` is written.
+#[cfg(feature = "mock")]
+pub mod mock {
+    use super::{Field, Readable, Register, Writable};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// The contents of a mocked peripheral's registers.  These may be
+    /// changed regardless of the registers' access, to model hardware.
+    pub struct Registers {
+        words: Vec<usize>,
+    }
+    impl Registers {
+        pub fn r<A>(&self, reg: Register<A>) -> usize {
+            self.words[reg.offset]
+        }
+        pub fn wo<A>(&mut self, reg: Register<A>, value: usize) {
+            self.words[reg.offset] = value;
+        }
+        pub fn rf<A>(&self, field: Field<A>) -> usize {
+            (self.words[field.register.offset] >> field.offset) & field.mask
+        }
+        /// Replace the contents of a field, leaving the rest of the
+        /// register unchanged
+        pub fn wf<A>(&mut self, field: Field<A>, value: usize) {
+            let word = &mut self.words[field.register.offset];
+            *word = (*word & !(field.mask << field.offset)) | ((value & field.mask) << field.offset);
+        }
+    }
+
+    type ReadHook = Box<dyn FnMut(&mut Registers) -> usize>;
+    type WriteHook = Box<dyn FnMut(&mut Registers, usize)>;
+
+    struct State {
+        base: usize,
+        registers: Registers,
+        read_hooks: Vec<(usize, ReadHook)>,
+        write_hooks: Vec<(usize, WriteHook)>,
+    }
+
+    thread_local! {
+        static PERIPHERALS: RefCell<Vec<Rc<RefCell<State>>>> = RefCell::new(Vec::new());
+    }
+
+    pub struct Peripheral {
+        state: Rc<RefCell<State>>,
+    }
+    impl Peripheral {
+        /// Create a mock of `words` registers at `base`, holding zero apart
+        /// from the `(offset, value)` pairs in `reset`.
+        pub fn new(base: usize, words: usize, reset: &[(usize, usize)]) -> Peripheral {
+            let mut registers = Registers {
+                words: vec![0; words],
+            };
+            for &(offset, value) in reset {
+                registers.words[offset] = value;
+            }
+            let state = Rc::new(RefCell::new(State {
+                base,
+                registers,
+                read_hooks: Vec::new(),
+                write_hooks: Vec::new(),
+            }));
+            PERIPHERALS.with(|peripherals| {
+                let mut peripherals = peripherals.borrow_mut();
+                if peripherals.iter().any(|other| other.borrow().base == base) {
+                    panic!("a mock peripheral already exists at {:08x}", base);
+                }
+                peripherals.push(state.clone());
+            });
+            Peripheral { state }
+        }
+        pub fn base(&self) -> usize {
+            self.state.borrow().base
+        }
+        pub fn r<A>(&self, reg: Register<A>) -> usize {
+            self.state.borrow().registers.r(reg)
+        }
+        pub fn wo<A>(&self, reg: Register<A>, value: usize) {
+            self.state.borrow_mut().registers.wo(reg, value)
+        }
+        pub fn rf<A>(&self, field: Field<A>) -> usize {
+            self.state.borrow().registers.rf(field)
+        }
+        pub fn wf<A>(&self, field: Field<A>, value: usize) {
+            self.state.borrow_mut().registers.wf(field, value)
+        }
+        /// Produce the value read from `reg` with `hook` rather than
+        /// returning its contents.  Hooks must not use a `CSR` for the
+        /// same peripheral.
+        pub fn on_read<A: Readable, F: FnMut(&mut Registers) -> usize + 'static>(
+            &self,
+            reg: Register<A>,
+            hook: F,
+        ) {
+            let mut state = self.state.borrow_mut();
+            state.read_hooks.retain(|(offset, _)| *offset != reg.offset);
+            state.read_hooks.push((reg.offset, Box::new(hook)));
+        }
+        /// Pass values written to `reg` to `hook` rather than storing
+        /// them.  The hook may store them itself.
+        pub fn on_write<A: Writable, F: FnMut(&mut Registers, usize) + 'static>(
+            &self,
+            reg: Register<A>,
+            hook: F,
+        ) {
+            let mut state = self.state.borrow_mut();
+            state.write_hooks.retain(|(offset, _)| *offset != reg.offset);
+            state.write_hooks.push((reg.offset, Box::new(hook)));
+        }
+    }
+    impl Drop for Peripheral {
+        fn drop(&mut self) {
+            PERIPHERALS.with(|peripherals| {
+                peripherals
+                    .borrow_mut()
+                    .retain(|other| !Rc::ptr_eq(other, &self.state))
+            });
+        }
+    }
+
+    fn find(base: usize) -> Rc<RefCell<State>> {
+        PERIPHERALS.with(|peripherals| {
+            peripherals
+                .borrow()
+                .iter()
+                .find(|state| state.borrow().base == base)
+                .cloned()
+                .unwrap_or_else(|| panic!("no mock peripheral at {:08x}", base))
+        })
+    }
+
+    pub(crate) fn read(base: usize, offset: usize) -> usize {
+        let state = find(base);
+        let mut state = state.borrow_mut();
+        let State {
+            registers,
+            read_hooks,
+            ..
+        } = &mut *state;
+        match read_hooks.iter_mut().find(|(hooked, _)| *hooked == offset) {
+            Some((_, hook)) => hook(registers),
+            None => registers.words[offset],
+        }
+    }
+
+    pub(crate) fn write(base: usize, offset: usize, value: usize) {
+        let state = find(base);
+        let mut state = state.borrow_mut();
+        let State {
+            registers,
+            write_hooks,
+            ..
+        } = &mut *state;
+        match write_hooks.iter_mut().find(|(hooked, _)| *hooked == offset) {
+            Some((_, hook)) => hook(registers, value),
+            None => registers.words[offset] = value,
+        }
+    }
+"####;
+    out.write_all(s.as_bytes())?;
+
+    // A constructor for each peripheral, holding its reset values
+    for peripheral in peripherals {
+        let mut words = peripheral.size / 4;
+        let mut reset = vec![];
+        for register in &peripheral.registers {
+            let reset_value = register.reset_value.unwrap_or(0);
+            for offset in element_offsets(&register.dims, register.offset) {
+                words = words.max(offset / 4 + 1);
+                if reset_value != 0 {
+                    reset.push(format!("({}, 0x{:x})", offset / 4, reset_value));
+                }
+            }
+        }
+        writeln!(out)?;
+        writeln!(
+            out,
+            "    /// A mock of `{}` at `HW_{}_BASE`",
+            peripheral.name.to_lowercase(),
+            peripheral.name.to_uppercase()
+        )?;
+        writeln!(
+            out,
+            "    pub fn {}() -> Peripheral {{",
+            peripheral.name.to_lowercase()
+        )?;
+        writeln!(
+            out,
+            "        Peripheral::new(super::HW_{}_BASE, {}, &[{}])",
+            peripheral.name.to_uppercase(),
+            words,
+            reset.join(", ")
+        )?;
+        writeln!(out, "    }}")?;
+    }
+    writeln!(out, "}}")?;
+    Ok(())
+}
+
 fn print_tests<U: Write>(peripherals: &[Peripheral], out: &mut U) -> std::io::Result<()> {
     let test_header = r####"
 #[cfg(test)]
@@ -648,21 +1653,81 @@ mod tests {
 "####.as_bytes();
     out.write_all(test_header)?;
     for peripheral in peripherals {
+        // Peripherals such as byte-wide UARTs have no registers to check
+        if peripheral.registers.is_empty() {
+            continue;
+        }
         let mod_name = peripheral.name.to_lowercase();
         let per_name = peripheral.name.to_lowercase() + "_csr";
         writeln!(out, "        let mut {} = CSR::new(HW_{}_BASE as *mut u32);", per_name, peripheral.name.to_uppercase())?;
         for register in &peripheral.registers {
             writeln!(out)?;
-            let reg_name = register.name.to_uppercase();
-            writeln!(out, "        let foo = {}.r(utra::{}::{});", per_name, mod_name, reg_name)?;
-            writeln!(out, "        {}.wo(utra::{}::{}, foo);", per_name, mod_name, reg_name)?;
+            // Arrays are checked through their first element
+            let index = "[0]".repeat(register.dims.len());
+            let reg_name = format!("{}{}", register.name.to_uppercase(), index);
+            let reg_access = register.access.unwrap_or(Access::ReadWrite);
+            // Only the accessors the register's access allows are used, so
+            // that the check fails if the generated permissions are wrong.
+            let reg_value = if !reg_access.readable() {
+                "0"
+            } else if !reg_access.writable() {
+                writeln!(out, "        let _ = {}.r(utra::{}::{});", per_name, mod_name, reg_name)?;
+                "0"
+            } else {
+                writeln!(out, "        let foo = {}.r(utra::{}::{});", per_name, mod_name, reg_name)?;
+                "foo"
+            };
+            if reg_access.writable() {
+                writeln!(out, "        {}.wo(utra::{}::{}, {});", per_name, mod_name, reg_name, reg_value)?;
+                writeln!(out, "        {}.reset(utra::{}::{});", per_name, mod_name, reg_name)?;
+            }
             for field in &register.fields {
-                let field_name = format!("{}_{}", reg_name, field.name.to_uppercase());
-                writeln!(out, "        let bar = {}.rf(utra::{}::{});", per_name, mod_name, field_name)?;
-                writeln!(out, "        {}.rmwf(utra::{}::{}, bar);", per_name, mod_name, field_name)?;
-                writeln!(out, "        let mut baz = {}.zf(utra::{}::{}, bar);", per_name, mod_name, field_name)?;
-                writeln!(out, "        baz |= {}.ms(utra::{}::{}, 1);", per_name, mod_name, field_name)?;
-                writeln!(out, "        {}.wfo(utra::{}::{}, baz);", per_name, mod_name, field_name)?;
+                let field_name = format!("{}_{}{}", register.name.to_uppercase(), field.name.to_uppercase(), index);
+                let field_access = field.access.unwrap_or(reg_access);
+                let field_value = if field_access.readable() {
+                    writeln!(out, "        let bar = {}.rf(utra::{}::{});", per_name, mod_name, field_name)?;
+                    "bar"
+                } else {
+                    "0"
+                };
+                if field_access.readable() && field_access.writable() {
+                    writeln!(out, "        {}.rmwf(utra::{}::{}, bar);", per_name, mod_name, field_name)?;
+                }
+                if field_access.writable() {
+                    writeln!(out, "        let mut baz = {}.zf(utra::{}::{}, {});", per_name, mod_name, field_name, field_value)?;
+                    writeln!(out, "        baz |= {}.ms(utra::{}::{}, 1);", per_name, mod_name, field_name)?;
+                    writeln!(out, "        {}.wfo(utra::{}::{}, baz);", per_name, mod_name, field_name)?;
+                } else {
+                    writeln!(out, "        let _ = {}.zf(utra::{}::{}, {}) | {}.ms(utra::{}::{}, 1);", per_name, mod_name, field_name, field_value, per_name, mod_name, field_name)?;
+                }
+                if let Some((value, _, _)) = field_values(field).first() {
+                    let enum_name =
+                        format!("utra::{}::{}", mod_name, field_value_name(register, field));
+                    let (value_type, value) = if register.dims.is_empty() {
+                        (enum_name.clone(), format!("{}::{}", enum_name, value))
+                    } else {
+                        let element = format!(
+                            "{}At::<{}>",
+                            enum_name,
+                            vec!["0"; register.dims.len()].join(", ")
+                        );
+                        let value = format!("{}({}::{})", element, enum_name, value);
+                        (element, value)
+                    };
+                    if field_access.readable() {
+                        writeln!(
+                            out,
+                            "        let _: Option<{}> = {}.rfv();",
+                            value_type, per_name
+                        )?;
+                    }
+                    if field_access.writable() {
+                        writeln!(out, "        {}.wfov({});", per_name, value)?;
+                    }
+                    if field_access.readable() && field_access.writable() {
+                        writeln!(out, "        {}.rmwfv({});", per_name, value)?;
+                    }
+                }
             }
         }
     }
@@ -685,6 +1750,13 @@ pub fn parse_svd<T: Read>(src: T) -> Result<Description, ParseError> {
                 b"vendorExtensions" => {
                     parse_vendor_extensions(&mut reader, &mut description)?;
                 }
+                b"access" => {
+                    description.access = Some(Access::parse(&extract_contents(&mut reader)?)?)
+                }
+                b"resetValue" => {
+                    description.reset_value =
+                        Some(parse_usize(extract_contents(&mut reader)?.as_bytes())?)
+                }
                 _ => (),
             },
             Ok(Event::Eof) => break,
@@ -693,16 +1765,373 @@ pub fn parse_svd<T: Read>(src: T) -> Result<Description, ParseError> {
         }
         buf.clear();
     }
+    resolve_derived_peripherals(&mut description.peripherals)?;
+    inherit_properties(&mut description);
     Ok(description)
 }
 
+/// Give each peripheral that is `derivedFrom` an earlier one the registers
+/// of that peripheral, along with any properties it doesn't set itself.
+/// Registers the derived peripheral lists replace those of the same name.
+fn resolve_derived_peripherals(peripherals: &mut [Peripheral]) -> Result<(), ParseError> {
+    for index in 0..peripherals.len() {
+        let base_name = match &peripherals[index].derived_from {
+            Some(name) => name.clone(),
+            None => continue,
+        };
+        let base = peripherals[..index]
+            .iter()
+            .find(|peripheral| peripheral.name == base_name)
+            .ok_or(ParseError::UnknownPeripheral(base_name))?;
+        let mut registers = base.registers.clone();
+        let (size, access, reset_value) = (base.size, base.access, base.reset_value);
+        let description = base.description.clone();
+
+        let peripheral = &mut peripherals[index];
+        for register in peripheral.registers.drain(..) {
+            match registers.iter_mut().find(|r| r.name == register.name) {
+                Some(existing) => *existing = register,
+                None => registers.push(register),
+            }
+        }
+        peripheral.registers = registers;
+        if peripheral.size == 0 {
+            peripheral.size = size;
+        }
+        peripheral.description = peripheral.description.take().or(description);
+        peripheral.access = peripheral.access.or(access);
+        peripheral.reset_value = peripheral.reset_value.or(reset_value);
+    }
+    Ok(())
+}
+
+/// Give every peripheral, register and field that doesn't specify an
+/// access or reset value the one of its parent.
+fn inherit_properties(description: &mut Description) {
+    let access = description.access.unwrap_or(Access::ReadWrite);
+    let reset_value = description.reset_value.unwrap_or(0);
+    for peripheral in &mut description.peripherals {
+        let access = *peripheral.access.get_or_insert(access);
+        let reset_value = *peripheral.reset_value.get_or_insert(reset_value);
+        for register in &mut peripheral.registers {
+            let access = *register.access.get_or_insert(access);
+            register.reset_value.get_or_insert(reset_value);
+            for field in &mut register.fields {
+                field.access.get_or_insert(access);
+            }
+        }
+    }
+}
+
+/// Collapse a description onto a single line, for comments and table cells
+fn one_line(description: &str) -> String {
+    description.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Turn an enumerated value's name into the last part of a C macro name
+fn c_identifier(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Write a C comment holding `text`, which mustn't end the comment early
+fn print_c_comment<U: Write>(out: &mut U, text: &str) -> std::io::Result<()> {
+    writeln!(out, "/* {} */", text.replace("*/", "* /"))
+}
+
+/// The name of a register or field macro and its value.  Each element of
+/// an array is picked by a parameter for each dimension, such as
+/// `UTRA_DMA_CH_ADDR(n0)`, and the value is an expression of them.
+fn c_array_macro(name: &str, dims: &[ArrayDim], offset: usize) -> (String, String) {
+    if dims.is_empty() {
+        return (name.to_owned(), format!("0x{:x}", offset));
+    }
+    let params: Vec<String> = (0..dims.len()).map(|index| format!("n{}", index)).collect();
+    let mut value = format!("0x{:x}", offset);
+    for (param, (_, increment)) in params.iter().zip(dims) {
+        value.push_str(&format!(" + ({}) * 0x{:x}", param, increment));
+    }
+    (
+        format!("{}({})", name, params.join(", ")),
+        format!("({})", value),
+    )
+}
+
+const C_HEADER: &str = r####"/* Generated by svd2utra.  Do not edit. */
+#ifndef UTRA_GENERATED_H
+#define UTRA_GENERATED_H
+
+#include <stdint.h>
+
+/* Registers are addressed by the base address of their peripheral and
+ * their offset in bytes, such as utra_read(HW_UART_BASE, UTRA_UART_RXTX) */
+static inline uint32_t utra_read(uintptr_t base, uintptr_t offset) {
+    return *(volatile uint32_t *)(base + offset);
+}
+
+static inline void utra_write(uintptr_t base, uintptr_t offset, uint32_t value) {
+    *(volatile uint32_t *)(base + offset) = value;
+}
+
+/* Extract a field from the value of its register, or move a value into
+ * place for a field, such as UTRA_FIELD_GET(UTRA_UART_EV_PENDING_TX, v) */
+#define UTRA_FIELD_GET(field, value) (((value) >> field##_SHIFT) & field##_MASK)
+#define UTRA_FIELD_SET(field, value) (((value) & field##_MASK) << field##_SHIFT)
+
+"####;
+
+fn print_c_header<U: Write>(description: &Description, out: &mut U) -> std::io::Result<()> {
+    out.write_all(C_HEADER.as_bytes())?;
+
+    writeln!(out, "/* Physical base addresses of memory regions */")?;
+    for region in &description.memory_regions {
+        writeln!(out, "#define HW_{}_MEM 0x{:08x}u", region.name, region.base)?;
+        writeln!(out, "#define HW_{}_MEM_LEN {}u", region.name, region.size)?;
+    }
+    writeln!(out)?;
+
+    writeln!(out, "/* Physical base addresses of registers */")?;
+    for peripheral in &description.peripherals {
+        writeln!(
+            out,
+            "#define HW_{}_BASE 0x{:08x}u",
+            peripheral.name.to_uppercase(),
+            peripheral.base
+        )?;
+    }
+
+    for peripheral in &description.peripherals {
+        let per_name = peripheral.name.to_uppercase();
+        writeln!(out)?;
+        match &peripheral.description {
+            Some(text) => print_c_comment(out, &format!("{}: {}", per_name, one_line(text)))?,
+            None => print_c_comment(out, &per_name)?,
+        }
+        for interrupt in &peripheral.interrupt {
+            writeln!(
+                out,
+                "#define UTRA_{}_{}_IRQ {}",
+                per_name,
+                interrupt.name.to_uppercase(),
+                interrupt.value
+            )?;
+        }
+        for register in &peripheral.registers {
+            let reg_name = format!("UTRA_{}_{}", per_name, register.name.to_uppercase());
+            let reg_access = register.access.unwrap_or(Access::ReadWrite);
+            let mut comment = format!(
+                "{}: {}, reset 0x{:08x}",
+                register.name.to_uppercase(),
+                reg_access.svd_name(),
+                register.reset_value.unwrap_or(0)
+            );
+            if let Some(text) = &register.description {
+                comment.push_str(&format!(" - {}", one_line(text)));
+            }
+            writeln!(out)?;
+            print_c_comment(out, &comment)?;
+            let (name, offset) = c_array_macro(&reg_name, &register.dims, register.offset);
+            writeln!(out, "#define {} {}", name, offset)?;
+            writeln!(
+                out,
+                "#define {}_RESET 0x{:08x}u",
+                reg_name,
+                register.reset_value.unwrap_or(0)
+            )?;
+            for field in &register.fields {
+                let field_name = format!("{}_{}", reg_name, field.name.to_uppercase());
+                let width = field.msb + 1 - field.lsb;
+                let mut comment = format!(
+                    "{}: bits [{}:{}], {}",
+                    field.name.to_uppercase(),
+                    field.msb,
+                    field.lsb,
+                    field.access.unwrap_or(reg_access).svd_name()
+                );
+                if let Some(text) = &field.description {
+                    comment.push_str(&format!(" - {}", one_line(text)));
+                }
+                print_c_comment(out, &comment)?;
+                writeln!(out, "#define {}_SHIFT {}", field_name, field.lsb)?;
+                writeln!(out, "#define {}_WIDTH {}", field_name, width)?;
+                let mask = u64::MAX >> (64 - width);
+                writeln!(out, "#define {}_MASK 0x{:x}u", field_name, mask)?;
+                // Values are given unshifted, for `UTRA_FIELD_SET`
+                let mut names = vec![];
+                for (_, bits, value) in field_values(field) {
+                    let name = format!("{}_{}", field_name, c_identifier(&value.name));
+                    if names.contains(&name) {
+                        continue;
+                    }
+                    writeln!(out, "#define {} {}u", name, bits)?;
+                    names.push(name);
+                }
+            }
+        }
+    }
+
+    writeln!(out)?;
+    writeln!(out, "#endif /* UTRA_GENERATED_H */")?;
+    Ok(())
+}
+
+/// Make a description safe to put in a cell of a Markdown table
+fn md_cell(description: &str) -> String {
+    one_line(description).replace('|', "\\|")
+}
+
+/// Write a description as Markdown, keeping its paragraphs
+fn print_md_description<U: Write>(out: &mut U, description: &str) -> std::io::Result<()> {
+    for line in description.trim().lines() {
+        writeln!(out, "{}", line.trim())?;
+    }
+    writeln!(out)
+}
+
+fn md_bits(field: &Field) -> String {
+    if field.msb == field.lsb {
+        format!("[{}]", field.lsb)
+    } else {
+        format!("[{}:{}]", field.msb, field.lsb)
+    }
+}
+
+fn print_markdown<U: Write>(description: &Description, out: &mut U) -> std::io::Result<()> {
+    writeln!(out, "# Register reference")?;
+    writeln!(out)?;
+    writeln!(out, "Generated by svd2utra.  Do not edit.")?;
+    writeln!(out)?;
+
+    if !description.memory_regions.is_empty() {
+        writeln!(out, "## Memory regions")?;
+        writeln!(out)?;
+        writeln!(out, "| Region | Base | Size |")?;
+        writeln!(out, "| ------ | ---- | ---- |")?;
+        for region in &description.memory_regions {
+            writeln!(
+                out,
+                "| {} | `0x{:08x}` | `0x{:x}` |",
+                region.name, region.base, region.size
+            )?;
+        }
+        writeln!(out)?;
+    }
+
+    for peripheral in &description.peripherals {
+        let per_name = peripheral.name.to_uppercase();
+        writeln!(out, "## {}", per_name)?;
+        writeln!(out)?;
+        writeln!(out, "Base address `0x{:08x}`.", peripheral.base)?;
+        writeln!(out)?;
+        if let Some(text) = &peripheral.description {
+            print_md_description(out, text)?;
+        }
+        if !peripheral.interrupt.is_empty() {
+            writeln!(out, "| Interrupt | Number |")?;
+            writeln!(out, "| --------- | ------ |")?;
+            for interrupt in &peripheral.interrupt {
+                writeln!(
+                    out,
+                    "| `{}` | {} |",
+                    interrupt.name.to_uppercase(),
+                    interrupt.value
+                )?;
+            }
+            writeln!(out)?;
+        }
+
+        for register in &peripheral.registers {
+            let reg_access = register.access.unwrap_or(Access::ReadWrite);
+            writeln!(out, "### {}_{}", per_name, register.name.to_uppercase())?;
+            writeln!(out)?;
+            write!(
+                out,
+                "Offset `0x{:04x}`, {}, reset `0x{:08x}`",
+                register.offset,
+                reg_access.svd_name(),
+                register.reset_value.unwrap_or(0)
+            )?;
+            for (count, increment) in &register.dims {
+                write!(out, ", repeated {} times ", count)?;
+                write!(out, "every `0x{:x}` bytes", increment)?;
+            }
+            writeln!(out, ".")?;
+            writeln!(out)?;
+            if let Some(text) = &register.description {
+                print_md_description(out, text)?;
+            }
+            if register.fields.is_empty() {
+                continue;
+            }
+
+            writeln!(out, "| Bits | Field | Access | Description |")?;
+            writeln!(out, "| ---- | ----- | ------ | ----------- |")?;
+            for field in &register.fields {
+                let description = field.description.as_deref().map(md_cell);
+                writeln!(
+                    out,
+                    "| `{}` | `{}` | {} | {} |",
+                    md_bits(field),
+                    field.name.to_uppercase(),
+                    field.access.unwrap_or(reg_access).svd_name(),
+                    description.unwrap_or_default()
+                )?;
+            }
+            writeln!(out)?;
+
+            for field in &register.fields {
+                let values = field_values(field);
+                if values.is_empty() {
+                    continue;
+                }
+                writeln!(out, "Values of `{}`:", field.name.to_uppercase())?;
+                writeln!(out)?;
+                writeln!(out, "| Value | Name | Description |")?;
+                writeln!(out, "| ----- | ---- | ----------- |")?;
+                for (_, bits, value) in values {
+                    let description = value.description.as_deref().map(md_cell);
+                    writeln!(
+                        out,
+                        "| `{}` | `{}` | {} |",
+                        bits,
+                        value.name,
+                        description.unwrap_or_default()
+                    )?;
+                }
+                writeln!(out)?;
+            }
+        }
+    }
+    Ok(())
+}
+
 pub fn generate<T: Read, U: Write>(src: T, dest: &mut U) -> Result<(), ParseError> {
     let description = parse_svd(src)?;
 
     print_header(dest).or(Err(ParseError::WriteError))?;
     print_memory_regions(&description.memory_regions, dest).or(Err(ParseError::WriteError))?;
     print_peripherals(&description.peripherals, dest).or(Err(ParseError::WriteError))?;
+    print_mock(&description.peripherals, dest).or(Err(ParseError::WriteError))?;
     print_tests(&description.peripherals, dest).or(Err(ParseError::WriteError))?;
 
     Ok(())
 }
+
+/// Generate a C header with the same registers and fields as `generate`
+pub fn generate_c_header<T: Read, U: Write>(src: T, dest: &mut U) -> Result<(), ParseError> {
+    let description = parse_svd(src)?;
+    print_c_header(&description, dest).or(Err(ParseError::WriteError))
+}
+
+/// Generate a register reference in Markdown
+pub fn generate_markdown<T: Read, U: Write>(src: T, dest: &mut U) -> Result<(), ParseError> {
+    let description = parse_svd(src)?;
+    print_markdown(&description, dest).or(Err(ParseError::WriteError))
+}
@@ -12,4 +12,322 @@ mod tests {
         let mut dest = File::create("target/example.rs").unwrap();
         generate(src, &mut dest).unwrap();
     }
+
+    const ACCESS_SVD: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<device>
+    <name>TEST</name>
+    <access>read-write</access>
+    <resetValue>0x00000000</resetValue>
+    <peripherals>
+        <peripheral>
+            <name>TIMER</name>
+            <baseAddress>0xF0001000</baseAddress>
+            <addressBlock>
+                <offset>0</offset>
+                <size>0x10</size>
+                <usage>registers</usage>
+            </addressBlock>
+            <registers>
+                <register>
+                    <name>STATUS</name>
+                    <addressOffset>0x0000</addressOffset>
+                    <access>read-only</access>
+                    <fields>
+                        <field>
+                            <name>state</name>
+                            <msb>1</msb>
+                            <bitRange>[1:0]</bitRange>
+                            <lsb>0</lsb>
+                            <enumeratedValues>
+                                <enumeratedValue>
+                                    <name>idle</name>
+                                    <description><![CDATA[Not counting]]></description>
+                                    <value>0</value>
+                                </enumeratedValue>
+                                <enumeratedValue>
+                                    <name>running</name>
+                                    <value>#01</value>
+                                </enumeratedValue>
+                                <enumeratedValue>
+                                    <name>reserved</name>
+                                    <isDefault>true</isDefault>
+                                </enumeratedValue>
+                            </enumeratedValues>
+                        </field>
+                    </fields>
+                </register>
+                <register>
+                    <name>LOAD</name>
+                    <addressOffset>0x0004</addressOffset>
+                    <access>write-only</access>
+                    <resetValue>0xffff</resetValue>
+                    <fields>
+                        <field>
+                            <name>load</name>
+                            <msb>15</msb>
+                            <bitRange>[15:0]</bitRange>
+                            <lsb>0</lsb>
+                        </field>
+                    </fields>
+                </register>
+                <register>
+                    <name>CONTROL</name>
+                    <description>Starts and stops the timer.
+
+                    Changes take effect on the next tick.</description>
+                    <addressOffset>0x0008</addressOffset>
+                    <resetValue>0x2</resetValue>
+                    <fields>
+                        <field>
+                            <name>enable</name>
+                            <description>1 to count | 0 to stop</description>
+                            <msb>0</msb>
+                            <bitRange>[0:0]</bitRange>
+                            <lsb>0</lsb>
+                        </field>
+                        <field>
+                            <name>mode</name>
+                            <msb>2</msb>
+                            <bitRange>[2:1]</bitRange>
+                            <lsb>1</lsb>
+                            <access>read-only</access>
+                        </field>
+                    </fields>
+                </register>
+            </registers>
+        </peripheral>
+    </peripherals>
+</device>
+"#;
+
+    fn generate_string(svd: &str) -> String {
+        let mut dest = vec![];
+        generate(svd.as_bytes(), &mut dest).unwrap();
+        String::from_utf8(dest).unwrap()
+    }
+
+    #[test]
+    fn access_types() {
+        let out = generate_string(ACCESS_SVD);
+        assert!(out.contains(
+            "pub const STATUS: crate::Register<crate::RO> = crate::Register::new(0, 0x0);"
+        ));
+        assert!(out.contains(
+            "pub const LOAD: crate::Register<crate::WO> = crate::Register::new(1, 0xffff);"
+        ));
+        assert!(out.contains(
+            "pub const LOAD_LOAD: crate::Field<crate::WO> = crate::Field::new(16, 0, LOAD);"
+        ));
+        assert!(out.contains(
+            "pub const CONTROL: crate::Register<crate::RW> = crate::Register::new(2, 0x2);"
+        ));
+        // A field whose access differs from its register's can't share it
+        assert!(out.contains("pub const CONTROL_MODE: crate::Field<crate::RO> = crate::Field::new(2, 1, crate::Register::new(2, 0x2));"));
+
+        // The compile check only uses the accessors that are allowed
+        assert!(out.contains("let _ = timer_csr.r(utra::timer::STATUS);"));
+        assert!(!out.contains("timer_csr.wo(utra::timer::STATUS"));
+        assert!(out.contains("timer_csr.wo(utra::timer::LOAD, 0);"));
+        assert!(out.contains("timer_csr.reset(utra::timer::LOAD);"));
+        assert!(!out.contains("timer_csr.r(utra::timer::LOAD)"));
+        assert!(!out.contains("timer_csr.rmwf(utra::timer::CONTROL_MODE"));
+    }
+
+    #[test]
+    fn enumerated_values() {
+        let out = generate_string(ACCESS_SVD);
+        assert!(out.contains("pub enum StatusState {"));
+        assert!(out.contains("/// Not counting\n            Idle = 0,"));
+        assert!(out.contains("Running = 1,"));
+        // The default entry has no value of its own
+        assert!(!out.contains("Reserved"));
+        assert!(out.contains("type Access = crate::RO;"));
+        assert!(out.contains("const FIELD: crate::Field<crate::RO> = STATUS_STATE;"));
+        assert!(out.contains("1 => Some(StatusState::Running),"));
+        assert!(out.contains("let _: Option<utra::timer::StatusState> = timer_csr.rfv();"));
+        assert!(!out.contains("timer_csr.wfov(utra::timer::StatusState"));
+    }
+
+    /// An array of registers inside an array of clusters, whose field
+    /// repeats the register's name
+    const ARRAY_SVD: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<device>
+    <name>TEST</name>
+    <access>read-write</access>
+    <resetValue>0x00000000</resetValue>
+    <peripherals>
+        <peripheral>
+            <name>GPIO</name>
+            <baseAddress>0xF0002000</baseAddress>
+            <addressBlock>
+                <offset>0</offset>
+                <size>0x20</size>
+                <usage>registers</usage>
+            </addressBlock>
+            <registers>
+                <cluster>
+                    <dim>2</dim>
+                    <dimIncrement>0x10</dimIncrement>
+                    <name>PORT[%s]</name>
+                    <addressOffset>0x0</addressOffset>
+                    <register>
+                        <dim>4</dim>
+                        <dimIncrement>4</dimIncrement>
+                        <name>DIR[%s]</name>
+                        <addressOffset>0x0</addressOffset>
+                        <fields>
+                            <field>
+                                <name>PORT_DIR</name>
+                                <bitRange>[0:0]</bitRange>
+                                <enumeratedValues>
+                                    <enumeratedValue>
+                                        <name>INPUT</name>
+                                        <value>0</value>
+                                    </enumeratedValue>
+                                    <enumeratedValue>
+                                        <name>OUTPUT</name>
+                                        <value>1</value>
+                                    </enumeratedValue>
+                                </enumeratedValues>
+                            </field>
+                        </fields>
+                    </register>
+                </cluster>
+            </registers>
+        </peripheral>
+    </peripherals>
+</device>
+"#;
+
+    #[test]
+    fn array_field_values() {
+        let out = generate_string(ARRAY_SVD);
+        // `PORT_DIR_PORT_DIR` is named once
+        assert!(out.contains("pub enum PortDir {"));
+        assert!(out.contains("pub fn from_bits(bits: usize) -> Option<Self> {"));
+        assert!(
+            out.contains("pub struct PortDirAt<const N0: usize, const N1: usize>(pub PortDir);")
+        );
+        assert!(out.contains(
+            "impl<const N0: usize, const N1: usize> crate::FieldValue for PortDirAt<N0, N1> {"
+        ));
+        assert!(out.contains("const FIELD: crate::Field<crate::RW> = PORT_DIR_PORT_DIR[N0][N1];"));
+        assert!(out.contains(
+            "gpio_csr.rmwfv(utra::gpio::PortDirAt::<0, 0>(utra::gpio::PortDir::Input));"
+        ));
+    }
+
+    #[test]
+    fn mock_backend() {
+        let out = generate_string(ACCESS_SVD);
+        assert!(out.contains("#[cfg(feature = \"mock\")]\npub mod mock {"));
+        // Registers are sized to cover the address block, and only reset
+        // values that aren't zero are listed
+        assert!(out.contains(
+            "    pub fn timer() -> Peripheral {\n        Peripheral::new(super::HW_TIMER_BASE, 4, &[(1, 0xffff), (2, 0x2)])\n    }"
+        ));
+        // Every access goes through the backend
+        assert_eq!(out.matches("read_volatile").count(), 1);
+        assert_eq!(out.matches("write_volatile").count(), 1);
+    }
+
+    /// Compare each kind of output for each sample in `testdata` with the
+    /// `.rs`, `.h` and `.md` files beside it.  Run with `UPDATE_GOLDEN` set
+    /// to rewrite them.
+    #[test]
+    fn golden_files() {
+        type Generator = fn(File, &mut Vec<u8>) -> Result<(), ParseError>;
+        let generators: [(&str, Generator); 3] = [
+            ("rs", generate),
+            ("h", generate_c_header),
+            ("md", generate_markdown),
+        ];
+        for name in &["cmsis-dma", "nrf-timer", "stm32-gpio"] {
+            for (extension, generator) in &generators {
+                let src = File::open(format!("testdata/{}.svd", name)).unwrap();
+                let mut out = vec![];
+                generator(src, &mut out).unwrap();
+                let golden = format!("testdata/{}.{}", name, extension);
+                if std::env::var_os("UPDATE_GOLDEN").is_some() {
+                    std::fs::write(&golden, &out).unwrap();
+                    continue;
+                }
+                let expected = std::fs::read(&golden).unwrap();
+                assert!(
+                    out == expected,
+                    "output for {}.svd differs from {}",
+                    name,
+                    golden
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn descriptions() {
+        let out = generate_string(ACCESS_SVD);
+        assert!(out.contains(
+            "        /// Starts and stops the timer.\n        ///\n        /// Changes take effect on the next tick.\n        pub const CONTROL:"
+        ));
+        assert!(
+            out.contains("        /// 1 to count | 0 to stop\n        pub const CONTROL_ENABLE:")
+        );
+    }
+
+    #[test]
+    fn c_header() {
+        let mut out = vec![];
+        generate_c_header(ACCESS_SVD.as_bytes(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("#define HW_TIMER_BASE 0xf0001000u\n"));
+        assert!(
+            out.contains("/* LOAD: write-only, reset 0x0000ffff */\n#define UTRA_TIMER_LOAD 0x4\n")
+        );
+        assert!(out.contains("#define UTRA_TIMER_LOAD_RESET 0x0000ffffu\n"));
+        assert!(out.contains("#define UTRA_TIMER_STATUS_STATE_SHIFT 0\n"));
+        assert!(out.contains("#define UTRA_TIMER_STATUS_STATE_WIDTH 2\n"));
+        assert!(out.contains("#define UTRA_TIMER_STATUS_STATE_MASK 0x3u\n"));
+        assert!(out.contains("#define UTRA_TIMER_CONTROL_MODE_SHIFT 1\n"));
+        assert!(out.contains("#define UTRA_TIMER_STATUS_STATE_RUNNING 1u\n"));
+        assert!(!out.contains("RESERVED"));
+        assert!(out.contains("/* CONTROL: read-write, reset 0x00000002 - Starts and stops the timer. Changes take effect on the next tick. */\n"));
+    }
+
+    #[test]
+    fn markdown() {
+        let mut out = vec![];
+        generate_markdown(ACCESS_SVD.as_bytes(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("## TIMER\n\nBase address `0xf0001000`.\n"));
+        assert!(
+            out.contains("### TIMER_LOAD\n\nOffset `0x0004`, write-only, reset `0x0000ffff`.\n")
+        );
+        assert!(out.contains("| `[1:0]` | `STATE` | read-only |  |\n"));
+        assert!(out.contains("| `1` | `running` |  |\n"));
+        assert!(out.contains("| `0` | `idle` | Not counting |\n"));
+        assert!(
+            out.contains("Starts and stops the timer.\n\nChanges take effect on the next tick.\n")
+        );
+        assert!(out.contains("| `[0]` | `ENABLE` | read-write | 1 to count \\| 0 to stop |\n"));
+    }
+
+    #[test]
+    fn no_registers() {
+        let start = ACCESS_SVD.find("<registers>").unwrap();
+        let end = ACCESS_SVD.find("</registers>").unwrap() + "</registers>".len();
+        let svd = format!("{}{}", &ACCESS_SVD[..start], &ACCESS_SVD[end..]);
+        let out = generate_string(&svd);
+        assert!(out.contains("pub const HW_TIMER_BASE: usize = 0x"));
+        // The compile check would otherwise create an unused CSR
+        assert!(!out.contains("timer_csr"));
+    }
+
+    #[test]
+    fn derived_from_unknown() {
+        let svd = ACCESS_SVD.replace("<peripheral>", r#"<peripheral derivedFrom="UART">"#);
+        match parse_svd(svd.as_bytes()) {
+            Err(ParseError::UnknownPeripheral(name)) => assert_eq!(name, "UART"),
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
 }
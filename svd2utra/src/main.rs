@@ -7,7 +7,7 @@ use std::io::{Read, Write};
 
 fn main() -> anyhow::Result<()> {
     let matches = App::new("svd2utra")
-        .about("Generate a UTRA Rust API from SVD files")
+        .about("Generate a UTRA Rust API, C header or register reference from SVD files")
         .arg(
             Arg::with_name("input")
                 .help("Input SVD file")
@@ -15,9 +15,17 @@ fn main() -> anyhow::Result<()> {
                 .takes_value(true)
                 .value_name("FILE"),
         )
+        .arg(
+            Arg::with_name("format")
+                .help("Kind of output to generate")
+                .short("f")
+                .takes_value(true)
+                .possible_values(&["rust", "c", "markdown"])
+                .default_value("rust"),
+        )
         .arg(
             Arg::with_name("output")
-                .help("Output .rs file or crate directory")
+                .help("Output file")
                 .short("o")
                 .takes_value(true)
                 .value_name("FILE"),
@@ -35,10 +43,15 @@ fn main() -> anyhow::Result<()> {
 
     let mut dest: Box<dyn Write> = match matches.value_of("output") {
         None => Box::new(std::io::stdout()),
-        Some(path) => Box::new(File::open(path).context("Cannot open destination file")?),
+        Some(path) => Box::new(File::create(path).context("Cannot open destination file")?),
     };
 
-    generate::generate(src, &mut dest).context("Cannot generate output file")?;
+    match matches.value_of("format") {
+        Some("c") => generate::generate_c_header(src, &mut dest),
+        Some("markdown") => generate::generate_markdown(src, &mut dest),
+        _ => generate::generate(src, &mut dest),
+    }
+    .context("Cannot generate output file")?;
 
     Ok(())
 }
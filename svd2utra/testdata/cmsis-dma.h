@@ -0,0 +1,85 @@
+/* Generated by svd2utra.  Do not edit. */
+#ifndef UTRA_GENERATED_H
+#define UTRA_GENERATED_H
+
+#include <stdint.h>
+
+/* Registers are addressed by the base address of their peripheral and
+ * their offset in bytes, such as utra_read(HW_UART_BASE, UTRA_UART_RXTX) */
+static inline uint32_t utra_read(uintptr_t base, uintptr_t offset) {
+    return *(volatile uint32_t *)(base + offset);
+}
+
+static inline void utra_write(uintptr_t base, uintptr_t offset, uint32_t value) {
+    *(volatile uint32_t *)(base + offset) = value;
+}
+
+/* Extract a field from the value of its register, or move a value into
+ * place for a field, such as UTRA_FIELD_GET(UTRA_UART_EV_PENDING_TX, v) */
+#define UTRA_FIELD_GET(field, value) (((value) >> field##_SHIFT) & field##_MASK)
+#define UTRA_FIELD_SET(field, value) (((value) & field##_MASK) << field##_SHIFT)
+
+/* Physical base addresses of memory regions */
+
+/* Physical base addresses of registers */
+#define HW_DMA_BASE 0x40002000u
+
+/* DMA: Two channel DMA controller */
+#define UTRA_DMA_DMA_IRQ 4
+
+/* IRQ_TX: read-only, reset 0x00000000 - Interrupt status for the %s direction */
+#define UTRA_DMA_IRQ_TX 0x0
+#define UTRA_DMA_IRQ_TX_RESET 0x00000000u
+/* DONE: bits [0:0], read-only */
+#define UTRA_DMA_IRQ_TX_DONE_SHIFT 0
+#define UTRA_DMA_IRQ_TX_DONE_WIDTH 1
+#define UTRA_DMA_IRQ_TX_DONE_MASK 0x1u
+/* ERROR: bits [1:1], read-only */
+#define UTRA_DMA_IRQ_TX_ERROR_SHIFT 1
+#define UTRA_DMA_IRQ_TX_ERROR_WIDTH 1
+#define UTRA_DMA_IRQ_TX_ERROR_MASK 0x1u
+
+/* IRQ_RX: read-only, reset 0x00000000 - Interrupt status for the %s direction */
+#define UTRA_DMA_IRQ_RX 0x4
+#define UTRA_DMA_IRQ_RX_RESET 0x00000000u
+/* DONE: bits [0:0], read-only */
+#define UTRA_DMA_IRQ_RX_DONE_SHIFT 0
+#define UTRA_DMA_IRQ_RX_DONE_WIDTH 1
+#define UTRA_DMA_IRQ_RX_DONE_MASK 0x1u
+/* ERROR: bits [1:1], read-only */
+#define UTRA_DMA_IRQ_RX_ERROR_SHIFT 1
+#define UTRA_DMA_IRQ_RX_ERROR_WIDTH 1
+#define UTRA_DMA_IRQ_RX_ERROR_MASK 0x1u
+
+/* CH_CFG: read-write, reset 0x00000010 - Channel configuration */
+#define UTRA_DMA_CH_CFG(n0) (0x10 + (n0) * 0x20)
+#define UTRA_DMA_CH_CFG_RESET 0x00000010u
+/* EN: bits [0:0], read-write */
+#define UTRA_DMA_CH_CFG_EN_SHIFT 0
+#define UTRA_DMA_CH_CFG_EN_WIDTH 1
+#define UTRA_DMA_CH_CFG_EN_MASK 0x1u
+/* BURST: bits [5:4], read-write */
+#define UTRA_DMA_CH_CFG_BURST_SHIFT 4
+#define UTRA_DMA_CH_CFG_BURST_WIDTH 2
+#define UTRA_DMA_CH_CFG_BURST_MASK 0x3u
+#define UTRA_DMA_CH_CFG_BURST_1 0u
+#define UTRA_DMA_CH_CFG_BURST_4 1u
+#define UTRA_DMA_CH_CFG_BURST_16 2u
+
+/* CH_ADDR: read-write, reset 0x00000000 - Source and destination addresses */
+#define UTRA_DMA_CH_ADDR(n0, n1) (0x14 + (n0) * 0x20 + (n1) * 0x4)
+#define UTRA_DMA_CH_ADDR_RESET 0x00000000u
+/* ADDR: bits [31:0], read-write */
+#define UTRA_DMA_CH_ADDR_ADDR_SHIFT 0
+#define UTRA_DMA_CH_ADDR_ADDR_WIDTH 32
+#define UTRA_DMA_CH_ADDR_ADDR_MASK 0xffffffffu
+
+/* CH_STAT_COUNT: read-only, reset 0x00000000 - Words remaining */
+#define UTRA_DMA_CH_STAT_COUNT(n0) (0x20 + (n0) * 0x20)
+#define UTRA_DMA_CH_STAT_COUNT_RESET 0x00000000u
+/* COUNT: bits [15:0], read-only */
+#define UTRA_DMA_CH_STAT_COUNT_COUNT_SHIFT 0
+#define UTRA_DMA_CH_STAT_COUNT_COUNT_WIDTH 16
+#define UTRA_DMA_CH_STAT_COUNT_COUNT_MASK 0xffffu
+
+#endif /* UTRA_GENERATED_H */
@@ -0,0 +1,524 @@
+
+use core::convert::TryInto;
+use core::marker::PhantomData;
+/// Access marker for registers and fields that may only be read
+pub struct RO;
+/// Access marker for registers and fields that may only be written
+pub struct WO;
+/// Access marker for registers and fields that may be read and written
+pub struct RW;
+/// Implemented by access markers that allow reading
+pub trait Readable {}
+/// Implemented by access markers that allow writing
+pub trait Writable {}
+impl Readable for RO {}
+impl Readable for RW {}
+impl Writable for WO {}
+impl Writable for RW {}
+pub struct Register<A = RW> {
+    /// Offset of this register within this CSR
+    offset: usize,
+    /// Value of this register after the peripheral is reset
+    reset: usize,
+    access: PhantomData<A>,
+}
+impl<A> Register<A> {
+    pub const fn new(offset: usize, reset: usize) -> Register<A> {
+        Register {
+            offset,
+            reset,
+            access: PhantomData,
+        }
+    }
+    /// Offset of this register, in words, from the start of the CSR
+    pub const fn offset(&self) -> usize {
+        self.offset
+    }
+    /// Value of this register after the peripheral is reset
+    pub const fn reset_value(&self) -> usize {
+        self.reset
+    }
+}
+impl<A> Clone for Register<A> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<A> Copy for Register<A> {}
+pub struct Field<A = RW> {
+    /// A bitmask we use to AND to the value, unshifted.
+    /// E.g. for a width of `3` bits, this mask would be 0b111.
+    mask: usize,
+    /// Offset of the first bit in this field
+    offset: usize,
+    /// A copy of the register address that this field
+    /// is a member of. Ideally this is optimized out by the
+    /// compiler.
+    register: Register<A>,
+}
+impl<A> Field<A> {
+    /// Define a new CSR field with the given width at a specified
+    /// offset from the start of the register.
+    pub const fn new(width: usize, offset: usize, register: Register<A>) -> Field<A> {
+        // Asserts don't work in const fn yet.
+        // assert!(width != 0, "field width cannot be 0");
+        // assert!((width + offset) < 32, "field with and offset must fit within a 32-bit value");
+        // It would be lovely if we could call `usize::pow()` in a const fn.
+        let mask = match width {
+            0 => 0,
+            1 => 1,
+            2 => 3,
+            3 => 7,
+            4 => 15,
+            5 => 31,
+            6 => 63,
+            7 => 127,
+            8 => 255,
+            9 => 511,
+            10 => 1023,
+            11 => 2047,
+            12 => 4095,
+            13 => 8191,
+            14 => 16383,
+            15 => 32767,
+            16 => 65535,
+            17 => 131071,
+            18 => 262143,
+            19 => 524287,
+            20 => 1048575,
+            21 => 2097151,
+            22 => 4194303,
+            23 => 8388607,
+            24 => 16777215,
+            25 => 33554431,
+            26 => 67108863,
+            27 => 134217727,
+            28 => 268435455,
+            29 => 536870911,
+            30 => 1073741823,
+            31 => 2147483647,
+            32 => 4294967295,
+            _ => 0,
+        };
+        Field {
+            mask,
+            offset,
+            register,
+        }
+    }
+    /// Value of this field after the peripheral is reset
+    pub const fn reset_value(&self) -> usize {
+        (self.register.reset >> self.offset) & self.mask
+    }
+}
+impl<A> Clone for Field<A> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<A> Copy for Field<A> {}
+/// Implemented by the enums generated for fields with enumerated values.
+pub trait FieldValue: Sized {
+    type Access;
+    /// The field these are the values of
+    const FIELD: Field<Self::Access>;
+    /// Convert the contents of the field, returning `None` if the value
+    /// isn't one of those listed.
+    fn from_bits(bits: usize) -> Option<Self>;
+    fn bits(self) -> usize;
+}
+pub struct CSR<T> {
+    base: *mut T,
+}
+impl<T> CSR<T>
+where
+    T: core::convert::TryFrom<usize> + core::convert::TryInto<usize> + core::default::Default,
+{
+    pub fn new(base: *mut T) -> Self {
+        CSR { base }
+    }
+    /// Read the word `offset` words from the base of this CSR
+    #[cfg(not(feature = "mock"))]
+    fn read_word(&self, offset: usize) -> usize {
+        let usize_base: *mut usize = unsafe { core::mem::transmute(self.base) };
+        unsafe { usize_base.add(offset).read_volatile() }
+    }
+    /// Write the word `offset` words from the base of this CSR
+    #[cfg(not(feature = "mock"))]
+    fn write_word(&mut self, offset: usize, value: usize) {
+        let usize_base: *mut usize = unsafe { core::mem::transmute(self.base) };
+        unsafe { usize_base.add(offset).write_volatile(value) };
+    }
+    #[cfg(feature = "mock")]
+    fn read_word(&self, offset: usize) -> usize {
+        mock::read(self.base as usize, offset)
+    }
+    #[cfg(feature = "mock")]
+    fn write_word(&mut self, offset: usize, value: usize) {
+        mock::write(self.base as usize, offset, value)
+    }
+    /// Read the contents of this register
+    pub fn r<A: Readable>(&self, reg: Register<A>) -> T {
+        self.read_word(reg.offset).try_into().unwrap_or_default()
+    }
+    /// Read a field from this CSR
+    pub fn rf<A: Readable>(&self, field: Field<A>) -> T {
+        ((self.read_word(field.register.offset) >> field.offset) & field.mask)
+            .try_into()
+            .unwrap_or_default()
+    }
+    /// Read-modify-write a given field in this CSR
+    pub fn rmwf<A: Readable + Writable>(&mut self, field: Field<A>, value: T) {
+        let value_as_usize: usize = value.try_into().unwrap_or_default() << field.offset;
+        let previous = self.read_word(field.register.offset) & !field.mask;
+        self.write_word(field.register.offset, previous | value_as_usize);
+    }
+    /// Write a given field without reading it first
+    pub fn wfo<A: Writable>(&mut self, field: Field<A>, value: T) {
+        let value_as_usize: usize = (value.try_into().unwrap_or_default() & field.mask) << field.offset;
+        self.write_word(field.register.offset, value_as_usize);
+    }
+    /// Write the entire contents of a register without reading it first
+    pub fn wo<A: Writable>(&mut self, reg: Register<A>, value: T) {
+        let value_as_usize: usize = value.try_into().unwrap_or_default();
+        self.write_word(reg.offset, value_as_usize);
+    }
+    /// Zero a field from a provided value
+    pub fn zf<A>(&mut self, field: Field<A>, value: T) -> T {
+        let value_as_usize: usize = value.try_into().unwrap_or_default();
+        (value_as_usize & !(field.mask << field.offset))
+            .try_into()
+            .unwrap_or_default()
+    }
+    /// Shift & mask a value to its final field position
+    pub fn ms<A>(&mut self, field: Field<A>, value: T) -> T {
+        let value_as_usize: usize = value.try_into().unwrap_or_default();
+        ((value_as_usize & field.mask) << field.offset)
+            .try_into()
+            .unwrap_or_default()
+    }
+    /// Write the value a register has after the peripheral is reset
+    pub fn reset<A: Writable>(&mut self, reg: Register<A>) {
+        self.write_word(reg.offset, reg.reset);
+    }
+    /// Read a field with enumerated values, returning `None` if it holds
+    /// a value that isn't listed
+    pub fn rfv<E: FieldValue>(&self) -> Option<E>
+    where
+        E::Access: Readable,
+    {
+        let field = E::FIELD;
+        E::from_bits((self.read_word(field.register.offset) >> field.offset) & field.mask)
+    }
+    /// Write a field with enumerated values without reading it first
+    pub fn wfov<E: FieldValue>(&mut self, value: E)
+    where
+        E::Access: Writable,
+    {
+        let field = E::FIELD;
+        let value_as_usize = (value.bits() & field.mask) << field.offset;
+        self.write_word(field.register.offset, value_as_usize);
+    }
+    /// Read-modify-write a field with enumerated values
+    pub fn rmwfv<E: FieldValue>(&mut self, value: E)
+    where
+        E::Access: Readable + Writable,
+    {
+        let field = E::FIELD;
+        let value_as_usize = (value.bits() & field.mask) << field.offset;
+        let previous = self.read_word(field.register.offset) & !(field.mask << field.offset);
+        self.write_word(field.register.offset, previous | value_as_usize);
+    }
+}
+// Physical base addresses of memory regions
+
+// Physical base addresses of registers
+pub const HW_DMA_BASE :   usize = 0x40002000;
+
+pub mod utra {
+
+    /// Two channel DMA controller
+    pub mod dma {
+
+        /// Interrupt status for the %s direction
+        pub const IRQ_TX: crate::Register<crate::RO> = crate::Register::new(0, 0x0);
+        pub const IRQ_TX_DONE: crate::Field<crate::RO> = crate::Field::new(1, 0, IRQ_TX);
+        pub const IRQ_TX_ERROR: crate::Field<crate::RO> = crate::Field::new(1, 1, IRQ_TX);
+
+        /// Interrupt status for the %s direction
+        pub const IRQ_RX: crate::Register<crate::RO> = crate::Register::new(1, 0x0);
+        pub const IRQ_RX_DONE: crate::Field<crate::RO> = crate::Field::new(1, 0, IRQ_RX);
+        pub const IRQ_RX_ERROR: crate::Field<crate::RO> = crate::Field::new(1, 1, IRQ_RX);
+
+        /// Channel configuration
+        pub const CH_CFG: [crate::Register<crate::RW>; 2] = [crate::Register::new(4, 0x10), crate::Register::new(12, 0x10)];
+        pub const CH_CFG_EN: [crate::Field<crate::RW>; 2] = [crate::Field::new(1, 0, crate::Register::new(4, 0x10)), crate::Field::new(1, 0, crate::Register::new(12, 0x10))];
+        pub const CH_CFG_BURST: [crate::Field<crate::RW>; 2] = [crate::Field::new(2, 4, crate::Register::new(4, 0x10)), crate::Field::new(2, 4, crate::Register::new(12, 0x10))];
+        /// Values of `CH_CFG_BURST`
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum ChCfgBurst {
+            V1 = 0,
+            V4 = 1,
+            V16 = 2,
+        }
+        impl ChCfgBurst {
+            pub fn from_bits(bits: usize) -> Option<Self> {
+                match bits {
+                    0 => Some(ChCfgBurst::V1),
+                    1 => Some(ChCfgBurst::V4),
+                    2 => Some(ChCfgBurst::V16),
+                    _ => None,
+                }
+            }
+            pub fn bits(self) -> usize {
+                self as usize
+            }
+        }
+        /// A value of one element of `CH_CFG_BURST`, for use with `rfv()`, `wfov()` and `rmwfv()`
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub struct ChCfgBurstAt<const N: usize>(pub ChCfgBurst);
+        impl<const N: usize> crate::FieldValue for ChCfgBurstAt<N> {
+            type Access = crate::RW;
+            const FIELD: crate::Field<crate::RW> = CH_CFG_BURST[N];
+            fn from_bits(bits: usize) -> Option<Self> {
+                ChCfgBurst::from_bits(bits).map(Self)
+            }
+            fn bits(self) -> usize {
+                self.0.bits()
+            }
+        }
+
+        /// Source and destination addresses
+        pub const CH_ADDR: [[crate::Register<crate::RW>; 2]; 2] = [[crate::Register::new(5, 0x0), crate::Register::new(6, 0x0)], [crate::Register::new(13, 0x0), crate::Register::new(14, 0x0)]];
+        pub const CH_ADDR_ADDR: [[crate::Field<crate::RW>; 2]; 2] = [[crate::Field::new(32, 0, crate::Register::new(5, 0x0)), crate::Field::new(32, 0, crate::Register::new(6, 0x0))], [crate::Field::new(32, 0, crate::Register::new(13, 0x0)), crate::Field::new(32, 0, crate::Register::new(14, 0x0))]];
+
+        /// Words remaining
+        pub const CH_STAT_COUNT: [crate::Register<crate::RO>; 2] = [crate::Register::new(8, 0x0), crate::Register::new(16, 0x0)];
+        pub const CH_STAT_COUNT_COUNT: [crate::Field<crate::RO>; 2] = [crate::Field::new(16, 0, crate::Register::new(8, 0x0)), crate::Field::new(16, 0, crate::Register::new(16, 0x0))];
+
+        pub const DMA_IRQ: usize = 4;
+        pub const HW_DMA_BASE: usize = 0x40002000;
+    }
+}
+
+/// In-memory stand-ins for peripherals, so that drivers can be tested on a
+/// host.  While a `mock::Peripheral` exists, a `CSR` created on the same
+/// thread with its base address reads and writes the mock rather than
+/// hardware.  Hooks may be attached to registers to model their side
+/// effects, such as sending a byte when `RXTX` is written.
+#[cfg(feature = "mock")]
+pub mod mock {
+    use super::{Field, Readable, Register, Writable};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// The contents of a mocked peripheral's registers.  These may be
+    /// changed regardless of the registers' access, to model hardware.
+    pub struct Registers {
+        words: Vec<usize>,
+    }
+    impl Registers {
+        pub fn r<A>(&self, reg: Register<A>) -> usize {
+            self.words[reg.offset]
+        }
+        pub fn wo<A>(&mut self, reg: Register<A>, value: usize) {
+            self.words[reg.offset] = value;
+        }
+        pub fn rf<A>(&self, field: Field<A>) -> usize {
+            (self.words[field.register.offset] >> field.offset) & field.mask
+        }
+        /// Replace the contents of a field, leaving the rest of the
+        /// register unchanged
+        pub fn wf<A>(&mut self, field: Field<A>, value: usize) {
+            let word = &mut self.words[field.register.offset];
+            *word = (*word & !(field.mask << field.offset)) | ((value & field.mask) << field.offset);
+        }
+    }
+
+    type ReadHook = Box<dyn FnMut(&mut Registers) -> usize>;
+    type WriteHook = Box<dyn FnMut(&mut Registers, usize)>;
+
+    struct State {
+        base: usize,
+        registers: Registers,
+        read_hooks: Vec<(usize, ReadHook)>,
+        write_hooks: Vec<(usize, WriteHook)>,
+    }
+
+    thread_local! {
+        static PERIPHERALS: RefCell<Vec<Rc<RefCell<State>>>> = RefCell::new(Vec::new());
+    }
+
+    pub struct Peripheral {
+        state: Rc<RefCell<State>>,
+    }
+    impl Peripheral {
+        /// Create a mock of `words` registers at `base`, holding zero apart
+        /// from the `(offset, value)` pairs in `reset`.
+        pub fn new(base: usize, words: usize, reset: &[(usize, usize)]) -> Peripheral {
+            let mut registers = Registers {
+                words: vec![0; words],
+            };
+            for &(offset, value) in reset {
+                registers.words[offset] = value;
+            }
+            let state = Rc::new(RefCell::new(State {
+                base,
+                registers,
+                read_hooks: Vec::new(),
+                write_hooks: Vec::new(),
+            }));
+            PERIPHERALS.with(|peripherals| {
+                let mut peripherals = peripherals.borrow_mut();
+                if peripherals.iter().any(|other| other.borrow().base == base) {
+                    panic!("a mock peripheral already exists at {:08x}", base);
+                }
+                peripherals.push(state.clone());
+            });
+            Peripheral { state }
+        }
+        pub fn base(&self) -> usize {
+            self.state.borrow().base
+        }
+        pub fn r<A>(&self, reg: Register<A>) -> usize {
+            self.state.borrow().registers.r(reg)
+        }
+        pub fn wo<A>(&self, reg: Register<A>, value: usize) {
+            self.state.borrow_mut().registers.wo(reg, value)
+        }
+        pub fn rf<A>(&self, field: Field<A>) -> usize {
+            self.state.borrow().registers.rf(field)
+        }
+        pub fn wf<A>(&self, field: Field<A>, value: usize) {
+            self.state.borrow_mut().registers.wf(field, value)
+        }
+        /// Produce the value read from `reg` with `hook` rather than
+        /// returning its contents.  Hooks must not use a `CSR` for the
+        /// same peripheral.
+        pub fn on_read<A: Readable, F: FnMut(&mut Registers) -> usize + 'static>(
+            &self,
+            reg: Register<A>,
+            hook: F,
+        ) {
+            let mut state = self.state.borrow_mut();
+            state.read_hooks.retain(|(offset, _)| *offset != reg.offset);
+            state.read_hooks.push((reg.offset, Box::new(hook)));
+        }
+        /// Pass values written to `reg` to `hook` rather than storing
+        /// them.  The hook may store them itself.
+        pub fn on_write<A: Writable, F: FnMut(&mut Registers, usize) + 'static>(
+            &self,
+            reg: Register<A>,
+            hook: F,
+        ) {
+            let mut state = self.state.borrow_mut();
+            state.write_hooks.retain(|(offset, _)| *offset != reg.offset);
+            state.write_hooks.push((reg.offset, Box::new(hook)));
+        }
+    }
+    impl Drop for Peripheral {
+        fn drop(&mut self) {
+            PERIPHERALS.with(|peripherals| {
+                peripherals
+                    .borrow_mut()
+                    .retain(|other| !Rc::ptr_eq(other, &self.state))
+            });
+        }
+    }
+
+    fn find(base: usize) -> Rc<RefCell<State>> {
+        PERIPHERALS.with(|peripherals| {
+            peripherals
+                .borrow()
+                .iter()
+                .find(|state| state.borrow().base == base)
+                .cloned()
+                .unwrap_or_else(|| panic!("no mock peripheral at {:08x}", base))
+        })
+    }
+
+    pub(crate) fn read(base: usize, offset: usize) -> usize {
+        let state = find(base);
+        let mut state = state.borrow_mut();
+        let State {
+            registers,
+            read_hooks,
+            ..
+        } = &mut *state;
+        match read_hooks.iter_mut().find(|(hooked, _)| *hooked == offset) {
+            Some((_, hook)) => hook(registers),
+            None => registers.words[offset],
+        }
+    }
+
+    pub(crate) fn write(base: usize, offset: usize, value: usize) {
+        let state = find(base);
+        let mut state = state.borrow_mut();
+        let State {
+            registers,
+            write_hooks,
+            ..
+        } = &mut *state;
+        match write_hooks.iter_mut().find(|(hooked, _)| *hooked == offset) {
+            Some((_, hook)) => hook(registers, value),
+            None => registers.words[offset] = value,
+        }
+    }
+
+    /// A mock of `dma` at `HW_DMA_BASE`
+    pub fn dma() -> Peripheral {
+        Peripheral::new(super::HW_DMA_BASE, 64, &[(4, 0x10), (12, 0x10)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    #[ignore]
+    fn compile_check() {
+        use super::*;
+        let mut dma_csr = CSR::new(HW_DMA_BASE as *mut u32);
+
+        let _ = dma_csr.r(utra::dma::IRQ_TX);
+        let bar = dma_csr.rf(utra::dma::IRQ_TX_DONE);
+        let _ = dma_csr.zf(utra::dma::IRQ_TX_DONE, bar) | dma_csr.ms(utra::dma::IRQ_TX_DONE, 1);
+        let bar = dma_csr.rf(utra::dma::IRQ_TX_ERROR);
+        let _ = dma_csr.zf(utra::dma::IRQ_TX_ERROR, bar) | dma_csr.ms(utra::dma::IRQ_TX_ERROR, 1);
+
+        let _ = dma_csr.r(utra::dma::IRQ_RX);
+        let bar = dma_csr.rf(utra::dma::IRQ_RX_DONE);
+        let _ = dma_csr.zf(utra::dma::IRQ_RX_DONE, bar) | dma_csr.ms(utra::dma::IRQ_RX_DONE, 1);
+        let bar = dma_csr.rf(utra::dma::IRQ_RX_ERROR);
+        let _ = dma_csr.zf(utra::dma::IRQ_RX_ERROR, bar) | dma_csr.ms(utra::dma::IRQ_RX_ERROR, 1);
+
+        let foo = dma_csr.r(utra::dma::CH_CFG[0]);
+        dma_csr.wo(utra::dma::CH_CFG[0], foo);
+        dma_csr.reset(utra::dma::CH_CFG[0]);
+        let bar = dma_csr.rf(utra::dma::CH_CFG_EN[0]);
+        dma_csr.rmwf(utra::dma::CH_CFG_EN[0], bar);
+        let mut baz = dma_csr.zf(utra::dma::CH_CFG_EN[0], bar);
+        baz |= dma_csr.ms(utra::dma::CH_CFG_EN[0], 1);
+        dma_csr.wfo(utra::dma::CH_CFG_EN[0], baz);
+        let bar = dma_csr.rf(utra::dma::CH_CFG_BURST[0]);
+        dma_csr.rmwf(utra::dma::CH_CFG_BURST[0], bar);
+        let mut baz = dma_csr.zf(utra::dma::CH_CFG_BURST[0], bar);
+        baz |= dma_csr.ms(utra::dma::CH_CFG_BURST[0], 1);
+        dma_csr.wfo(utra::dma::CH_CFG_BURST[0], baz);
+        let _: Option<utra::dma::ChCfgBurstAt::<0>> = dma_csr.rfv();
+        dma_csr.wfov(utra::dma::ChCfgBurstAt::<0>(utra::dma::ChCfgBurst::V1));
+        dma_csr.rmwfv(utra::dma::ChCfgBurstAt::<0>(utra::dma::ChCfgBurst::V1));
+
+        let foo = dma_csr.r(utra::dma::CH_ADDR[0][0]);
+        dma_csr.wo(utra::dma::CH_ADDR[0][0], foo);
+        dma_csr.reset(utra::dma::CH_ADDR[0][0]);
+        let bar = dma_csr.rf(utra::dma::CH_ADDR_ADDR[0][0]);
+        dma_csr.rmwf(utra::dma::CH_ADDR_ADDR[0][0], bar);
+        let mut baz = dma_csr.zf(utra::dma::CH_ADDR_ADDR[0][0], bar);
+        baz |= dma_csr.ms(utra::dma::CH_ADDR_ADDR[0][0], 1);
+        dma_csr.wfo(utra::dma::CH_ADDR_ADDR[0][0], baz);
+
+        let _ = dma_csr.r(utra::dma::CH_STAT_COUNT[0]);
+        let bar = dma_csr.rf(utra::dma::CH_STAT_COUNT_COUNT[0]);
+        let _ = dma_csr.zf(utra::dma::CH_STAT_COUNT_COUNT[0], bar) | dma_csr.ms(utra::dma::CH_STAT_COUNT_COUNT[0], 1);
+    }
+}
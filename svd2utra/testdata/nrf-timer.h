@@ -0,0 +1,220 @@
+/* Generated by svd2utra.  Do not edit. */
+#ifndef UTRA_GENERATED_H
+#define UTRA_GENERATED_H
+
+#include <stdint.h>
+
+/* Registers are addressed by the base address of their peripheral and
+ * their offset in bytes, such as utra_read(HW_UART_BASE, UTRA_UART_RXTX) */
+static inline uint32_t utra_read(uintptr_t base, uintptr_t offset) {
+    return *(volatile uint32_t *)(base + offset);
+}
+
+static inline void utra_write(uintptr_t base, uintptr_t offset, uint32_t value) {
+    *(volatile uint32_t *)(base + offset) = value;
+}
+
+/* Extract a field from the value of its register, or move a value into
+ * place for a field, such as UTRA_FIELD_GET(UTRA_UART_EV_PENDING_TX, v) */
+#define UTRA_FIELD_GET(field, value) (((value) >> field##_SHIFT) & field##_MASK)
+#define UTRA_FIELD_SET(field, value) (((value) & field##_MASK) << field##_SHIFT)
+
+/* Physical base addresses of memory regions */
+
+/* Physical base addresses of registers */
+#define HW_TIMER0_BASE 0x40008000u
+#define HW_TIMER1_BASE 0x40009000u
+#define HW_TIMER2_BASE 0x4000a000u
+#define HW_PPI_BASE 0x4001f000u
+
+/* TIMER0: Timer/Counter 0 */
+#define UTRA_TIMER0_TIMER0_IRQ 8
+
+/* TASKS_START: write-only, reset 0x00000000 - Start Timer */
+#define UTRA_TIMER0_TASKS_START 0x0
+#define UTRA_TIMER0_TASKS_START_RESET 0x00000000u
+/* TASKS_START: bits [0:0], write-only */
+#define UTRA_TIMER0_TASKS_START_TASKS_START_SHIFT 0
+#define UTRA_TIMER0_TASKS_START_TASKS_START_WIDTH 1
+#define UTRA_TIMER0_TASKS_START_TASKS_START_MASK 0x1u
+
+/* TASKS_CAPTURE: write-only, reset 0x00000000 - Description collection: Capture Timer value to CC[n] register */
+#define UTRA_TIMER0_TASKS_CAPTURE(n0) (0x40 + (n0) * 0x4)
+#define UTRA_TIMER0_TASKS_CAPTURE_RESET 0x00000000u
+/* TASKS_CAPTURE: bits [0:0], write-only */
+#define UTRA_TIMER0_TASKS_CAPTURE_TASKS_CAPTURE_SHIFT 0
+#define UTRA_TIMER0_TASKS_CAPTURE_TASKS_CAPTURE_WIDTH 1
+#define UTRA_TIMER0_TASKS_CAPTURE_TASKS_CAPTURE_MASK 0x1u
+
+/* EVENTS_COMPARE: read-write, reset 0x00000000 - Description collection: Compare event on CC[n] match */
+#define UTRA_TIMER0_EVENTS_COMPARE(n0) (0x140 + (n0) * 0x4)
+#define UTRA_TIMER0_EVENTS_COMPARE_RESET 0x00000000u
+/* EVENTS_COMPARE: bits [0:0], read-write */
+#define UTRA_TIMER0_EVENTS_COMPARE_EVENTS_COMPARE_SHIFT 0
+#define UTRA_TIMER0_EVENTS_COMPARE_EVENTS_COMPARE_WIDTH 1
+#define UTRA_TIMER0_EVENTS_COMPARE_EVENTS_COMPARE_MASK 0x1u
+#define UTRA_TIMER0_EVENTS_COMPARE_EVENTS_COMPARE_NOTGENERATED 0u
+#define UTRA_TIMER0_EVENTS_COMPARE_EVENTS_COMPARE_GENERATED 1u
+
+/* MODE: read-write, reset 0x00000000 - Timer mode selection */
+#define UTRA_TIMER0_MODE 0x504
+#define UTRA_TIMER0_MODE_RESET 0x00000000u
+/* MODE: bits [1:0], read-write - Timer mode */
+#define UTRA_TIMER0_MODE_MODE_SHIFT 0
+#define UTRA_TIMER0_MODE_MODE_WIDTH 2
+#define UTRA_TIMER0_MODE_MODE_MASK 0x3u
+#define UTRA_TIMER0_MODE_MODE_TIMER 0u
+#define UTRA_TIMER0_MODE_MODE_COUNTER 1u
+#define UTRA_TIMER0_MODE_MODE_LOWPOWERCOUNTER 2u
+
+/* CC: read-write, reset 0x00000000 - Description collection: Capture/Compare register n */
+#define UTRA_TIMER0_CC(n0) (0x540 + (n0) * 0x4)
+#define UTRA_TIMER0_CC_RESET 0x00000000u
+/* CC: bits [31:0], read-write - Capture/Compare value */
+#define UTRA_TIMER0_CC_CC_SHIFT 0
+#define UTRA_TIMER0_CC_CC_WIDTH 32
+#define UTRA_TIMER0_CC_CC_MASK 0xffffffffu
+
+/* TIMER1: Timer/Counter 1 */
+#define UTRA_TIMER1_TIMER1_IRQ 9
+
+/* TASKS_START: write-only, reset 0x00000000 - Start Timer */
+#define UTRA_TIMER1_TASKS_START 0x0
+#define UTRA_TIMER1_TASKS_START_RESET 0x00000000u
+/* TASKS_START: bits [0:0], write-only */
+#define UTRA_TIMER1_TASKS_START_TASKS_START_SHIFT 0
+#define UTRA_TIMER1_TASKS_START_TASKS_START_WIDTH 1
+#define UTRA_TIMER1_TASKS_START_TASKS_START_MASK 0x1u
+
+/* TASKS_CAPTURE: write-only, reset 0x00000000 - Description collection: Capture Timer value to CC[n] register */
+#define UTRA_TIMER1_TASKS_CAPTURE(n0) (0x40 + (n0) * 0x4)
+#define UTRA_TIMER1_TASKS_CAPTURE_RESET 0x00000000u
+/* TASKS_CAPTURE: bits [0:0], write-only */
+#define UTRA_TIMER1_TASKS_CAPTURE_TASKS_CAPTURE_SHIFT 0
+#define UTRA_TIMER1_TASKS_CAPTURE_TASKS_CAPTURE_WIDTH 1
+#define UTRA_TIMER1_TASKS_CAPTURE_TASKS_CAPTURE_MASK 0x1u
+
+/* EVENTS_COMPARE: read-write, reset 0x00000000 - Description collection: Compare event on CC[n] match */
+#define UTRA_TIMER1_EVENTS_COMPARE(n0) (0x140 + (n0) * 0x4)
+#define UTRA_TIMER1_EVENTS_COMPARE_RESET 0x00000000u
+/* EVENTS_COMPARE: bits [0:0], read-write */
+#define UTRA_TIMER1_EVENTS_COMPARE_EVENTS_COMPARE_SHIFT 0
+#define UTRA_TIMER1_EVENTS_COMPARE_EVENTS_COMPARE_WIDTH 1
+#define UTRA_TIMER1_EVENTS_COMPARE_EVENTS_COMPARE_MASK 0x1u
+#define UTRA_TIMER1_EVENTS_COMPARE_EVENTS_COMPARE_NOTGENERATED 0u
+#define UTRA_TIMER1_EVENTS_COMPARE_EVENTS_COMPARE_GENERATED 1u
+
+/* MODE: read-write, reset 0x00000000 - Timer mode selection */
+#define UTRA_TIMER1_MODE 0x504
+#define UTRA_TIMER1_MODE_RESET 0x00000000u
+/* MODE: bits [1:0], read-write - Timer mode */
+#define UTRA_TIMER1_MODE_MODE_SHIFT 0
+#define UTRA_TIMER1_MODE_MODE_WIDTH 2
+#define UTRA_TIMER1_MODE_MODE_MASK 0x3u
+#define UTRA_TIMER1_MODE_MODE_TIMER 0u
+#define UTRA_TIMER1_MODE_MODE_COUNTER 1u
+#define UTRA_TIMER1_MODE_MODE_LOWPOWERCOUNTER 2u
+
+/* CC: read-write, reset 0x00000000 - Description collection: Capture/Compare register n */
+#define UTRA_TIMER1_CC(n0) (0x540 + (n0) * 0x4)
+#define UTRA_TIMER1_CC_RESET 0x00000000u
+/* CC: bits [31:0], read-write - Capture/Compare value */
+#define UTRA_TIMER1_CC_CC_SHIFT 0
+#define UTRA_TIMER1_CC_CC_WIDTH 32
+#define UTRA_TIMER1_CC_CC_MASK 0xffffffffu
+
+/* TIMER2: Timer/Counter 2 */
+#define UTRA_TIMER2_TIMER2_IRQ 10
+
+/* TASKS_START: write-only, reset 0x00000000 - Start Timer */
+#define UTRA_TIMER2_TASKS_START 0x0
+#define UTRA_TIMER2_TASKS_START_RESET 0x00000000u
+/* TASKS_START: bits [0:0], write-only */
+#define UTRA_TIMER2_TASKS_START_TASKS_START_SHIFT 0
+#define UTRA_TIMER2_TASKS_START_TASKS_START_WIDTH 1
+#define UTRA_TIMER2_TASKS_START_TASKS_START_MASK 0x1u
+
+/* TASKS_CAPTURE: write-only, reset 0x00000000 - Description collection: Capture Timer value to CC[n] register */
+#define UTRA_TIMER2_TASKS_CAPTURE(n0) (0x40 + (n0) * 0x4)
+#define UTRA_TIMER2_TASKS_CAPTURE_RESET 0x00000000u
+/* TASKS_CAPTURE: bits [0:0], write-only */
+#define UTRA_TIMER2_TASKS_CAPTURE_TASKS_CAPTURE_SHIFT 0
+#define UTRA_TIMER2_TASKS_CAPTURE_TASKS_CAPTURE_WIDTH 1
+#define UTRA_TIMER2_TASKS_CAPTURE_TASKS_CAPTURE_MASK 0x1u
+
+/* EVENTS_COMPARE: read-write, reset 0x00000000 - Description collection: Compare event on CC[n] match */
+#define UTRA_TIMER2_EVENTS_COMPARE(n0) (0x140 + (n0) * 0x4)
+#define UTRA_TIMER2_EVENTS_COMPARE_RESET 0x00000000u
+/* EVENTS_COMPARE: bits [0:0], read-write */
+#define UTRA_TIMER2_EVENTS_COMPARE_EVENTS_COMPARE_SHIFT 0
+#define UTRA_TIMER2_EVENTS_COMPARE_EVENTS_COMPARE_WIDTH 1
+#define UTRA_TIMER2_EVENTS_COMPARE_EVENTS_COMPARE_MASK 0x1u
+#define UTRA_TIMER2_EVENTS_COMPARE_EVENTS_COMPARE_NOTGENERATED 0u
+#define UTRA_TIMER2_EVENTS_COMPARE_EVENTS_COMPARE_GENERATED 1u
+
+/* MODE: read-write, reset 0x00000000 - Timer mode selection */
+#define UTRA_TIMER2_MODE 0x504
+#define UTRA_TIMER2_MODE_RESET 0x00000000u
+/* MODE: bits [1:0], read-write - Timer mode */
+#define UTRA_TIMER2_MODE_MODE_SHIFT 0
+#define UTRA_TIMER2_MODE_MODE_WIDTH 2
+#define UTRA_TIMER2_MODE_MODE_MASK 0x3u
+#define UTRA_TIMER2_MODE_MODE_TIMER 0u
+#define UTRA_TIMER2_MODE_MODE_COUNTER 1u
+#define UTRA_TIMER2_MODE_MODE_LOWPOWERCOUNTER 2u
+
+/* CC: read-write, reset 0x00000000 - Description collection: Capture/Compare register n */
+#define UTRA_TIMER2_CC(n0) (0x540 + (n0) * 0x4)
+#define UTRA_TIMER2_CC_RESET 0x00000000u
+/* CC: bits [31:0], read-write - Capture/Compare value */
+#define UTRA_TIMER2_CC_CC_SHIFT 0
+#define UTRA_TIMER2_CC_CC_WIDTH 32
+#define UTRA_TIMER2_CC_CC_MASK 0xffffffffu
+
+/* PPI: Programmable Peripheral Interconnect */
+
+/* TASKS_CHG_EN: write-only, reset 0x00000000 - Description cluster: Enable channel group n */
+#define UTRA_PPI_TASKS_CHG_EN(n0) (0x0 + (n0) * 0x8)
+#define UTRA_PPI_TASKS_CHG_EN_RESET 0x00000000u
+/* EN: bits [0:0], write-only */
+#define UTRA_PPI_TASKS_CHG_EN_EN_SHIFT 0
+#define UTRA_PPI_TASKS_CHG_EN_EN_WIDTH 1
+#define UTRA_PPI_TASKS_CHG_EN_EN_MASK 0x1u
+
+/* TASKS_CHG_DIS: write-only, reset 0x00000000 - Description cluster: Disable channel group n */
+#define UTRA_PPI_TASKS_CHG_DIS(n0) (0x4 + (n0) * 0x8)
+#define UTRA_PPI_TASKS_CHG_DIS_RESET 0x00000000u
+/* DIS: bits [0:0], write-only */
+#define UTRA_PPI_TASKS_CHG_DIS_DIS_SHIFT 0
+#define UTRA_PPI_TASKS_CHG_DIS_DIS_WIDTH 1
+#define UTRA_PPI_TASKS_CHG_DIS_DIS_MASK 0x1u
+
+/* CHEN: read-write, reset 0x00000000 - Channel enable register */
+#define UTRA_PPI_CHEN 0x500
+#define UTRA_PPI_CHEN_RESET 0x00000000u
+/* CH0: bits [0:0], read-write */
+#define UTRA_PPI_CHEN_CH0_SHIFT 0
+#define UTRA_PPI_CHEN_CH0_WIDTH 1
+#define UTRA_PPI_CHEN_CH0_MASK 0x1u
+/* CH1: bits [1:1], read-write */
+#define UTRA_PPI_CHEN_CH1_SHIFT 1
+#define UTRA_PPI_CHEN_CH1_WIDTH 1
+#define UTRA_PPI_CHEN_CH1_MASK 0x1u
+
+/* CH_EEP: read-write, reset 0x00000000 - Description cluster: Channel n event endpoint */
+#define UTRA_PPI_CH_EEP(n0) (0x510 + (n0) * 0x8)
+#define UTRA_PPI_CH_EEP_RESET 0x00000000u
+/* EEP: bits [31:0], read-write */
+#define UTRA_PPI_CH_EEP_EEP_SHIFT 0
+#define UTRA_PPI_CH_EEP_EEP_WIDTH 32
+#define UTRA_PPI_CH_EEP_EEP_MASK 0xffffffffu
+
+/* CH_TEP: read-write, reset 0x00000000 - Description cluster: Channel n task endpoint */
+#define UTRA_PPI_CH_TEP(n0) (0x514 + (n0) * 0x8)
+#define UTRA_PPI_CH_TEP_RESET 0x00000000u
+/* TEP: bits [31:0], read-write */
+#define UTRA_PPI_CH_TEP_TEP_SHIFT 0
+#define UTRA_PPI_CH_TEP_TEP_WIDTH 32
+#define UTRA_PPI_CH_TEP_TEP_MASK 0xffffffffu
+
+#endif /* UTRA_GENERATED_H */
@@ -0,0 +1,907 @@
+
+use core::convert::TryInto;
+use core::marker::PhantomData;
+/// Access marker for registers and fields that may only be read
+pub struct RO;
+/// Access marker for registers and fields that may only be written
+pub struct WO;
+/// Access marker for registers and fields that may be read and written
+pub struct RW;
+/// Implemented by access markers that allow reading
+pub trait Readable {}
+/// Implemented by access markers that allow writing
+pub trait Writable {}
+impl Readable for RO {}
+impl Readable for RW {}
+impl Writable for WO {}
+impl Writable for RW {}
+pub struct Register<A = RW> {
+    /// Offset of this register within this CSR
+    offset: usize,
+    /// Value of this register after the peripheral is reset
+    reset: usize,
+    access: PhantomData<A>,
+}
+impl<A> Register<A> {
+    pub const fn new(offset: usize, reset: usize) -> Register<A> {
+        Register {
+            offset,
+            reset,
+            access: PhantomData,
+        }
+    }
+    /// Offset of this register, in words, from the start of the CSR
+    pub const fn offset(&self) -> usize {
+        self.offset
+    }
+    /// Value of this register after the peripheral is reset
+    pub const fn reset_value(&self) -> usize {
+        self.reset
+    }
+}
+impl<A> Clone for Register<A> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<A> Copy for Register<A> {}
+pub struct Field<A = RW> {
+    /// A bitmask we use to AND to the value, unshifted.
+    /// E.g. for a width of `3` bits, this mask would be 0b111.
+    mask: usize,
+    /// Offset of the first bit in this field
+    offset: usize,
+    /// A copy of the register address that this field
+    /// is a member of. Ideally this is optimized out by the
+    /// compiler.
+    register: Register<A>,
+}
+impl<A> Field<A> {
+    /// Define a new CSR field with the given width at a specified
+    /// offset from the start of the register.
+    pub const fn new(width: usize, offset: usize, register: Register<A>) -> Field<A> {
+        // Asserts don't work in const fn yet.
+        // assert!(width != 0, "field width cannot be 0");
+        // assert!((width + offset) < 32, "field with and offset must fit within a 32-bit value");
+        // It would be lovely if we could call `usize::pow()` in a const fn.
+        let mask = match width {
+            0 => 0,
+            1 => 1,
+            2 => 3,
+            3 => 7,
+            4 => 15,
+            5 => 31,
+            6 => 63,
+            7 => 127,
+            8 => 255,
+            9 => 511,
+            10 => 1023,
+            11 => 2047,
+            12 => 4095,
+            13 => 8191,
+            14 => 16383,
+            15 => 32767,
+            16 => 65535,
+            17 => 131071,
+            18 => 262143,
+            19 => 524287,
+            20 => 1048575,
+            21 => 2097151,
+            22 => 4194303,
+            23 => 8388607,
+            24 => 16777215,
+            25 => 33554431,
+            26 => 67108863,
+            27 => 134217727,
+            28 => 268435455,
+            29 => 536870911,
+            30 => 1073741823,
+            31 => 2147483647,
+            32 => 4294967295,
+            _ => 0,
+        };
+        Field {
+            mask,
+            offset,
+            register,
+        }
+    }
+    /// Value of this field after the peripheral is reset
+    pub const fn reset_value(&self) -> usize {
+        (self.register.reset >> self.offset) & self.mask
+    }
+}
+impl<A> Clone for Field<A> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<A> Copy for Field<A> {}
+/// Implemented by the enums generated for fields with enumerated values.
+pub trait FieldValue: Sized {
+    type Access;
+    /// The field these are the values of
+    const FIELD: Field<Self::Access>;
+    /// Convert the contents of the field, returning `None` if the value
+    /// isn't one of those listed.
+    fn from_bits(bits: usize) -> Option<Self>;
+    fn bits(self) -> usize;
+}
+pub struct CSR<T> {
+    base: *mut T,
+}
+impl<T> CSR<T>
+where
+    T: core::convert::TryFrom<usize> + core::convert::TryInto<usize> + core::default::Default,
+{
+    pub fn new(base: *mut T) -> Self {
+        CSR { base }
+    }
+    /// Read the word `offset` words from the base of this CSR
+    #[cfg(not(feature = "mock"))]
+    fn read_word(&self, offset: usize) -> usize {
+        let usize_base: *mut usize = unsafe { core::mem::transmute(self.base) };
+        unsafe { usize_base.add(offset).read_volatile() }
+    }
+    /// Write the word `offset` words from the base of this CSR
+    #[cfg(not(feature = "mock"))]
+    fn write_word(&mut self, offset: usize, value: usize) {
+        let usize_base: *mut usize = unsafe { core::mem::transmute(self.base) };
+        unsafe { usize_base.add(offset).write_volatile(value) };
+    }
+    #[cfg(feature = "mock")]
+    fn read_word(&self, offset: usize) -> usize {
+        mock::read(self.base as usize, offset)
+    }
+    #[cfg(feature = "mock")]
+    fn write_word(&mut self, offset: usize, value: usize) {
+        mock::write(self.base as usize, offset, value)
+    }
+    /// Read the contents of this register
+    pub fn r<A: Readable>(&self, reg: Register<A>) -> T {
+        self.read_word(reg.offset).try_into().unwrap_or_default()
+    }
+    /// Read a field from this CSR
+    pub fn rf<A: Readable>(&self, field: Field<A>) -> T {
+        ((self.read_word(field.register.offset) >> field.offset) & field.mask)
+            .try_into()
+            .unwrap_or_default()
+    }
+    /// Read-modify-write a given field in this CSR
+    pub fn rmwf<A: Readable + Writable>(&mut self, field: Field<A>, value: T) {
+        let value_as_usize: usize = value.try_into().unwrap_or_default() << field.offset;
+        let previous = self.read_word(field.register.offset) & !field.mask;
+        self.write_word(field.register.offset, previous | value_as_usize);
+    }
+    /// Write a given field without reading it first
+    pub fn wfo<A: Writable>(&mut self, field: Field<A>, value: T) {
+        let value_as_usize: usize = (value.try_into().unwrap_or_default() & field.mask) << field.offset;
+        self.write_word(field.register.offset, value_as_usize);
+    }
+    /// Write the entire contents of a register without reading it first
+    pub fn wo<A: Writable>(&mut self, reg: Register<A>, value: T) {
+        let value_as_usize: usize = value.try_into().unwrap_or_default();
+        self.write_word(reg.offset, value_as_usize);
+    }
+    /// Zero a field from a provided value
+    pub fn zf<A>(&mut self, field: Field<A>, value: T) -> T {
+        let value_as_usize: usize = value.try_into().unwrap_or_default();
+        (value_as_usize & !(field.mask << field.offset))
+            .try_into()
+            .unwrap_or_default()
+    }
+    /// Shift & mask a value to its final field position
+    pub fn ms<A>(&mut self, field: Field<A>, value: T) -> T {
+        let value_as_usize: usize = value.try_into().unwrap_or_default();
+        ((value_as_usize & field.mask) << field.offset)
+            .try_into()
+            .unwrap_or_default()
+    }
+    /// Write the value a register has after the peripheral is reset
+    pub fn reset<A: Writable>(&mut self, reg: Register<A>) {
+        self.write_word(reg.offset, reg.reset);
+    }
+    /// Read a field with enumerated values, returning `None` if it holds
+    /// a value that isn't listed
+    pub fn rfv<E: FieldValue>(&self) -> Option<E>
+    where
+        E::Access: Readable,
+    {
+        let field = E::FIELD;
+        E::from_bits((self.read_word(field.register.offset) >> field.offset) & field.mask)
+    }
+    /// Write a field with enumerated values without reading it first
+    pub fn wfov<E: FieldValue>(&mut self, value: E)
+    where
+        E::Access: Writable,
+    {
+        let field = E::FIELD;
+        let value_as_usize = (value.bits() & field.mask) << field.offset;
+        self.write_word(field.register.offset, value_as_usize);
+    }
+    /// Read-modify-write a field with enumerated values
+    pub fn rmwfv<E: FieldValue>(&mut self, value: E)
+    where
+        E::Access: Readable + Writable,
+    {
+        let field = E::FIELD;
+        let value_as_usize = (value.bits() & field.mask) << field.offset;
+        let previous = self.read_word(field.register.offset) & !(field.mask << field.offset);
+        self.write_word(field.register.offset, previous | value_as_usize);
+    }
+}
+// Physical base addresses of memory regions
+
+// Physical base addresses of registers
+pub const HW_TIMER0_BASE :   usize = 0x40008000;
+pub const HW_TIMER1_BASE :   usize = 0x40009000;
+pub const HW_TIMER2_BASE :   usize = 0x4000a000;
+pub const HW_PPI_BASE :   usize = 0x4001f000;
+
+pub mod utra {
+
+    /// Timer/Counter 0
+    pub mod timer0 {
+
+        /// Start Timer
+        pub const TASKS_START: crate::Register<crate::WO> = crate::Register::new(0, 0x0);
+        pub const TASKS_START_TASKS_START: crate::Field<crate::WO> = crate::Field::new(1, 0, TASKS_START);
+
+        /// Description collection: Capture Timer value to CC[n] register
+        pub const TASKS_CAPTURE: [crate::Register<crate::WO>; 4] = [crate::Register::new(16, 0x0), crate::Register::new(17, 0x0), crate::Register::new(18, 0x0), crate::Register::new(19, 0x0)];
+        pub const TASKS_CAPTURE_TASKS_CAPTURE: [crate::Field<crate::WO>; 4] = [crate::Field::new(1, 0, crate::Register::new(16, 0x0)), crate::Field::new(1, 0, crate::Register::new(17, 0x0)), crate::Field::new(1, 0, crate::Register::new(18, 0x0)), crate::Field::new(1, 0, crate::Register::new(19, 0x0))];
+
+        /// Description collection: Compare event on CC[n] match
+        pub const EVENTS_COMPARE: [crate::Register<crate::RW>; 4] = [crate::Register::new(80, 0x0), crate::Register::new(81, 0x0), crate::Register::new(82, 0x0), crate::Register::new(83, 0x0)];
+        pub const EVENTS_COMPARE_EVENTS_COMPARE: [crate::Field<crate::RW>; 4] = [crate::Field::new(1, 0, crate::Register::new(80, 0x0)), crate::Field::new(1, 0, crate::Register::new(81, 0x0)), crate::Field::new(1, 0, crate::Register::new(82, 0x0)), crate::Field::new(1, 0, crate::Register::new(83, 0x0))];
+        /// Values of `EVENTS_COMPARE_EVENTS_COMPARE`
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum EventsCompare {
+            /// Event not generated
+            NotGenerated = 0,
+            /// Event generated
+            Generated = 1,
+        }
+        impl EventsCompare {
+            pub fn from_bits(bits: usize) -> Option<Self> {
+                match bits {
+                    0 => Some(EventsCompare::NotGenerated),
+                    1 => Some(EventsCompare::Generated),
+                    _ => None,
+                }
+            }
+            pub fn bits(self) -> usize {
+                self as usize
+            }
+        }
+        /// A value of one element of `EVENTS_COMPARE_EVENTS_COMPARE`, for use with `rfv()`, `wfov()` and `rmwfv()`
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub struct EventsCompareAt<const N: usize>(pub EventsCompare);
+        impl<const N: usize> crate::FieldValue for EventsCompareAt<N> {
+            type Access = crate::RW;
+            const FIELD: crate::Field<crate::RW> = EVENTS_COMPARE_EVENTS_COMPARE[N];
+            fn from_bits(bits: usize) -> Option<Self> {
+                EventsCompare::from_bits(bits).map(Self)
+            }
+            fn bits(self) -> usize {
+                self.0.bits()
+            }
+        }
+
+        /// Timer mode selection
+        pub const MODE: crate::Register<crate::RW> = crate::Register::new(321, 0x0);
+        /// Timer mode
+        pub const MODE_MODE: crate::Field<crate::RW> = crate::Field::new(2, 0, MODE);
+        /// Values of `MODE_MODE`
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum Mode {
+            /// Select Timer mode
+            Timer = 0,
+            /// Deprecated enumerator -  Select Counter mode
+            Counter = 1,
+            /// Select Low Power Counter mode
+            LowPowerCounter = 2,
+        }
+        impl crate::FieldValue for Mode {
+            type Access = crate::RW;
+            const FIELD: crate::Field<crate::RW> = MODE_MODE;
+            fn from_bits(bits: usize) -> Option<Self> {
+                match bits {
+                    0 => Some(Mode::Timer),
+                    1 => Some(Mode::Counter),
+                    2 => Some(Mode::LowPowerCounter),
+                    _ => None,
+                }
+            }
+            fn bits(self) -> usize {
+                self as usize
+            }
+        }
+
+        /// Description collection: Capture/Compare register n
+        pub const CC: [crate::Register<crate::RW>; 4] = [crate::Register::new(336, 0x0), crate::Register::new(337, 0x0), crate::Register::new(338, 0x0), crate::Register::new(339, 0x0)];
+        /// Capture/Compare value
+        pub const CC_CC: [crate::Field<crate::RW>; 4] = [crate::Field::new(32, 0, crate::Register::new(336, 0x0)), crate::Field::new(32, 0, crate::Register::new(337, 0x0)), crate::Field::new(32, 0, crate::Register::new(338, 0x0)), crate::Field::new(32, 0, crate::Register::new(339, 0x0))];
+
+        pub const TIMER0_IRQ: usize = 8;
+        pub const HW_TIMER0_BASE: usize = 0x40008000;
+    }
+
+    /// Timer/Counter 1
+    pub mod timer1 {
+
+        /// Start Timer
+        pub const TASKS_START: crate::Register<crate::WO> = crate::Register::new(0, 0x0);
+        pub const TASKS_START_TASKS_START: crate::Field<crate::WO> = crate::Field::new(1, 0, TASKS_START);
+
+        /// Description collection: Capture Timer value to CC[n] register
+        pub const TASKS_CAPTURE: [crate::Register<crate::WO>; 4] = [crate::Register::new(16, 0x0), crate::Register::new(17, 0x0), crate::Register::new(18, 0x0), crate::Register::new(19, 0x0)];
+        pub const TASKS_CAPTURE_TASKS_CAPTURE: [crate::Field<crate::WO>; 4] = [crate::Field::new(1, 0, crate::Register::new(16, 0x0)), crate::Field::new(1, 0, crate::Register::new(17, 0x0)), crate::Field::new(1, 0, crate::Register::new(18, 0x0)), crate::Field::new(1, 0, crate::Register::new(19, 0x0))];
+
+        /// Description collection: Compare event on CC[n] match
+        pub const EVENTS_COMPARE: [crate::Register<crate::RW>; 4] = [crate::Register::new(80, 0x0), crate::Register::new(81, 0x0), crate::Register::new(82, 0x0), crate::Register::new(83, 0x0)];
+        pub const EVENTS_COMPARE_EVENTS_COMPARE: [crate::Field<crate::RW>; 4] = [crate::Field::new(1, 0, crate::Register::new(80, 0x0)), crate::Field::new(1, 0, crate::Register::new(81, 0x0)), crate::Field::new(1, 0, crate::Register::new(82, 0x0)), crate::Field::new(1, 0, crate::Register::new(83, 0x0))];
+        /// Values of `EVENTS_COMPARE_EVENTS_COMPARE`
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum EventsCompare {
+            /// Event not generated
+            NotGenerated = 0,
+            /// Event generated
+            Generated = 1,
+        }
+        impl EventsCompare {
+            pub fn from_bits(bits: usize) -> Option<Self> {
+                match bits {
+                    0 => Some(EventsCompare::NotGenerated),
+                    1 => Some(EventsCompare::Generated),
+                    _ => None,
+                }
+            }
+            pub fn bits(self) -> usize {
+                self as usize
+            }
+        }
+        /// A value of one element of `EVENTS_COMPARE_EVENTS_COMPARE`, for use with `rfv()`, `wfov()` and `rmwfv()`
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub struct EventsCompareAt<const N: usize>(pub EventsCompare);
+        impl<const N: usize> crate::FieldValue for EventsCompareAt<N> {
+            type Access = crate::RW;
+            const FIELD: crate::Field<crate::RW> = EVENTS_COMPARE_EVENTS_COMPARE[N];
+            fn from_bits(bits: usize) -> Option<Self> {
+                EventsCompare::from_bits(bits).map(Self)
+            }
+            fn bits(self) -> usize {
+                self.0.bits()
+            }
+        }
+
+        /// Timer mode selection
+        pub const MODE: crate::Register<crate::RW> = crate::Register::new(321, 0x0);
+        /// Timer mode
+        pub const MODE_MODE: crate::Field<crate::RW> = crate::Field::new(2, 0, MODE);
+        /// Values of `MODE_MODE`
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum Mode {
+            /// Select Timer mode
+            Timer = 0,
+            /// Deprecated enumerator -  Select Counter mode
+            Counter = 1,
+            /// Select Low Power Counter mode
+            LowPowerCounter = 2,
+        }
+        impl crate::FieldValue for Mode {
+            type Access = crate::RW;
+            const FIELD: crate::Field<crate::RW> = MODE_MODE;
+            fn from_bits(bits: usize) -> Option<Self> {
+                match bits {
+                    0 => Some(Mode::Timer),
+                    1 => Some(Mode::Counter),
+                    2 => Some(Mode::LowPowerCounter),
+                    _ => None,
+                }
+            }
+            fn bits(self) -> usize {
+                self as usize
+            }
+        }
+
+        /// Description collection: Capture/Compare register n
+        pub const CC: [crate::Register<crate::RW>; 4] = [crate::Register::new(336, 0x0), crate::Register::new(337, 0x0), crate::Register::new(338, 0x0), crate::Register::new(339, 0x0)];
+        /// Capture/Compare value
+        pub const CC_CC: [crate::Field<crate::RW>; 4] = [crate::Field::new(32, 0, crate::Register::new(336, 0x0)), crate::Field::new(32, 0, crate::Register::new(337, 0x0)), crate::Field::new(32, 0, crate::Register::new(338, 0x0)), crate::Field::new(32, 0, crate::Register::new(339, 0x0))];
+
+        pub const TIMER1_IRQ: usize = 9;
+        pub const HW_TIMER1_BASE: usize = 0x40009000;
+    }
+
+    /// Timer/Counter 2
+    pub mod timer2 {
+
+        /// Start Timer
+        pub const TASKS_START: crate::Register<crate::WO> = crate::Register::new(0, 0x0);
+        pub const TASKS_START_TASKS_START: crate::Field<crate::WO> = crate::Field::new(1, 0, TASKS_START);
+
+        /// Description collection: Capture Timer value to CC[n] register
+        pub const TASKS_CAPTURE: [crate::Register<crate::WO>; 4] = [crate::Register::new(16, 0x0), crate::Register::new(17, 0x0), crate::Register::new(18, 0x0), crate::Register::new(19, 0x0)];
+        pub const TASKS_CAPTURE_TASKS_CAPTURE: [crate::Field<crate::WO>; 4] = [crate::Field::new(1, 0, crate::Register::new(16, 0x0)), crate::Field::new(1, 0, crate::Register::new(17, 0x0)), crate::Field::new(1, 0, crate::Register::new(18, 0x0)), crate::Field::new(1, 0, crate::Register::new(19, 0x0))];
+
+        /// Description collection: Compare event on CC[n] match
+        pub const EVENTS_COMPARE: [crate::Register<crate::RW>; 4] = [crate::Register::new(80, 0x0), crate::Register::new(81, 0x0), crate::Register::new(82, 0x0), crate::Register::new(83, 0x0)];
+        pub const EVENTS_COMPARE_EVENTS_COMPARE: [crate::Field<crate::RW>; 4] = [crate::Field::new(1, 0, crate::Register::new(80, 0x0)), crate::Field::new(1, 0, crate::Register::new(81, 0x0)), crate::Field::new(1, 0, crate::Register::new(82, 0x0)), crate::Field::new(1, 0, crate::Register::new(83, 0x0))];
+        /// Values of `EVENTS_COMPARE_EVENTS_COMPARE`
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum EventsCompare {
+            /// Event not generated
+            NotGenerated = 0,
+            /// Event generated
+            Generated = 1,
+        }
+        impl EventsCompare {
+            pub fn from_bits(bits: usize) -> Option<Self> {
+                match bits {
+                    0 => Some(EventsCompare::NotGenerated),
+                    1 => Some(EventsCompare::Generated),
+                    _ => None,
+                }
+            }
+            pub fn bits(self) -> usize {
+                self as usize
+            }
+        }
+        /// A value of one element of `EVENTS_COMPARE_EVENTS_COMPARE`, for use with `rfv()`, `wfov()` and `rmwfv()`
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub struct EventsCompareAt<const N: usize>(pub EventsCompare);
+        impl<const N: usize> crate::FieldValue for EventsCompareAt<N> {
+            type Access = crate::RW;
+            const FIELD: crate::Field<crate::RW> = EVENTS_COMPARE_EVENTS_COMPARE[N];
+            fn from_bits(bits: usize) -> Option<Self> {
+                EventsCompare::from_bits(bits).map(Self)
+            }
+            fn bits(self) -> usize {
+                self.0.bits()
+            }
+        }
+
+        /// Timer mode selection
+        pub const MODE: crate::Register<crate::RW> = crate::Register::new(321, 0x0);
+        /// Timer mode
+        pub const MODE_MODE: crate::Field<crate::RW> = crate::Field::new(2, 0, MODE);
+        /// Values of `MODE_MODE`
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum Mode {
+            /// Select Timer mode
+            Timer = 0,
+            /// Deprecated enumerator -  Select Counter mode
+            Counter = 1,
+            /// Select Low Power Counter mode
+            LowPowerCounter = 2,
+        }
+        impl crate::FieldValue for Mode {
+            type Access = crate::RW;
+            const FIELD: crate::Field<crate::RW> = MODE_MODE;
+            fn from_bits(bits: usize) -> Option<Self> {
+                match bits {
+                    0 => Some(Mode::Timer),
+                    1 => Some(Mode::Counter),
+                    2 => Some(Mode::LowPowerCounter),
+                    _ => None,
+                }
+            }
+            fn bits(self) -> usize {
+                self as usize
+            }
+        }
+
+        /// Description collection: Capture/Compare register n
+        pub const CC: [crate::Register<crate::RW>; 4] = [crate::Register::new(336, 0x0), crate::Register::new(337, 0x0), crate::Register::new(338, 0x0), crate::Register::new(339, 0x0)];
+        /// Capture/Compare value
+        pub const CC_CC: [crate::Field<crate::RW>; 4] = [crate::Field::new(32, 0, crate::Register::new(336, 0x0)), crate::Field::new(32, 0, crate::Register::new(337, 0x0)), crate::Field::new(32, 0, crate::Register::new(338, 0x0)), crate::Field::new(32, 0, crate::Register::new(339, 0x0))];
+
+        pub const TIMER2_IRQ: usize = 10;
+        pub const HW_TIMER2_BASE: usize = 0x4000a000;
+    }
+
+    /// Programmable Peripheral Interconnect
+    pub mod ppi {
+
+        /// Description cluster: Enable channel group n
+        pub const TASKS_CHG_EN: [crate::Register<crate::WO>; 2] = [crate::Register::new(0, 0x0), crate::Register::new(2, 0x0)];
+        pub const TASKS_CHG_EN_EN: [crate::Field<crate::WO>; 2] = [crate::Field::new(1, 0, crate::Register::new(0, 0x0)), crate::Field::new(1, 0, crate::Register::new(2, 0x0))];
+
+        /// Description cluster: Disable channel group n
+        pub const TASKS_CHG_DIS: [crate::Register<crate::WO>; 2] = [crate::Register::new(1, 0x0), crate::Register::new(3, 0x0)];
+        pub const TASKS_CHG_DIS_DIS: [crate::Field<crate::WO>; 2] = [crate::Field::new(1, 0, crate::Register::new(1, 0x0)), crate::Field::new(1, 0, crate::Register::new(3, 0x0))];
+
+        /// Channel enable register
+        pub const CHEN: crate::Register<crate::RW> = crate::Register::new(320, 0x0);
+        pub const CHEN_CH0: crate::Field<crate::RW> = crate::Field::new(1, 0, CHEN);
+        pub const CHEN_CH1: crate::Field<crate::RW> = crate::Field::new(1, 1, CHEN);
+
+        /// Description cluster: Channel n event endpoint
+        pub const CH_EEP: [crate::Register<crate::RW>; 4] = [crate::Register::new(324, 0x0), crate::Register::new(326, 0x0), crate::Register::new(328, 0x0), crate::Register::new(330, 0x0)];
+        pub const CH_EEP_EEP: [crate::Field<crate::RW>; 4] = [crate::Field::new(32, 0, crate::Register::new(324, 0x0)), crate::Field::new(32, 0, crate::Register::new(326, 0x0)), crate::Field::new(32, 0, crate::Register::new(328, 0x0)), crate::Field::new(32, 0, crate::Register::new(330, 0x0))];
+
+        /// Description cluster: Channel n task endpoint
+        pub const CH_TEP: [crate::Register<crate::RW>; 4] = [crate::Register::new(325, 0x0), crate::Register::new(327, 0x0), crate::Register::new(329, 0x0), crate::Register::new(331, 0x0)];
+        pub const CH_TEP_TEP: [crate::Field<crate::RW>; 4] = [crate::Field::new(32, 0, crate::Register::new(325, 0x0)), crate::Field::new(32, 0, crate::Register::new(327, 0x0)), crate::Field::new(32, 0, crate::Register::new(329, 0x0)), crate::Field::new(32, 0, crate::Register::new(331, 0x0))];
+
+        pub const HW_PPI_BASE: usize = 0x4001f000;
+    }
+}
+
+/// In-memory stand-ins for peripherals, so that drivers can be tested on a
+/// host.  While a `mock::Peripheral` exists, a `CSR` created on the same
+/// thread with its base address reads and writes the mock rather than
+/// hardware.  Hooks may be attached to registers to model their side
+/// effects, such as sending a byte when `RXTX` is written.
+#[cfg(feature = "mock")]
+pub mod mock {
+    use super::{Field, Readable, Register, Writable};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// The contents of a mocked peripheral's registers.  These may be
+    /// changed regardless of the registers' access, to model hardware.
+    pub struct Registers {
+        words: Vec<usize>,
+    }
+    impl Registers {
+        pub fn r<A>(&self, reg: Register<A>) -> usize {
+            self.words[reg.offset]
+        }
+        pub fn wo<A>(&mut self, reg: Register<A>, value: usize) {
+            self.words[reg.offset] = value;
+        }
+        pub fn rf<A>(&self, field: Field<A>) -> usize {
+            (self.words[field.register.offset] >> field.offset) & field.mask
+        }
+        /// Replace the contents of a field, leaving the rest of the
+        /// register unchanged
+        pub fn wf<A>(&mut self, field: Field<A>, value: usize) {
+            let word = &mut self.words[field.register.offset];
+            *word = (*word & !(field.mask << field.offset)) | ((value & field.mask) << field.offset);
+        }
+    }
+
+    type ReadHook = Box<dyn FnMut(&mut Registers) -> usize>;
+    type WriteHook = Box<dyn FnMut(&mut Registers, usize)>;
+
+    struct State {
+        base: usize,
+        registers: Registers,
+        read_hooks: Vec<(usize, ReadHook)>,
+        write_hooks: Vec<(usize, WriteHook)>,
+    }
+
+    thread_local! {
+        static PERIPHERALS: RefCell<Vec<Rc<RefCell<State>>>> = RefCell::new(Vec::new());
+    }
+
+    pub struct Peripheral {
+        state: Rc<RefCell<State>>,
+    }
+    impl Peripheral {
+        /// Create a mock of `words` registers at `base`, holding zero apart
+        /// from the `(offset, value)` pairs in `reset`.
+        pub fn new(base: usize, words: usize, reset: &[(usize, usize)]) -> Peripheral {
+            let mut registers = Registers {
+                words: vec![0; words],
+            };
+            for &(offset, value) in reset {
+                registers.words[offset] = value;
+            }
+            let state = Rc::new(RefCell::new(State {
+                base,
+                registers,
+                read_hooks: Vec::new(),
+                write_hooks: Vec::new(),
+            }));
+            PERIPHERALS.with(|peripherals| {
+                let mut peripherals = peripherals.borrow_mut();
+                if peripherals.iter().any(|other| other.borrow().base == base) {
+                    panic!("a mock peripheral already exists at {:08x}", base);
+                }
+                peripherals.push(state.clone());
+            });
+            Peripheral { state }
+        }
+        pub fn base(&self) -> usize {
+            self.state.borrow().base
+        }
+        pub fn r<A>(&self, reg: Register<A>) -> usize {
+            self.state.borrow().registers.r(reg)
+        }
+        pub fn wo<A>(&self, reg: Register<A>, value: usize) {
+            self.state.borrow_mut().registers.wo(reg, value)
+        }
+        pub fn rf<A>(&self, field: Field<A>) -> usize {
+            self.state.borrow().registers.rf(field)
+        }
+        pub fn wf<A>(&self, field: Field<A>, value: usize) {
+            self.state.borrow_mut().registers.wf(field, value)
+        }
+        /// Produce the value read from `reg` with `hook` rather than
+        /// returning its contents.  Hooks must not use a `CSR` for the
+        /// same peripheral.
+        pub fn on_read<A: Readable, F: FnMut(&mut Registers) -> usize + 'static>(
+            &self,
+            reg: Register<A>,
+            hook: F,
+        ) {
+            let mut state = self.state.borrow_mut();
+            state.read_hooks.retain(|(offset, _)| *offset != reg.offset);
+            state.read_hooks.push((reg.offset, Box::new(hook)));
+        }
+        /// Pass values written to `reg` to `hook` rather than storing
+        /// them.  The hook may store them itself.
+        pub fn on_write<A: Writable, F: FnMut(&mut Registers, usize) + 'static>(
+            &self,
+            reg: Register<A>,
+            hook: F,
+        ) {
+            let mut state = self.state.borrow_mut();
+            state.write_hooks.retain(|(offset, _)| *offset != reg.offset);
+            state.write_hooks.push((reg.offset, Box::new(hook)));
+        }
+    }
+    impl Drop for Peripheral {
+        fn drop(&mut self) {
+            PERIPHERALS.with(|peripherals| {
+                peripherals
+                    .borrow_mut()
+                    .retain(|other| !Rc::ptr_eq(other, &self.state))
+            });
+        }
+    }
+
+    fn find(base: usize) -> Rc<RefCell<State>> {
+        PERIPHERALS.with(|peripherals| {
+            peripherals
+                .borrow()
+                .iter()
+                .find(|state| state.borrow().base == base)
+                .cloned()
+                .unwrap_or_else(|| panic!("no mock peripheral at {:08x}", base))
+        })
+    }
+
+    pub(crate) fn read(base: usize, offset: usize) -> usize {
+        let state = find(base);
+        let mut state = state.borrow_mut();
+        let State {
+            registers,
+            read_hooks,
+            ..
+        } = &mut *state;
+        match read_hooks.iter_mut().find(|(hooked, _)| *hooked == offset) {
+            Some((_, hook)) => hook(registers),
+            None => registers.words[offset],
+        }
+    }
+
+    pub(crate) fn write(base: usize, offset: usize, value: usize) {
+        let state = find(base);
+        let mut state = state.borrow_mut();
+        let State {
+            registers,
+            write_hooks,
+            ..
+        } = &mut *state;
+        match write_hooks.iter_mut().find(|(hooked, _)| *hooked == offset) {
+            Some((_, hook)) => hook(registers, value),
+            None => registers.words[offset] = value,
+        }
+    }
+
+    /// A mock of `timer0` at `HW_TIMER0_BASE`
+    pub fn timer0() -> Peripheral {
+        Peripheral::new(super::HW_TIMER0_BASE, 1024, &[])
+    }
+
+    /// A mock of `timer1` at `HW_TIMER1_BASE`
+    pub fn timer1() -> Peripheral {
+        Peripheral::new(super::HW_TIMER1_BASE, 1024, &[])
+    }
+
+    /// A mock of `timer2` at `HW_TIMER2_BASE`
+    pub fn timer2() -> Peripheral {
+        Peripheral::new(super::HW_TIMER2_BASE, 1024, &[])
+    }
+
+    /// A mock of `ppi` at `HW_PPI_BASE`
+    pub fn ppi() -> Peripheral {
+        Peripheral::new(super::HW_PPI_BASE, 1024, &[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    #[ignore]
+    fn compile_check() {
+        use super::*;
+        let mut timer0_csr = CSR::new(HW_TIMER0_BASE as *mut u32);
+
+        timer0_csr.wo(utra::timer0::TASKS_START, 0);
+        timer0_csr.reset(utra::timer0::TASKS_START);
+        let mut baz = timer0_csr.zf(utra::timer0::TASKS_START_TASKS_START, 0);
+        baz |= timer0_csr.ms(utra::timer0::TASKS_START_TASKS_START, 1);
+        timer0_csr.wfo(utra::timer0::TASKS_START_TASKS_START, baz);
+
+        timer0_csr.wo(utra::timer0::TASKS_CAPTURE[0], 0);
+        timer0_csr.reset(utra::timer0::TASKS_CAPTURE[0]);
+        let mut baz = timer0_csr.zf(utra::timer0::TASKS_CAPTURE_TASKS_CAPTURE[0], 0);
+        baz |= timer0_csr.ms(utra::timer0::TASKS_CAPTURE_TASKS_CAPTURE[0], 1);
+        timer0_csr.wfo(utra::timer0::TASKS_CAPTURE_TASKS_CAPTURE[0], baz);
+
+        let foo = timer0_csr.r(utra::timer0::EVENTS_COMPARE[0]);
+        timer0_csr.wo(utra::timer0::EVENTS_COMPARE[0], foo);
+        timer0_csr.reset(utra::timer0::EVENTS_COMPARE[0]);
+        let bar = timer0_csr.rf(utra::timer0::EVENTS_COMPARE_EVENTS_COMPARE[0]);
+        timer0_csr.rmwf(utra::timer0::EVENTS_COMPARE_EVENTS_COMPARE[0], bar);
+        let mut baz = timer0_csr.zf(utra::timer0::EVENTS_COMPARE_EVENTS_COMPARE[0], bar);
+        baz |= timer0_csr.ms(utra::timer0::EVENTS_COMPARE_EVENTS_COMPARE[0], 1);
+        timer0_csr.wfo(utra::timer0::EVENTS_COMPARE_EVENTS_COMPARE[0], baz);
+        let _: Option<utra::timer0::EventsCompareAt::<0>> = timer0_csr.rfv();
+        timer0_csr.wfov(utra::timer0::EventsCompareAt::<0>(utra::timer0::EventsCompare::NotGenerated));
+        timer0_csr.rmwfv(utra::timer0::EventsCompareAt::<0>(utra::timer0::EventsCompare::NotGenerated));
+
+        let foo = timer0_csr.r(utra::timer0::MODE);
+        timer0_csr.wo(utra::timer0::MODE, foo);
+        timer0_csr.reset(utra::timer0::MODE);
+        let bar = timer0_csr.rf(utra::timer0::MODE_MODE);
+        timer0_csr.rmwf(utra::timer0::MODE_MODE, bar);
+        let mut baz = timer0_csr.zf(utra::timer0::MODE_MODE, bar);
+        baz |= timer0_csr.ms(utra::timer0::MODE_MODE, 1);
+        timer0_csr.wfo(utra::timer0::MODE_MODE, baz);
+        let _: Option<utra::timer0::Mode> = timer0_csr.rfv();
+        timer0_csr.wfov(utra::timer0::Mode::Timer);
+        timer0_csr.rmwfv(utra::timer0::Mode::Timer);
+
+        let foo = timer0_csr.r(utra::timer0::CC[0]);
+        timer0_csr.wo(utra::timer0::CC[0], foo);
+        timer0_csr.reset(utra::timer0::CC[0]);
+        let bar = timer0_csr.rf(utra::timer0::CC_CC[0]);
+        timer0_csr.rmwf(utra::timer0::CC_CC[0], bar);
+        let mut baz = timer0_csr.zf(utra::timer0::CC_CC[0], bar);
+        baz |= timer0_csr.ms(utra::timer0::CC_CC[0], 1);
+        timer0_csr.wfo(utra::timer0::CC_CC[0], baz);
+        let mut timer1_csr = CSR::new(HW_TIMER1_BASE as *mut u32);
+
+        timer1_csr.wo(utra::timer1::TASKS_START, 0);
+        timer1_csr.reset(utra::timer1::TASKS_START);
+        let mut baz = timer1_csr.zf(utra::timer1::TASKS_START_TASKS_START, 0);
+        baz |= timer1_csr.ms(utra::timer1::TASKS_START_TASKS_START, 1);
+        timer1_csr.wfo(utra::timer1::TASKS_START_TASKS_START, baz);
+
+        timer1_csr.wo(utra::timer1::TASKS_CAPTURE[0], 0);
+        timer1_csr.reset(utra::timer1::TASKS_CAPTURE[0]);
+        let mut baz = timer1_csr.zf(utra::timer1::TASKS_CAPTURE_TASKS_CAPTURE[0], 0);
+        baz |= timer1_csr.ms(utra::timer1::TASKS_CAPTURE_TASKS_CAPTURE[0], 1);
+        timer1_csr.wfo(utra::timer1::TASKS_CAPTURE_TASKS_CAPTURE[0], baz);
+
+        let foo = timer1_csr.r(utra::timer1::EVENTS_COMPARE[0]);
+        timer1_csr.wo(utra::timer1::EVENTS_COMPARE[0], foo);
+        timer1_csr.reset(utra::timer1::EVENTS_COMPARE[0]);
+        let bar = timer1_csr.rf(utra::timer1::EVENTS_COMPARE_EVENTS_COMPARE[0]);
+        timer1_csr.rmwf(utra::timer1::EVENTS_COMPARE_EVENTS_COMPARE[0], bar);
+        let mut baz = timer1_csr.zf(utra::timer1::EVENTS_COMPARE_EVENTS_COMPARE[0], bar);
+        baz |= timer1_csr.ms(utra::timer1::EVENTS_COMPARE_EVENTS_COMPARE[0], 1);
+        timer1_csr.wfo(utra::timer1::EVENTS_COMPARE_EVENTS_COMPARE[0], baz);
+        let _: Option<utra::timer1::EventsCompareAt::<0>> = timer1_csr.rfv();
+        timer1_csr.wfov(utra::timer1::EventsCompareAt::<0>(utra::timer1::EventsCompare::NotGenerated));
+        timer1_csr.rmwfv(utra::timer1::EventsCompareAt::<0>(utra::timer1::EventsCompare::NotGenerated));
+
+        let foo = timer1_csr.r(utra::timer1::MODE);
+        timer1_csr.wo(utra::timer1::MODE, foo);
+        timer1_csr.reset(utra::timer1::MODE);
+        let bar = timer1_csr.rf(utra::timer1::MODE_MODE);
+        timer1_csr.rmwf(utra::timer1::MODE_MODE, bar);
+        let mut baz = timer1_csr.zf(utra::timer1::MODE_MODE, bar);
+        baz |= timer1_csr.ms(utra::timer1::MODE_MODE, 1);
+        timer1_csr.wfo(utra::timer1::MODE_MODE, baz);
+        let _: Option<utra::timer1::Mode> = timer1_csr.rfv();
+        timer1_csr.wfov(utra::timer1::Mode::Timer);
+        timer1_csr.rmwfv(utra::timer1::Mode::Timer);
+
+        let foo = timer1_csr.r(utra::timer1::CC[0]);
+        timer1_csr.wo(utra::timer1::CC[0], foo);
+        timer1_csr.reset(utra::timer1::CC[0]);
+        let bar = timer1_csr.rf(utra::timer1::CC_CC[0]);
+        timer1_csr.rmwf(utra::timer1::CC_CC[0], bar);
+        let mut baz = timer1_csr.zf(utra::timer1::CC_CC[0], bar);
+        baz |= timer1_csr.ms(utra::timer1::CC_CC[0], 1);
+        timer1_csr.wfo(utra::timer1::CC_CC[0], baz);
+        let mut timer2_csr = CSR::new(HW_TIMER2_BASE as *mut u32);
+
+        timer2_csr.wo(utra::timer2::TASKS_START, 0);
+        timer2_csr.reset(utra::timer2::TASKS_START);
+        let mut baz = timer2_csr.zf(utra::timer2::TASKS_START_TASKS_START, 0);
+        baz |= timer2_csr.ms(utra::timer2::TASKS_START_TASKS_START, 1);
+        timer2_csr.wfo(utra::timer2::TASKS_START_TASKS_START, baz);
+
+        timer2_csr.wo(utra::timer2::TASKS_CAPTURE[0], 0);
+        timer2_csr.reset(utra::timer2::TASKS_CAPTURE[0]);
+        let mut baz = timer2_csr.zf(utra::timer2::TASKS_CAPTURE_TASKS_CAPTURE[0], 0);
+        baz |= timer2_csr.ms(utra::timer2::TASKS_CAPTURE_TASKS_CAPTURE[0], 1);
+        timer2_csr.wfo(utra::timer2::TASKS_CAPTURE_TASKS_CAPTURE[0], baz);
+
+        let foo = timer2_csr.r(utra::timer2::EVENTS_COMPARE[0]);
+        timer2_csr.wo(utra::timer2::EVENTS_COMPARE[0], foo);
+        timer2_csr.reset(utra::timer2::EVENTS_COMPARE[0]);
+        let bar = timer2_csr.rf(utra::timer2::EVENTS_COMPARE_EVENTS_COMPARE[0]);
+        timer2_csr.rmwf(utra::timer2::EVENTS_COMPARE_EVENTS_COMPARE[0], bar);
+        let mut baz = timer2_csr.zf(utra::timer2::EVENTS_COMPARE_EVENTS_COMPARE[0], bar);
+        baz |= timer2_csr.ms(utra::timer2::EVENTS_COMPARE_EVENTS_COMPARE[0], 1);
+        timer2_csr.wfo(utra::timer2::EVENTS_COMPARE_EVENTS_COMPARE[0], baz);
+        let _: Option<utra::timer2::EventsCompareAt::<0>> = timer2_csr.rfv();
+        timer2_csr.wfov(utra::timer2::EventsCompareAt::<0>(utra::timer2::EventsCompare::NotGenerated));
+        timer2_csr.rmwfv(utra::timer2::EventsCompareAt::<0>(utra::timer2::EventsCompare::NotGenerated));
+
+        let foo = timer2_csr.r(utra::timer2::MODE);
+        timer2_csr.wo(utra::timer2::MODE, foo);
+        timer2_csr.reset(utra::timer2::MODE);
+        let bar = timer2_csr.rf(utra::timer2::MODE_MODE);
+        timer2_csr.rmwf(utra::timer2::MODE_MODE, bar);
+        let mut baz = timer2_csr.zf(utra::timer2::MODE_MODE, bar);
+        baz |= timer2_csr.ms(utra::timer2::MODE_MODE, 1);
+        timer2_csr.wfo(utra::timer2::MODE_MODE, baz);
+        let _: Option<utra::timer2::Mode> = timer2_csr.rfv();
+        timer2_csr.wfov(utra::timer2::Mode::Timer);
+        timer2_csr.rmwfv(utra::timer2::Mode::Timer);
+
+        let foo = timer2_csr.r(utra::timer2::CC[0]);
+        timer2_csr.wo(utra::timer2::CC[0], foo);
+        timer2_csr.reset(utra::timer2::CC[0]);
+        let bar = timer2_csr.rf(utra::timer2::CC_CC[0]);
+        timer2_csr.rmwf(utra::timer2::CC_CC[0], bar);
+        let mut baz = timer2_csr.zf(utra::timer2::CC_CC[0], bar);
+        baz |= timer2_csr.ms(utra::timer2::CC_CC[0], 1);
+        timer2_csr.wfo(utra::timer2::CC_CC[0], baz);
+        let mut ppi_csr = CSR::new(HW_PPI_BASE as *mut u32);
+
+        ppi_csr.wo(utra::ppi::TASKS_CHG_EN[0], 0);
+        ppi_csr.reset(utra::ppi::TASKS_CHG_EN[0]);
+        let mut baz = ppi_csr.zf(utra::ppi::TASKS_CHG_EN_EN[0], 0);
+        baz |= ppi_csr.ms(utra::ppi::TASKS_CHG_EN_EN[0], 1);
+        ppi_csr.wfo(utra::ppi::TASKS_CHG_EN_EN[0], baz);
+
+        ppi_csr.wo(utra::ppi::TASKS_CHG_DIS[0], 0);
+        ppi_csr.reset(utra::ppi::TASKS_CHG_DIS[0]);
+        let mut baz = ppi_csr.zf(utra::ppi::TASKS_CHG_DIS_DIS[0], 0);
+        baz |= ppi_csr.ms(utra::ppi::TASKS_CHG_DIS_DIS[0], 1);
+        ppi_csr.wfo(utra::ppi::TASKS_CHG_DIS_DIS[0], baz);
+
+        let foo = ppi_csr.r(utra::ppi::CHEN);
+        ppi_csr.wo(utra::ppi::CHEN, foo);
+        ppi_csr.reset(utra::ppi::CHEN);
+        let bar = ppi_csr.rf(utra::ppi::CHEN_CH0);
+        ppi_csr.rmwf(utra::ppi::CHEN_CH0, bar);
+        let mut baz = ppi_csr.zf(utra::ppi::CHEN_CH0, bar);
+        baz |= ppi_csr.ms(utra::ppi::CHEN_CH0, 1);
+        ppi_csr.wfo(utra::ppi::CHEN_CH0, baz);
+        let bar = ppi_csr.rf(utra::ppi::CHEN_CH1);
+        ppi_csr.rmwf(utra::ppi::CHEN_CH1, bar);
+        let mut baz = ppi_csr.zf(utra::ppi::CHEN_CH1, bar);
+        baz |= ppi_csr.ms(utra::ppi::CHEN_CH1, 1);
+        ppi_csr.wfo(utra::ppi::CHEN_CH1, baz);
+
+        let foo = ppi_csr.r(utra::ppi::CH_EEP[0]);
+        ppi_csr.wo(utra::ppi::CH_EEP[0], foo);
+        ppi_csr.reset(utra::ppi::CH_EEP[0]);
+        let bar = ppi_csr.rf(utra::ppi::CH_EEP_EEP[0]);
+        ppi_csr.rmwf(utra::ppi::CH_EEP_EEP[0], bar);
+        let mut baz = ppi_csr.zf(utra::ppi::CH_EEP_EEP[0], bar);
+        baz |= ppi_csr.ms(utra::ppi::CH_EEP_EEP[0], 1);
+        ppi_csr.wfo(utra::ppi::CH_EEP_EEP[0], baz);
+
+        let foo = ppi_csr.r(utra::ppi::CH_TEP[0]);
+        ppi_csr.wo(utra::ppi::CH_TEP[0], foo);
+        ppi_csr.reset(utra::ppi::CH_TEP[0]);
+        let bar = ppi_csr.rf(utra::ppi::CH_TEP_TEP[0]);
+        ppi_csr.rmwf(utra::ppi::CH_TEP_TEP[0], bar);
+        let mut baz = ppi_csr.zf(utra::ppi::CH_TEP_TEP[0], bar);
+        baz |= ppi_csr.ms(utra::ppi::CH_TEP_TEP[0], 1);
+        ppi_csr.wfo(utra::ppi::CH_TEP_TEP[0], baz);
+    }
+}
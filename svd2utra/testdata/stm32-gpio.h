@@ -0,0 +1,295 @@
+/* Generated by svd2utra.  Do not edit. */
+#ifndef UTRA_GENERATED_H
+#define UTRA_GENERATED_H
+
+#include <stdint.h>
+
+/* Registers are addressed by the base address of their peripheral and
+ * their offset in bytes, such as utra_read(HW_UART_BASE, UTRA_UART_RXTX) */
+static inline uint32_t utra_read(uintptr_t base, uintptr_t offset) {
+    return *(volatile uint32_t *)(base + offset);
+}
+
+static inline void utra_write(uintptr_t base, uintptr_t offset, uint32_t value) {
+    *(volatile uint32_t *)(base + offset) = value;
+}
+
+/* Extract a field from the value of its register, or move a value into
+ * place for a field, such as UTRA_FIELD_GET(UTRA_UART_EV_PENDING_TX, v) */
+#define UTRA_FIELD_GET(field, value) (((value) >> field##_SHIFT) & field##_MASK)
+#define UTRA_FIELD_SET(field, value) (((value) & field##_MASK) << field##_SHIFT)
+
+/* Physical base addresses of memory regions */
+
+/* Physical base addresses of registers */
+#define HW_GPIOA_BASE 0x40020000u
+#define HW_GPIOB_BASE 0x40020400u
+#define HW_GPIOC_BASE 0x40020800u
+
+/* GPIOA: General-purpose I/Os */
+
+/* MODER: read-write, reset 0xa8000000 - GPIO port mode register */
+#define UTRA_GPIOA_MODER 0x0
+#define UTRA_GPIOA_MODER_RESET 0xa8000000u
+/* MODER0: bits [1:0], read-write - Port x configuration bits (y = 0..3) */
+#define UTRA_GPIOA_MODER_MODER0_SHIFT 0
+#define UTRA_GPIOA_MODER_MODER0_WIDTH 2
+#define UTRA_GPIOA_MODER_MODER0_MASK 0x3u
+#define UTRA_GPIOA_MODER_MODER0_INPUT 0u
+#define UTRA_GPIOA_MODER_MODER0_OUTPUT 1u
+#define UTRA_GPIOA_MODER_MODER0_ALTERNATE 2u
+#define UTRA_GPIOA_MODER_MODER0_ANALOG 3u
+/* MODER1: bits [3:2], read-write - Port x configuration bits (y = 0..3) */
+#define UTRA_GPIOA_MODER_MODER1_SHIFT 2
+#define UTRA_GPIOA_MODER_MODER1_WIDTH 2
+#define UTRA_GPIOA_MODER_MODER1_MASK 0x3u
+#define UTRA_GPIOA_MODER_MODER1_INPUT 0u
+#define UTRA_GPIOA_MODER_MODER1_OUTPUT 1u
+#define UTRA_GPIOA_MODER_MODER1_ALTERNATE 2u
+#define UTRA_GPIOA_MODER_MODER1_ANALOG 3u
+/* MODER2: bits [5:4], read-write - Port x configuration bits (y = 0..3) */
+#define UTRA_GPIOA_MODER_MODER2_SHIFT 4
+#define UTRA_GPIOA_MODER_MODER2_WIDTH 2
+#define UTRA_GPIOA_MODER_MODER2_MASK 0x3u
+#define UTRA_GPIOA_MODER_MODER2_INPUT 0u
+#define UTRA_GPIOA_MODER_MODER2_OUTPUT 1u
+#define UTRA_GPIOA_MODER_MODER2_ALTERNATE 2u
+#define UTRA_GPIOA_MODER_MODER2_ANALOG 3u
+/* MODER3: bits [7:6], read-write - Port x configuration bits (y = 0..3) */
+#define UTRA_GPIOA_MODER_MODER3_SHIFT 6
+#define UTRA_GPIOA_MODER_MODER3_WIDTH 2
+#define UTRA_GPIOA_MODER_MODER3_MASK 0x3u
+#define UTRA_GPIOA_MODER_MODER3_INPUT 0u
+#define UTRA_GPIOA_MODER_MODER3_OUTPUT 1u
+#define UTRA_GPIOA_MODER_MODER3_ALTERNATE 2u
+#define UTRA_GPIOA_MODER_MODER3_ANALOG 3u
+
+/* IDR: read-only, reset 0x00000000 - GPIO port input data register */
+#define UTRA_GPIOA_IDR 0x10
+#define UTRA_GPIOA_IDR_RESET 0x00000000u
+/* IDR0: bits [0:0], read-only - Port input data (y = 0..3) */
+#define UTRA_GPIOA_IDR_IDR0_SHIFT 0
+#define UTRA_GPIOA_IDR_IDR0_WIDTH 1
+#define UTRA_GPIOA_IDR_IDR0_MASK 0x1u
+/* IDR1: bits [1:1], read-only - Port input data (y = 0..3) */
+#define UTRA_GPIOA_IDR_IDR1_SHIFT 1
+#define UTRA_GPIOA_IDR_IDR1_WIDTH 1
+#define UTRA_GPIOA_IDR_IDR1_MASK 0x1u
+/* IDR2: bits [2:2], read-only - Port input data (y = 0..3) */
+#define UTRA_GPIOA_IDR_IDR2_SHIFT 2
+#define UTRA_GPIOA_IDR_IDR2_WIDTH 1
+#define UTRA_GPIOA_IDR_IDR2_MASK 0x1u
+/* IDR3: bits [3:3], read-only - Port input data (y = 0..3) */
+#define UTRA_GPIOA_IDR_IDR3_SHIFT 3
+#define UTRA_GPIOA_IDR_IDR3_WIDTH 1
+#define UTRA_GPIOA_IDR_IDR3_MASK 0x1u
+
+/* BSRR: write-only, reset 0x00000000 - GPIO port bit set/reset register */
+#define UTRA_GPIOA_BSRR 0x18
+#define UTRA_GPIOA_BSRR_RESET 0x00000000u
+/* BR0: bits [16:16], write-only - Port x reset bit y (y = 0..3) */
+#define UTRA_GPIOA_BSRR_BR0_SHIFT 16
+#define UTRA_GPIOA_BSRR_BR0_WIDTH 1
+#define UTRA_GPIOA_BSRR_BR0_MASK 0x1u
+/* BR1: bits [17:17], write-only - Port x reset bit y (y = 0..3) */
+#define UTRA_GPIOA_BSRR_BR1_SHIFT 17
+#define UTRA_GPIOA_BSRR_BR1_WIDTH 1
+#define UTRA_GPIOA_BSRR_BR1_MASK 0x1u
+/* BR2: bits [18:18], write-only - Port x reset bit y (y = 0..3) */
+#define UTRA_GPIOA_BSRR_BR2_SHIFT 18
+#define UTRA_GPIOA_BSRR_BR2_WIDTH 1
+#define UTRA_GPIOA_BSRR_BR2_MASK 0x1u
+/* BR3: bits [19:19], write-only - Port x reset bit y (y = 0..3) */
+#define UTRA_GPIOA_BSRR_BR3_SHIFT 19
+#define UTRA_GPIOA_BSRR_BR3_WIDTH 1
+#define UTRA_GPIOA_BSRR_BR3_MASK 0x1u
+/* BS0: bits [0:0], write-only - Port x set bit y (y = 0..3) */
+#define UTRA_GPIOA_BSRR_BS0_SHIFT 0
+#define UTRA_GPIOA_BSRR_BS0_WIDTH 1
+#define UTRA_GPIOA_BSRR_BS0_MASK 0x1u
+/* BS1: bits [1:1], write-only - Port x set bit y (y = 0..3) */
+#define UTRA_GPIOA_BSRR_BS1_SHIFT 1
+#define UTRA_GPIOA_BSRR_BS1_WIDTH 1
+#define UTRA_GPIOA_BSRR_BS1_MASK 0x1u
+/* BS2: bits [2:2], write-only - Port x set bit y (y = 0..3) */
+#define UTRA_GPIOA_BSRR_BS2_SHIFT 2
+#define UTRA_GPIOA_BSRR_BS2_WIDTH 1
+#define UTRA_GPIOA_BSRR_BS2_MASK 0x1u
+/* BS3: bits [3:3], write-only - Port x set bit y (y = 0..3) */
+#define UTRA_GPIOA_BSRR_BS3_SHIFT 3
+#define UTRA_GPIOA_BSRR_BS3_WIDTH 1
+#define UTRA_GPIOA_BSRR_BS3_MASK 0x1u
+
+/* GPIOB: General-purpose I/Os */
+
+/* MODER: read-write, reset 0x00000280 - GPIO port mode register */
+#define UTRA_GPIOB_MODER 0x0
+#define UTRA_GPIOB_MODER_RESET 0x00000280u
+/* MODER0: bits [1:0], read-write - Port x configuration bits (y = 0..3) */
+#define UTRA_GPIOB_MODER_MODER0_SHIFT 0
+#define UTRA_GPIOB_MODER_MODER0_WIDTH 2
+#define UTRA_GPIOB_MODER_MODER0_MASK 0x3u
+/* MODER1: bits [3:2], read-write - Port x configuration bits (y = 0..3) */
+#define UTRA_GPIOB_MODER_MODER1_SHIFT 2
+#define UTRA_GPIOB_MODER_MODER1_WIDTH 2
+#define UTRA_GPIOB_MODER_MODER1_MASK 0x3u
+/* MODER2: bits [5:4], read-write - Port x configuration bits (y = 0..3) */
+#define UTRA_GPIOB_MODER_MODER2_SHIFT 4
+#define UTRA_GPIOB_MODER_MODER2_WIDTH 2
+#define UTRA_GPIOB_MODER_MODER2_MASK 0x3u
+/* MODER3: bits [7:6], read-write - Port x configuration bits (y = 0..3) */
+#define UTRA_GPIOB_MODER_MODER3_SHIFT 6
+#define UTRA_GPIOB_MODER_MODER3_WIDTH 2
+#define UTRA_GPIOB_MODER_MODER3_MASK 0x3u
+
+/* IDR: read-only, reset 0x00000000 - GPIO port input data register */
+#define UTRA_GPIOB_IDR 0x10
+#define UTRA_GPIOB_IDR_RESET 0x00000000u
+/* IDR0: bits [0:0], read-only - Port input data (y = 0..3) */
+#define UTRA_GPIOB_IDR_IDR0_SHIFT 0
+#define UTRA_GPIOB_IDR_IDR0_WIDTH 1
+#define UTRA_GPIOB_IDR_IDR0_MASK 0x1u
+/* IDR1: bits [1:1], read-only - Port input data (y = 0..3) */
+#define UTRA_GPIOB_IDR_IDR1_SHIFT 1
+#define UTRA_GPIOB_IDR_IDR1_WIDTH 1
+#define UTRA_GPIOB_IDR_IDR1_MASK 0x1u
+/* IDR2: bits [2:2], read-only - Port input data (y = 0..3) */
+#define UTRA_GPIOB_IDR_IDR2_SHIFT 2
+#define UTRA_GPIOB_IDR_IDR2_WIDTH 1
+#define UTRA_GPIOB_IDR_IDR2_MASK 0x1u
+/* IDR3: bits [3:3], read-only - Port input data (y = 0..3) */
+#define UTRA_GPIOB_IDR_IDR3_SHIFT 3
+#define UTRA_GPIOB_IDR_IDR3_WIDTH 1
+#define UTRA_GPIOB_IDR_IDR3_MASK 0x1u
+
+/* BSRR: write-only, reset 0x00000000 - GPIO port bit set/reset register */
+#define UTRA_GPIOB_BSRR 0x18
+#define UTRA_GPIOB_BSRR_RESET 0x00000000u
+/* BR0: bits [16:16], write-only - Port x reset bit y (y = 0..3) */
+#define UTRA_GPIOB_BSRR_BR0_SHIFT 16
+#define UTRA_GPIOB_BSRR_BR0_WIDTH 1
+#define UTRA_GPIOB_BSRR_BR0_MASK 0x1u
+/* BR1: bits [17:17], write-only - Port x reset bit y (y = 0..3) */
+#define UTRA_GPIOB_BSRR_BR1_SHIFT 17
+#define UTRA_GPIOB_BSRR_BR1_WIDTH 1
+#define UTRA_GPIOB_BSRR_BR1_MASK 0x1u
+/* BR2: bits [18:18], write-only - Port x reset bit y (y = 0..3) */
+#define UTRA_GPIOB_BSRR_BR2_SHIFT 18
+#define UTRA_GPIOB_BSRR_BR2_WIDTH 1
+#define UTRA_GPIOB_BSRR_BR2_MASK 0x1u
+/* BR3: bits [19:19], write-only - Port x reset bit y (y = 0..3) */
+#define UTRA_GPIOB_BSRR_BR3_SHIFT 19
+#define UTRA_GPIOB_BSRR_BR3_WIDTH 1
+#define UTRA_GPIOB_BSRR_BR3_MASK 0x1u
+/* BS0: bits [0:0], write-only - Port x set bit y (y = 0..3) */
+#define UTRA_GPIOB_BSRR_BS0_SHIFT 0
+#define UTRA_GPIOB_BSRR_BS0_WIDTH 1
+#define UTRA_GPIOB_BSRR_BS0_MASK 0x1u
+/* BS1: bits [1:1], write-only - Port x set bit y (y = 0..3) */
+#define UTRA_GPIOB_BSRR_BS1_SHIFT 1
+#define UTRA_GPIOB_BSRR_BS1_WIDTH 1
+#define UTRA_GPIOB_BSRR_BS1_MASK 0x1u
+/* BS2: bits [2:2], write-only - Port x set bit y (y = 0..3) */
+#define UTRA_GPIOB_BSRR_BS2_SHIFT 2
+#define UTRA_GPIOB_BSRR_BS2_WIDTH 1
+#define UTRA_GPIOB_BSRR_BS2_MASK 0x1u
+/* BS3: bits [3:3], write-only - Port x set bit y (y = 0..3) */
+#define UTRA_GPIOB_BSRR_BS3_SHIFT 3
+#define UTRA_GPIOB_BSRR_BS3_WIDTH 1
+#define UTRA_GPIOB_BSRR_BS3_MASK 0x1u
+
+/* GPIOC: General-purpose I/Os */
+
+/* MODER: read-write, reset 0xa8000000 - GPIO port mode register */
+#define UTRA_GPIOC_MODER 0x0
+#define UTRA_GPIOC_MODER_RESET 0xa8000000u
+/* MODER0: bits [1:0], read-write - Port x configuration bits (y = 0..3) */
+#define UTRA_GPIOC_MODER_MODER0_SHIFT 0
+#define UTRA_GPIOC_MODER_MODER0_WIDTH 2
+#define UTRA_GPIOC_MODER_MODER0_MASK 0x3u
+#define UTRA_GPIOC_MODER_MODER0_INPUT 0u
+#define UTRA_GPIOC_MODER_MODER0_OUTPUT 1u
+#define UTRA_GPIOC_MODER_MODER0_ALTERNATE 2u
+#define UTRA_GPIOC_MODER_MODER0_ANALOG 3u
+/* MODER1: bits [3:2], read-write - Port x configuration bits (y = 0..3) */
+#define UTRA_GPIOC_MODER_MODER1_SHIFT 2
+#define UTRA_GPIOC_MODER_MODER1_WIDTH 2
+#define UTRA_GPIOC_MODER_MODER1_MASK 0x3u
+#define UTRA_GPIOC_MODER_MODER1_INPUT 0u
+#define UTRA_GPIOC_MODER_MODER1_OUTPUT 1u
+#define UTRA_GPIOC_MODER_MODER1_ALTERNATE 2u
+#define UTRA_GPIOC_MODER_MODER1_ANALOG 3u
+/* MODER2: bits [5:4], read-write - Port x configuration bits (y = 0..3) */
+#define UTRA_GPIOC_MODER_MODER2_SHIFT 4
+#define UTRA_GPIOC_MODER_MODER2_WIDTH 2
+#define UTRA_GPIOC_MODER_MODER2_MASK 0x3u
+#define UTRA_GPIOC_MODER_MODER2_INPUT 0u
+#define UTRA_GPIOC_MODER_MODER2_OUTPUT 1u
+#define UTRA_GPIOC_MODER_MODER2_ALTERNATE 2u
+#define UTRA_GPIOC_MODER_MODER2_ANALOG 3u
+/* MODER3: bits [7:6], read-write - Port x configuration bits (y = 0..3) */
+#define UTRA_GPIOC_MODER_MODER3_SHIFT 6
+#define UTRA_GPIOC_MODER_MODER3_WIDTH 2
+#define UTRA_GPIOC_MODER_MODER3_MASK 0x3u
+#define UTRA_GPIOC_MODER_MODER3_INPUT 0u
+#define UTRA_GPIOC_MODER_MODER3_OUTPUT 1u
+#define UTRA_GPIOC_MODER_MODER3_ALTERNATE 2u
+#define UTRA_GPIOC_MODER_MODER3_ANALOG 3u
+
+/* IDR: read-only, reset 0x00000000 - GPIO port input data register */
+#define UTRA_GPIOC_IDR 0x10
+#define UTRA_GPIOC_IDR_RESET 0x00000000u
+/* IDR0: bits [0:0], read-only - Port input data (y = 0..3) */
+#define UTRA_GPIOC_IDR_IDR0_SHIFT 0
+#define UTRA_GPIOC_IDR_IDR0_WIDTH 1
+#define UTRA_GPIOC_IDR_IDR0_MASK 0x1u
+/* IDR1: bits [1:1], read-only - Port input data (y = 0..3) */
+#define UTRA_GPIOC_IDR_IDR1_SHIFT 1
+#define UTRA_GPIOC_IDR_IDR1_WIDTH 1
+#define UTRA_GPIOC_IDR_IDR1_MASK 0x1u
+/* IDR2: bits [2:2], read-only - Port input data (y = 0..3) */
+#define UTRA_GPIOC_IDR_IDR2_SHIFT 2
+#define UTRA_GPIOC_IDR_IDR2_WIDTH 1
+#define UTRA_GPIOC_IDR_IDR2_MASK 0x1u
+/* IDR3: bits [3:3], read-only - Port input data (y = 0..3) */
+#define UTRA_GPIOC_IDR_IDR3_SHIFT 3
+#define UTRA_GPIOC_IDR_IDR3_WIDTH 1
+#define UTRA_GPIOC_IDR_IDR3_MASK 0x1u
+
+/* BSRR: write-only, reset 0x00000000 - GPIO port bit set/reset register */
+#define UTRA_GPIOC_BSRR 0x18
+#define UTRA_GPIOC_BSRR_RESET 0x00000000u
+/* BR0: bits [16:16], write-only - Port x reset bit y (y = 0..3) */
+#define UTRA_GPIOC_BSRR_BR0_SHIFT 16
+#define UTRA_GPIOC_BSRR_BR0_WIDTH 1
+#define UTRA_GPIOC_BSRR_BR0_MASK 0x1u
+/* BR1: bits [17:17], write-only - Port x reset bit y (y = 0..3) */
+#define UTRA_GPIOC_BSRR_BR1_SHIFT 17
+#define UTRA_GPIOC_BSRR_BR1_WIDTH 1
+#define UTRA_GPIOC_BSRR_BR1_MASK 0x1u
+/* BR2: bits [18:18], write-only - Port x reset bit y (y = 0..3) */
+#define UTRA_GPIOC_BSRR_BR2_SHIFT 18
+#define UTRA_GPIOC_BSRR_BR2_WIDTH 1
+#define UTRA_GPIOC_BSRR_BR2_MASK 0x1u
+/* BR3: bits [19:19], write-only - Port x reset bit y (y = 0..3) */
+#define UTRA_GPIOC_BSRR_BR3_SHIFT 19
+#define UTRA_GPIOC_BSRR_BR3_WIDTH 1
+#define UTRA_GPIOC_BSRR_BR3_MASK 0x1u
+/* BS0: bits [0:0], write-only - Port x set bit y (y = 0..3) */
+#define UTRA_GPIOC_BSRR_BS0_SHIFT 0
+#define UTRA_GPIOC_BSRR_BS0_WIDTH 1
+#define UTRA_GPIOC_BSRR_BS0_MASK 0x1u
+/* BS1: bits [1:1], write-only - Port x set bit y (y = 0..3) */
+#define UTRA_GPIOC_BSRR_BS1_SHIFT 1
+#define UTRA_GPIOC_BSRR_BS1_WIDTH 1
+#define UTRA_GPIOC_BSRR_BS1_MASK 0x1u
+/* BS2: bits [2:2], write-only - Port x set bit y (y = 0..3) */
+#define UTRA_GPIOC_BSRR_BS2_SHIFT 2
+#define UTRA_GPIOC_BSRR_BS2_WIDTH 1
+#define UTRA_GPIOC_BSRR_BS2_MASK 0x1u
+/* BS3: bits [3:3], write-only - Port x set bit y (y = 0..3) */
+#define UTRA_GPIOC_BSRR_BS3_SHIFT 3
+#define UTRA_GPIOC_BSRR_BS3_WIDTH 1
+#define UTRA_GPIOC_BSRR_BS3_MASK 0x1u
+
+#endif /* UTRA_GENERATED_H */
@@ -0,0 +1,1005 @@
+
+use core::convert::TryInto;
+use core::marker::PhantomData;
+/// Access marker for registers and fields that may only be read
+pub struct RO;
+/// Access marker for registers and fields that may only be written
+pub struct WO;
+/// Access marker for registers and fields that may be read and written
+pub struct RW;
+/// Implemented by access markers that allow reading
+pub trait Readable {}
+/// Implemented by access markers that allow writing
+pub trait Writable {}
+impl Readable for RO {}
+impl Readable for RW {}
+impl Writable for WO {}
+impl Writable for RW {}
+pub struct Register<A = RW> {
+    /// Offset of this register within this CSR
+    offset: usize,
+    /// Value of this register after the peripheral is reset
+    reset: usize,
+    access: PhantomData<A>,
+}
+impl<A> Register<A> {
+    pub const fn new(offset: usize, reset: usize) -> Register<A> {
+        Register {
+            offset,
+            reset,
+            access: PhantomData,
+        }
+    }
+    /// Offset of this register, in words, from the start of the CSR
+    pub const fn offset(&self) -> usize {
+        self.offset
+    }
+    /// Value of this register after the peripheral is reset
+    pub const fn reset_value(&self) -> usize {
+        self.reset
+    }
+}
+impl<A> Clone for Register<A> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<A> Copy for Register<A> {}
+pub struct Field<A = RW> {
+    /// A bitmask we use to AND to the value, unshifted.
+    /// E.g. for a width of `3` bits, this mask would be 0b111.
+    mask: usize,
+    /// Offset of the first bit in this field
+    offset: usize,
+    /// A copy of the register address that this field
+    /// is a member of. Ideally this is optimized out by the
+    /// compiler.
+    register: Register<A>,
+}
+impl<A> Field<A> {
+    /// Define a new CSR field with the given width at a specified
+    /// offset from the start of the register.
+    pub const fn new(width: usize, offset: usize, register: Register<A>) -> Field<A> {
+        // Asserts don't work in const fn yet.
+        // assert!(width != 0, "field width cannot be 0");
+        // assert!((width + offset) < 32, "field with and offset must fit within a 32-bit value");
+        // It would be lovely if we could call `usize::pow()` in a const fn.
+        let mask = match width {
+            0 => 0,
+            1 => 1,
+            2 => 3,
+            3 => 7,
+            4 => 15,
+            5 => 31,
+            6 => 63,
+            7 => 127,
+            8 => 255,
+            9 => 511,
+            10 => 1023,
+            11 => 2047,
+            12 => 4095,
+            13 => 8191,
+            14 => 16383,
+            15 => 32767,
+            16 => 65535,
+            17 => 131071,
+            18 => 262143,
+            19 => 524287,
+            20 => 1048575,
+            21 => 2097151,
+            22 => 4194303,
+            23 => 8388607,
+            24 => 16777215,
+            25 => 33554431,
+            26 => 67108863,
+            27 => 134217727,
+            28 => 268435455,
+            29 => 536870911,
+            30 => 1073741823,
+            31 => 2147483647,
+            32 => 4294967295,
+            _ => 0,
+        };
+        Field {
+            mask,
+            offset,
+            register,
+        }
+    }
+    /// Value of this field after the peripheral is reset
+    pub const fn reset_value(&self) -> usize {
+        (self.register.reset >> self.offset) & self.mask
+    }
+}
+impl<A> Clone for Field<A> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<A> Copy for Field<A> {}
+/// Implemented by the enums generated for fields with enumerated values.
+pub trait FieldValue: Sized {
+    type Access;
+    /// The field these are the values of
+    const FIELD: Field<Self::Access>;
+    /// Convert the contents of the field, returning `None` if the value
+    /// isn't one of those listed.
+    fn from_bits(bits: usize) -> Option<Self>;
+    fn bits(self) -> usize;
+}
+pub struct CSR<T> {
+    base: *mut T,
+}
+impl<T> CSR<T>
+where
+    T: core::convert::TryFrom<usize> + core::convert::TryInto<usize> + core::default::Default,
+{
+    pub fn new(base: *mut T) -> Self {
+        CSR { base }
+    }
+    /// Read the word `offset` words from the base of this CSR
+    #[cfg(not(feature = "mock"))]
+    fn read_word(&self, offset: usize) -> usize {
+        let usize_base: *mut usize = unsafe { core::mem::transmute(self.base) };
+        unsafe { usize_base.add(offset).read_volatile() }
+    }
+    /// Write the word `offset` words from the base of this CSR
+    #[cfg(not(feature = "mock"))]
+    fn write_word(&mut self, offset: usize, value: usize) {
+        let usize_base: *mut usize = unsafe { core::mem::transmute(self.base) };
+        unsafe { usize_base.add(offset).write_volatile(value) };
+    }
+    #[cfg(feature = "mock")]
+    fn read_word(&self, offset: usize) -> usize {
+        mock::read(self.base as usize, offset)
+    }
+    #[cfg(feature = "mock")]
+    fn write_word(&mut self, offset: usize, value: usize) {
+        mock::write(self.base as usize, offset, value)
+    }
+    /// Read the contents of this register
+    pub fn r<A: Readable>(&self, reg: Register<A>) -> T {
+        self.read_word(reg.offset).try_into().unwrap_or_default()
+    }
+    /// Read a field from this CSR
+    pub fn rf<A: Readable>(&self, field: Field<A>) -> T {
+        ((self.read_word(field.register.offset) >> field.offset) & field.mask)
+            .try_into()
+            .unwrap_or_default()
+    }
+    /// Read-modify-write a given field in this CSR
+    pub fn rmwf<A: Readable + Writable>(&mut self, field: Field<A>, value: T) {
+        let value_as_usize: usize = value.try_into().unwrap_or_default() << field.offset;
+        let previous = self.read_word(field.register.offset) & !field.mask;
+        self.write_word(field.register.offset, previous | value_as_usize);
+    }
+    /// Write a given field without reading it first
+    pub fn wfo<A: Writable>(&mut self, field: Field<A>, value: T) {
+        let value_as_usize: usize = (value.try_into().unwrap_or_default() & field.mask) << field.offset;
+        self.write_word(field.register.offset, value_as_usize);
+    }
+    /// Write the entire contents of a register without reading it first
+    pub fn wo<A: Writable>(&mut self, reg: Register<A>, value: T) {
+        let value_as_usize: usize = value.try_into().unwrap_or_default();
+        self.write_word(reg.offset, value_as_usize);
+    }
+    /// Zero a field from a provided value
+    pub fn zf<A>(&mut self, field: Field<A>, value: T) -> T {
+        let value_as_usize: usize = value.try_into().unwrap_or_default();
+        (value_as_usize & !(field.mask << field.offset))
+            .try_into()
+            .unwrap_or_default()
+    }
+    /// Shift & mask a value to its final field position
+    pub fn ms<A>(&mut self, field: Field<A>, value: T) -> T {
+        let value_as_usize: usize = value.try_into().unwrap_or_default();
+        ((value_as_usize & field.mask) << field.offset)
+            .try_into()
+            .unwrap_or_default()
+    }
+    /// Write the value a register has after the peripheral is reset
+    pub fn reset<A: Writable>(&mut self, reg: Register<A>) {
+        self.write_word(reg.offset, reg.reset);
+    }
+    /// Read a field with enumerated values, returning `None` if it holds
+    /// a value that isn't listed
+    pub fn rfv<E: FieldValue>(&self) -> Option<E>
+    where
+        E::Access: Readable,
+    {
+        let field = E::FIELD;
+        E::from_bits((self.read_word(field.register.offset) >> field.offset) & field.mask)
+    }
+    /// Write a field with enumerated values without reading it first
+    pub fn wfov<E: FieldValue>(&mut self, value: E)
+    where
+        E::Access: Writable,
+    {
+        let field = E::FIELD;
+        let value_as_usize = (value.bits() & field.mask) << field.offset;
+        self.write_word(field.register.offset, value_as_usize);
+    }
+    /// Read-modify-write a field with enumerated values
+    pub fn rmwfv<E: FieldValue>(&mut self, value: E)
+    where
+        E::Access: Readable + Writable,
+    {
+        let field = E::FIELD;
+        let value_as_usize = (value.bits() & field.mask) << field.offset;
+        let previous = self.read_word(field.register.offset) & !(field.mask << field.offset);
+        self.write_word(field.register.offset, previous | value_as_usize);
+    }
+}
+// Physical base addresses of memory regions
+
+// Physical base addresses of registers
+pub const HW_GPIOA_BASE :   usize = 0x40020000;
+pub const HW_GPIOB_BASE :   usize = 0x40020400;
+pub const HW_GPIOC_BASE :   usize = 0x40020800;
+
+pub mod utra {
+
+    /// General-purpose I/Os
+    pub mod gpioa {
+
+        /// GPIO port mode register
+        pub const MODER: crate::Register<crate::RW> = crate::Register::new(0, 0xa8000000);
+        /// Port x configuration bits (y = 0..3)
+        pub const MODER_MODER0: crate::Field<crate::RW> = crate::Field::new(2, 0, MODER);
+        /// Port x configuration bits (y = 0..3)
+        pub const MODER_MODER1: crate::Field<crate::RW> = crate::Field::new(2, 2, MODER);
+        /// Port x configuration bits (y = 0..3)
+        pub const MODER_MODER2: crate::Field<crate::RW> = crate::Field::new(2, 4, MODER);
+        /// Port x configuration bits (y = 0..3)
+        pub const MODER_MODER3: crate::Field<crate::RW> = crate::Field::new(2, 6, MODER);
+        /// Values of `MODER_MODER0`
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum ModerModer0 {
+            /// Input mode (reset state)
+            Input = 0,
+            /// General purpose output mode
+            Output = 1,
+            /// Alternate function mode
+            Alternate = 2,
+            /// Analog mode
+            Analog = 3,
+        }
+        impl crate::FieldValue for ModerModer0 {
+            type Access = crate::RW;
+            const FIELD: crate::Field<crate::RW> = MODER_MODER0;
+            fn from_bits(bits: usize) -> Option<Self> {
+                match bits {
+                    0 => Some(ModerModer0::Input),
+                    1 => Some(ModerModer0::Output),
+                    2 => Some(ModerModer0::Alternate),
+                    3 => Some(ModerModer0::Analog),
+                    _ => None,
+                }
+            }
+            fn bits(self) -> usize {
+                self as usize
+            }
+        }
+        /// Values of `MODER_MODER1`
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum ModerModer1 {
+            /// Input mode (reset state)
+            Input = 0,
+            /// General purpose output mode
+            Output = 1,
+            /// Alternate function mode
+            Alternate = 2,
+            /// Analog mode
+            Analog = 3,
+        }
+        impl crate::FieldValue for ModerModer1 {
+            type Access = crate::RW;
+            const FIELD: crate::Field<crate::RW> = MODER_MODER1;
+            fn from_bits(bits: usize) -> Option<Self> {
+                match bits {
+                    0 => Some(ModerModer1::Input),
+                    1 => Some(ModerModer1::Output),
+                    2 => Some(ModerModer1::Alternate),
+                    3 => Some(ModerModer1::Analog),
+                    _ => None,
+                }
+            }
+            fn bits(self) -> usize {
+                self as usize
+            }
+        }
+        /// Values of `MODER_MODER2`
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum ModerModer2 {
+            /// Input mode (reset state)
+            Input = 0,
+            /// General purpose output mode
+            Output = 1,
+            /// Alternate function mode
+            Alternate = 2,
+            /// Analog mode
+            Analog = 3,
+        }
+        impl crate::FieldValue for ModerModer2 {
+            type Access = crate::RW;
+            const FIELD: crate::Field<crate::RW> = MODER_MODER2;
+            fn from_bits(bits: usize) -> Option<Self> {
+                match bits {
+                    0 => Some(ModerModer2::Input),
+                    1 => Some(ModerModer2::Output),
+                    2 => Some(ModerModer2::Alternate),
+                    3 => Some(ModerModer2::Analog),
+                    _ => None,
+                }
+            }
+            fn bits(self) -> usize {
+                self as usize
+            }
+        }
+        /// Values of `MODER_MODER3`
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum ModerModer3 {
+            /// Input mode (reset state)
+            Input = 0,
+            /// General purpose output mode
+            Output = 1,
+            /// Alternate function mode
+            Alternate = 2,
+            /// Analog mode
+            Analog = 3,
+        }
+        impl crate::FieldValue for ModerModer3 {
+            type Access = crate::RW;
+            const FIELD: crate::Field<crate::RW> = MODER_MODER3;
+            fn from_bits(bits: usize) -> Option<Self> {
+                match bits {
+                    0 => Some(ModerModer3::Input),
+                    1 => Some(ModerModer3::Output),
+                    2 => Some(ModerModer3::Alternate),
+                    3 => Some(ModerModer3::Analog),
+                    _ => None,
+                }
+            }
+            fn bits(self) -> usize {
+                self as usize
+            }
+        }
+
+        /// GPIO port input data register
+        pub const IDR: crate::Register<crate::RO> = crate::Register::new(4, 0x0);
+        /// Port input data (y = 0..3)
+        pub const IDR_IDR0: crate::Field<crate::RO> = crate::Field::new(1, 0, IDR);
+        /// Port input data (y = 0..3)
+        pub const IDR_IDR1: crate::Field<crate::RO> = crate::Field::new(1, 1, IDR);
+        /// Port input data (y = 0..3)
+        pub const IDR_IDR2: crate::Field<crate::RO> = crate::Field::new(1, 2, IDR);
+        /// Port input data (y = 0..3)
+        pub const IDR_IDR3: crate::Field<crate::RO> = crate::Field::new(1, 3, IDR);
+
+        /// GPIO port bit set/reset register
+        pub const BSRR: crate::Register<crate::WO> = crate::Register::new(6, 0x0);
+        /// Port x reset bit y (y = 0..3)
+        pub const BSRR_BR0: crate::Field<crate::WO> = crate::Field::new(1, 16, BSRR);
+        /// Port x reset bit y (y = 0..3)
+        pub const BSRR_BR1: crate::Field<crate::WO> = crate::Field::new(1, 17, BSRR);
+        /// Port x reset bit y (y = 0..3)
+        pub const BSRR_BR2: crate::Field<crate::WO> = crate::Field::new(1, 18, BSRR);
+        /// Port x reset bit y (y = 0..3)
+        pub const BSRR_BR3: crate::Field<crate::WO> = crate::Field::new(1, 19, BSRR);
+        /// Port x set bit y (y = 0..3)
+        pub const BSRR_BS0: crate::Field<crate::WO> = crate::Field::new(1, 0, BSRR);
+        /// Port x set bit y (y = 0..3)
+        pub const BSRR_BS1: crate::Field<crate::WO> = crate::Field::new(1, 1, BSRR);
+        /// Port x set bit y (y = 0..3)
+        pub const BSRR_BS2: crate::Field<crate::WO> = crate::Field::new(1, 2, BSRR);
+        /// Port x set bit y (y = 0..3)
+        pub const BSRR_BS3: crate::Field<crate::WO> = crate::Field::new(1, 3, BSRR);
+
+        pub const HW_GPIOA_BASE: usize = 0x40020000;
+    }
+
+    /// General-purpose I/Os
+    pub mod gpiob {
+
+        /// GPIO port mode register
+        pub const MODER: crate::Register<crate::RW> = crate::Register::new(0, 0x280);
+        /// Port x configuration bits (y = 0..3)
+        pub const MODER_MODER0: crate::Field<crate::RW> = crate::Field::new(2, 0, MODER);
+        /// Port x configuration bits (y = 0..3)
+        pub const MODER_MODER1: crate::Field<crate::RW> = crate::Field::new(2, 2, MODER);
+        /// Port x configuration bits (y = 0..3)
+        pub const MODER_MODER2: crate::Field<crate::RW> = crate::Field::new(2, 4, MODER);
+        /// Port x configuration bits (y = 0..3)
+        pub const MODER_MODER3: crate::Field<crate::RW> = crate::Field::new(2, 6, MODER);
+
+        /// GPIO port input data register
+        pub const IDR: crate::Register<crate::RO> = crate::Register::new(4, 0x0);
+        /// Port input data (y = 0..3)
+        pub const IDR_IDR0: crate::Field<crate::RO> = crate::Field::new(1, 0, IDR);
+        /// Port input data (y = 0..3)
+        pub const IDR_IDR1: crate::Field<crate::RO> = crate::Field::new(1, 1, IDR);
+        /// Port input data (y = 0..3)
+        pub const IDR_IDR2: crate::Field<crate::RO> = crate::Field::new(1, 2, IDR);
+        /// Port input data (y = 0..3)
+        pub const IDR_IDR3: crate::Field<crate::RO> = crate::Field::new(1, 3, IDR);
+
+        /// GPIO port bit set/reset register
+        pub const BSRR: crate::Register<crate::WO> = crate::Register::new(6, 0x0);
+        /// Port x reset bit y (y = 0..3)
+        pub const BSRR_BR0: crate::Field<crate::WO> = crate::Field::new(1, 16, BSRR);
+        /// Port x reset bit y (y = 0..3)
+        pub const BSRR_BR1: crate::Field<crate::WO> = crate::Field::new(1, 17, BSRR);
+        /// Port x reset bit y (y = 0..3)
+        pub const BSRR_BR2: crate::Field<crate::WO> = crate::Field::new(1, 18, BSRR);
+        /// Port x reset bit y (y = 0..3)
+        pub const BSRR_BR3: crate::Field<crate::WO> = crate::Field::new(1, 19, BSRR);
+        /// Port x set bit y (y = 0..3)
+        pub const BSRR_BS0: crate::Field<crate::WO> = crate::Field::new(1, 0, BSRR);
+        /// Port x set bit y (y = 0..3)
+        pub const BSRR_BS1: crate::Field<crate::WO> = crate::Field::new(1, 1, BSRR);
+        /// Port x set bit y (y = 0..3)
+        pub const BSRR_BS2: crate::Field<crate::WO> = crate::Field::new(1, 2, BSRR);
+        /// Port x set bit y (y = 0..3)
+        pub const BSRR_BS3: crate::Field<crate::WO> = crate::Field::new(1, 3, BSRR);
+
+        pub const HW_GPIOB_BASE: usize = 0x40020400;
+    }
+
+    /// General-purpose I/Os
+    pub mod gpioc {
+
+        /// GPIO port mode register
+        pub const MODER: crate::Register<crate::RW> = crate::Register::new(0, 0xa8000000);
+        /// Port x configuration bits (y = 0..3)
+        pub const MODER_MODER0: crate::Field<crate::RW> = crate::Field::new(2, 0, MODER);
+        /// Port x configuration bits (y = 0..3)
+        pub const MODER_MODER1: crate::Field<crate::RW> = crate::Field::new(2, 2, MODER);
+        /// Port x configuration bits (y = 0..3)
+        pub const MODER_MODER2: crate::Field<crate::RW> = crate::Field::new(2, 4, MODER);
+        /// Port x configuration bits (y = 0..3)
+        pub const MODER_MODER3: crate::Field<crate::RW> = crate::Field::new(2, 6, MODER);
+        /// Values of `MODER_MODER0`
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum ModerModer0 {
+            /// Input mode (reset state)
+            Input = 0,
+            /// General purpose output mode
+            Output = 1,
+            /// Alternate function mode
+            Alternate = 2,
+            /// Analog mode
+            Analog = 3,
+        }
+        impl crate::FieldValue for ModerModer0 {
+            type Access = crate::RW;
+            const FIELD: crate::Field<crate::RW> = MODER_MODER0;
+            fn from_bits(bits: usize) -> Option<Self> {
+                match bits {
+                    0 => Some(ModerModer0::Input),
+                    1 => Some(ModerModer0::Output),
+                    2 => Some(ModerModer0::Alternate),
+                    3 => Some(ModerModer0::Analog),
+                    _ => None,
+                }
+            }
+            fn bits(self) -> usize {
+                self as usize
+            }
+        }
+        /// Values of `MODER_MODER1`
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum ModerModer1 {
+            /// Input mode (reset state)
+            Input = 0,
+            /// General purpose output mode
+            Output = 1,
+            /// Alternate function mode
+            Alternate = 2,
+            /// Analog mode
+            Analog = 3,
+        }
+        impl crate::FieldValue for ModerModer1 {
+            type Access = crate::RW;
+            const FIELD: crate::Field<crate::RW> = MODER_MODER1;
+            fn from_bits(bits: usize) -> Option<Self> {
+                match bits {
+                    0 => Some(ModerModer1::Input),
+                    1 => Some(ModerModer1::Output),
+                    2 => Some(ModerModer1::Alternate),
+                    3 => Some(ModerModer1::Analog),
+                    _ => None,
+                }
+            }
+            fn bits(self) -> usize {
+                self as usize
+            }
+        }
+        /// Values of `MODER_MODER2`
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum ModerModer2 {
+            /// Input mode (reset state)
+            Input = 0,
+            /// General purpose output mode
+            Output = 1,
+            /// Alternate function mode
+            Alternate = 2,
+            /// Analog mode
+            Analog = 3,
+        }
+        impl crate::FieldValue for ModerModer2 {
+            type Access = crate::RW;
+            const FIELD: crate::Field<crate::RW> = MODER_MODER2;
+            fn from_bits(bits: usize) -> Option<Self> {
+                match bits {
+                    0 => Some(ModerModer2::Input),
+                    1 => Some(ModerModer2::Output),
+                    2 => Some(ModerModer2::Alternate),
+                    3 => Some(ModerModer2::Analog),
+                    _ => None,
+                }
+            }
+            fn bits(self) -> usize {
+                self as usize
+            }
+        }
+        /// Values of `MODER_MODER3`
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum ModerModer3 {
+            /// Input mode (reset state)
+            Input = 0,
+            /// General purpose output mode
+            Output = 1,
+            /// Alternate function mode
+            Alternate = 2,
+            /// Analog mode
+            Analog = 3,
+        }
+        impl crate::FieldValue for ModerModer3 {
+            type Access = crate::RW;
+            const FIELD: crate::Field<crate::RW> = MODER_MODER3;
+            fn from_bits(bits: usize) -> Option<Self> {
+                match bits {
+                    0 => Some(ModerModer3::Input),
+                    1 => Some(ModerModer3::Output),
+                    2 => Some(ModerModer3::Alternate),
+                    3 => Some(ModerModer3::Analog),
+                    _ => None,
+                }
+            }
+            fn bits(self) -> usize {
+                self as usize
+            }
+        }
+
+        /// GPIO port input data register
+        pub const IDR: crate::Register<crate::RO> = crate::Register::new(4, 0x0);
+        /// Port input data (y = 0..3)
+        pub const IDR_IDR0: crate::Field<crate::RO> = crate::Field::new(1, 0, IDR);
+        /// Port input data (y = 0..3)
+        pub const IDR_IDR1: crate::Field<crate::RO> = crate::Field::new(1, 1, IDR);
+        /// Port input data (y = 0..3)
+        pub const IDR_IDR2: crate::Field<crate::RO> = crate::Field::new(1, 2, IDR);
+        /// Port input data (y = 0..3)
+        pub const IDR_IDR3: crate::Field<crate::RO> = crate::Field::new(1, 3, IDR);
+
+        /// GPIO port bit set/reset register
+        pub const BSRR: crate::Register<crate::WO> = crate::Register::new(6, 0x0);
+        /// Port x reset bit y (y = 0..3)
+        pub const BSRR_BR0: crate::Field<crate::WO> = crate::Field::new(1, 16, BSRR);
+        /// Port x reset bit y (y = 0..3)
+        pub const BSRR_BR1: crate::Field<crate::WO> = crate::Field::new(1, 17, BSRR);
+        /// Port x reset bit y (y = 0..3)
+        pub const BSRR_BR2: crate::Field<crate::WO> = crate::Field::new(1, 18, BSRR);
+        /// Port x reset bit y (y = 0..3)
+        pub const BSRR_BR3: crate::Field<crate::WO> = crate::Field::new(1, 19, BSRR);
+        /// Port x set bit y (y = 0..3)
+        pub const BSRR_BS0: crate::Field<crate::WO> = crate::Field::new(1, 0, BSRR);
+        /// Port x set bit y (y = 0..3)
+        pub const BSRR_BS1: crate::Field<crate::WO> = crate::Field::new(1, 1, BSRR);
+        /// Port x set bit y (y = 0..3)
+        pub const BSRR_BS2: crate::Field<crate::WO> = crate::Field::new(1, 2, BSRR);
+        /// Port x set bit y (y = 0..3)
+        pub const BSRR_BS3: crate::Field<crate::WO> = crate::Field::new(1, 3, BSRR);
+
+        pub const HW_GPIOC_BASE: usize = 0x40020800;
+    }
+}
+
+/// In-memory stand-ins for peripherals, so that drivers can be tested on a
+/// host.  While a `mock::Peripheral` exists, a `CSR` created on the same
+/// thread with its base address reads and writes the mock rather than
+/// hardware.  Hooks may be attached to registers to model their side
+/// effects, such as sending a byte when `RXTX` is written.
+#[cfg(feature = "mock")]
+pub mod mock {
+    use super::{Field, Readable, Register, Writable};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// The contents of a mocked peripheral's registers.  These may be
+    /// changed regardless of the registers' access, to model hardware.
+    pub struct Registers {
+        words: Vec<usize>,
+    }
+    impl Registers {
+        pub fn r<A>(&self, reg: Register<A>) -> usize {
+            self.words[reg.offset]
+        }
+        pub fn wo<A>(&mut self, reg: Register<A>, value: usize) {
+            self.words[reg.offset] = value;
+        }
+        pub fn rf<A>(&self, field: Field<A>) -> usize {
+            (self.words[field.register.offset] >> field.offset) & field.mask
+        }
+        /// Replace the contents of a field, leaving the rest of the
+        /// register unchanged
+        pub fn wf<A>(&mut self, field: Field<A>, value: usize) {
+            let word = &mut self.words[field.register.offset];
+            *word = (*word & !(field.mask << field.offset)) | ((value & field.mask) << field.offset);
+        }
+    }
+
+    type ReadHook = Box<dyn FnMut(&mut Registers) -> usize>;
+    type WriteHook = Box<dyn FnMut(&mut Registers, usize)>;
+
+    struct State {
+        base: usize,
+        registers: Registers,
+        read_hooks: Vec<(usize, ReadHook)>,
+        write_hooks: Vec<(usize, WriteHook)>,
+    }
+
+    thread_local! {
+        static PERIPHERALS: RefCell<Vec<Rc<RefCell<State>>>> = RefCell::new(Vec::new());
+    }
+
+    pub struct Peripheral {
+        state: Rc<RefCell<State>>,
+    }
+    impl Peripheral {
+        /// Create a mock of `words` registers at `base`, holding zero apart
+        /// from the `(offset, value)` pairs in `reset`.
+        pub fn new(base: usize, words: usize, reset: &[(usize, usize)]) -> Peripheral {
+            let mut registers = Registers {
+                words: vec![0; words],
+            };
+            for &(offset, value) in reset {
+                registers.words[offset] = value;
+            }
+            let state = Rc::new(RefCell::new(State {
+                base,
+                registers,
+                read_hooks: Vec::new(),
+                write_hooks: Vec::new(),
+            }));
+            PERIPHERALS.with(|peripherals| {
+                let mut peripherals = peripherals.borrow_mut();
+                if peripherals.iter().any(|other| other.borrow().base == base) {
+                    panic!("a mock peripheral already exists at {:08x}", base);
+                }
+                peripherals.push(state.clone());
+            });
+            Peripheral { state }
+        }
+        pub fn base(&self) -> usize {
+            self.state.borrow().base
+        }
+        pub fn r<A>(&self, reg: Register<A>) -> usize {
+            self.state.borrow().registers.r(reg)
+        }
+        pub fn wo<A>(&self, reg: Register<A>, value: usize) {
+            self.state.borrow_mut().registers.wo(reg, value)
+        }
+        pub fn rf<A>(&self, field: Field<A>) -> usize {
+            self.state.borrow().registers.rf(field)
+        }
+        pub fn wf<A>(&self, field: Field<A>, value: usize) {
+            self.state.borrow_mut().registers.wf(field, value)
+        }
+        /// Produce the value read from `reg` with `hook` rather than
+        /// returning its contents.  Hooks must not use a `CSR` for the
+        /// same peripheral.
+        pub fn on_read<A: Readable, F: FnMut(&mut Registers) -> usize + 'static>(
+            &self,
+            reg: Register<A>,
+            hook: F,
+        ) {
+            let mut state = self.state.borrow_mut();
+            state.read_hooks.retain(|(offset, _)| *offset != reg.offset);
+            state.read_hooks.push((reg.offset, Box::new(hook)));
+        }
+        /// Pass values written to `reg` to `hook` rather than storing
+        /// them.  The hook may store them itself.
+        pub fn on_write<A: Writable, F: FnMut(&mut Registers, usize) + 'static>(
+            &self,
+            reg: Register<A>,
+            hook: F,
+        ) {
+            let mut state = self.state.borrow_mut();
+            state.write_hooks.retain(|(offset, _)| *offset != reg.offset);
+            state.write_hooks.push((reg.offset, Box::new(hook)));
+        }
+    }
+    impl Drop for Peripheral {
+        fn drop(&mut self) {
+            PERIPHERALS.with(|peripherals| {
+                peripherals
+                    .borrow_mut()
+                    .retain(|other| !Rc::ptr_eq(other, &self.state))
+            });
+        }
+    }
+
+    fn find(base: usize) -> Rc<RefCell<State>> {
+        PERIPHERALS.with(|peripherals| {
+            peripherals
+                .borrow()
+                .iter()
+                .find(|state| state.borrow().base == base)
+                .cloned()
+                .unwrap_or_else(|| panic!("no mock peripheral at {:08x}", base))
+        })
+    }
+
+    pub(crate) fn read(base: usize, offset: usize) -> usize {
+        let state = find(base);
+        let mut state = state.borrow_mut();
+        let State {
+            registers,
+            read_hooks,
+            ..
+        } = &mut *state;
+        match read_hooks.iter_mut().find(|(hooked, _)| *hooked == offset) {
+            Some((_, hook)) => hook(registers),
+            None => registers.words[offset],
+        }
+    }
+
+    pub(crate) fn write(base: usize, offset: usize, value: usize) {
+        let state = find(base);
+        let mut state = state.borrow_mut();
+        let State {
+            registers,
+            write_hooks,
+            ..
+        } = &mut *state;
+        match write_hooks.iter_mut().find(|(hooked, _)| *hooked == offset) {
+            Some((_, hook)) => hook(registers, value),
+            None => registers.words[offset] = value,
+        }
+    }
+
+    /// A mock of `gpioa` at `HW_GPIOA_BASE`
+    pub fn gpioa() -> Peripheral {
+        Peripheral::new(super::HW_GPIOA_BASE, 256, &[(0, 0xa8000000)])
+    }
+
+    /// A mock of `gpiob` at `HW_GPIOB_BASE`
+    pub fn gpiob() -> Peripheral {
+        Peripheral::new(super::HW_GPIOB_BASE, 256, &[(0, 0x280)])
+    }
+
+    /// A mock of `gpioc` at `HW_GPIOC_BASE`
+    pub fn gpioc() -> Peripheral {
+        Peripheral::new(super::HW_GPIOC_BASE, 256, &[(0, 0xa8000000)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    #[ignore]
+    fn compile_check() {
+        use super::*;
+        let mut gpioa_csr = CSR::new(HW_GPIOA_BASE as *mut u32);
+
+        let foo = gpioa_csr.r(utra::gpioa::MODER);
+        gpioa_csr.wo(utra::gpioa::MODER, foo);
+        gpioa_csr.reset(utra::gpioa::MODER);
+        let bar = gpioa_csr.rf(utra::gpioa::MODER_MODER0);
+        gpioa_csr.rmwf(utra::gpioa::MODER_MODER0, bar);
+        let mut baz = gpioa_csr.zf(utra::gpioa::MODER_MODER0, bar);
+        baz |= gpioa_csr.ms(utra::gpioa::MODER_MODER0, 1);
+        gpioa_csr.wfo(utra::gpioa::MODER_MODER0, baz);
+        let _: Option<utra::gpioa::ModerModer0> = gpioa_csr.rfv();
+        gpioa_csr.wfov(utra::gpioa::ModerModer0::Input);
+        gpioa_csr.rmwfv(utra::gpioa::ModerModer0::Input);
+        let bar = gpioa_csr.rf(utra::gpioa::MODER_MODER1);
+        gpioa_csr.rmwf(utra::gpioa::MODER_MODER1, bar);
+        let mut baz = gpioa_csr.zf(utra::gpioa::MODER_MODER1, bar);
+        baz |= gpioa_csr.ms(utra::gpioa::MODER_MODER1, 1);
+        gpioa_csr.wfo(utra::gpioa::MODER_MODER1, baz);
+        let _: Option<utra::gpioa::ModerModer1> = gpioa_csr.rfv();
+        gpioa_csr.wfov(utra::gpioa::ModerModer1::Input);
+        gpioa_csr.rmwfv(utra::gpioa::ModerModer1::Input);
+        let bar = gpioa_csr.rf(utra::gpioa::MODER_MODER2);
+        gpioa_csr.rmwf(utra::gpioa::MODER_MODER2, bar);
+        let mut baz = gpioa_csr.zf(utra::gpioa::MODER_MODER2, bar);
+        baz |= gpioa_csr.ms(utra::gpioa::MODER_MODER2, 1);
+        gpioa_csr.wfo(utra::gpioa::MODER_MODER2, baz);
+        let _: Option<utra::gpioa::ModerModer2> = gpioa_csr.rfv();
+        gpioa_csr.wfov(utra::gpioa::ModerModer2::Input);
+        gpioa_csr.rmwfv(utra::gpioa::ModerModer2::Input);
+        let bar = gpioa_csr.rf(utra::gpioa::MODER_MODER3);
+        gpioa_csr.rmwf(utra::gpioa::MODER_MODER3, bar);
+        let mut baz = gpioa_csr.zf(utra::gpioa::MODER_MODER3, bar);
+        baz |= gpioa_csr.ms(utra::gpioa::MODER_MODER3, 1);
+        gpioa_csr.wfo(utra::gpioa::MODER_MODER3, baz);
+        let _: Option<utra::gpioa::ModerModer3> = gpioa_csr.rfv();
+        gpioa_csr.wfov(utra::gpioa::ModerModer3::Input);
+        gpioa_csr.rmwfv(utra::gpioa::ModerModer3::Input);
+
+        let _ = gpioa_csr.r(utra::gpioa::IDR);
+        let bar = gpioa_csr.rf(utra::gpioa::IDR_IDR0);
+        let _ = gpioa_csr.zf(utra::gpioa::IDR_IDR0, bar) | gpioa_csr.ms(utra::gpioa::IDR_IDR0, 1);
+        let bar = gpioa_csr.rf(utra::gpioa::IDR_IDR1);
+        let _ = gpioa_csr.zf(utra::gpioa::IDR_IDR1, bar) | gpioa_csr.ms(utra::gpioa::IDR_IDR1, 1);
+        let bar = gpioa_csr.rf(utra::gpioa::IDR_IDR2);
+        let _ = gpioa_csr.zf(utra::gpioa::IDR_IDR2, bar) | gpioa_csr.ms(utra::gpioa::IDR_IDR2, 1);
+        let bar = gpioa_csr.rf(utra::gpioa::IDR_IDR3);
+        let _ = gpioa_csr.zf(utra::gpioa::IDR_IDR3, bar) | gpioa_csr.ms(utra::gpioa::IDR_IDR3, 1);
+
+        gpioa_csr.wo(utra::gpioa::BSRR, 0);
+        gpioa_csr.reset(utra::gpioa::BSRR);
+        let mut baz = gpioa_csr.zf(utra::gpioa::BSRR_BR0, 0);
+        baz |= gpioa_csr.ms(utra::gpioa::BSRR_BR0, 1);
+        gpioa_csr.wfo(utra::gpioa::BSRR_BR0, baz);
+        let mut baz = gpioa_csr.zf(utra::gpioa::BSRR_BR1, 0);
+        baz |= gpioa_csr.ms(utra::gpioa::BSRR_BR1, 1);
+        gpioa_csr.wfo(utra::gpioa::BSRR_BR1, baz);
+        let mut baz = gpioa_csr.zf(utra::gpioa::BSRR_BR2, 0);
+        baz |= gpioa_csr.ms(utra::gpioa::BSRR_BR2, 1);
+        gpioa_csr.wfo(utra::gpioa::BSRR_BR2, baz);
+        let mut baz = gpioa_csr.zf(utra::gpioa::BSRR_BR3, 0);
+        baz |= gpioa_csr.ms(utra::gpioa::BSRR_BR3, 1);
+        gpioa_csr.wfo(utra::gpioa::BSRR_BR3, baz);
+        let mut baz = gpioa_csr.zf(utra::gpioa::BSRR_BS0, 0);
+        baz |= gpioa_csr.ms(utra::gpioa::BSRR_BS0, 1);
+        gpioa_csr.wfo(utra::gpioa::BSRR_BS0, baz);
+        let mut baz = gpioa_csr.zf(utra::gpioa::BSRR_BS1, 0);
+        baz |= gpioa_csr.ms(utra::gpioa::BSRR_BS1, 1);
+        gpioa_csr.wfo(utra::gpioa::BSRR_BS1, baz);
+        let mut baz = gpioa_csr.zf(utra::gpioa::BSRR_BS2, 0);
+        baz |= gpioa_csr.ms(utra::gpioa::BSRR_BS2, 1);
+        gpioa_csr.wfo(utra::gpioa::BSRR_BS2, baz);
+        let mut baz = gpioa_csr.zf(utra::gpioa::BSRR_BS3, 0);
+        baz |= gpioa_csr.ms(utra::gpioa::BSRR_BS3, 1);
+        gpioa_csr.wfo(utra::gpioa::BSRR_BS3, baz);
+        let mut gpiob_csr = CSR::new(HW_GPIOB_BASE as *mut u32);
+
+        let foo = gpiob_csr.r(utra::gpiob::MODER);
+        gpiob_csr.wo(utra::gpiob::MODER, foo);
+        gpiob_csr.reset(utra::gpiob::MODER);
+        let bar = gpiob_csr.rf(utra::gpiob::MODER_MODER0);
+        gpiob_csr.rmwf(utra::gpiob::MODER_MODER0, bar);
+        let mut baz = gpiob_csr.zf(utra::gpiob::MODER_MODER0, bar);
+        baz |= gpiob_csr.ms(utra::gpiob::MODER_MODER0, 1);
+        gpiob_csr.wfo(utra::gpiob::MODER_MODER0, baz);
+        let bar = gpiob_csr.rf(utra::gpiob::MODER_MODER1);
+        gpiob_csr.rmwf(utra::gpiob::MODER_MODER1, bar);
+        let mut baz = gpiob_csr.zf(utra::gpiob::MODER_MODER1, bar);
+        baz |= gpiob_csr.ms(utra::gpiob::MODER_MODER1, 1);
+        gpiob_csr.wfo(utra::gpiob::MODER_MODER1, baz);
+        let bar = gpiob_csr.rf(utra::gpiob::MODER_MODER2);
+        gpiob_csr.rmwf(utra::gpiob::MODER_MODER2, bar);
+        let mut baz = gpiob_csr.zf(utra::gpiob::MODER_MODER2, bar);
+        baz |= gpiob_csr.ms(utra::gpiob::MODER_MODER2, 1);
+        gpiob_csr.wfo(utra::gpiob::MODER_MODER2, baz);
+        let bar = gpiob_csr.rf(utra::gpiob::MODER_MODER3);
+        gpiob_csr.rmwf(utra::gpiob::MODER_MODER3, bar);
+        let mut baz = gpiob_csr.zf(utra::gpiob::MODER_MODER3, bar);
+        baz |= gpiob_csr.ms(utra::gpiob::MODER_MODER3, 1);
+        gpiob_csr.wfo(utra::gpiob::MODER_MODER3, baz);
+
+        let _ = gpiob_csr.r(utra::gpiob::IDR);
+        let bar = gpiob_csr.rf(utra::gpiob::IDR_IDR0);
+        let _ = gpiob_csr.zf(utra::gpiob::IDR_IDR0, bar) | gpiob_csr.ms(utra::gpiob::IDR_IDR0, 1);
+        let bar = gpiob_csr.rf(utra::gpiob::IDR_IDR1);
+        let _ = gpiob_csr.zf(utra::gpiob::IDR_IDR1, bar) | gpiob_csr.ms(utra::gpiob::IDR_IDR1, 1);
+        let bar = gpiob_csr.rf(utra::gpiob::IDR_IDR2);
+        let _ = gpiob_csr.zf(utra::gpiob::IDR_IDR2, bar) | gpiob_csr.ms(utra::gpiob::IDR_IDR2, 1);
+        let bar = gpiob_csr.rf(utra::gpiob::IDR_IDR3);
+        let _ = gpiob_csr.zf(utra::gpiob::IDR_IDR3, bar) | gpiob_csr.ms(utra::gpiob::IDR_IDR3, 1);
+
+        gpiob_csr.wo(utra::gpiob::BSRR, 0);
+        gpiob_csr.reset(utra::gpiob::BSRR);
+        let mut baz = gpiob_csr.zf(utra::gpiob::BSRR_BR0, 0);
+        baz |= gpiob_csr.ms(utra::gpiob::BSRR_BR0, 1);
+        gpiob_csr.wfo(utra::gpiob::BSRR_BR0, baz);
+        let mut baz = gpiob_csr.zf(utra::gpiob::BSRR_BR1, 0);
+        baz |= gpiob_csr.ms(utra::gpiob::BSRR_BR1, 1);
+        gpiob_csr.wfo(utra::gpiob::BSRR_BR1, baz);
+        let mut baz = gpiob_csr.zf(utra::gpiob::BSRR_BR2, 0);
+        baz |= gpiob_csr.ms(utra::gpiob::BSRR_BR2, 1);
+        gpiob_csr.wfo(utra::gpiob::BSRR_BR2, baz);
+        let mut baz = gpiob_csr.zf(utra::gpiob::BSRR_BR3, 0);
+        baz |= gpiob_csr.ms(utra::gpiob::BSRR_BR3, 1);
+        gpiob_csr.wfo(utra::gpiob::BSRR_BR3, baz);
+        let mut baz = gpiob_csr.zf(utra::gpiob::BSRR_BS0, 0);
+        baz |= gpiob_csr.ms(utra::gpiob::BSRR_BS0, 1);
+        gpiob_csr.wfo(utra::gpiob::BSRR_BS0, baz);
+        let mut baz = gpiob_csr.zf(utra::gpiob::BSRR_BS1, 0);
+        baz |= gpiob_csr.ms(utra::gpiob::BSRR_BS1, 1);
+        gpiob_csr.wfo(utra::gpiob::BSRR_BS1, baz);
+        let mut baz = gpiob_csr.zf(utra::gpiob::BSRR_BS2, 0);
+        baz |= gpiob_csr.ms(utra::gpiob::BSRR_BS2, 1);
+        gpiob_csr.wfo(utra::gpiob::BSRR_BS2, baz);
+        let mut baz = gpiob_csr.zf(utra::gpiob::BSRR_BS3, 0);
+        baz |= gpiob_csr.ms(utra::gpiob::BSRR_BS3, 1);
+        gpiob_csr.wfo(utra::gpiob::BSRR_BS3, baz);
+        let mut gpioc_csr = CSR::new(HW_GPIOC_BASE as *mut u32);
+
+        let foo = gpioc_csr.r(utra::gpioc::MODER);
+        gpioc_csr.wo(utra::gpioc::MODER, foo);
+        gpioc_csr.reset(utra::gpioc::MODER);
+        let bar = gpioc_csr.rf(utra::gpioc::MODER_MODER0);
+        gpioc_csr.rmwf(utra::gpioc::MODER_MODER0, bar);
+        let mut baz = gpioc_csr.zf(utra::gpioc::MODER_MODER0, bar);
+        baz |= gpioc_csr.ms(utra::gpioc::MODER_MODER0, 1);
+        gpioc_csr.wfo(utra::gpioc::MODER_MODER0, baz);
+        let _: Option<utra::gpioc::ModerModer0> = gpioc_csr.rfv();
+        gpioc_csr.wfov(utra::gpioc::ModerModer0::Input);
+        gpioc_csr.rmwfv(utra::gpioc::ModerModer0::Input);
+        let bar = gpioc_csr.rf(utra::gpioc::MODER_MODER1);
+        gpioc_csr.rmwf(utra::gpioc::MODER_MODER1, bar);
+        let mut baz = gpioc_csr.zf(utra::gpioc::MODER_MODER1, bar);
+        baz |= gpioc_csr.ms(utra::gpioc::MODER_MODER1, 1);
+        gpioc_csr.wfo(utra::gpioc::MODER_MODER1, baz);
+        let _: Option<utra::gpioc::ModerModer1> = gpioc_csr.rfv();
+        gpioc_csr.wfov(utra::gpioc::ModerModer1::Input);
+        gpioc_csr.rmwfv(utra::gpioc::ModerModer1::Input);
+        let bar = gpioc_csr.rf(utra::gpioc::MODER_MODER2);
+        gpioc_csr.rmwf(utra::gpioc::MODER_MODER2, bar);
+        let mut baz = gpioc_csr.zf(utra::gpioc::MODER_MODER2, bar);
+        baz |= gpioc_csr.ms(utra::gpioc::MODER_MODER2, 1);
+        gpioc_csr.wfo(utra::gpioc::MODER_MODER2, baz);
+        let _: Option<utra::gpioc::ModerModer2> = gpioc_csr.rfv();
+        gpioc_csr.wfov(utra::gpioc::ModerModer2::Input);
+        gpioc_csr.rmwfv(utra::gpioc::ModerModer2::Input);
+        let bar = gpioc_csr.rf(utra::gpioc::MODER_MODER3);
+        gpioc_csr.rmwf(utra::gpioc::MODER_MODER3, bar);
+        let mut baz = gpioc_csr.zf(utra::gpioc::MODER_MODER3, bar);
+        baz |= gpioc_csr.ms(utra::gpioc::MODER_MODER3, 1);
+        gpioc_csr.wfo(utra::gpioc::MODER_MODER3, baz);
+        let _: Option<utra::gpioc::ModerModer3> = gpioc_csr.rfv();
+        gpioc_csr.wfov(utra::gpioc::ModerModer3::Input);
+        gpioc_csr.rmwfv(utra::gpioc::ModerModer3::Input);
+
+        let _ = gpioc_csr.r(utra::gpioc::IDR);
+        let bar = gpioc_csr.rf(utra::gpioc::IDR_IDR0);
+        let _ = gpioc_csr.zf(utra::gpioc::IDR_IDR0, bar) | gpioc_csr.ms(utra::gpioc::IDR_IDR0, 1);
+        let bar = gpioc_csr.rf(utra::gpioc::IDR_IDR1);
+        let _ = gpioc_csr.zf(utra::gpioc::IDR_IDR1, bar) | gpioc_csr.ms(utra::gpioc::IDR_IDR1, 1);
+        let bar = gpioc_csr.rf(utra::gpioc::IDR_IDR2);
+        let _ = gpioc_csr.zf(utra::gpioc::IDR_IDR2, bar) | gpioc_csr.ms(utra::gpioc::IDR_IDR2, 1);
+        let bar = gpioc_csr.rf(utra::gpioc::IDR_IDR3);
+        let _ = gpioc_csr.zf(utra::gpioc::IDR_IDR3, bar) | gpioc_csr.ms(utra::gpioc::IDR_IDR3, 1);
+
+        gpioc_csr.wo(utra::gpioc::BSRR, 0);
+        gpioc_csr.reset(utra::gpioc::BSRR);
+        let mut baz = gpioc_csr.zf(utra::gpioc::BSRR_BR0, 0);
+        baz |= gpioc_csr.ms(utra::gpioc::BSRR_BR0, 1);
+        gpioc_csr.wfo(utra::gpioc::BSRR_BR0, baz);
+        let mut baz = gpioc_csr.zf(utra::gpioc::BSRR_BR1, 0);
+        baz |= gpioc_csr.ms(utra::gpioc::BSRR_BR1, 1);
+        gpioc_csr.wfo(utra::gpioc::BSRR_BR1, baz);
+        let mut baz = gpioc_csr.zf(utra::gpioc::BSRR_BR2, 0);
+        baz |= gpioc_csr.ms(utra::gpioc::BSRR_BR2, 1);
+        gpioc_csr.wfo(utra::gpioc::BSRR_BR2, baz);
+        let mut baz = gpioc_csr.zf(utra::gpioc::BSRR_BR3, 0);
+        baz |= gpioc_csr.ms(utra::gpioc::BSRR_BR3, 1);
+        gpioc_csr.wfo(utra::gpioc::BSRR_BR3, baz);
+        let mut baz = gpioc_csr.zf(utra::gpioc::BSRR_BS0, 0);
+        baz |= gpioc_csr.ms(utra::gpioc::BSRR_BS0, 1);
+        gpioc_csr.wfo(utra::gpioc::BSRR_BS0, baz);
+        let mut baz = gpioc_csr.zf(utra::gpioc::BSRR_BS1, 0);
+        baz |= gpioc_csr.ms(utra::gpioc::BSRR_BS1, 1);
+        gpioc_csr.wfo(utra::gpioc::BSRR_BS1, baz);
+        let mut baz = gpioc_csr.zf(utra::gpioc::BSRR_BS2, 0);
+        baz |= gpioc_csr.ms(utra::gpioc::BSRR_BS2, 1);
+        gpioc_csr.wfo(utra::gpioc::BSRR_BS2, baz);
+        let mut baz = gpioc_csr.zf(utra::gpioc::BSRR_BS3, 0);
+        baz |= gpioc_csr.ms(utra::gpioc::BSRR_BS3, 1);
+        gpioc_csr.wfo(utra::gpioc::BSRR_BS3, baz);
+    }
+}
@@ -5,11 +5,15 @@ extern crate crc;
 
 use std::convert::TryInto;
 use std::fs::File;
+use std::path::Path;
 
 use tools::elf::{read_minielf, read_program};
+use tools::manifest::{Init, Manifest, Ram};
 use tools::tags::bflg::Bflg;
 use tools::tags::inie::IniE;
+use tools::tags::inir::IniR;
 use tools::tags::memory::{MemoryRegion, MemoryRegions};
+use tools::tags::sign::Sign;
 use tools::tags::xkrn::XousKernel;
 use tools::utils::{parse_csr_csv, parse_u32};
 use tools::xous_arguments::XousArguments;
@@ -62,6 +66,104 @@ fn csr_to_config(hv: tools::utils::CsrConfig, ram_config: &mut RamConfig) {
             .add(MemoryRegion::new(v.start, round_mem(v.length), region_name));
     }
 }
+
+fn svd_to_config(soc_svd: &Path, ram_config: &mut RamConfig) {
+    let soc_svd_file = std::fs::File::open(soc_svd).unwrap();
+    let desc = svd2utra::parse_svd(soc_svd_file).unwrap();
+    let mut map = std::collections::BTreeMap::new();
+
+    let mut csr_top = 0;
+    for peripheral in desc.peripherals {
+        if peripheral.base > csr_top {
+            csr_top = peripheral.base;
+        }
+    }
+    for region in desc.memory_regions {
+        // Ignore the "CSR" region and manually reconstruct it, because this
+        // region is largely empty and we want to avoid allocating too much space.
+        if region.name == "CSR" {
+            const PAGE_SIZE: usize = 4096;
+            map.insert(
+                region.name.to_lowercase(),
+                tools::utils::CsrMemoryRegion {
+                    start: region.base.try_into().unwrap(),
+                    length: (((csr_top - region.base) + PAGE_SIZE) & !(PAGE_SIZE - 1)).try_into().unwrap(),
+                },
+            );
+        } else {
+            map.insert(
+                region.name.to_lowercase(),
+                tools::utils::CsrMemoryRegion {
+                    start: region.base.try_into().unwrap(),
+                    length: region.size.try_into().unwrap(),
+                },
+            );
+        }
+    }
+    csr_to_config(tools::utils::CsrConfig { regions: map }, ram_config);
+}
+
+/// Describe the image using the individual command line options, for when
+/// no manifest was given.
+fn manifest_from_args(matches: &clap::ArgMatches) -> Option<Manifest> {
+    let mut manifest = Manifest::new(
+        matches
+            .value_of("kernel")
+            .expect("kernel was somehow missing"),
+    );
+
+    if let Some(val) = matches.value_of("ram") {
+        let ram_parts: Vec<&str> = val.split(':').collect();
+        if ram_parts.len() != 2 {
+            eprintln!("Error: --ram argument should be of the form [offset]:[size]");
+            return None;
+        }
+
+        let offset = match parse_u32(ram_parts[0]) {
+            Ok(o) => o,
+            Err(e) => {
+                eprintln!("Error: Unable to parse {}: {:?}", ram_parts[0], e);
+                return None;
+            }
+        };
+
+        let size = match parse_u32(ram_parts[1]) {
+            Ok(o) => o,
+            Err(e) => {
+                eprintln!("Error: Unable to parse {}: {:?}", ram_parts[1], e);
+                return None;
+            }
+        };
+
+        manifest.ram = Some(Ram {
+            offset,
+            size,
+            name: "sram".to_owned(),
+        });
+    }
+
+    manifest.csv = matches.value_of("csv").map(|csv| csv.into());
+    manifest.svd = matches.value_of("svd").map(|svd| svd.into());
+    manifest.sign_key = matches.value_of("sign-key").map(|key| key.into());
+    manifest.boot.debug = matches.is_present("debug");
+    manifest.boot.compress = matches.is_present("compress");
+    manifest.boot.aslr = matches.is_present("aslr");
+    manifest.boot.memtest = matches.is_present("memtest");
+
+    if let Some(init_paths) = matches.values_of("init") {
+        for init_path in init_paths {
+            manifest.init.push(Init {
+                path: init_path.into(),
+                name: None,
+                stack_size: None,
+                heap_max: None,
+                priority: None,
+            });
+        }
+    }
+    Some(manifest)
+}
+
 fn main() {
     env_logger::init();
     let matches = App::new("Xous Image Creator")
@@ -74,7 +176,7 @@ fn main() {
                 .long("kernel")
                 .value_name("KERNEL_ELF")
                 .takes_value(true)
-                .required(true)
+                .required_unless("manifest")
                 .help("Kernel ELF image to bundle into the image"),
         )
         .arg(
@@ -95,7 +197,7 @@ fn main() {
                 .value_name("CSR_CSV")
                 .help("csr.csv file from litex")
                 .takes_value(true)
-                .required_unless_one(&["ram", "svd", "csv"]),
+                .required_unless_one(&["ram", "svd", "csv", "manifest"]),
         )
         .arg(
             Arg::with_name("svd")
@@ -104,7 +206,7 @@ fn main() {
                 .value_name("SOC_SVD")
                 .help("soc.csv file from litex")
                 .takes_value(true)
-                .required_unless_one(&["ram", "svd", "csv"]),
+                .required_unless_one(&["ram", "svd", "csv", "manifest"]),
         )
         .arg(
             Arg::with_name("ram")
@@ -113,7 +215,7 @@ fn main() {
                 .takes_value(true)
                 .value_name("OFFSET:SIZE")
                 .help("RAM offset and size, in the form of [offset]:[size]")
-                .required_unless_one(&["ram", "svd", "csv"]),
+                .required_unless_one(&["ram", "svd", "csv", "manifest"]),
         )
         .arg(
             Arg::with_name("debug")
@@ -122,6 +224,44 @@ fn main() {
                 .takes_value(false)
                 .help("Reduce kernel-userspace security and enable debugging programs"),
         )
+        .arg(
+            Arg::with_name("compress")
+                .short("z")
+                .long("compress")
+                .takes_value(false)
+                .help("Compress the kernel and initial programs"),
+        )
+        .arg(
+            Arg::with_name("aslr")
+                .long("aslr")
+                .takes_value(false)
+                .help("Place initial programs and their stacks at random addresses"),
+        )
+        .arg(
+            Arg::with_name("memtest")
+                .long("memtest")
+                .takes_value(false)
+                .help("Test RAM at boot and keep any pages that fail from being used"),
+        )
+        .arg(
+            Arg::with_name("sign-key")
+                .long("sign-key")
+                .value_name("KEY_FILE")
+                .takes_value(true)
+                .help("Ed25519 secret key, as 64 hex digits, used to sign the image"),
+        )
+        .arg(
+            Arg::with_name("manifest")
+                .short("m")
+                .long("manifest")
+                .value_name("MANIFEST_TOML")
+                .takes_value(true)
+                .conflicts_with_all(&[
+                    "kernel", "init", "csv", "svd", "ram", "debug", "compress", "aslr", "memtest",
+                    "sign-key",
+                ])
+                .help("TOML manifest describing the whole image, used instead of other options"),
+        )
         .arg(
             Arg::with_name("output")
                 .value_name("OUTPUT")
@@ -130,6 +270,24 @@ fn main() {
         )
         .get_matches();
 
+    let manifest = match matches.value_of("manifest") {
+        Some(path) => match Manifest::load(path) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                eprintln!("Error: {}: {}", path, e);
+                return;
+            }
+        },
+        None => match manifest_from_args(&matches) {
+            Some(manifest) => manifest,
+            None => return,
+        },
+    };
+    if let Err(e) = manifest.validate() {
+        eprintln!("Error: {}", e);
+        return;
+    }
+
     let mut ram_config = RamConfig {
         offset: Default::default(),
         size: Default::default(),
@@ -138,71 +296,29 @@ fn main() {
         memory_required: 0,
     };
 
-    if let Some(val) = matches.value_of("ram") {
-        let ram_parts: Vec<&str> = val.split(':').collect();
-        if ram_parts.len() != 2 {
-            eprintln!("Error: --ram argument should be of the form [offset]:[size]");
-            return;
-        }
-
-        ram_config.offset = match parse_u32(ram_parts[0]) {
-            Ok(o) => o,
-            Err(e) => {
-                eprintln!("Error: Unable to parse {}: {:?}", ram_parts[0], e);
-                return;
-            }
-        };
-
-        ram_config.size = match parse_u32(ram_parts[1]) {
-            Ok(o) => o,
-            Err(e) => {
-                eprintln!("Error: Unable to parse {}: {:?}", ram_parts[1], e);
-                return;
-            }
-        };
-
+    if let Some(ram) = &manifest.ram {
+        ram_config.offset = ram.offset;
+        ram_config.size = ram.size;
+        ram_config.name = MemoryRegion::make_name(&ram.name);
         ram_config.memory_required += ram_config.size / 4096;
     }
 
-    if let Some(csr_csv) = matches.value_of("csv") {
-        let hv = parse_csr_csv(csr_csv).expect("couldn't find csr.csv file");
+    if let Some(csr_csv) = &manifest.csv {
+        let hv = parse_csr_csv(&csr_csv.to_string_lossy()).expect("couldn't find csr.csv file");
         csr_to_config(hv, &mut ram_config);
     }
 
-    if let Some(soc_svd) = matches.value_of("svd") {
-        let soc_svd_file = std::fs::File::open(soc_svd).unwrap();
-        let desc = svd2utra::parse_svd(soc_svd_file).unwrap();
-        let mut map = std::collections::BTreeMap::new();
+    if let Some(soc_svd) = &manifest.svd {
+        svd_to_config(soc_svd, &mut ram_config);
+    }
 
-        let mut csr_top = 0;
-        for peripheral in desc.peripherals {
-            if peripheral.base > csr_top {
-                csr_top = peripheral.base;
-            }
-        }
-        for region in desc.memory_regions {
-            // Ignore the "CSR" region and manually reconstruct it, because this
-            // region is largely empty and we want to avoid allocating too much space.
-            if region.name == "CSR" {
-                const PAGE_SIZE: usize = 4096;
-                map.insert(
-                    region.name.to_lowercase(),
-                    tools::utils::CsrMemoryRegion {
-                        start: region.base.try_into().unwrap(),
-                        length: (((csr_top - region.base) + PAGE_SIZE) & !(PAGE_SIZE - 1)).try_into().unwrap(),
-                    },
-                );
-            } else {
-                map.insert(
-                    region.name.to_lowercase(),
-                    tools::utils::CsrMemoryRegion {
-                        start: region.base.try_into().unwrap(),
-                        length: region.size.try_into().unwrap(),
-                    },
-                );
-            }
-        }
-        csr_to_config(tools::utils::CsrConfig { regions: map }, &mut ram_config);
+    for region in &manifest.regions {
+        ram_config.memory_required += region.length / 4096;
+        ram_config.regions.add(MemoryRegion::new(
+            region.start,
+            region.length,
+            MemoryRegion::make_name(&region.name),
+        ));
     }
 
     let mut args = XousArguments::new(ram_config.offset, ram_config.size, ram_config.name);
@@ -211,25 +327,46 @@ fn main() {
         args.add(ram_config.regions);
     }
 
-    if matches.is_present("debug") {
-        args.add(Bflg::new().debug());
+    let compress = manifest.boot.compress;
+    let aslr = manifest.boot.aslr;
+    let memtest = manifest.boot.memtest;
+    if manifest.boot.debug || compress || aslr || memtest {
+        let mut bflg = Bflg::new();
+        if manifest.boot.debug {
+            bflg = bflg.debug();
+        }
+        if compress {
+            bflg = bflg.compressed();
+        }
+        if aslr {
+            bflg = bflg.aslr();
+        }
+        if memtest {
+            bflg = bflg.memtest();
+        }
+        args.add(bflg);
     }
 
-    let kernel = read_program(
-        matches
-            .value_of("kernel")
-            .expect("kernel was somehow missing"),
-    )
-    .expect("unable to read kernel");
+    let kernel = read_program(&manifest.kernel).expect("unable to read kernel");
 
-    if let Some(init_paths) = matches.values_of("init") {
-        for init_path in init_paths {
-            let init = read_minielf(init_path).expect("couldn't parse init file");
-            args.add(IniE::new(init.entry_point, init.sections, init.program));
+    for init_config in &manifest.init {
+        let init = read_minielf(&init_config.path)
+            .unwrap_or_else(|e| panic!("couldn't parse init file {}: {:?}", init_config.name(), e));
+        let mut inie = IniE::new(init.entry_point, init.sections, init.program);
+        inie.set_name(&init_config.name());
+        inie.set_stack_size(init_config.stack_size.unwrap_or(0));
+        inie.set_heap_max(init_config.heap_max.unwrap_or(0));
+        if compress {
+            inie.compress();
+        }
+        args.add(inie);
+        // Position-independent programs are followed by their relocations
+        if let Some(relocations) = init.relocations {
+            args.add(IniR::new(relocations));
         }
     }
 
-    let xkrn = XousKernel::new(
+    let mut xkrn = XousKernel::new(
         kernel.text_offset,
         kernel.text_size,
         kernel.data_offset,
@@ -238,8 +375,21 @@ fn main() {
         kernel.entry_point,
         kernel.program,
     );
+    if compress {
+        xkrn.compress();
+    }
     args.add(xkrn);
 
+    // The signature covers every other tag, so it must be added last.
+    let sign = manifest.sign_key.as_ref().map(|key_file| {
+        Sign::from_key_file(key_file)
+            .unwrap_or_else(|e| panic!("Couldn't load signing key {}: {}", key_file.display(), e))
+    });
+    let public_key = sign.as_ref().map(|s| s.public_key_hex());
+    if let Some(sign) = sign {
+        args.add(sign);
+    }
+
     // Add tags for init and kernel.  These point to the actual data, which should
     // immediately follow the tags.  Therefore, we must know the length of the tags
     // before we create them.
@@ -257,5 +407,9 @@ fn main() {
         "Runtime will require {} bytes to track memory allocations",
         ram_config.memory_required
     );
+    match public_key {
+        Some(public_key) => println!("Image signed with public key {}", public_key),
+        None => println!("Warning: image is unsigned, and the loader will refuse to boot it"),
+    }
     println!("Image created in file {}", output_filename);
 }
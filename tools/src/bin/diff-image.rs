@@ -0,0 +1,546 @@
+#[macro_use]
+extern crate clap;
+
+use clap::{App, Arg};
+use std::process;
+use tools::image::{Image, Payload, TagContents};
+use tools::tags::bflg::Bflg;
+use tools::tags::inie::{limit_name, IniE};
+use tools::tags::memory::MemoryRegion;
+use tools::tags::sign::Signature;
+use tools::tags::xkrn::XousKernel;
+use tools::xous_arguments::code_name;
+
+/// An initial program, along with the relocations that follow it
+struct Process<'a> {
+    inie: &'a IniE,
+    payload: Option<&'a Payload>,
+    relocations: Option<(u32, Option<&'a Payload>)>,
+}
+
+/// Differences found in one part of the image, printed under a heading
+struct Report {
+    heading: String,
+    lines: Vec<String>,
+}
+
+impl Report {
+    fn new<S: Into<String>>(heading: S) -> Report {
+        Report {
+            heading: heading.into(),
+            lines: vec![],
+        }
+    }
+
+    /// Note a change in a value, if there was one.
+    fn value<T: PartialEq + std::fmt::Display>(&mut self, name: &str, old: T, new: T) {
+        if old != new {
+            self.lines.push(format!("{}: {} -> {}", name, old, new));
+        }
+    }
+
+    /// Note a change in an address, if there was one.
+    fn address(&mut self, name: &str, old: u32, new: u32) {
+        if old != new {
+            self.lines
+                .push(format!("{}: {:08x} -> {:08x}", name, old, new));
+        }
+    }
+
+    /// Note a change in a size, along with how much it grew or shrank.
+    fn size(&mut self, name: &str, old: u32, new: u32) {
+        if old != new {
+            self.lines.push(format!(
+                "{}: {} -> {} bytes ({:+})",
+                name,
+                old,
+                new,
+                new as i64 - old as i64
+            ));
+        }
+    }
+
+    /// Note changes in the size and contents of a payload.
+    fn payload(&mut self, name: &str, old: Option<&Payload>, new: Option<&Payload>) {
+        let (old, new) = match (old, new) {
+            (Some(old), Some(new)) => (old, new),
+            _ => return,
+        };
+        self.size(&format!("{} size", name), old.length, new.length);
+        self.value(&format!("{} sha256", name), &old.sha256, &new.sha256);
+    }
+
+    fn print(&self) -> bool {
+        if self.lines.is_empty() {
+            return false;
+        }
+        println!("{}:", self.heading);
+        for line in &self.lines {
+            println!("    {}", line);
+        }
+        true
+    }
+}
+
+fn processes(image: &Image) -> Vec<Process<'_>> {
+    let mut processes: Vec<Process> = vec![];
+    for tag in &image.tags {
+        match &tag.contents {
+            TagContents::IniE(inie) => processes.push(Process {
+                inie,
+                payload: tag.payload.as_ref(),
+                relocations: None,
+            }),
+            TagContents::IniR(inir) => {
+                if let Some(process) = processes.last_mut() {
+                    process.relocations = Some((inir.count(), tag.payload.as_ref()));
+                }
+            }
+            _ => (),
+        }
+    }
+    processes
+}
+
+/// A process in the old image matched with one in the new image, along
+/// with the position of each
+type ProcessPair<'a, 'b> = (
+    Option<(usize, &'b Process<'a>)>,
+    Option<(usize, &'b Process<'a>)>,
+);
+
+/// Match up the processes of two images.  Processes are paired by name, so
+/// that inserting or reordering programs doesn't show up as every later
+/// process changing.  Unnamed processes fall back to being paired by
+/// position.
+fn pair_processes<'a, 'b>(
+    old: &'b [Process<'a>],
+    new: &'b [Process<'a>],
+) -> Vec<ProcessPair<'a, 'b>> {
+    let mut matched = vec![false; new.len()];
+    let mut pairs = vec![];
+    for (index, process) in old.iter().enumerate() {
+        let name = process.inie.name();
+        let other = if name.is_empty() {
+            new.get(index)
+                .filter(|other| other.inie.name().is_empty())
+                .map(|_| index)
+        } else {
+            new.iter().position(|other| other.inie.name() == name)
+        }
+        .filter(|&other| !matched[other]);
+        if let Some(other) = other {
+            matched[other] = true;
+        }
+        pairs.push((
+            Some((index, process)),
+            other.map(|other| (other, &new[other])),
+        ));
+    }
+    for (index, process) in new.iter().enumerate() {
+        if !matched[index] {
+            pairs.push((None, Some((index, process))));
+        }
+    }
+    pairs
+}
+
+fn kernel(image: &Image) -> Option<(&XousKernel, Option<&Payload>)> {
+    image.tags.iter().find_map(|tag| match &tag.contents {
+        TagContents::Kernel(xkrn) => Some((xkrn, tag.payload.as_ref())),
+        _ => None,
+    })
+}
+
+/// The boot flags as they're displayed, such as `-no_copy +DEBUG`.  An
+/// image without a `Bflg` tag has every flag cleared.
+fn boot_flags(image: &Image) -> String {
+    let flags = image
+        .tags
+        .iter()
+        .find_map(|tag| match &tag.contents {
+            TagContents::Bflg(bflg) => Some(bflg.to_string()),
+            _ => None,
+        })
+        .unwrap_or_else(|| Bflg::new().to_string());
+    flags.trim().trim_start_matches("Bflg:").trim().to_owned()
+}
+
+fn signature(image: &Image) -> Option<&Signature> {
+    image.tags.iter().find_map(|tag| match &tag.contents {
+        TagContents::Signature(signature) => Some(signature),
+        _ => None,
+    })
+}
+
+fn memory_regions(image: &Image) -> Vec<&MemoryRegion> {
+    image
+        .tags
+        .iter()
+        .flat_map(|tag| match &tag.contents {
+            TagContents::MemoryRegions(regions) => regions.regions().iter().collect(),
+            _ => vec![],
+        })
+        .collect()
+}
+
+fn region_range(region: &MemoryRegion) -> String {
+    format!(
+        "{:08x} - {:08x}",
+        region.start(),
+        region.start() as u64 + region.length() as u64
+    )
+}
+
+/// Report the differences between each pair of processes.
+fn process_reports(old_processes: &[Process], new_processes: &[Process]) -> Vec<Report> {
+    let mut reports = vec![];
+    for pair in pair_processes(old_processes, new_processes) {
+        let (index, process) = pair.0.or(pair.1).unwrap();
+        let mut report = match process.inie.name() {
+            "" => Report::new(format!("Process {}", index + 1)),
+            name => Report::new(format!("Process {}", name)),
+        };
+        match pair {
+            (Some((old_index, old)), Some((new_index, new))) => {
+                report.value("position", old_index + 1, new_index + 1);
+                report.address("entrypoint", old.inie.entrypoint(), new.inie.entrypoint());
+                report.value(
+                    "stack size",
+                    limit_name(old.inie.stack_size()),
+                    limit_name(new.inie.stack_size()),
+                );
+                report.value(
+                    "heap maximum",
+                    limit_name(old.inie.heap_max()),
+                    limit_name(new.inie.heap_max()),
+                );
+                let (old_sections, new_sections) = (old.inie.sections(), new.inie.sections());
+                for section in 0..old_sections.len().max(new_sections.len()) {
+                    let old_section = old_sections.get(section);
+                    let new_section = new_sections.get(section);
+                    if old_section == new_section {
+                        continue;
+                    }
+                    if let Some(old_section) = old_section {
+                        report.lines.push(format!("- {}", old_section));
+                    }
+                    if let Some(new_section) = new_section {
+                        report.lines.push(format!("+ {}", new_section));
+                    }
+                }
+                report.payload("program", old.payload, new.payload);
+                match (old.relocations, new.relocations) {
+                    (Some((old_count, old_payload)), Some((new_count, new_payload))) => {
+                        report.value("relocations", old_count, new_count);
+                        report.payload("relocation table", old_payload, new_payload);
+                    }
+                    (Some(_), None) => report
+                        .lines
+                        .push("no longer position-independent".to_owned()),
+                    (None, Some(_)) => report.lines.push("now position-independent".to_owned()),
+                    (None, None) => (),
+                }
+            }
+            (Some((_, old)), None) => report.lines.push(format!(
+                "removed, with entrypoint @ {:08x}",
+                old.inie.entrypoint()
+            )),
+            (None, Some((_, new))) => report.lines.push(format!(
+                "added, with entrypoint @ {:08x}",
+                new.inie.entrypoint()
+            )),
+            (None, None) => (),
+        }
+        reports.push(report);
+    }
+    reports
+}
+
+fn diff_images(old: &Image, new: &Image) -> bool {
+    let mut reports = vec![];
+
+    let mut report = Report::new("Image");
+    report.size("length", old.length as u32, new.length as u32);
+    let old_tags: Vec<String> = old.tags.iter().map(|tag| code_name(tag.code)).collect();
+    let new_tags: Vec<String> = new.tags.iter().map(|tag| code_name(tag.code)).collect();
+    report.value("tags", old_tags.join(" "), new_tags.join(" "));
+    reports.push(report);
+
+    let mut report = Report::new("Memory regions");
+    let (old_args, new_args) = (old.arguments(), new.arguments());
+    report.value(
+        "main RAM",
+        format!(
+            "{} {:08x} - {:08x}",
+            code_name(old_args.ram_name),
+            old_args.ram_start,
+            old_args.ram_start as u64 + old_args.ram_length as u64
+        ),
+        format!(
+            "{} {:08x} - {:08x}",
+            code_name(new_args.ram_name),
+            new_args.ram_start,
+            new_args.ram_start as u64 + new_args.ram_length as u64
+        ),
+    );
+    let (old_regions, new_regions) = (memory_regions(old), memory_regions(new));
+    for region in &old_regions {
+        let name = code_name(region.name());
+        match new_regions
+            .iter()
+            .find(|other| other.name() == region.name())
+        {
+            Some(other) => report.value(&name, region_range(region), region_range(other)),
+            None => report
+                .lines
+                .push(format!("removed {} {}", name, region_range(region))),
+        }
+    }
+    for region in &new_regions {
+        if !old_regions
+            .iter()
+            .any(|other| other.name() == region.name())
+        {
+            report.lines.push(format!(
+                "added {} {}",
+                code_name(region.name()),
+                region_range(region)
+            ));
+        }
+    }
+    reports.push(report);
+
+    let mut report = Report::new("Boot flags");
+    report.value("flags", boot_flags(old), boot_flags(new));
+    reports.push(report);
+
+    let mut report = Report::new("Kernel");
+    match (kernel(old), kernel(new)) {
+        (Some((old, old_payload)), Some((new, new_payload))) => {
+            report.address("entrypoint", old.entrypoint(), new.entrypoint());
+            report.address("text address", old.text_offset(), new.text_offset());
+            report.size("text", old.text_size(), new.text_size());
+            report.address("data address", old.data_offset(), new.data_offset());
+            report.size("data", old.data_size(), new.data_size());
+            report.size("bss", old.bss_size(), new.bss_size());
+            report.payload("image", old_payload, new_payload);
+        }
+        (Some(_), None) => report.lines.push("removed".to_owned()),
+        (None, Some(_)) => report.lines.push("added".to_owned()),
+        (None, None) => (),
+    }
+    reports.push(report);
+
+    let (old_processes, new_processes) = (processes(old), processes(new));
+    reports.extend(process_reports(&old_processes, &new_processes));
+
+    let mut report = Report::new("Signature");
+    match (signature(old), signature(new)) {
+        (Some(old), Some(new)) => {
+            report.size("signed length", old.signed_length(), new.signed_length());
+            if old.signature() != new.signature() {
+                report.lines.push("signature changed".to_owned());
+            }
+        }
+        (Some(_), None) => report.lines.push("removed".to_owned()),
+        (None, Some(_)) => report.lines.push("added".to_owned()),
+        (None, None) => (),
+    }
+    reports.push(report);
+
+    let mut changed = false;
+    for report in &reports {
+        changed |= report.print();
+    }
+    changed
+}
+
+fn read_image(filename: &str) -> Image {
+    let data = std::fs::read(filename).unwrap_or_else(|e| {
+        eprintln!("Error: couldn't read {}: {}", filename, e);
+        process::exit(2);
+    });
+    let image = Image::parse(&data).unwrap_or_else(|e| {
+        eprintln!("Error: {}: {}", filename, e);
+        process::exit(2);
+    });
+    for tag in image.tags.iter().filter(|tag| !tag.crc_ok()) {
+        eprintln!(
+            "Warning: {}: {} tag at offset {:08x} has a bad CRC16",
+            filename,
+            code_name(tag.code),
+            tag.offset
+        );
+    }
+    image
+}
+
+fn main() {
+    let matches = App::new("Xous Image Differ")
+        .version(crate_version!())
+        .author("Sean Cross <sean@xobs.io>")
+        .about("Compare two Xous boot images")
+        .arg(
+            Arg::with_name("old")
+                .value_name("OLD_IMAGE")
+                .required(true)
+                .help("Image to compare against"),
+        )
+        .arg(
+            Arg::with_name("new")
+                .value_name("NEW_IMAGE")
+                .required(true)
+                .help("Image to compare"),
+        )
+        .get_matches();
+
+    let old = read_image(
+        matches
+            .value_of("old")
+            .expect("old image was somehow missing"),
+    );
+    let new = read_image(
+        matches
+            .value_of("new")
+            .expect("new image was somehow missing"),
+    );
+
+    // Like diff(1), exit with 1 if the images are different
+    if diff_images(&old, &new) {
+        process::exit(1);
+    }
+    println!("Images are identical");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inie(name: &str, entrypoint: u32) -> IniE {
+        let mut inie = IniE::new(entrypoint, vec![], vec![]);
+        inie.set_name(name);
+        inie
+    }
+
+    fn process(inie: &IniE) -> Process<'_> {
+        Process {
+            inie,
+            payload: None,
+            relocations: None,
+        }
+    }
+
+    /// The positions of each pair, counting from 1 as the reports do
+    fn positions(pairs: &[ProcessPair]) -> Vec<(Option<usize>, Option<usize>)> {
+        pairs
+            .iter()
+            .map(|(old, new)| (old.map(|(i, _)| i + 1), new.map(|(i, _)| i + 1)))
+            .collect()
+    }
+
+    /// The headings and lines of the reports that found a difference
+    fn changes(old: &[Process], new: &[Process]) -> Vec<(String, Vec<String>)> {
+        process_reports(old, new)
+            .into_iter()
+            .filter(|report| !report.lines.is_empty())
+            .map(|report| (report.heading, report.lines))
+            .collect()
+    }
+
+    fn change(heading: &str, lines: &[&str]) -> (String, Vec<String>) {
+        (
+            heading.to_owned(),
+            lines.iter().map(|line| line.to_string()).collect(),
+        )
+    }
+
+    #[test]
+    fn identical() {
+        let inies = [inie("shell", 0x2000_0000), inie("log", 0x2000_1000)];
+        let old: Vec<Process> = inies.iter().map(process).collect();
+        let new: Vec<Process> = inies.iter().map(process).collect();
+        let pairs = pair_processes(&old, &new);
+        assert_eq!(positions(&pairs), [(Some(1), Some(1)), (Some(2), Some(2))]);
+        assert!(changes(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn renamed() {
+        // Processes are paired by name, so a new name is a new process
+        let (old_inie, new_inie) = (inie("shell", 0x2000_0000), inie("console", 0x2000_0000));
+        let (old, new) = ([process(&old_inie)], [process(&new_inie)]);
+        let pairs = pair_processes(&old, &new);
+        assert_eq!(positions(&pairs), [(Some(1), None), (None, Some(1))]);
+        assert_eq!(
+            changes(&old, &new),
+            [
+                change("Process shell", &["removed, with entrypoint @ 20000000"]),
+                change("Process console", &["added, with entrypoint @ 20000000"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn reordered() {
+        let old_inies = [inie("shell", 0x2000_0000), inie("log", 0x2000_1000)];
+        let new_inies = [inie("log", 0x2000_1000), inie("shell", 0x2000_2000)];
+        let old: Vec<Process> = old_inies.iter().map(process).collect();
+        let new: Vec<Process> = new_inies.iter().map(process).collect();
+        let pairs = pair_processes(&old, &new);
+        assert_eq!(positions(&pairs), [(Some(1), Some(2)), (Some(2), Some(1))]);
+        assert_eq!(
+            changes(&old, &new),
+            [
+                change(
+                    "Process shell",
+                    &["position: 1 -> 2", "entrypoint: 20000000 -> 20002000"]
+                ),
+                change("Process log", &["position: 2 -> 1"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn unnamed_by_position() {
+        let old_inies = [inie("", 0x2000_0000), inie("", 0x2000_1000)];
+        let new_inies = [inie("", 0x2000_0000), inie("", 0x2000_3000)];
+        let old: Vec<Process> = old_inies.iter().map(process).collect();
+        let new: Vec<Process> = new_inies.iter().map(process).collect();
+        let pairs = pair_processes(&old, &new);
+        assert_eq!(positions(&pairs), [(Some(1), Some(1)), (Some(2), Some(2))]);
+        assert_eq!(
+            changes(&old, &new),
+            [change("Process 2", &["entrypoint: 20001000 -> 20003000"])]
+        );
+
+        // An unnamed process isn't paired with a named one in its place
+        let new_inies = [inie("", 0x2000_0000), inie("log", 0x2000_1000)];
+        let new: Vec<Process> = new_inies.iter().map(process).collect();
+        let pairs = pair_processes(&old, &new);
+        assert_eq!(
+            positions(&pairs),
+            [(Some(1), Some(1)), (Some(2), None), (None, Some(2))]
+        );
+    }
+
+    #[test]
+    fn added_and_removed() {
+        let old_inies = [inie("shell", 0x2000_0000), inie("com", 0x2000_1000)];
+        let new_inies = [inie("shell", 0x2000_0000), inie("log", 0x2000_2000)];
+        let old: Vec<Process> = old_inies.iter().map(process).collect();
+        let new: Vec<Process> = new_inies.iter().map(process).collect();
+        let pairs = pair_processes(&old, &new);
+        assert_eq!(
+            positions(&pairs),
+            [(Some(1), Some(1)), (Some(2), None), (None, Some(2))]
+        );
+        assert_eq!(
+            changes(&old, &new),
+            [
+                change("Process com", &["removed, with entrypoint @ 20001000"]),
+                change("Process log", &["added, with entrypoint @ 20002000"]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,590 @@
+//! Break down where the bytes in an image go, and compare them against a
+//! baseline of budgets.  A baseline looks like this, and every entry in it
+//! is optional:
+//!
+//! ```toml
+//! # Bytes of RAM the loader commits at boot, and bytes of flash the image uses
+//! ram = 1048576
+//! flash = 524288
+//!
+//! [programs.kernel]
+//! text = 131072
+//! data = 8192
+//! bss = 65536
+//! ram = 262144
+//! ```
+//!
+//! `--write-baseline` saves the current sizes in this format, which can then
+//! be checked in and given some headroom.
+
+#[macro_use]
+extern crate clap;
+
+use clap::{App, Arg};
+use minielf::{
+    CONTEXT_OFFSET, EXCEPTION_STACK_TOP, PAGE_TABLE_OFFSET, PAGE_TABLE_ROOT_OFFSET,
+    PIE_LOAD_OFFSET, STACK_PAGE_COUNT, USER_STACK_TOP,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::process;
+use tools::elf::{read_minielf, MiniElf, MiniElfFlags};
+use tools::manifest::Manifest;
+use tools::utils::parse_csr_csv;
+
+const PAGE_SIZE: u32 = 4096;
+
+/// Pages covered by one second-level page table
+const PAGES_PER_L2_TABLE: u32 = 1024;
+
+/// Where the loader maps the pages it gives every program: its root page
+/// table, its context, and the first of its page table mappings
+const PROCESS_PAGES: [usize; 3] = [PAGE_TABLE_ROOT_OFFSET, CONTEXT_OFFSET, PAGE_TABLE_OFFSET];
+
+/// Sizes of one program, and the RAM the loader commits to it at boot
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct Sizes {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bss: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ram: Option<u32>,
+}
+
+/// Budgets for the whole image.  Each limit is optional.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct Baseline {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ram: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    flash: Option<u32>,
+    #[serde(default)]
+    programs: BTreeMap<String, Sizes>,
+}
+
+/// One program as it was measured
+struct Program {
+    name: String,
+    text: u32,
+    data: u32,
+    bss: u32,
+    ram: u32,
+}
+
+impl Program {
+    fn sizes(&self) -> Sizes {
+        Sizes {
+            text: Some(self.text),
+            data: Some(self.data),
+            bss: Some(self.bss),
+            ram: Some(self.ram),
+        }
+    }
+}
+
+/// A memory region from the SVD file, CSV file or manifest
+struct Region {
+    name: String,
+    size: u32,
+}
+
+fn round_mem(bytes: u32) -> u32 {
+    (bytes + PAGE_SIZE - 1) & !(PAGE_SIZE - 1)
+}
+
+/// A run of consecutive virtual pages the loader maps for a program
+struct PageRun {
+    first: u32,
+    count: u32,
+    /// Whether ASLR moves the run to a random page
+    moves: bool,
+}
+
+impl PageRun {
+    /// The `count` pages that end with the one holding `top`
+    fn below(top: usize, count: usize, moves: bool) -> PageRun {
+        let last = (top / PAGE_SIZE as usize) as u32;
+        PageRun {
+            first: last + 1 - count as u32,
+            count: count as u32,
+            moves,
+        }
+    }
+}
+
+/// Count the second-level page tables `map_page()` allocates for `runs`,
+/// which is one for every 4 MiB they touch.  A run that ASLR moves can end
+/// up across one more 4 MiB boundary than it is as linked, so with `aslr`
+/// this is an upper bound.
+fn l2_tables(runs: &[PageRun], aslr: bool) -> u32 {
+    let mut tables = BTreeSet::new();
+    let mut moved_tables = 0;
+    for run in runs.iter().filter(|run| run.count > 0) {
+        if aslr && run.moves {
+            moved_tables += (run.count + PAGES_PER_L2_TABLE - 2) / PAGES_PER_L2_TABLE + 1;
+        } else {
+            let last = run.first + run.count - 1;
+            tables.extend(run.first / PAGES_PER_L2_TABLE..=last / PAGES_PER_L2_TABLE);
+        }
+    }
+    tables.len() as u32 + moved_tables
+}
+
+/// Measure a program.  Read-only sections, including `.rodata`, are
+/// counted as `.text`, as `size(1)` does.
+fn measure(
+    name: String,
+    elf: &MiniElf,
+    is_kernel: bool,
+    stack_size: Option<u32>,
+    aslr: bool,
+) -> Program {
+    let mut program = Program {
+        name,
+        text: 0,
+        data: 0,
+        bss: 0,
+        ram: 0,
+    };
+
+    // Count every page a section touches once, as the loader maps them.
+    // Position-independent programs are moved up, and ASLR moves them
+    // further.
+    let load_offset = match elf.relocations {
+        Some(_) => PIE_LOAD_OFFSET as u32,
+        None => 0,
+    };
+    let mut runs: Vec<PageRun> = vec![];
+    let mut next_page = 0;
+    for section in elf.sections.iter().filter(|s| s.size > 0) {
+        if section.flags.contains(MiniElfFlags::NOCOPY) {
+            program.bss += section.size;
+        } else if section.flags.contains(MiniElfFlags::WRITE) {
+            program.data += section.size;
+        } else {
+            program.text += section.size;
+        }
+        let virt = section.virt + load_offset;
+        let first = (virt / PAGE_SIZE).max(next_page);
+        let last = (virt + section.size - 1) / PAGE_SIZE;
+        if last < first {
+            continue;
+        }
+        match runs.last_mut() {
+            Some(run) if run.first + run.count == first => run.count += last - first + 1,
+            _ => runs.push(PageRun {
+                first,
+                count: last - first + 1,
+                moves: elf.relocations.is_some(),
+            }),
+        }
+        next_page = last + 1;
+    }
+    let section_pages: u32 = runs.iter().map(|run| run.count).sum();
+
+    // The kernel's text and data are loaded in two separate runs of pages.
+    let ram_pages = if is_kernel {
+        runs.push(PageRun::below(USER_STACK_TOP - 4, STACK_PAGE_COUNT, false));
+        runs.push(PageRun::below(
+            EXCEPTION_STACK_TOP - 4,
+            STACK_PAGE_COUNT,
+            false,
+        ));
+        (round_mem(program.text) + round_mem(program.data + program.bss)) / PAGE_SIZE
+            + STACK_PAGE_COUNT as u32 * 2
+    } else {
+        let stack_pages = minielf::stack_page_count(stack_size.unwrap_or(0) as usize);
+        runs.push(PageRun::below(USER_STACK_TOP - 4, stack_pages, true));
+        section_pages + stack_pages as u32
+    };
+    for page in PROCESS_PAGES.iter() {
+        runs.push(PageRun::below(*page, 1, false));
+    }
+    program.ram = (ram_pages + PROCESS_PAGES.len() as u32 + l2_tables(&runs, aslr)) * PAGE_SIZE;
+    program
+}
+
+/// Gather the memory regions the image describes.  The first returned
+/// region is main RAM and the second is flash, if either can be found.
+fn memory_limits(manifest: &Manifest) -> (Option<Region>, Option<Region>) {
+    let mut regions = vec![];
+    if let Some(svd) = &manifest.svd {
+        let svd_file = std::fs::File::open(svd).expect("couldn't open svd file");
+        let desc = svd2utra::parse_svd(svd_file).expect("couldn't parse svd file");
+        for region in desc.memory_regions {
+            regions.push(Region {
+                name: region.name,
+                size: region.size as u32,
+            });
+        }
+    }
+    if let Some(csv) = &manifest.csv {
+        let config = parse_csr_csv(&csv.to_string_lossy()).expect("couldn't parse csr.csv file");
+        for (name, region) in config.regions {
+            regions.push(Region {
+                name,
+                size: region.length,
+            });
+        }
+    }
+    for region in &manifest.regions {
+        regions.push(Region {
+            name: region.name.clone(),
+            size: region.length,
+        });
+    }
+
+    // Like create-image, treat the largest "ram" region as main memory
+    // unless the manifest names it.
+    let largest = |kind: &str| {
+        regions
+            .iter()
+            .filter(|r| r.name.to_lowercase().contains(kind))
+            .max_by_key(|r| r.size)
+            .map(|r| Region {
+                name: r.name.clone(),
+                size: r.size,
+            })
+    };
+    let ram = match &manifest.ram {
+        Some(ram) => Some(Region {
+            name: ram.name.clone(),
+            size: ram.size,
+        }),
+        None => largest("ram"),
+    };
+    (ram, largest("flash"))
+}
+
+/// Print how much of a region is used, and note a failure if it's full.
+fn check_region(what: &str, used: u32, region: Option<&Region>, failures: &mut Vec<String>) {
+    match region {
+        Some(region) => {
+            println!(
+                "{}: {} of {} bytes in {} ({:.1}%)",
+                what,
+                used,
+                region.size,
+                region.name,
+                used as f64 * 100.0 / region.size as f64
+            );
+            if used > region.size {
+                failures.push(format!(
+                    "{} needs {} bytes, but {} only has {}",
+                    what, used, region.name, region.size
+                ));
+            }
+        }
+        None => println!(
+            "{}: {} bytes (no region found to compare against)",
+            what, used
+        ),
+    }
+}
+
+/// Note a failure if `value` is over `budget`.
+fn check_budget(what: &str, value: u32, budget: Option<u32>, failures: &mut Vec<String>) {
+    if let Some(budget) = budget {
+        if value > budget {
+            failures.push(format!(
+                "{} is {} bytes, {} over its budget of {}",
+                what,
+                value,
+                value - budget,
+                budget
+            ));
+        }
+    }
+}
+
+fn main() {
+    env_logger::init();
+    let matches = App::new("Xous Size Report")
+        .version(crate_version!())
+        .author("Sean Cross <sean@xobs.io>")
+        .about("Report where the space in a Xous image goes")
+        .arg(
+            Arg::with_name("manifest")
+                .short("m")
+                .long("manifest")
+                .value_name("MANIFEST_TOML")
+                .takes_value(true)
+                .required(true)
+                .help("Manifest the image was created from"),
+        )
+        .arg(
+            Arg::with_name("image")
+                .short("i")
+                .long("image")
+                .value_name("IMAGE")
+                .takes_value(true)
+                .help("Image as written to flash"),
+        )
+        .arg(
+            Arg::with_name("baseline")
+                .short("b")
+                .long("baseline")
+                .value_name("BASELINE_TOML")
+                .takes_value(true)
+                .help("Budgets to check against.  Exits with 1 if any is exceeded"),
+        )
+        .arg(
+            Arg::with_name("write-baseline")
+                .long("write-baseline")
+                .value_name("BASELINE_TOML")
+                .takes_value(true)
+                .help("Save the current sizes as a baseline"),
+        )
+        .get_matches();
+
+    let manifest_path = matches
+        .value_of("manifest")
+        .expect("manifest was somehow missing");
+    let manifest = Manifest::load(manifest_path)
+        .unwrap_or_else(|e| panic!("couldn't load manifest {}: {}", manifest_path, e));
+
+    let aslr = manifest.boot.aslr;
+    let kernel =
+        read_minielf(&manifest.kernel).unwrap_or_else(|e| panic!("couldn't parse kernel: {}", e));
+    let mut programs = vec![measure("kernel".to_owned(), &kernel, true, None, aslr)];
+    for init in &manifest.init {
+        let elf = read_minielf(&init.path)
+            .unwrap_or_else(|e| panic!("couldn't parse init file {}: {}", init.name(), e));
+        programs.push(measure(init.name(), &elf, false, init.stack_size, aslr));
+    }
+
+    println!(
+        "{:<20} {:>10} {:>10} {:>10} {:>12}",
+        "Program", ".text", ".data", ".bss", "RAM at boot"
+    );
+    for program in &programs {
+        println!(
+            "{:<20} {:>10} {:>10} {:>10} {:>12}",
+            program.name, program.text, program.data, program.bss, program.ram
+        );
+    }
+    let ram: u32 = programs.iter().map(|p| p.ram).sum();
+    println!(
+        "{:<20} {:>10} {:>10} {:>10} {:>12}",
+        "Total",
+        programs.iter().map(|p| p.text).sum::<u32>(),
+        programs.iter().map(|p| p.data).sum::<u32>(),
+        programs.iter().map(|p| p.bss).sum::<u32>(),
+        ram
+    );
+    println!();
+
+    let mut failures = vec![];
+    let (ram_region, flash_region) = memory_limits(&manifest);
+    check_region("RAM", ram, ram_region.as_ref(), &mut failures);
+    let flash = matches.value_of("image").map(|image| {
+        let flash = std::fs::metadata(image)
+            .unwrap_or_else(|e| panic!("couldn't read image {}: {}", image, e))
+            .len() as u32;
+        check_region("Flash", flash, flash_region.as_ref(), &mut failures);
+        flash
+    });
+
+    if let Some(path) = matches.value_of("baseline") {
+        let contents = std::fs::read_to_string(path)
+            .unwrap_or_else(|e| panic!("couldn't read baseline {}: {}", path, e));
+        let baseline: Baseline = toml::from_str(&contents)
+            .unwrap_or_else(|e| panic!("couldn't parse baseline {}: {}", path, e));
+        check_budget("RAM at boot", ram, baseline.ram, &mut failures);
+        if let Some(flash) = flash {
+            check_budget("Flash", flash, baseline.flash, &mut failures);
+        }
+        for program in &programs {
+            let budget = match baseline.programs.get(&program.name) {
+                Some(budget) => budget,
+                None => {
+                    println!("Warning: {} has no budget", program.name);
+                    continue;
+                }
+            };
+            let checks = [
+                (".text", program.text, budget.text),
+                (".data", program.data, budget.data),
+                (".bss", program.bss, budget.bss),
+                ("RAM", program.ram, budget.ram),
+            ];
+            for (what, value, limit) in checks.iter() {
+                let what = format!("{} {}", program.name, what);
+                check_budget(&what, *value, *limit, &mut failures);
+            }
+        }
+    }
+
+    if let Some(path) = matches.value_of("write-baseline") {
+        let baseline = Baseline {
+            ram: Some(ram),
+            flash,
+            programs: programs
+                .iter()
+                .map(|p| (p.name.clone(), p.sizes()))
+                .collect(),
+        };
+        let contents = toml::to_string(&baseline).expect("couldn't serialize baseline");
+        std::fs::write(path, contents)
+            .unwrap_or_else(|e| panic!("couldn't write baseline {}: {}", path, e));
+        println!("Baseline written to {}", path);
+    }
+
+    if !failures.is_empty() {
+        for failure in &failures {
+            eprintln!("Error: {}", failure);
+        }
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tools::elf::MiniElfSection;
+
+    /// A program with the given sections, as (address, size, flags)
+    fn elf(sections: &[(u32, u32, MiniElfFlags)], pie: bool) -> MiniElf {
+        MiniElf {
+            entry_point: sections[0].0,
+            sections: sections
+                .iter()
+                .map(|&(virt, size, flags)| MiniElfSection {
+                    virt,
+                    size,
+                    flags,
+                    name: String::new(),
+                })
+                .collect(),
+            program: vec![],
+            relocations: if pie { Some(vec![]) } else { None },
+        }
+    }
+
+    /// `.text` and `.rodata` share a page, as do `.data` and `.bss`
+    fn sample(pie: bool) -> MiniElf {
+        elf(
+            &[
+                (0x2000_0000, 0x1800, MiniElfFlags::EXECUTE),
+                (0x2000_1800, 0x100, MiniElfFlags::NONE),
+                (0x2000_2000, 0x10, MiniElfFlags::WRITE),
+                (
+                    0x2000_2010,
+                    0x2000,
+                    MiniElfFlags::WRITE | MiniElfFlags::NOCOPY,
+                ),
+            ],
+            pie,
+        )
+    }
+
+    #[test]
+    fn measure_program() {
+        let program = measure("test".to_owned(), &sample(false), false, None, false);
+        assert_eq!(program.text, 0x1900);
+        assert_eq!(program.data, 0x10);
+        assert_eq!(program.bss, 0x2000);
+        // Five pages of sections, the default stack and the process pages,
+        // with page tables for the sections, the stack, the root page table
+        // and context, and the page table mappings.
+        assert_eq!(program.ram, (5 + 5 + 3 + 4) * PAGE_SIZE);
+
+        let program = measure(
+            "test".to_owned(),
+            &sample(false),
+            false,
+            Some(0x2000),
+            false,
+        );
+        assert_eq!(program.ram, (5 + 2 + 3 + 4) * PAGE_SIZE);
+    }
+
+    #[test]
+    fn measure_spanning_page_tables() {
+        // One section that crosses into the next 4 MiB needs another table
+        let program = measure(
+            "test".to_owned(),
+            &elf(&[(0x203f_f000, 0x2000, MiniElfFlags::EXECUTE)], false),
+            false,
+            None,
+            false,
+        );
+        assert_eq!(program.ram, (2 + 5 + 3 + 5) * PAGE_SIZE);
+    }
+
+    #[test]
+    fn measure_aslr() {
+        // Moving a position-independent program up by `PIE_LOAD_OFFSET`
+        // doesn't change which page tables it needs
+        let program = measure("test".to_owned(), &sample(true), false, None, false);
+        assert_eq!(program.ram, (5 + 5 + 3 + 4) * PAGE_SIZE);
+
+        // With ASLR, both the sections and the stack might straddle two
+        let program = measure("test".to_owned(), &sample(true), false, None, true);
+        assert_eq!(program.ram, (5 + 5 + 3 + 6) * PAGE_SIZE);
+
+        // Programs that aren't position-independent keep their sections
+        // where they are, but not their stacks
+        let program = measure("test".to_owned(), &sample(false), false, None, true);
+        assert_eq!(program.ram, (5 + 5 + 3 + 5) * PAGE_SIZE);
+    }
+
+    #[test]
+    fn measure_kernel() {
+        let kernel = elf(
+            &[
+                (0xffd0_0000, 0x1000, MiniElfFlags::EXECUTE),
+                (0xffd0_1000, 0x100, MiniElfFlags::WRITE),
+                (
+                    0xffd0_1100,
+                    0x100,
+                    MiniElfFlags::WRITE | MiniElfFlags::NOCOPY,
+                ),
+            ],
+            false,
+        );
+        let program = measure("kernel".to_owned(), &kernel, true, None, true);
+        // The exception stack shares a page table with the kernel's sections
+        assert_eq!(program.ram, (1 + 1 + 10 + 3 + 4) * PAGE_SIZE);
+    }
+
+    #[test]
+    fn l2_tables_per_4mib() {
+        let run = |first, count| PageRun {
+            first,
+            count,
+            moves: true,
+        };
+        assert_eq!(l2_tables(&[], false), 0);
+        assert_eq!(l2_tables(&[run(0, 1024)], false), 1);
+        assert_eq!(l2_tables(&[run(1023, 2)], false), 2);
+        assert_eq!(l2_tables(&[run(0, 1), run(1023, 1)], false), 1);
+        assert_eq!(l2_tables(&[run(0, 1), run(1, 0)], false), 1);
+
+        // A run that moves might straddle one more boundary, but no more
+        assert_eq!(l2_tables(&[run(0, 1)], true), 1);
+        assert_eq!(l2_tables(&[run(0, 2)], true), 2);
+        assert_eq!(l2_tables(&[run(0, 1025)], true), 2);
+        assert_eq!(l2_tables(&[run(0, 1026)], true), 3);
+    }
+
+    #[test]
+    fn budgets() {
+        let mut failures = vec![];
+        check_budget("ram", 10, None, &mut failures);
+        check_budget("ram", 10, Some(10), &mut failures);
+        check_budget("ram", 9, Some(10), &mut failures);
+        assert!(failures.is_empty());
+
+        check_budget("kernel .text", 11, Some(10), &mut failures);
+        assert_eq!(
+            failures,
+            ["kernel .text is 11 bytes, 1 over its budget of 10"]
+        );
+    }
+}
@@ -0,0 +1,11 @@
+/// Append `data` to `output` as a compressed stream: its length in bytes,
+/// followed by LZ4 block data padded to a multiple of 4 bytes.  This is the
+/// format the loader expects when the `COMPRESSED` boot flag is set.
+pub fn compress_stream(data: &[u8], output: &mut Vec<u8>) {
+    let compressed = lz4_flex::block::compress(data);
+    output.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+    output.extend_from_slice(&compressed);
+    while output.len() & 3 != 0 {
+        output.push(0);
+    }
+}
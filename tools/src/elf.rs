@@ -1,24 +1,16 @@
-use bitflags::bitflags;
 use log::debug;
+use serde::ser::SerializeSeq;
+use serde::{Serialize, Serializer};
 use std::fmt;
 use std::fs::File;
 use std::io::{Cursor, Read, Seek, SeekFrom, Write};
 use std::path::Path;
+use xmas_elf::header::Type as HeaderType;
 use xmas_elf::program::Type as ProgramType;
-use xmas_elf::sections::ShType;
+use xmas_elf::sections::{SectionData, ShType};
 use xmas_elf::ElfFile;
 
-// Normal ELF flags
-use xmas_elf::sections::{SHF_ALLOC, SHF_EXECINSTR, SHF_WRITE};
-
-bitflags! {
-    pub struct MiniElfFlags: u8 {
-        const NONE = 0;
-        const WRITE = 1;
-        const NOCOPY = 2;
-        const EXECUTE = 4;
-    }
-}
+pub use minielf::SectionFlags as MiniElfFlags;
 
 pub struct ProgramDescription {
     /// Virtual address of .text section in RAM
@@ -43,20 +35,46 @@ pub struct ProgramDescription {
     pub program: Vec<u8>,
 }
 
-#[derive(Debug)]
+/// Serialize section flags as a list of flag names.
+fn serialize_flags<S: Serializer>(flags: &MiniElfFlags, s: S) -> Result<S::Ok, S::Error> {
+    let names = [
+        (MiniElfFlags::WRITE, "write"),
+        (MiniElfFlags::NOCOPY, "nocopy"),
+        (MiniElfFlags::EXECUTE, "execute"),
+    ];
+    let mut seq = s.serialize_seq(None)?;
+    for (flag, name) in names.iter() {
+        if flags.contains(*flag) {
+            seq.serialize_element(name)?;
+        }
+    }
+    seq.end()
+}
+
+#[derive(Debug, PartialEq, Serialize)]
 pub struct MiniElfSection {
     pub virt: u32,
     pub size: u32,
+    #[serde(serialize_with = "serialize_flags")]
     pub flags: MiniElfFlags,
+
+    /// Name of the section in the ELF file.  This isn't stored in the
+    /// image, so it's empty for sections read back from one.
+    #[serde(skip_serializing_if = "String::is_empty")]
     pub name: String,
 }
 
 impl fmt::Display for MiniElfSection {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.name.is_empty() {
+            write!(f, "Section: ")?;
+        } else {
+            write!(f, "Section {}: ", self.name)?;
+        }
         write!(
             f,
-            "Section {}: {} bytes loading @ {:08x} flags: {:?}",
-            self.name, self.size, self.virt, self.flags
+            "{} bytes loading @ {:08x} flags: {:?}",
+            self.size, self.virt, self.flags
         )
     }
 }
@@ -71,6 +89,11 @@ pub struct MiniElf {
 
     /// Actual section data
     pub program: Vec<u8>,
+
+    /// For position-independent programs, the address of each word that
+    /// must have the load offset added to it.  `None` if the program must
+    /// be loaded at the addresses it was linked at.
+    pub relocations: Option<Vec<u32>>,
 }
 
 #[derive(Debug)]
@@ -101,6 +124,12 @@ pub enum ElfReadError {
 
     /// Couldn't write the section to the file
     WriteSectionError(std::io::Error),
+
+    /// A relocation wasn't `R_RISCV_RELATIVE`
+    UnsupportedRelocation(u32 /* offset */, u8 /* type */),
+
+    /// A relocation didn't point to an aligned word inside a loaded section
+    RelocationRangeError(u32 /* offset */),
 }
 
 impl fmt::Display for ElfReadError {
@@ -118,6 +147,12 @@ impl fmt::Display for ElfReadError {
             SectionNotAligned(s, a) => write!(f, "elf section {} had unaligned length {}", s, a),
             FileSeekError(e) => write!(f, "couldn't seek in the output file: {}", e),
             WriteSectionError(e) => write!(f, "couldn't write a section to the output file: {}", e),
+            UnsupportedRelocation(o, t) => {
+                write!(f, "relocation at {:08x} has unsupported type {}", o, t)
+            }
+            RelocationRangeError(o) => {
+                write!(f, "relocation at {:08x} is outside of the program", o)
+            }
         }
     }
 }
@@ -283,7 +318,6 @@ pub fn read_minielf<P: AsRef<Path>>(filename: P) -> Result<MiniElf, ElfReadError
     // This keeps a running offset of where data is getting copied.
     let mut program_offset = 0;
     for s in elf.section_iter() {
-        let mut flags = MiniElfFlags::NONE;
         let name = s.get_name(&elf).unwrap_or("<<error>>");
 
         if s.address() == 0 {
@@ -303,19 +337,14 @@ pub fn read_minielf<P: AsRef<Path>>(filename: P) -> Result<MiniElf, ElfReadError
         debug!("    link:             {:?}", s.link());
         let size = s.size();
 
-        if s.flags() & SHF_ALLOC == 0 {
-            debug!("section has no allocations -- skipping");
-            continue;
-        }
-        if s.get_type() == Ok(ShType::NoBits) {
-            flags |= MiniElfFlags::NOCOPY;
-        }
-        if s.flags() & SHF_EXECINSTR != 0 {
-            flags |= MiniElfFlags::EXECUTE;
-        }
-        if s.flags() & SHF_WRITE != 0 {
-            flags |= MiniElfFlags::WRITE;
-        }
+        let nobits = s.get_type() == Ok(ShType::NoBits);
+        let flags = match MiniElfFlags::from_section(s.address() as u32, s.flags() as u32, nobits) {
+            Some(flags) => flags,
+            None => {
+                debug!("section has no allocations -- skipping");
+                continue;
+            }
+        };
 
         debug!("Adding {} to the file", name);
         debug!(
@@ -351,9 +380,67 @@ pub fn read_minielf<P: AsRef<Path>>(filename: P) -> Result<MiniElf, ElfReadError
         .seek(SeekFrom::End(0)).map_err(ElfReadError::SeekFromEndError)?;
 
     debug!("Program size: {} bytes", observed_size);
+    let mut program = program_data.into_inner();
+    let relocations = if elf.header.pt2.type_().as_type() == HeaderType::SharedObject {
+        Some(read_relocations(&elf, &sections, &mut program)?)
+    } else {
+        None
+    };
     Ok(MiniElf {
         entry_point,
         sections,
-        program: program_data.into_inner(),
+        program,
+        relocations,
     })
 }
+
+/// Gather the relocations of a position-independent program.  Only
+/// `R_RISCV_RELATIVE` relocations are supported, which is all a static
+/// PIE needs.  The addend of each is written into `program`, so that the
+/// loader only has to add the load offset to each word.
+fn read_relocations(
+    elf: &ElfFile,
+    sections: &[MiniElfSection],
+    program: &mut [u8],
+) -> Result<Vec<u32>, ElfReadError> {
+    let mut relocations = vec![];
+    for s in elf.section_iter() {
+        if s.get_type() != Ok(ShType::Rela) {
+            continue;
+        }
+        let entries = match s.get_data(elf).map_err(ElfReadError::ParseElfError)? {
+            SectionData::Rela32(entries) => entries,
+            _ => return Err(ElfReadError::ParseElfError("relocations are not 32-bit")),
+        };
+        for rela in entries {
+            let offset = rela.get_offset();
+            match minielf::is_relative(rela.get_type()) {
+                Ok(false) => continue,
+                Ok(true) => (),
+                Err(other) => return Err(ElfReadError::UnsupportedRelocation(offset, other)),
+            }
+
+            // Find where this word lives in the program stream.
+            let mut program_offset = 0;
+            let mut position = None;
+            for section in sections {
+                if section.flags.contains(MiniElfFlags::NOCOPY) {
+                    continue;
+                }
+                if offset >= section.virt && offset + 4 <= section.virt + section.size {
+                    position = Some(program_offset + (offset - section.virt) as usize);
+                    break;
+                }
+                program_offset += section.size as usize;
+            }
+            let position = match position {
+                Some(position) if offset & 3 == 0 => position,
+                _ => return Err(ElfReadError::RelocationRangeError(offset)),
+            };
+            program[position..position + 4].copy_from_slice(&rela.get_addend().to_le_bytes());
+            relocations.push(offset);
+        }
+    }
+    debug!("Program has {} relocations", relocations.len());
+    Ok(relocations)
+}
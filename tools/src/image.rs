@@ -0,0 +1,493 @@
+//! Read a boot image made by `create-image` back into its tags, so that
+//! images can be inspected and compared.
+
+use crate::elf::MiniElfFlags;
+use crate::tags::bflg::Bflg;
+use crate::tags::inie::IniE;
+use crate::tags::inir::IniR;
+use crate::tags::memory::MemoryRegions;
+use crate::tags::sign::Signature;
+use crate::tags::xkrn::XousKernel;
+use crate::xous_arguments::{code_name, read_word, serialize_code, XousArgumentCode};
+use crc::{crc16, Hasher16};
+use serde::{Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+#[derive(Debug)]
+pub enum ImageError {
+    /// The image doesn't start with an `XArg` tag
+    NotXousImage,
+
+    /// The `XArg` tag claims the tags are longer than the whole file
+    ArgumentsOutOfRange(u32 /* length in bytes */),
+
+    /// The tag at this offset runs past the end of the tags
+    Truncated(usize /* tag offset */),
+
+    /// A tag's size doesn't match its type
+    BadTagSize(
+        XousArgumentCode,
+        usize, /* tag offset */
+        usize, /* size */
+    ),
+
+    /// A tag's payload lies outside of the file
+    PayloadOutOfRange(
+        XousArgumentCode,
+        usize, /* tag offset */
+        u32,   /* payload offset */
+    ),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ImageError::*;
+        match self {
+            NotXousImage => write!(f, "image does not start with an XArg tag"),
+            ArgumentsOutOfRange(length) => write!(
+                f,
+                "XArg tag says the tags are {} bytes long, which is longer than the image",
+                length
+            ),
+            Truncated(offset) => write!(f, "tag at offset {:08x} is truncated", offset),
+            BadTagSize(code, offset, size) => write!(
+                f,
+                "{} tag at offset {:08x} has invalid size {}",
+                code_name(*code),
+                offset,
+                size
+            ),
+            PayloadOutOfRange(code, offset, payload) => write!(
+                f,
+                "{} tag at offset {:08x} has its data at {:08x}, outside of the image",
+                code_name(*code),
+                offset,
+                payload
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+/// Serialize bytes as a string of hex digits, for use with `serialize_with`.
+fn serialize_hex<T: AsRef<[u8]>, S: Serializer>(data: &T, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&hex::encode(data))
+}
+
+/// The contents of the `XArg` tag, which starts every image.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Arguments {
+    /// Length of all of the tags, in bytes
+    pub length: u32,
+
+    pub version: u32,
+    pub ram_start: u32,
+    pub ram_length: u32,
+
+    #[serde(serialize_with = "serialize_code")]
+    pub ram_name: XousArgumentCode,
+}
+
+impl fmt::Display for Arguments {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "    XArg: version {}, {} bytes of tags",
+            self.version, self.length
+        )?;
+        writeln!(
+            f,
+            "    Main RAM \"{}\" ({:08x}): {:08x} - {:08x}",
+            code_name(self.ram_name),
+            self.ram_name,
+            self.ram_start,
+            self.ram_start as u64 + self.ram_length as u64
+        )
+    }
+}
+
+impl Arguments {
+    pub fn parse(data: &[u8]) -> Option<Arguments> {
+        if data.len() != 20 {
+            return None;
+        }
+        Some(Arguments {
+            length: read_word(data, 0).wrapping_mul(4),
+            version: read_word(data, 1),
+            ram_start: read_word(data, 2),
+            ram_length: read_word(data, 3),
+            ram_name: read_word(data, 4),
+        })
+    }
+}
+
+/// A decoded tag.  Tags that carry a payload only describe it; the
+/// payload itself is in the `Payload` of the enclosing `Tag`.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum TagContents {
+    Arguments(Arguments),
+    MemoryRegions(MemoryRegions),
+    Bflg(Bflg),
+    IniE(IniE),
+    IniR(IniR),
+    Kernel(XousKernel),
+    Signature(Signature),
+
+    /// A tag this tool doesn't know about
+    Unknown(#[serde(serialize_with = "serialize_hex")] Vec<u8>),
+}
+
+impl fmt::Display for TagContents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TagContents::Arguments(arguments) => write!(f, "{}", arguments),
+            TagContents::MemoryRegions(regions) => write!(f, "{}", regions),
+            TagContents::Bflg(bflg) => write!(f, "{}", bflg),
+            TagContents::IniE(inie) => write!(f, "{}", inie),
+            TagContents::IniR(inir) => write!(f, "{}", inir),
+            TagContents::Kernel(xkrn) => write!(f, "{}", xkrn),
+            TagContents::Signature(signature) => write!(f, "{}", signature),
+            TagContents::Unknown(data) => {
+                write!(f, "    Unknown:")?;
+                for index in 0..data.len() / 4 {
+                    write!(f, " {:08x}", read_word(data, index))?;
+                }
+                writeln!(f)
+            }
+        }
+    }
+}
+
+/// Data stored after the tags, such as a program.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Payload {
+    /// Offset of the data from the start of the image
+    pub offset: u32,
+
+    /// Length of the data, including any padding
+    pub length: u32,
+
+    /// SHA-256 of the data, as hex digits
+    pub sha256: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Tag {
+    /// Offset of the tag header from the start of the image
+    pub offset: usize,
+
+    #[serde(rename = "name", serialize_with = "serialize_code")]
+    pub code: XousArgumentCode,
+
+    /// Size of the tag contents, in bytes
+    pub size: usize,
+
+    /// CRC16 stored in the tag header
+    pub crc: u16,
+
+    /// CRC16 of the tag contents as they are in the image
+    pub calculated_crc: u16,
+
+    pub contents: TagContents,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload: Option<Payload>,
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:08x} ({}) ({} bytes, crc: {:04x})",
+            self.code,
+            code_name(self.code),
+            self.size,
+            self.crc
+        )?;
+        if self.crc_ok() {
+            writeln!(f, "  CRC: OK")?;
+        } else {
+            writeln!(f, "  CRC: FAIL (calc: {:04x})", self.calculated_crc)?;
+        }
+        write!(f, "{}", self.contents)?;
+        if let Some(payload) = &self.payload {
+            writeln!(
+                f,
+                "    Payload: {} bytes at {:08x}, sha256 {}",
+                payload.length, payload.offset, payload.sha256
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Tag {
+    pub fn crc_ok(&self) -> bool {
+        self.crc == self.calculated_crc
+    }
+
+    /// The offset of this tag's payload, and the fewest bytes it may
+    /// occupy, if it has one.
+    fn payload_bounds(&self, compressed: bool) -> Option<(u32, u64)> {
+        match &self.contents {
+            TagContents::IniE(inie) => {
+                let copied = inie
+                    .sections()
+                    .iter()
+                    .filter(|section| !section.flags.contains(MiniElfFlags::NOCOPY))
+                    .map(|section| section.size as u64)
+                    .sum();
+                Some((inie.load_offset(), if compressed { 0 } else { copied }))
+            }
+            TagContents::IniR(inir) => Some((inir.load_offset(), inir.count() as u64 * 4)),
+            TagContents::Kernel(xkrn) => {
+                let copied = xkrn.text_size() as u64 + xkrn.data_size() as u64;
+                Some((xkrn.load_offset(), if compressed { 0 } else { copied }))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Image {
+    /// Length of the whole image, in bytes
+    pub length: usize,
+
+    pub tags: Vec<Tag>,
+}
+
+impl Image {
+    /// Decode every tag in `image`, checking that each tag and payload
+    /// lies within the file.  Tags with a bad CRC16 are still decoded.
+    pub fn parse(image: &[u8]) -> Result<Image, ImageError> {
+        if image.len() < 8 + 20 || image[0..4] != *b"XArg" {
+            return Err(ImageError::NotXousImage);
+        }
+        let args_length = read_word(image, 2).wrapping_mul(4);
+        if (args_length as usize) < 8 + 20 {
+            return Err(ImageError::NotXousImage);
+        }
+        if args_length as usize > image.len() {
+            return Err(ImageError::ArgumentsOutOfRange(args_length));
+        }
+        let tag_area = &image[..args_length as usize];
+
+        let mut tags = vec![];
+        let mut offset = 0;
+        while offset < tag_area.len() {
+            if offset + 8 > tag_area.len() {
+                return Err(ImageError::Truncated(offset));
+            }
+            let code = read_word(tag_area, offset / 4);
+            let header = read_word(tag_area, offset / 4 + 1);
+            let crc = header as u16;
+            let size = (header >> 16) as usize * 4;
+            let data = tag_area
+                .get(offset + 8..offset + 8 + size)
+                .ok_or(ImageError::Truncated(offset))?;
+
+            let mut digest = crc16::Digest::new(crc16::X25);
+            digest.write(data);
+
+            let contents = match &code.to_le_bytes() {
+                b"XArg" => Arguments::parse(data).map(TagContents::Arguments),
+                b"MREx" => MemoryRegions::parse(data).map(TagContents::MemoryRegions),
+                b"Bflg" => Bflg::parse(data).map(TagContents::Bflg),
+                b"IniE" => IniE::parse(data).map(TagContents::IniE),
+                b"IniR" => IniR::parse(data).map(TagContents::IniR),
+                b"XKrn" => XousKernel::parse(data).map(TagContents::Kernel),
+                b"Sign" => Signature::parse(data).map(TagContents::Signature),
+                _ => Some(TagContents::Unknown(data.to_vec())),
+            }
+            .ok_or(ImageError::BadTagSize(code, offset, size))?;
+
+            tags.push(Tag {
+                offset,
+                code,
+                size,
+                crc,
+                calculated_crc: digest.sum16(),
+                contents,
+                payload: None,
+            });
+            offset += 8 + size;
+        }
+
+        // Payloads are stored one after another, so each one runs until
+        // the next one starts.
+        let compressed = tags.iter().any(|tag| match &tag.contents {
+            TagContents::Bflg(bflg) => bflg.is_compressed(),
+            _ => false,
+        });
+        let bounds: Vec<_> = tags
+            .iter()
+            .map(|tag| tag.payload_bounds(compressed))
+            .collect();
+        for (tag, tag_bounds) in tags.iter_mut().zip(bounds.iter()) {
+            let (start, min_length) = match tag_bounds {
+                Some(tag_bounds) => *tag_bounds,
+                None => continue,
+            };
+            if start < args_length || start as usize > image.len() {
+                return Err(ImageError::PayloadOutOfRange(tag.code, tag.offset, start));
+            }
+            let end = bounds
+                .iter()
+                .flatten()
+                .map(|(other, _)| *other as usize)
+                .filter(|other| *other > start as usize)
+                .min()
+                .unwrap_or(image.len());
+            let data = &image[start as usize..end];
+            if (data.len() as u64) < min_length {
+                return Err(ImageError::PayloadOutOfRange(tag.code, tag.offset, start));
+            }
+            tag.payload = Some(Payload {
+                offset: start,
+                length: data.len() as u32,
+                sha256: hex::encode(Sha256::digest(data)),
+            });
+        }
+
+        Ok(Image {
+            length: image.len(),
+            tags,
+        })
+    }
+
+    /// The `XArg` tag, which `parse()` guarantees is first.
+    pub fn arguments(&self) -> &Arguments {
+        match &self.tags[0].contents {
+            TagContents::Arguments(arguments) => arguments,
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An image with `XArg`, `MREx`, `IniE` and `XKrn` tags, whose tags
+    /// take up 232 bytes
+    const IMAGE: &[u8] = include_bytes!("../../loader/test/args-default.bin");
+    const INIE_OFFSET: usize = 132;
+    const XKRN_OFFSET: usize = 196;
+
+    fn write_word(image: &mut [u8], offset: usize, value: u32) {
+        image[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// Change the length of the tags stored in the `XArg` tag
+    fn set_args_length(image: &mut [u8], length: u32) {
+        write_word(image, 8, length / 4);
+    }
+
+    #[test]
+    fn parse_default() {
+        let image = Image::parse(IMAGE).unwrap();
+        assert_eq!(image.length, IMAGE.len());
+        let codes: Vec<String> = image.tags.iter().map(|tag| code_name(tag.code)).collect();
+        assert_eq!(codes, ["XArg", "MREx", "IniE", "XKrn"]);
+        assert!(image.tags.iter().all(|tag| tag.crc_ok()));
+        assert_eq!(image.tags[2].offset, INIE_OFFSET);
+        assert_eq!(image.tags[3].offset, XKRN_OFFSET);
+
+        // The program runs up to the kernel, which runs to the end
+        let program = image.tags[2].payload.as_ref().unwrap();
+        let kernel = image.tags[3].payload.as_ref().unwrap();
+        assert_eq!(program.offset, 232);
+        assert_eq!(program.offset + program.length, kernel.offset);
+        assert_eq!((kernel.offset + kernel.length) as usize, IMAGE.len());
+    }
+
+    #[test]
+    fn not_xous_image() {
+        let mut image = IMAGE.to_vec();
+        image[0] = b'Y';
+        assert!(matches!(
+            Image::parse(&image),
+            Err(ImageError::NotXousImage)
+        ));
+        assert!(matches!(
+            Image::parse(&IMAGE[..16]),
+            Err(ImageError::NotXousImage)
+        ));
+    }
+
+    #[test]
+    fn arguments_out_of_range() {
+        assert!(matches!(
+            Image::parse(&IMAGE[..200]),
+            Err(ImageError::ArgumentsOutOfRange(232))
+        ));
+    }
+
+    #[test]
+    fn truncated() {
+        // The tags end partway through the `XKrn` tag's data
+        let mut image = IMAGE.to_vec();
+        set_args_length(&mut image, 228);
+        assert!(matches!(
+            Image::parse(&image),
+            Err(ImageError::Truncated(XKRN_OFFSET))
+        ));
+
+        // The tags end partway through the `XKrn` tag's header
+        set_args_length(&mut image, 200);
+        assert!(matches!(
+            Image::parse(&image),
+            Err(ImageError::Truncated(XKRN_OFFSET))
+        ));
+    }
+
+    #[test]
+    fn bad_tag_size() {
+        // Shorten the `XKrn` tag from seven words to six
+        let mut image = IMAGE.to_vec();
+        let header = read_word(&image, XKRN_OFFSET / 4 + 1);
+        write_word(&mut image, XKRN_OFFSET + 4, (6 << 16) | (header & 0xffff));
+        assert!(matches!(
+            Image::parse(&image),
+            Err(ImageError::BadTagSize(_, XKRN_OFFSET, 24))
+        ));
+    }
+
+    #[test]
+    fn payload_out_of_range() {
+        // A program loaded from past the end of the file
+        let mut image = IMAGE.to_vec();
+        write_word(&mut image, INIE_OFFSET + 8, IMAGE.len() as u32 + 4);
+        assert!(matches!(
+            Image::parse(&image),
+            Err(ImageError::PayloadOutOfRange(_, INIE_OFFSET, _))
+        ));
+
+        // A program loaded from within the tags
+        let mut image = IMAGE.to_vec();
+        write_word(&mut image, INIE_OFFSET + 8, 0);
+        assert!(matches!(
+            Image::parse(&image),
+            Err(ImageError::PayloadOutOfRange(_, INIE_OFFSET, 0))
+        ));
+
+        // A kernel that's cut short
+        assert!(matches!(
+            Image::parse(&IMAGE[..IMAGE.len() - 4]),
+            Err(ImageError::PayloadOutOfRange(_, XKRN_OFFSET, _))
+        ));
+    }
+
+    #[test]
+    fn bad_crc_still_parsed() {
+        let mut image = IMAGE.to_vec();
+        image[INIE_OFFSET + 12] ^= 1;
+        let image = Image::parse(&image).unwrap();
+        assert!(!image.tags[2].crc_ok());
+        assert!(image.tags[3].crc_ok());
+    }
+}
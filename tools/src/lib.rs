@@ -1,6 +1,9 @@
 extern crate csv;
 extern crate log;
 #[macro_use] pub mod xous_arguments;
+pub mod compress;
 pub mod tags;
 pub mod utils;
 pub mod elf;
+pub mod image;
+pub mod manifest;
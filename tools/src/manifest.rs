@@ -0,0 +1,527 @@
+//! Image manifests describe everything `create-image` puts into a boot
+//! image, so that product variants can be checked in and reviewed rather
+//! than spelled out as command line flags.
+//!
+//! ```toml
+//! kernel = "target/riscv32imac-unknown-none-elf/release/xous-kernel"
+//! svd = "emulation/renode.svd"
+//! sign_key = "keys/developer.key"
+//!
+//! [boot]
+//! compress = true
+//!
+//! [[init]]
+//! name = "shell"
+//! path = "target/riscv32imac-unknown-none-elf/release/shell"
+//! stack_size = 0x20000
+//! ```
+//!
+//! Relative paths are resolved against the directory containing the
+//! manifest.
+
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::tags::inie::IniE;
+use crate::tags::memory::MemoryRegion;
+
+const PAGE_SIZE: u32 = 4096;
+
+#[derive(Debug)]
+pub enum ManifestError {
+    /// Couldn't read the manifest file
+    IoError(io::Error),
+
+    /// The manifest wasn't valid TOML, or had unexpected keys
+    ParseError(toml::de::Error),
+
+    /// None of `ram`, `svd` or `csv` were specified
+    NoMemory,
+
+    /// A file named in the manifest doesn't exist
+    MissingFile(PathBuf),
+
+    /// A value that must be a multiple of the page size wasn't
+    NotPageAligned(String /* what */, u32 /* value */),
+
+    /// A size was zero
+    EmptySize(String /* what */),
+
+    /// A region extends past the end of the address space
+    RegionOverflow(String /* region name */),
+
+    /// Two memory regions overlap
+    RegionOverlap(String, String),
+
+    /// Two regions or processes have the same name
+    DuplicateName(String),
+
+    /// A region or process name was empty
+    EmptyName,
+
+    /// An option was given that the image format can't yet store
+    Unsupported(String /* what */),
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManifestError::IoError(e) => write!(f, "couldn't read manifest: {}", e),
+            ManifestError::ParseError(e) => write!(f, "couldn't parse manifest: {}", e),
+            ManifestError::NoMemory => write!(f, "one of ram, svd or csv must be specified"),
+            ManifestError::MissingFile(path) => write!(f, "file {} does not exist", path.display()),
+            ManifestError::NotPageAligned(what, value) => write!(
+                f,
+                "{} ({:08x}) is not a multiple of the page size",
+                what, value
+            ),
+            ManifestError::EmptySize(what) => write!(f, "{} must not be zero", what),
+            ManifestError::RegionOverflow(name) => {
+                write!(f, "region {} extends past the end of memory", name)
+            }
+            ManifestError::RegionOverlap(a, b) => write!(f, "regions {} and {} overlap", a, b),
+            ManifestError::DuplicateName(name) => write!(f, "name {} is used more than once", name),
+            ManifestError::EmptyName => write!(f, "names must not be empty"),
+            ManifestError::Unsupported(what) => write!(f, "{} is not supported yet", what),
+        }
+    }
+}
+
+impl std::error::Error for ManifestError {}
+
+impl std::convert::From<io::Error> for ManifestError {
+    fn from(e: io::Error) -> ManifestError {
+        ManifestError::IoError(e)
+    }
+}
+
+impl std::convert::From<toml::de::Error> for ManifestError {
+    fn from(e: toml::de::Error) -> ManifestError {
+        ManifestError::ParseError(e)
+    }
+}
+
+/// Main memory, which the kernel allocates process memory from.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Ram {
+    pub offset: u32,
+    pub size: u32,
+
+    /// Name of the region, which is shortened to a four-character tag
+    #[serde(default = "Ram::default_name")]
+    pub name: String,
+}
+
+impl Ram {
+    fn default_name() -> String {
+        "sram".to_owned()
+    }
+}
+
+/// An additional memory region, such as a block of peripherals, that
+/// processes may map.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Region {
+    pub name: String,
+    pub start: u32,
+    pub length: u32,
+}
+
+/// Flags passed to the loader and kernel in the `Bflg` tag.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Boot {
+    /// Reduce kernel-userspace security and enable debugging programs
+    #[serde(default)]
+    pub debug: bool,
+
+    /// Compress the kernel and initial programs
+    #[serde(default)]
+    pub compress: bool,
+
+    /// Place initial programs and their stacks at random addresses
+    #[serde(default)]
+    pub aslr: bool,
+
+    /// Test main RAM before booting, and keep any pages that fail from
+    /// being used
+    #[serde(default)]
+    pub memtest: bool,
+}
+
+/// A program started by the loader
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Init {
+    pub path: PathBuf,
+
+    /// Name of the process.  Defaults to the file name of `path`, and is
+    /// shortened to 16 bytes in the image.
+    pub name: Option<String>,
+
+    /// Size of the main thread's stack, in bytes.  Defaults to the
+    /// kernel's default.
+    pub stack_size: Option<u32>,
+
+    /// Largest size the heap may grow to, in bytes.  Defaults to the
+    /// kernel's default.
+    pub heap_max: Option<u32>,
+
+    /// Scheduling priority.  This can't be stored in the image yet, so
+    /// setting it is an error.
+    pub priority: Option<u8>,
+}
+
+impl Init {
+    pub fn name(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => self
+                .path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// Kernel ELF image
+    pub kernel: PathBuf,
+
+    /// Explicit main memory range
+    pub ram: Option<Ram>,
+
+    /// SVD file to read main memory and additional regions from
+    pub svd: Option<PathBuf>,
+
+    /// `csr.csv` file from litex to read main memory and additional
+    /// regions from
+    pub csv: Option<PathBuf>,
+
+    /// Ed25519 secret key, as 64 hex digits, used to sign the image
+    pub sign_key: Option<PathBuf>,
+
+    #[serde(default)]
+    pub boot: Boot,
+
+    #[serde(default, rename = "region")]
+    pub regions: Vec<Region>,
+
+    #[serde(default)]
+    pub init: Vec<Init>,
+}
+
+impl Manifest {
+    /// Create an empty manifest for the given kernel, to be filled in from
+    /// command line arguments.
+    pub fn new<P: Into<PathBuf>>(kernel: P) -> Manifest {
+        Manifest {
+            kernel: kernel.into(),
+            ram: None,
+            svd: None,
+            csv: None,
+            sign_key: None,
+            boot: Default::default(),
+            regions: vec![],
+            init: vec![],
+        }
+    }
+
+    /// Read a manifest from a file, resolving any relative paths against
+    /// the directory the manifest is in.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Manifest, ManifestError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        let mut manifest = Self::parse(&contents)?;
+        if let Some(base) = path.parent() {
+            manifest.resolve_paths(base);
+        }
+        Ok(manifest)
+    }
+
+    pub fn parse(contents: &str) -> Result<Manifest, ManifestError> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    fn resolve_paths(&mut self, base: &Path) {
+        let mut paths = vec![&mut self.kernel];
+        paths.extend(self.svd.iter_mut());
+        paths.extend(self.csv.iter_mut());
+        paths.extend(self.sign_key.iter_mut());
+        paths.extend(self.init.iter_mut().map(|init| &mut init.path));
+        for path in paths {
+            *path = base.join(&*path);
+        }
+    }
+
+    /// Check the manifest for mistakes before any work is done generating
+    /// the image.
+    pub fn validate(&self) -> Result<(), ManifestError> {
+        if self.ram.is_none() && self.svd.is_none() && self.csv.is_none() {
+            return Err(ManifestError::NoMemory);
+        }
+
+        let mut files = vec![&self.kernel];
+        files.extend(self.svd.iter());
+        files.extend(self.csv.iter());
+        files.extend(self.sign_key.iter());
+        files.extend(self.init.iter().map(|init| &init.path));
+        for path in files {
+            if !path.exists() {
+                return Err(ManifestError::MissingFile(path.clone()));
+            }
+        }
+
+        // Regions are compared by the tag they'll be stored as, since two
+        // long names may shorten to the same tag.
+        let mut regions = vec![];
+        if let Some(ram) = &self.ram {
+            regions.push((ram.name.as_str(), ram.offset, ram.size));
+        }
+        for region in &self.regions {
+            regions.push((region.name.as_str(), region.start, region.length));
+        }
+        let mut tags = BTreeSet::new();
+        for (index, &(name, start, length)) in regions.iter().enumerate() {
+            if name.is_empty() {
+                return Err(ManifestError::EmptyName);
+            }
+            if !tags.insert(MemoryRegion::make_name(name)) {
+                return Err(ManifestError::DuplicateName(name.to_owned()));
+            }
+            check_page_aligned(&format!("start of region {}", name), start)?;
+            check_page_aligned(&format!("length of region {}", name), length)?;
+            if length == 0 {
+                return Err(ManifestError::EmptySize(format!(
+                    "length of region {}",
+                    name
+                )));
+            }
+            let end = (start as u64) + (length as u64);
+            if end > 1 << 32 {
+                return Err(ManifestError::RegionOverflow(name.to_owned()));
+            }
+            for &(other_name, other_start, other_length) in &regions[..index] {
+                let other_end = (other_start as u64) + (other_length as u64);
+                if (start as u64) < other_end && (other_start as u64) < end {
+                    return Err(ManifestError::RegionOverlap(
+                        other_name.to_owned(),
+                        name.to_owned(),
+                    ));
+                }
+            }
+        }
+
+        // Processes are likewise compared by the name stored in the image.
+        let mut names = BTreeSet::new();
+        for init in &self.init {
+            let name = init.name();
+            if name.is_empty() {
+                return Err(ManifestError::EmptyName);
+            }
+            if init.priority.is_some() {
+                return Err(ManifestError::Unsupported(format!("priority of {}", name)));
+            }
+            if let Some(stack_size) = init.stack_size {
+                let what = format!("stack size of {}", name);
+                check_page_aligned(&what, stack_size)?;
+                if stack_size == 0 {
+                    return Err(ManifestError::EmptySize(what));
+                }
+            }
+            // Zero is stored as "use the default", so it can't be requested
+            if let Some(heap_max) = init.heap_max {
+                let what = format!("heap maximum of {}", name);
+                check_page_aligned(&what, heap_max)?;
+                if heap_max == 0 {
+                    return Err(ManifestError::EmptySize(what));
+                }
+            }
+            let name = IniE::make_name(&name);
+            if !names.insert(name.clone()) {
+                return Err(ManifestError::DuplicateName(name));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn check_page_aligned(what: &str, value: u32) -> Result<(), ManifestError> {
+    if value & (PAGE_SIZE - 1) != 0 {
+        return Err(ManifestError::NotPageAligned(what.to_owned(), value));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A manifest whose files exist, to which each test adds entries
+    const BASE: &str = r#"
+kernel = "Cargo.toml"
+
+[ram]
+offset = 0x40000000
+size = 0x1000000
+"#;
+
+    fn parse(extra: &str) -> Manifest {
+        let mut manifest = Manifest::parse(&format!("{}{}", BASE, extra)).unwrap();
+        manifest.resolve_paths(Path::new(env!("CARGO_MANIFEST_DIR")));
+        manifest
+    }
+
+    #[test]
+    fn valid() {
+        let manifest = parse(
+            r#"
+[[region]]
+name = "csr"
+start = 0xf0000000
+length = 0x10000
+
+[[init]]
+path = "Cargo.toml"
+stack_size = 0x20000
+heap_max = 0x100000
+"#,
+        );
+        manifest.validate().unwrap();
+    }
+
+    #[test]
+    fn region_overlap() {
+        let manifest = parse(
+            r#"
+[[region]]
+name = "vram"
+start = 0x40fff000
+length = 0x2000
+"#,
+        );
+        match manifest.validate() {
+            Err(ManifestError::RegionOverlap(a, b)) => {
+                assert_eq!(a, "sram");
+                assert_eq!(b, "vram");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn duplicate_region_tag() {
+        // Both names shorten to the same four-character tag
+        let manifest = parse(
+            r#"
+[[region]]
+name = "csr0"
+start = 0xf0000000
+length = 0x1000
+
+[[region]]
+name = "csr0-extra"
+start = 0xf0001000
+length = 0x1000
+"#,
+        );
+        assert!(matches!(
+            manifest.validate(),
+            Err(ManifestError::DuplicateName(_))
+        ));
+    }
+
+    #[test]
+    fn duplicate_process_name() {
+        let manifest = parse(
+            r#"
+[[init]]
+path = "Cargo.toml"
+
+[[init]]
+name = "Cargo.toml"
+path = "README.md"
+"#,
+        );
+        assert!(matches!(
+            manifest.validate(),
+            Err(ManifestError::DuplicateName(_))
+        ));
+    }
+
+    #[test]
+    fn unaligned() {
+        let manifest = parse(
+            r#"
+[[region]]
+name = "csr"
+start = 0xf0000800
+length = 0x1000
+"#,
+        );
+        assert!(matches!(
+            manifest.validate(),
+            Err(ManifestError::NotPageAligned(_, 0xf000_0800))
+        ));
+
+        let manifest = parse(
+            r#"
+[[init]]
+path = "Cargo.toml"
+stack_size = 0x20010
+"#,
+        );
+        assert!(matches!(
+            manifest.validate(),
+            Err(ManifestError::NotPageAligned(_, 0x20010))
+        ));
+    }
+
+    #[test]
+    fn zero_sizes() {
+        let manifest = parse(
+            r#"
+[[region]]
+name = "csr"
+start = 0xf0000000
+length = 0
+"#,
+        );
+        assert!(matches!(
+            manifest.validate(),
+            Err(ManifestError::EmptySize(_))
+        ));
+
+        for field in &["stack_size", "heap_max"] {
+            let manifest = parse(&format!("[[init]]\npath = \"Cargo.toml\"\n{} = 0\n", field));
+            assert!(matches!(
+                manifest.validate(),
+                Err(ManifestError::EmptySize(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn priority_rejected() {
+        let manifest = parse(
+            r#"
+[[init]]
+path = "Cargo.toml"
+priority = 1
+"#,
+        );
+        assert!(matches!(
+            manifest.validate(),
+            Err(ManifestError::Unsupported(_))
+        ));
+    }
+}
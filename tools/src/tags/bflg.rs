@@ -1,17 +1,33 @@
-use crate::xous_arguments::{XousArgument, XousArgumentCode, XousSize};
+use crate::xous_arguments::{read_word, XousArgument, XousArgumentCode, XousSize};
+use serde::Serialize;
 use std::fmt;
 use std::io;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, PartialEq, Serialize)]
 pub struct Bflg {
     /// Disable copying data
+    #[serde(rename = "no_copy")]
     no_copy_: bool,
 
     /// Addresses are all absolute
+    #[serde(rename = "absolute")]
     absolute_: bool,
 
     /// Set the SUM bit in $mstatus to allow Supervisor to access User memory
+    #[serde(rename = "debug")]
     debug_: bool,
+
+    /// Kernel and program payloads are LZ4-compressed
+    #[serde(rename = "compressed")]
+    compressed_: bool,
+
+    /// Place initial programs at random addresses
+    #[serde(rename = "aslr")]
+    aslr_: bool,
+
+    /// Test main RAM before booting
+    #[serde(rename = "memtest")]
+    memtest_: bool,
 }
 
 impl fmt::Display for Bflg {
@@ -33,6 +49,24 @@ impl fmt::Display for Bflg {
         } else {
             write!(f, " -debug")?;
         }
+
+        if self.compressed_ {
+            write!(f, " +COMPRESSED")?;
+        } else {
+            write!(f, " -compressed")?;
+        }
+
+        if self.aslr_ {
+            write!(f, " +ASLR")?;
+        } else {
+            write!(f, " -aslr")?;
+        }
+
+        if self.memtest_ {
+            write!(f, " +MEMTEST")?;
+        } else {
+            write!(f, " -memtest")?;
+        }
         writeln!(f)
     }
 }
@@ -58,6 +92,38 @@ impl Bflg {
         self.debug_ = true;
         self
     }
+    pub fn compressed(mut self) -> Bflg {
+        self.compressed_ = true;
+        self
+    }
+    pub fn aslr(mut self) -> Bflg {
+        self.aslr_ = true;
+        self
+    }
+    pub fn memtest(mut self) -> Bflg {
+        self.memtest_ = true;
+        self
+    }
+
+    /// Decode the contents of a `Bflg` tag read from an image.
+    pub fn parse(data: &[u8]) -> Option<Bflg> {
+        if data.len() != 4 {
+            return None;
+        }
+        let val = read_word(data, 0);
+        Some(Bflg {
+            no_copy_: val & 1 != 0,
+            absolute_: val & (1 << 1) != 0,
+            debug_: val & (1 << 2) != 0,
+            compressed_: val & (1 << 3) != 0,
+            aslr_: val & (1 << 4) != 0,
+            memtest_: val & (1 << 5) != 0,
+        })
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.compressed_
+    }
 }
 
 impl XousArgument for Bflg {
@@ -79,6 +145,15 @@ impl XousArgument for Bflg {
         if self.debug_ {
             val |= 1 << 2;
         }
+        if self.compressed_ {
+            val |= 1 << 3;
+        }
+        if self.aslr_ {
+            val |= 1 << 4;
+        }
+        if self.memtest_ {
+            val |= 1 << 5;
+        }
         written += output.write(&val.to_le_bytes())?;
         Ok(written)
     }
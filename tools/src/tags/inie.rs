@@ -1,9 +1,14 @@
-use crate::elf::MiniElfSection;
-use crate::xous_arguments::{XousArgument, XousArgumentCode, XousSize};
+use crate::compress::compress_stream;
+use crate::elf::{MiniElfFlags, MiniElfSection};
+use crate::xous_arguments::{read_word, XousArgument, XousArgumentCode, XousSize};
+use serde::Serialize;
 use std::fmt;
 use std::io;
 
-#[derive(Debug)]
+/// The longest process name that fits in an `IniE` tag, in bytes
+pub const NAME_LENGTH: usize = 16;
+
+#[derive(Debug, Serialize)]
 pub struct IniE {
     /// Address of Init in RAM (i.e. SPI flash)
     load_offset: u32,
@@ -11,29 +16,60 @@ pub struct IniE {
     /// Virtual address entry point
     entrypoint: u32,
 
+    /// Size of the main thread's stack in bytes, or 0 for the default
+    stack_size: u32,
+
+    /// Largest size the heap may grow to in bytes, or 0 for the default
+    heap_max: u32,
+
+    /// Name of the process, which may be empty
+    #[serde(skip_serializing_if = "String::is_empty")]
+    name: String,
+
     /// Array of minielf sections
     sections: Vec<MiniElfSection>,
 
     /// Actual program data
+    #[serde(skip)]
     data: Vec<u8>,
 }
 
 impl fmt::Display for IniE {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "    IniE: ")?;
+        if !self.name.is_empty() {
+            write!(f, "process \"{}\", ", self.name)?;
+        }
         writeln!(
             f,
-            "    IniE: entrypoint @ {:08x}, loaded from {:08x}.  Sections:",
+            "entrypoint @ {:08x}, loaded from {:08x}",
             self.entrypoint, self.load_offset
         )?;
+        writeln!(
+            f,
+            "        Stack size: {}, heap maximum: {}.  Sections:",
+            limit_name(self.stack_size),
+            limit_name(self.heap_max)
+        )?;
         let mut load_offset = self.load_offset;
         for section in &self.sections {
             writeln!(f, "        Loaded from {:08x} - {}", load_offset, section)?;
-            load_offset += section.size as u32;
+            load_offset = load_offset.wrapping_add(section.size);
         }
         Ok(())
     }
 }
 
+/// Describe a stack size or heap maximum, where 0 means the kernel's
+/// default.
+pub fn limit_name(limit: u32) -> String {
+    if limit == 0 {
+        "default".to_owned()
+    } else {
+        format!("{} bytes", limit)
+    }
+}
+
 impl IniE {
     pub fn new(entrypoint: u32, sections: Vec<MiniElfSection>, mut data: Vec<u8>) -> IniE {
         // pad the data to 4 bytes
@@ -43,10 +79,142 @@ impl IniE {
         IniE {
             load_offset: 0,
             entrypoint,
+            stack_size: 0,
+            heap_max: 0,
+            name: String::new(),
             sections,
             data,
         }
     }
+
+    /// Shorten `name` to the longest prefix that fits in an `IniE` tag.
+    pub fn make_name(name: &str) -> String {
+        let mut length = name.len().min(NAME_LENGTH);
+        while !name.is_char_boundary(length) {
+            length -= 1;
+        }
+        name[..length].to_owned()
+    }
+
+    /// Set the size of the main thread's stack.  The kernel's default is
+    /// used if this is 0.
+    pub fn set_stack_size(&mut self, stack_size: u32) {
+        self.stack_size = stack_size;
+    }
+
+    /// Set the largest size the heap may grow to.  The kernel's default is
+    /// used if this is 0.
+    pub fn set_heap_max(&mut self, heap_max: u32) {
+        self.heap_max = heap_max;
+    }
+
+    /// Name the process.  Names longer than `NAME_LENGTH` are shortened.
+    pub fn set_name(&mut self, name: &str) {
+        self.name = Self::make_name(name);
+    }
+
+    /// Decode the contents of an `IniE` tag read from an image.  The
+    /// program data stays in the image, and is not read.
+    pub fn parse(data: &[u8]) -> Option<IniE> {
+        if data.len() < 16 + NAME_LENGTH || data.len() & 7 != 0 {
+            return None;
+        }
+        let name = &data[16..16 + NAME_LENGTH];
+        let name_length = name.iter().position(|&c| c == 0).unwrap_or(NAME_LENGTH);
+        let sections = (4..data.len() / 8)
+            .map(|index| {
+                let word2 = read_word(data, index * 2 + 1);
+                MiniElfSection {
+                    virt: read_word(data, index * 2),
+                    size: word2 & 0x00ff_ffff,
+                    flags: MiniElfFlags::from_bits_truncate((word2 >> 24) as u8),
+                    name: String::new(),
+                }
+            })
+            .collect();
+        Some(IniE {
+            load_offset: read_word(data, 0),
+            entrypoint: read_word(data, 1),
+            stack_size: read_word(data, 2),
+            heap_max: read_word(data, 3),
+            name: String::from_utf8_lossy(&name[..name_length]).into_owned(),
+            sections,
+            data: vec![],
+        })
+    }
+
+    /// Offset of the program data from the start of the image
+    pub fn load_offset(&self) -> u32 {
+        self.load_offset
+    }
+
+    pub fn entrypoint(&self) -> u32 {
+        self.entrypoint
+    }
+
+    pub fn stack_size(&self) -> u32 {
+        self.stack_size
+    }
+
+    pub fn heap_max(&self) -> u32 {
+        self.heap_max
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn sections(&self) -> &[MiniElfSection] {
+        &self.sections
+    }
+
+    /// Replace the program data with a compressed stream containing
+    /// every page the program occupies.
+    pub fn compress(&mut self) {
+        let mut compressed = vec![];
+        compress_stream(&self.page_images(), &mut compressed);
+        self.data = compressed;
+    }
+
+    /// Lay out the program as the loader would, returning the contents of
+    /// each page in the order the loader allocates them.
+    fn page_images(&self) -> Vec<u8> {
+        const PAGE_SIZE: usize = 4096;
+        let mut pages = vec![];
+        let mut page_addr = 0;
+        let mut data = self.data.iter();
+
+        for section in &self.sections {
+            let virt = section.virt as usize;
+            let len = section.size as usize;
+            if pages.is_empty() || virt & !(PAGE_SIZE - 1) != page_addr {
+                pages.resize(pages.len() + PAGE_SIZE, 0);
+            }
+
+            // The first chunk fills out the current page, and each
+            // subsequent chunk starts a new one.
+            let mut dest = pages.len() - PAGE_SIZE + (virt & (PAGE_SIZE - 1));
+            let mut bytes_to_copy = len;
+            let mut chunk_size = (PAGE_SIZE - (virt & (PAGE_SIZE - 1))).min(len);
+            loop {
+                if !section.flags.contains(MiniElfFlags::NOCOPY) {
+                    for byte in &mut pages[dest..dest + chunk_size] {
+                        *byte = *data.next().expect("section data is missing");
+                    }
+                }
+                bytes_to_copy -= chunk_size;
+                if bytes_to_copy == 0 {
+                    break;
+                }
+                dest = pages.len();
+                pages.resize(pages.len() + PAGE_SIZE, 0);
+                chunk_size = bytes_to_copy.min(PAGE_SIZE);
+            }
+
+            page_addr = (virt + len) & !(PAGE_SIZE - 1);
+        }
+        pages
+    }
 }
 
 impl XousArgument for IniE {
@@ -55,7 +223,7 @@ impl XousArgument for IniE {
     }
 
     fn length(&self) -> XousSize {
-        4 + 4 + (self.sections.len() * 8) as XousSize
+        4 + 4 + 4 + 4 + NAME_LENGTH as XousSize + (self.sections.len() * 8) as XousSize
     }
 
     fn finalize(&mut self, offset: usize) -> usize {
@@ -71,6 +239,11 @@ impl XousArgument for IniE {
         let mut written = 0;
         written += output.write(&self.load_offset.to_le_bytes())?;
         written += output.write(&self.entrypoint.to_le_bytes())?;
+        written += output.write(&self.stack_size.to_le_bytes())?;
+        written += output.write(&self.heap_max.to_le_bytes())?;
+        let mut name = [0u8; NAME_LENGTH];
+        name[..self.name.len()].copy_from_slice(self.name.as_bytes());
+        written += output.write(&name)?;
         for section in &self.sections {
             written += output.write(&section.virt.to_le_bytes())?;
             let mut word2 = section.size.to_le_bytes();
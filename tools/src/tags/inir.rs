@@ -0,0 +1,92 @@
+use crate::xous_arguments::{read_word, XousArgument, XousArgumentCode, XousSize};
+use serde::Serialize;
+use std::fmt;
+use std::io;
+
+/// Relocations for the position-independent program described by the
+/// `IniE` tag that immediately precedes this one.
+#[derive(Debug, Serialize)]
+pub struct IniR {
+    /// Address of the relocation table in RAM (i.e. SPI flash)
+    load_offset: u32,
+
+    /// Number of entries in the relocation table
+    count: u32,
+
+    /// The link-time address of each word that needs the load offset
+    /// added, as it is stored in the image
+    #[serde(skip)]
+    data: Vec<u8>,
+}
+
+impl fmt::Display for IniR {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "    IniR: {} relocations, loaded from {:08x}",
+            self.count, self.load_offset
+        )
+    }
+}
+
+impl IniR {
+    pub fn new(relocations: Vec<u32>) -> IniR {
+        let mut data = vec![];
+        for relocation in &relocations {
+            data.extend_from_slice(&relocation.to_le_bytes());
+        }
+        IniR {
+            load_offset: 0,
+            count: relocations.len() as u32,
+            data,
+        }
+    }
+
+    /// Decode the contents of an `IniR` tag read from an image.  The
+    /// relocation table stays in the image, and is not read.
+    pub fn parse(data: &[u8]) -> Option<IniR> {
+        if data.len() != 8 {
+            return None;
+        }
+        Some(IniR {
+            load_offset: read_word(data, 0),
+            count: read_word(data, 1),
+            data: vec![],
+        })
+    }
+
+    /// Offset of the relocation table from the start of the image
+    pub fn load_offset(&self) -> u32 {
+        self.load_offset
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+}
+
+impl XousArgument for IniR {
+    fn code(&self) -> XousArgumentCode {
+        u32::from_le_bytes(*b"IniR")
+    }
+
+    fn length(&self) -> XousSize {
+        4 + 4
+    }
+
+    fn finalize(&mut self, offset: usize) -> usize {
+        self.load_offset = offset as u32;
+        self.data.len()
+    }
+
+    fn last_data(&self) -> &[u8] {
+        &self.data
+    }
+
+    fn serialize(&self, output: &mut dyn io::Write) -> io::Result<usize> {
+        let mut written = 0;
+        written += output.write(&self.load_offset.to_le_bytes())?;
+        written += output.write(&self.count.to_le_bytes())?;
+        Ok(written)
+    }
+}
@@ -1,4 +1,5 @@
-use crate::xous_arguments::{XousArgument, XousArgumentCode, XousSize};
+use crate::xous_arguments::{read_word, serialize_code, XousArgument, XousArgumentCode, XousSize};
+use serde::Serialize;
 use std::fmt;
 use std::io;
 
@@ -11,7 +12,7 @@ macro_rules! make_type {
     }};
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, PartialEq, Serialize)]
 pub struct MemoryRegion {
     /// Starting offset (in bytes)
     start: u32,
@@ -20,13 +21,16 @@ pub struct MemoryRegion {
     length: u32,
 
     /// Region name (as a type)
+    #[serde(serialize_with = "serialize_code")]
     name: XousArgumentCode,
 
     /// Unused
+    #[serde(skip)]
     padding: u32,
 }
 
-#[derive(Default)]
+#[derive(Debug, Default, Serialize)]
+#[serde(transparent)]
 pub struct MemoryRegions {
     regions: Vec<MemoryRegion>,
 }
@@ -43,7 +47,7 @@ impl fmt::Display for MemoryRegions {
                 tag_name_str,
                 region.name,
                 region.start,
-                region.start + region.length
+                region.start as u64 + region.length as u64
             )?;
         }
         Ok(())
@@ -60,6 +64,18 @@ impl MemoryRegion {
         }
     }
 
+    pub fn start(&self) -> u32 {
+        self.start
+    }
+
+    pub fn length(&self) -> u32 {
+        self.length
+    }
+
+    pub fn name(&self) -> XousArgumentCode {
+        self.name
+    }
+
     pub fn make_name(name: &str) -> u32 {
         match name {
             "sram_ext" => u32::from_le_bytes(*b"SrEx"),
@@ -93,6 +109,27 @@ impl MemoryRegions {
     pub fn is_empty(&self) -> bool {
         self.regions.is_empty()
     }
+    pub fn regions(&self) -> &[MemoryRegion] {
+        &self.regions
+    }
+
+    /// Decode the contents of an `MREx` tag read from an image.
+    pub fn parse(data: &[u8]) -> Option<MemoryRegions> {
+        let chunks = data.chunks_exact(std::mem::size_of::<MemoryRegion>());
+        if !chunks.remainder().is_empty() {
+            return None;
+        }
+        let regions = chunks
+            .map(|region| {
+                MemoryRegion::new(
+                    read_word(region, 0),
+                    read_word(region, 1),
+                    read_word(region, 2),
+                )
+            })
+            .collect();
+        Some(MemoryRegions { regions })
+    }
 }
 
 impl XousArgument for MemoryRegions {
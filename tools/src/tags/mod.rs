@@ -1,4 +1,6 @@
 pub mod bflg;
 pub mod inie;
+pub mod inir;
 pub mod memory;
+pub mod sign;
 pub mod xkrn;
@@ -0,0 +1,149 @@
+use crate::xous_arguments::{read_word, XousArgument, XousArgumentCode, XousSize};
+use crc::{crc16, Hasher16};
+use ed25519_dalek::{Keypair, PublicKey, SecretKey};
+use serde::Serialize;
+use sha2::{Digest, Sha512};
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+/// Context string mixed into every image signature.  The loader uses
+/// the same string when verifying.
+pub const SIGNATURE_CONTEXT: &[u8] = b"xous-boot-image";
+
+/// Size of the signed length that precedes the signature
+const SIGNED_LENGTH_SIZE: usize = 4;
+
+/// Size of an Ed25519 signature
+const SIGNATURE_SIZE: usize = 64;
+
+/// Signs the image with an Ed25519 key.  The signature covers every byte
+/// of the image, including all payloads, except for this tag itself.
+/// This must be the last tag in the image.
+pub struct Sign {
+    keypair: Keypair,
+}
+
+impl fmt::Display for Sign {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "    Sign: Ed25519 public key {}", self.public_key_hex())
+    }
+}
+
+impl Sign {
+    pub fn new(secret: SecretKey) -> Sign {
+        let public = PublicKey::from(&secret);
+        Sign {
+            keypair: Keypair { secret, public },
+        }
+    }
+
+    /// Load a secret key stored as 64 hex digits.
+    pub fn from_key_file<P: AsRef<Path>>(path: P) -> io::Result<Sign> {
+        let contents = std::fs::read_to_string(path)?;
+        let bytes = hex::decode(contents.trim())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let secret = SecretKey::from_bytes(&bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Sign::new(secret))
+    }
+
+    /// The public key the loader must be built with in order to boot
+    /// images signed by this tag.
+    pub fn public_key_hex(&self) -> String {
+        hex::encode(self.keypair.public.as_bytes())
+    }
+}
+
+/// The contents of a `Sign` tag read back from an image.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Signature {
+    /// Number of bytes of the image covered by the signature
+    signed_length: u32,
+
+    /// The Ed25519 signature, as hex digits
+    signature: String,
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "    Sign: {} bytes signed, signature {}",
+            self.signed_length, self.signature
+        )
+    }
+}
+
+impl Signature {
+    /// Decode the contents of a `Sign` tag read from an image.
+    pub fn parse(data: &[u8]) -> Option<Signature> {
+        if data.len() != SIGNED_LENGTH_SIZE + SIGNATURE_SIZE {
+            return None;
+        }
+        Some(Signature {
+            signed_length: read_word(data, 0),
+            signature: hex::encode(&data[SIGNED_LENGTH_SIZE..]),
+        })
+    }
+
+    pub fn signed_length(&self) -> u32 {
+        self.signed_length
+    }
+
+    pub fn signature(&self) -> &str {
+        &self.signature
+    }
+}
+
+impl XousArgument for Sign {
+    fn code(&self) -> XousArgumentCode {
+        u32::from_le_bytes(*b"Sign")
+    }
+
+    fn length(&self) -> XousSize {
+        (SIGNED_LENGTH_SIZE + SIGNATURE_SIZE) as XousSize
+    }
+
+    fn serialize(&self, output: &mut dyn io::Write) -> io::Result<usize> {
+        // The signature can only be computed once the whole image exists,
+        // so reserve space for it here and fill it in from `seal()`.
+        let mut written = 0;
+        written += output.write(&[0; SIGNED_LENGTH_SIZE])?;
+        written += output.write(&[0; SIGNATURE_SIZE])?;
+        Ok(written)
+    }
+
+    fn seal(&self, image: &mut [u8], offset: usize) {
+        let contents = offset + 8;
+        let signature_start = contents + SIGNED_LENGTH_SIZE;
+        let end = signature_start + SIGNATURE_SIZE;
+
+        // The loader only accepts a signature as the final tag, since
+        // the signature must cover every other tag.
+        let mut args_length = [0; 4];
+        args_length.copy_from_slice(&image[8..12]);
+        assert_eq!(
+            u32::from_le_bytes(args_length) as usize * 4,
+            end,
+            "the Sign tag must be the last tag"
+        );
+
+        let signed_length = image.len() as u32;
+        image[contents..signature_start].copy_from_slice(&signed_length.to_le_bytes());
+
+        let mut digest = Sha512::new();
+        digest.update(&image[..offset]);
+        digest.update(&image[end..]);
+        let signature = self
+            .keypair
+            .sign_prehashed(digest, Some(SIGNATURE_CONTEXT))
+            .expect("couldn't sign image");
+        image[signature_start..end].copy_from_slice(&signature.to_bytes());
+
+        // The tag contents changed, so the CRC16 must be updated as well
+        let mut digest = crc16::Digest::new(crc16::X25);
+        digest.write(&image[contents..end]);
+        image[offset + 4..offset + 6].copy_from_slice(&digest.sum16().to_le_bytes());
+    }
+}
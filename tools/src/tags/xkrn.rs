@@ -1,8 +1,10 @@
-use crate::xous_arguments::{XousArgument, XousArgumentCode, XousSize};
+use crate::compress::compress_stream;
+use crate::xous_arguments::{read_word, XousArgument, XousArgumentCode, XousSize};
+use serde::Serialize;
 use std::fmt;
 use std::io;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct XousKernel {
     /// Address of PID1 in RAM (i.e. SPI flash)
     load_offset: u32,
@@ -26,6 +28,7 @@ pub struct XousKernel {
     entrypoint: u32,
 
     /// Actual program contents
+    #[serde(skip)]
     program: Vec<u8>,
 }
 
@@ -62,6 +65,64 @@ impl XousKernel {
             program,
         }
     }
+
+    /// Decode the contents of an `XKrn` tag read from an image.  The
+    /// program stays in the image, and is not read.
+    pub fn parse(data: &[u8]) -> Option<XousKernel> {
+        if data.len() != 28 {
+            return None;
+        }
+        Some(XousKernel {
+            load_offset: read_word(data, 0),
+            text_offset: read_word(data, 1),
+            text_size: read_word(data, 2),
+            data_offset: read_word(data, 3),
+            data_size: read_word(data, 4),
+            bss_size: read_word(data, 5),
+            entrypoint: read_word(data, 6),
+            program: vec![],
+        })
+    }
+
+    /// Offset of the program from the start of the image
+    pub fn load_offset(&self) -> u32 {
+        self.load_offset
+    }
+
+    pub fn text_offset(&self) -> u32 {
+        self.text_offset
+    }
+
+    pub fn text_size(&self) -> u32 {
+        self.text_size
+    }
+
+    pub fn data_offset(&self) -> u32 {
+        self.data_offset
+    }
+
+    pub fn data_size(&self) -> u32 {
+        self.data_size
+    }
+
+    pub fn bss_size(&self) -> u32 {
+        self.bss_size
+    }
+
+    pub fn entrypoint(&self) -> u32 {
+        self.entrypoint
+    }
+
+    /// Replace the program with a compressed stream of the text section,
+    /// followed by a compressed stream of the data section.
+    pub fn compress(&mut self) {
+        let text_end = self.text_size as usize;
+        let data_end = text_end + self.data_size as usize;
+        let mut compressed = vec![];
+        compress_stream(&self.program[..text_end], &mut compressed);
+        compress_stream(&self.program[text_end..data_end], &mut compressed);
+        self.program = compressed;
+    }
 }
 
 impl XousArgument for XousKernel {
@@ -3,6 +3,7 @@ use std::io::{Cursor, Result, Write};
 pub type XousArgumentCode = u32;
 pub type XousSize = u32;
 use crc::{crc16, Hasher16};
+use serde::Serializer;
 
 #[macro_export]
 macro_rules! make_type {
@@ -13,13 +14,33 @@ macro_rules! make_type {
     }};
 }
 
+/// Turn a fourcc code back into the string it was made from.
+pub fn code_name(code: XousArgumentCode) -> String {
+    String::from_utf8_lossy(&code.to_le_bytes()).to_string()
+}
+
+/// Serialize a fourcc code as its string, for use with `serialize_with`.
+pub fn serialize_code<S: Serializer>(
+    code: &XousArgumentCode,
+    s: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    s.serialize_str(&code_name(*code))
+}
+
+/// Read the little-endian word at word index `index` of `data`.  The caller
+/// must have checked that `data` is long enough.
+pub fn read_word(data: &[u8], index: usize) -> u32 {
+    let mut word = [0; 4];
+    word.copy_from_slice(&data[index * 4..index * 4 + 4]);
+    u32::from_le_bytes(word)
+}
+
 pub trait XousArgument: fmt::Display {
     /// A fourcc code of this tag
     fn code(&self) -> XousArgumentCode;
 
     fn name(&self) -> String {
-        let tag_name_bytes = self.code().to_le_bytes();
-        String::from_utf8_lossy(&tag_name_bytes).to_string()
+        code_name(self.code())
     }
 
     /// The total size of this argument, not including the code and the length.
@@ -37,6 +58,11 @@ pub trait XousArgument: fmt::Display {
 
     /// Any last data that needs to be written.
     fn last_data(&self) -> &[u8] { &[] }
+
+    /// Called once the entire image has been assembled, with the offset of
+    /// this tag's header.  Tags whose contents depend on the rest of the
+    /// image, such as signatures, fill themselves in here.
+    fn seal(&self, _image: &mut [u8], _offset: usize) {}
 }
 
 pub struct XousArguments {
@@ -92,12 +118,16 @@ impl XousArguments {
         self.arguments.push(Box::new(arg));
     }
 
-    pub fn write<T>(&mut self, mut w: T) -> Result<()>
+    pub fn write<T>(&mut self, mut output: T) -> Result<()>
     where
         T: Write,
     {
         let total_length = self.len();
 
+        // Assemble the image in memory so that tags may be sealed
+        // once everything else is in place.
+        let mut w = Cursor::new(Vec::new());
+
         // Finalize the arguments.  This lets any tags update their offsets
         // based on the size of the entire array.
         self.finalize();
@@ -124,7 +154,9 @@ impl XousArguments {
         w.write_all(tag_data.get_ref())?;
 
         // Write out each subsequent argument
+        let mut tag_offsets = vec![];
         for arg in &self.arguments {
+            tag_offsets.push(w.get_ref().len());
             let mut tag_data = Cursor::new(Vec::new());
             let advertised_len = arg.length() as u32;
             let actual_len = arg.serialize(&mut tag_data)? as u32;
@@ -156,7 +188,12 @@ impl XousArguments {
             w.write_all(arg.last_data()).expect("couldn't write extra arg data");
         }
 
-        Ok(())
+        let mut image = w.into_inner();
+        for (arg, offset) in self.arguments.iter().zip(tag_offsets) {
+            arg.seal(&mut image, offset);
+        }
+
+        output.write_all(&image)
     }
 
     pub fn len(&self) -> u32 {
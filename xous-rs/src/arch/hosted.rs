@@ -1,10 +1,8 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::convert::TryInto;
-use std::io::{Read, Write};
-use std::mem::size_of;
-use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream, ToSocketAddrs};
-use std::sync::{Arc, Mutex};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Condvar, Mutex};
 use std::thread_local;
 
 use crate::{Result, PID, TID};
@@ -12,12 +10,20 @@ use crate::{Result, PID, TID};
 mod mem;
 pub use mem::*;
 
+pub mod protocol;
+pub mod transport;
+pub use transport::{Transport, XousAddress};
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct ProcessKey([u8; 16]);
 impl ProcessKey {
     pub fn new(key: [u8; 16]) -> ProcessKey {
         ProcessKey(key)
     }
+
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -80,12 +86,12 @@ where
     let thread_main = std::thread::Builder::new()
         .name(args.name)
         .spawn(move || {
-            set_xous_address(server_address);
+            set_xous_address(server_address.clone());
             THREAD_ID.with(|tid| *tid.borrow_mut() = 1);
             PROCESS_ID.with(|p| *p.borrow_mut() = pid);
             XOUS_SERVER_CONNECTION.with(|xsc| {
                 let mut xsc = xsc.borrow_mut();
-                match xous_connect_impl(server_address, &init.key) {
+                match xous_connect_impl(&server_address, &init.key) {
                     Ok(a) => {
                         *xsc = Some(a);
                         Ok(())
@@ -194,9 +200,56 @@ pub struct WaitHandle<T>(std::thread::JoinHandle<T>);
 
 #[derive(Clone)]
 struct ServerConnection {
-    send: Arc<Mutex<TcpStream>>,
-    recv: Arc<Mutex<TcpStream>>,
-    mailbox: Arc<Mutex<HashMap<TID, Result>>>,
+    send: Arc<Mutex<Box<dyn Transport>>>,
+    mailbox: Arc<Mailbox>,
+    /// The call each thread is waiting on, which determines whether the response
+    /// is followed by memory.
+    calls: Arc<Mutex<HashMap<TID, crate::SysCall>>>,
+    /// Closes the connection once the last thread using it has gone away.
+    _closer: Arc<ConnectionCloser>,
+}
+
+/// Responses that have been read from the kernel but not yet collected by the
+/// thread that made the call.
+#[derive(Default)]
+struct Mailbox {
+    /// Responses indexed by thread, and whether the connection has closed.
+    state: Mutex<(HashMap<TID, Result>, bool)>,
+    delivered: Condvar,
+}
+
+impl Mailbox {
+    fn deliver(&self, thread_id: TID, response: Result) {
+        self.state.lock().unwrap().0.insert(thread_id, response);
+        self.delivered.notify_all();
+    }
+
+    fn close(&self) {
+        self.state.lock().unwrap().1 = true;
+        self.delivered.notify_all();
+    }
+
+    /// Block until a response for `thread_id` arrives. Returns `None` if the
+    /// connection closes first.
+    fn wait(&self, thread_id: TID) -> Option<Result> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(response) = state.0.remove(&thread_id) {
+                return Some(response);
+            }
+            if state.1 {
+                return None;
+            }
+            state = self.delivered.wait(state).unwrap();
+        }
+    }
+}
+
+struct ConnectionCloser(Mutex<Box<dyn Transport>>);
+impl Drop for ConnectionCloser {
+    fn drop(&mut self) {
+        self.0.lock().unwrap().close().ok();
+    }
 }
 
 pub fn thread_to_args(call: usize, _init: &ThreadInit) -> [usize; 8] {
@@ -249,22 +302,18 @@ pub fn args_to_process(
     })
 }
 
-thread_local!(static NETWORK_CONNECT_ADDRESS: RefCell<Option<SocketAddr>> = RefCell::new(None));
+thread_local!(static NETWORK_CONNECT_ADDRESS: RefCell<Option<XousAddress>> = RefCell::new(None));
 thread_local!(static XOUS_SERVER_CONNECTION: RefCell<Option<ServerConnection>> = RefCell::new(None));
 thread_local!(static THREAD_ID: RefCell<TID> = RefCell::new(1));
 thread_local!(static PROCESS_ID: RefCell<PID> = RefCell::new(PID::new(1).unwrap()));
 thread_local!(static PROCESS_KEY: RefCell<Option<ProcessKey>> = RefCell::new(None));
-thread_local!(static CALL_FOR_THREAD: RefCell<Arc<Mutex<HashMap<TID, crate::SysCall>>>> = RefCell::new(Arc::new(Mutex::new(HashMap::new()))));
 
-fn default_xous_address() -> SocketAddr {
+fn default_xous_address() -> XousAddress {
     std::env::var("XOUS_SERVER")
-        .map(|s| {
-            s.to_socket_addrs()
-                .expect("invalid server address")
-                .next()
-                .expect("unable to resolve server address")
+        .map(|s| s.parse().expect("invalid server address"))
+        .unwrap_or_else(|_| {
+            XousAddress::Tcp(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 0))
         })
-        .unwrap_or_else(|_| SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 0))
 }
 
 fn default_process_key() -> ProcessKey {
@@ -282,18 +331,18 @@ pub fn set_process_key(new_key: &[u8; 16]) {
 }
 
 /// Set the network address for this particular thread.
-pub fn set_xous_address(new_address: SocketAddr) {
+pub fn set_xous_address<A: Into<XousAddress>>(new_address: A) {
     NETWORK_CONNECT_ADDRESS.with(|nca| {
         let mut address = nca.borrow_mut();
-        *address = Some(new_address);
+        *address = Some(new_address.into());
         XOUS_SERVER_CONNECTION.with(|xsc| *xsc.borrow_mut() = None);
     });
 }
 
 /// Get the network address for this particular thread.
-fn xous_address() -> SocketAddr {
+fn xous_address() -> XousAddress {
     NETWORK_CONNECT_ADDRESS
-        .with(|nca| *nca.borrow())
+        .with(|nca| nca.borrow().clone())
         .unwrap_or_else(default_xous_address)
 }
 
@@ -342,14 +391,12 @@ where
     let server_connection =
         XOUS_SERVER_CONNECTION.with(|xsc| xsc.borrow().as_ref().unwrap().clone());
     let process_id = PROCESS_ID.with(|pid| *pid.borrow());
-    let call_for_thread = CALL_FOR_THREAD.with(|cft| cft.borrow().clone());
     Ok(std::thread::Builder::new()
         .spawn(move || {
             set_xous_address(server_address);
             THREAD_ID.with(|tid| *tid.borrow_mut() = thread_id);
             PROCESS_ID.with(|pid| *pid.borrow_mut() = process_id);
             XOUS_SERVER_CONNECTION.with(|xsc| *xsc.borrow_mut() = Some(server_connection));
-            CALL_FOR_THREAD.with(|cft| *cft.borrow_mut() = call_for_thread);
             f()
         })
         .map(WaitHandle)
@@ -369,11 +416,11 @@ pub fn ensure_connection() -> core::result::Result<(), crate::Error> {
         let mut xsc = xsc.borrow_mut();
         if xsc.is_none() {
             NETWORK_CONNECT_ADDRESS.with(|nca| {
-                let addr = nca.borrow().unwrap_or_else(default_xous_address);
+                let addr = nca.borrow().clone().unwrap_or_else(default_xous_address);
                 let pid1_key = PROCESS_KEY
                     .with(|pk| *pk.borrow())
                     .unwrap_or_else(default_process_key);
-                match xous_connect_impl(addr, &pid1_key) {
+                match xous_connect_impl(&addr, &pid1_key) {
                     Ok(a) => {
                         *xsc = Some(a);
                         Ok(())
@@ -388,20 +435,34 @@ pub fn ensure_connection() -> core::result::Result<(), crate::Error> {
 }
 
 fn xous_connect_impl(
-    addr: SocketAddr,
+    addr: &XousAddress,
     key: &ProcessKey,
 ) -> core::result::Result<ServerConnection, ()> {
     // eprintln!("Opening connection to Xous server @ {} with key {:?}...", addr, key);
     assert_ne!(&key.0, &[0u8; 16]);
-    match TcpStream::connect(addr) {
+    match transport::connect(addr) {
         Ok(mut conn) => {
-            conn.write_all(&key.0).unwrap(); // Send key to authenticate us as PID 1
-            conn.flush().unwrap();
-            Ok(ServerConnection {
-                send: Arc::new(Mutex::new(conn.try_clone().unwrap())),
-                recv: Arc::new(Mutex::new(conn)),
-                mailbox: Arc::new(Mutex::new(HashMap::new())),
-            })
+            // Prove that we hold the key, which identifies the process we'll become.
+            protocol::client_handshake(&mut *conn, &key.0)
+                .map_err(|e| eprintln!("Unable to connect to Xous server: {}", e))?;
+            let server_connection = ServerConnection {
+                send: Arc::new(Mutex::new(conn.duplicate().unwrap())),
+                mailbox: Arc::new(Mailbox::default()),
+                calls: Arc::new(Mutex::new(HashMap::new())),
+                _closer: Arc::new(ConnectionCloser(Mutex::new(conn.duplicate().unwrap()))),
+            };
+            let mailbox = server_connection.mailbox.clone();
+            let calls = server_connection.calls.clone();
+            std::thread::Builder::new()
+                .name("xous server connection reader".to_owned())
+                .spawn(move || {
+                    if let Err(_e) = read_responses(conn, &mailbox, &calls) {
+                        // eprintln!("Server shut down: {}", _e);
+                    }
+                    mailbox.close();
+                })
+                .map_err(|_| ())?;
+            Ok(server_connection)
         }
         Err(_e) => {
             // eprintln!("Unable to connect to Xous server: {}", _e);
@@ -428,139 +489,78 @@ pub fn _xous_syscall(
 ) {
     XOUS_SERVER_CONNECTION.with(|xsc| {
         THREAD_ID.with(|tid| {
-            let call = crate::SysCall::from_args(nr, a1, a2, a3, a4, a5, a6, a7).unwrap();
+            let tid = *tid.borrow();
+            let xsc_borrowed = xsc.borrow();
+            let server_connection = xsc_borrowed.as_ref().expect("not connected to server (did you forget to create a thread with xous::create_thread()?)");
             {
-                CALL_FOR_THREAD.with(|cft| {
-                    let cft_rc = cft.borrow();
-                    let mut cft_mtx = cft_rc.lock().unwrap();
-                    let tid = *tid.borrow();
-                    assert!(cft_mtx.get(&tid).is_none());
-                    cft_mtx.insert(tid, call)
-                });
+                let call = crate::SysCall::from_args(nr, a1, a2, a3, a4, a5, a6, a7).unwrap();
+                let mut calls = server_connection.calls.lock().unwrap();
+                assert!(calls.get(&tid).is_none());
+                calls.insert(tid, call);
             }
             let call = crate::SysCall::from_args(nr, a1, a2, a3, a4, a5, a6, a7).unwrap();
 
-            let mut xsc_borrowed = xsc.borrow_mut();
-            let xsc_asmut = xsc_borrowed.as_mut().expect("not connected to server (did you forget to create a thread with xous::create_thread()?)");
-            loop {
-                _xous_syscall_to(
-                    nr,
-                    a1,
-                    a2,
-                    a3,
-                    a4,
-                    a5,
-                    a6,
-                    a7,
-                    &call,
-                    &mut xsc_asmut.send.lock().unwrap(),
-                );
-                _xous_syscall_result(ret, *tid.borrow(), xsc_asmut);
-                if *ret != Result::WouldBlock {
-                    return;
+            _xous_syscall_to(
+                nr,
+                a1,
+                a2,
+                a3,
+                a4,
+                a5,
+                a6,
+                a7,
+                &call,
+                &mut **server_connection.send.lock().unwrap(),
+            );
+
+            // The reader thread will wake us up as soon as the response arrives.
+            match server_connection.mailbox.wait(tid) {
+                Some(response) => *ret = response,
+                None => {
+                    eprintln!("Server shut down");
+                    std::process::exit(0);
                 }
-                std::thread::sleep(std::time::Duration::from_millis(50));
             }
         })
     });
 }
 
-fn _xous_syscall_result(ret: &mut Result, thread_id: TID, server_connection: &ServerConnection) {
-    // Check to see if this thread id has an entry in the mailbox already.
-    // This will block until the hashmap is free.
-    {
-        let mut mailbox = server_connection.mailbox.lock().unwrap();
-        if let Some(entry) = mailbox.get(&thread_id) {
-            if &Result::BlockedProcess != entry {
-                *ret = mailbox.remove(&thread_id).unwrap();
-                return;
-            }
-        }
-    }
-
-    // Receive the packet back
+/// Read responses from the kernel and deliver them to the mailbox of the thread
+/// they are destined for. This runs on its own thread until the connection closes.
+fn read_responses(
+    mut stream: Box<dyn Transport>,
+    mailbox: &Mailbox,
+    calls: &Mutex<HashMap<TID, crate::SysCall>>,
+) -> std::io::Result<()> {
     loop {
-        // Now that we have the Stream mutex, temporarily take the Mailbox mutex to see if
-        // this thread ID is there. If it is, there's no need to read via the network.
-        // Note that the mailbox mutex is released if it isn't found.
-        {
-            let mut mailbox = server_connection.mailbox.lock().unwrap();
-            if let Some(entry) = mailbox.get(&thread_id) {
-                if &Result::BlockedProcess != entry {
-                    *ret = mailbox.remove(&thread_id).unwrap();
-                    return;
-                }
-            }
-        }
-
-        let mut stream = match server_connection.recv.try_lock() {
-            Ok(lk) => lk,
-            Err(std::sync::TryLockError::WouldBlock) => {
-                std::thread::sleep(std::time::Duration::from_millis(10));
-                continue;
-            }
-            Err(e) => panic!("Receive error: {}", e),
-        };
-
-        // One more check, in case something came in while we waited for the receiver above.
-        {
-            let mut mailbox = server_connection.mailbox.lock().unwrap();
-            if let Some(entry) = mailbox.get(&thread_id) {
-                if &Result::BlockedProcess != entry {
-                    *ret = mailbox.remove(&thread_id).unwrap();
-                    return;
-                }
-            }
-        }
-
-        // This thread_id doesn't exist in the mailbox, so read additional data.
-        let mut pkt = [0usize; 8];
-        let mut raw_bytes = [0u8; size_of::<usize>() * 9];
-        if let Err(e) = stream.read_exact(&mut raw_bytes) {
-            eprintln!("Server shut down: {}", e);
-            std::process::exit(0);
-        }
-
-        let mut raw_bytes_chunks = raw_bytes.chunks(size_of::<usize>());
-
-        // Read the Thread ID, which comes across first, followed by the 8 words of
-        // the message data.
-        let msg_thread_id =
-            usize::from_le_bytes(raw_bytes_chunks.next().unwrap().try_into().unwrap());
-        for (pkt_word, word) in pkt.iter_mut().zip(raw_bytes_chunks) {
-            *pkt_word = usize::from_le_bytes(word.try_into().unwrap());
-        }
-
-        let mut response = Result::from_args(pkt);
-
-        // If we got a `WouldBlock`, then we need to retry the whole call
-        // again. Return and retry.
-        if response == Result::WouldBlock {
-            // If the incoming message was for this thread, return it directly.
-            if msg_thread_id == thread_id {
-                *ret = response;
-                return;
-            }
-
-            // Otherwise, add it to the mailbox and try again.
-            let mut mailbox = server_connection.mailbox.lock().unwrap();
-            mailbox.insert(msg_thread_id, response);
-            continue;
-        }
+        let packet = protocol::read_packet(&mut *stream)?;
+        let msg_thread_id = packet.thread_id;
+        let mut response = Result::from_args(packet.args);
 
         if response == Result::BlockedProcess {
-            // println!("   Waiting again");
             continue;
         }
 
         // Determine if this thread will have a memory packet following it.
-        let call = CALL_FOR_THREAD.with(|cft| {
-            let cft_borrowed = cft.borrow();
-            let mut cft_mtx = cft_borrowed.lock().unwrap();
-            cft_mtx
-                .remove(&msg_thread_id)
-                .expect("thread didn't declare whether it has data")
-        });
+        let call = calls
+            .lock()
+            .unwrap()
+            .remove(&msg_thread_id)
+            .expect("thread didn't declare whether it has data");
+
+        // Any memory in the response is taken from the packet's data, in order.
+        let mut data = packet.data.as_slice();
+        let mut take_data = |len: usize| -> std::io::Result<&[u8]> {
+            if len > data.len() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "response is missing memory",
+                ));
+            }
+            let (head, tail) = data.split_at(len);
+            data = tail;
+            Ok(head)
+        };
 
         // If the client is passing us memory, remap the array to our own space.
         if let Result::Message(msg) = &mut response {
@@ -568,12 +568,8 @@ fn _xous_syscall_result(ret: &mut Result, thread_id: TID, server_connection: &Se
                 crate::Message::Move(ref mut memory_message)
                 | crate::Message::Borrow(ref mut memory_message)
                 | crate::Message::MutableBorrow(ref mut memory_message) => {
-                    let data = vec![0u8; memory_message.buf.len()];
+                    let data = take_data(memory_message.buf.len())?.to_vec();
                     let mut data = std::mem::ManuallyDrop::new(data);
-                    if let Err(e) = stream.read_exact(&mut data) {
-                        eprintln!("Server shut down: {}", e);
-                        std::process::exit(0);
-                    }
                     let len = data.len();
                     let addr = data.as_mut_ptr();
                     memory_message.buf.addr = crate::MemoryAddress::new(addr as _).unwrap();
@@ -588,24 +584,14 @@ fn _xous_syscall_result(ret: &mut Result, thread_id: TID, server_connection: &Se
             if call.is_borrow() || call.is_mutableborrow() {
                 // Read the buffer back from the remote host.
                 use core::slice;
-                let mut data = unsafe { slice::from_raw_parts_mut(mem.as_mut_ptr(), mem.len()) };
-
-                // If it's a Borrow, verify the contents haven't changed.
-                let previous_data = if call.is_borrow() {
-                    Some(data.to_vec())
-                } else {
-                    None
-                };
-
-                if let Err(e) = stream.read_exact(&mut data) {
-                    eprintln!("Server shut down: {}", e);
-                    std::process::exit(0);
-                }
+                let data = unsafe { slice::from_raw_parts_mut(mem.as_mut_ptr(), mem.len()) };
+                let returned = take_data(mem.len())?;
 
                 // If it is an immutable borrow, verify the contents haven't changed somehow
-                if let Some(previous_data) = previous_data {
-                    assert_eq!(data, previous_data.as_slice());
+                if call.is_borrow() {
+                    assert_eq!(data, returned);
                 }
+                data.copy_from_slice(returned);
             }
 
             if call.is_move() {
@@ -624,20 +610,14 @@ fn _xous_syscall_result(ret: &mut Result, thread_id: TID, server_connection: &Se
             }
         }
 
-        // Now that we have the Stream mutex, temporarily take the Mailbox mutex to see if
-        // this thread ID is there. If it is, there's no need to read via the network.
-        // Note that the mailbox mutex is released if it isn't found.
-        {
-            // If the incoming message was for this thread, return it directly.
-            if msg_thread_id == thread_id {
-                *ret = response;
-                return;
-            }
-
-            // Otherwise, add it to the mailbox and try again.
-            let mut mailbox = server_connection.mailbox.lock().unwrap();
-            mailbox.insert(msg_thread_id, response);
+        if !data.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "response has unexpected trailing memory",
+            ));
         }
+
+        mailbox.deliver(msg_thread_id, response);
     }
 }
 
@@ -653,27 +633,22 @@ fn _xous_syscall_to(
     a6: usize,
     a7: usize,
     call: &crate::SysCall,
-    xsc: &mut TcpStream,
+    xsc: &mut dyn Transport,
 ) {
     // println!(
     //     "Making Syscall: {:?}",
     //     crate::SysCall::from_args(nr, a1, a2, a3, a4, a5, a6, a7).unwrap()
     // );
 
-    // Send the packet to the server
-    let mut pkt = vec![];
-    THREAD_ID.with(|tid| pkt.extend_from_slice(&tid.borrow().to_le_bytes()));
-    for word in &[nr, a1, a2, a3, a4, a5, a6, a7] {
-        pkt.extend_from_slice(&word.to_le_bytes());
-    }
-
     // Also send memory, if it's present.
-    if let Some(memory) = call.memory() {
-        use core::slice;
-        let data: &[u8] =
-            unsafe { slice::from_raw_parts(memory.as_ptr(), memory.len()) };
-        pkt.extend_from_slice(data);
-    }
+    let data: &[u8] = match call.memory() {
+        Some(memory) => unsafe { core::slice::from_raw_parts(memory.as_ptr(), memory.len()) },
+        None => &[],
+    };
+
+    // Send the packet to the server
+    let thread_id = THREAD_ID.with(|tid| *tid.borrow());
+    let pkt = protocol::encode_packet(thread_id, &[nr, a1, a2, a3, a4, a5, a6, a7], data);
 
     if let Err(e) = xsc.write_all(&pkt) {
         eprintln!("Server shut down: {}", e);
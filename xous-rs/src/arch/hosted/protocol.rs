@@ -0,0 +1,310 @@
+//! The wire protocol spoken between hosted processes and the kernel.
+//!
+//! A connection begins with a handshake, which also authenticates both sides
+//! using the process key. The key itself never crosses the wire, so processes
+//! may safely connect from other machines. The client sends a `Hello`:
+//!
+//! | Offset | Size | Contents                          |
+//! | ------ | ---- | --------------------------------- |
+//! | 0      | 4    | `MAGIC`                           |
+//! | 4      | 4    | Protocol version (LE)             |
+//! | 8      | 4    | Size of `usize` in bytes (LE)     |
+//! | 12     | 16   | Client nonce                      |
+//!
+//! The kernel answers with `MAGIC`, its own protocol version, a status word,
+//! and a 16-byte kernel nonce. If the status is anything other than `STATUS_OK`,
+//! the kernel closes the connection.
+//!
+//! The client then sends a 32-byte proof that it knows its process key, computed
+//! by `client_proof()`. The kernel finds the process whose key matches, and
+//! answers with a status word followed by its own 32-byte proof from
+//! `kernel_proof()`, which lets the client verify that it is talking to a kernel
+//! that issued its key. The stream is not encrypted.
+//!
+//! After the handshake, both sides exchange packets. Each packet is a 4-byte
+//! little-endian length followed by that many bytes of body. The body is a thread
+//! ID and eight argument words, followed by any memory that accompanies the call
+//! or its response.
+
+use std::convert::TryInto;
+use std::io::{self, Read, Write};
+use std::mem::size_of;
+
+use sha3::{Digest, Sha3_256};
+
+/// Bytes that open every handshake.
+pub const MAGIC: [u8; 4] = *b"XOUS";
+
+/// Increment this whenever the wire format changes.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// The handshake was accepted.
+pub const STATUS_OK: u32 = 0;
+/// The client speaks a different protocol version.
+pub const STATUS_VERSION_MISMATCH: u32 = 1;
+/// The client uses a different word size.
+pub const STATUS_WORD_SIZE_MISMATCH: u32 = 2;
+/// The client did not prove that it holds the key of any waiting process.
+pub const STATUS_ACCESS_DENIED: u32 = 3;
+
+/// Packets larger than this are assumed to be the result of a corrupt stream.
+pub const MAX_PACKET_SIZE: usize = 64 * 1024 * 1024;
+
+const HEADER_WORDS: usize = 9;
+
+/// A random value that makes each handshake unique.
+pub type Nonce = [u8; 16];
+
+/// Proof that one side of the connection knows a process key.
+pub type Proof = [u8; 32];
+
+/// The first thing a client sends after connecting.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hello {
+    pub version: u32,
+    pub word_size: u32,
+    pub nonce: Nonce,
+}
+
+impl Hello {
+    /// A `Hello` from this build, with a fresh nonce.
+    pub fn new() -> io::Result<Hello> {
+        Ok(Hello {
+            version: PROTOCOL_VERSION,
+            word_size: size_of::<usize>() as u32,
+            nonce: generate_nonce()?,
+        })
+    }
+
+    /// Determine whether a client that sent this `Hello` can talk to us.
+    pub fn status(&self) -> u32 {
+        if self.version != PROTOCOL_VERSION {
+            STATUS_VERSION_MISMATCH
+        } else if self.word_size != size_of::<usize>() as u32 {
+            STATUS_WORD_SIZE_MISMATCH
+        } else {
+            STATUS_OK
+        }
+    }
+}
+
+/// A single syscall or syscall response.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Packet {
+    pub thread_id: usize,
+    pub args: [usize; 8],
+    pub data: Vec<u8>,
+}
+
+/// Generate a nonce from the operating system's random number generator.
+pub fn generate_nonce() -> io::Result<Nonce> {
+    let mut nonce = [0u8; 16];
+    getrandom::getrandom(&mut nonce).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    Ok(nonce)
+}
+
+fn proof(role: &[u8], key: &[u8; 16], client_nonce: &Nonce, kernel_nonce: &Nonce) -> Proof {
+    let mut hasher = Sha3_256::new();
+    hasher.input(role);
+    hasher.input(key);
+    hasher.input(client_nonce);
+    hasher.input(kernel_nonce);
+    let mut out = [0u8; 32];
+    out.copy_from_slice(&hasher.result());
+    out
+}
+
+/// The proof a client sends to show that it holds `key`.
+pub fn client_proof(key: &[u8; 16], client_nonce: &Nonce, kernel_nonce: &Nonce) -> Proof {
+    proof(b"xous client", key, client_nonce, kernel_nonce)
+}
+
+/// The proof the kernel sends to show that it issued `key`.
+pub fn kernel_proof(key: &[u8; 16], client_nonce: &Nonce, kernel_nonce: &Nonce) -> Proof {
+    proof(b"xous kernel", key, client_nonce, kernel_nonce)
+}
+
+/// Compare two proofs without leaking how many bytes matched.
+pub fn proofs_match(a: &Proof, b: &Proof) -> bool {
+    a.iter()
+        .zip(b.iter())
+        .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+        == 0
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn read_u32<R: Read + ?Sized>(r: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    r.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_magic<R: Read + ?Sized>(r: &mut R) -> io::Result<()> {
+    let mut magic = [0u8; 4];
+    r.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(invalid_data("peer is not speaking the Xous protocol"));
+    }
+    Ok(())
+}
+
+pub fn write_hello<W: Write + ?Sized>(w: &mut W, hello: &Hello) -> io::Result<()> {
+    let mut buf = Vec::with_capacity(28);
+    buf.extend_from_slice(&MAGIC);
+    buf.extend_from_slice(&hello.version.to_le_bytes());
+    buf.extend_from_slice(&hello.word_size.to_le_bytes());
+    buf.extend_from_slice(&hello.nonce);
+    w.write_all(&buf)?;
+    w.flush()
+}
+
+pub fn read_hello<R: Read + ?Sized>(r: &mut R) -> io::Result<Hello> {
+    read_magic(r)?;
+    let version = read_u32(r)?;
+    let word_size = read_u32(r)?;
+    let mut nonce = [0u8; 16];
+    r.read_exact(&mut nonce)?;
+    Ok(Hello {
+        version,
+        word_size,
+        nonce,
+    })
+}
+
+pub fn write_hello_response<W: Write + ?Sized>(
+    w: &mut W,
+    status: u32,
+    nonce: &Nonce,
+) -> io::Result<()> {
+    let mut buf = Vec::with_capacity(28);
+    buf.extend_from_slice(&MAGIC);
+    buf.extend_from_slice(&PROTOCOL_VERSION.to_le_bytes());
+    buf.extend_from_slice(&status.to_le_bytes());
+    buf.extend_from_slice(nonce);
+    w.write_all(&buf)?;
+    w.flush()
+}
+
+/// Read the kernel's answer to a `Hello`, returning the kernel's nonce or an
+/// error if the kernel refused the connection.
+pub fn read_hello_response<R: Read + ?Sized>(r: &mut R) -> io::Result<Nonce> {
+    read_magic(r)?;
+    let version = read_u32(r)?;
+    let status = read_u32(r)?;
+    let mut nonce = [0u8; 16];
+    r.read_exact(&mut nonce)?;
+    check_status(status, version).map(|_| nonce)
+}
+
+pub fn write_proof<W: Write + ?Sized>(w: &mut W, proof: &Proof) -> io::Result<()> {
+    w.write_all(proof)?;
+    w.flush()
+}
+
+pub fn read_proof<R: Read + ?Sized>(r: &mut R) -> io::Result<Proof> {
+    let mut proof = [0u8; 32];
+    r.read_exact(&mut proof)?;
+    Ok(proof)
+}
+
+/// Send the kernel's verdict on a client's proof, along with the kernel's own proof.
+pub fn write_auth_response<W: Write + ?Sized>(
+    w: &mut W,
+    status: u32,
+    proof: &Proof,
+) -> io::Result<()> {
+    let mut buf = Vec::with_capacity(36);
+    buf.extend_from_slice(&status.to_le_bytes());
+    buf.extend_from_slice(proof);
+    w.write_all(&buf)?;
+    w.flush()
+}
+
+/// Read the kernel's verdict on our proof, returning the kernel's proof.
+pub fn read_auth_response<R: Read + ?Sized>(r: &mut R) -> io::Result<Proof> {
+    let status = read_u32(r)?;
+    let proof = read_proof(r)?;
+    check_status(status, PROTOCOL_VERSION).map(|_| proof)
+}
+
+/// Perform the client side of the handshake, authenticating with `key`.
+pub fn client_handshake<T: Read + Write + ?Sized>(conn: &mut T, key: &[u8; 16]) -> io::Result<()> {
+    let hello = Hello::new()?;
+    write_hello(conn, &hello)?;
+    let kernel_nonce = read_hello_response(conn)?;
+    write_proof(conn, &client_proof(key, &hello.nonce, &kernel_nonce))?;
+    let proof = read_auth_response(conn)?;
+    if !proofs_match(&proof, &kernel_proof(key, &hello.nonce, &kernel_nonce)) {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "kernel could not prove that it issued our process key",
+        ));
+    }
+    Ok(())
+}
+
+fn check_status(status: u32, version: u32) -> io::Result<()> {
+    match status {
+        STATUS_OK => Ok(()),
+        STATUS_VERSION_MISMATCH => Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!(
+                "kernel speaks protocol version {}, but this process speaks version {}",
+                version, PROTOCOL_VERSION
+            ),
+        )),
+        STATUS_WORD_SIZE_MISMATCH => Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            "kernel was built with a different word size",
+        )),
+        STATUS_ACCESS_DENIED => Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "kernel did not recognize our process key",
+        )),
+        status => Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!("kernel refused the connection (status {})", status),
+        )),
+    }
+}
+
+/// Serialize a packet, including its length prefix.
+pub fn encode_packet(thread_id: usize, args: &[usize; 8], data: &[u8]) -> Vec<u8> {
+    let body_len = HEADER_WORDS * size_of::<usize>() + data.len();
+    let mut buf = Vec::with_capacity(4 + body_len);
+    buf.extend_from_slice(&(body_len as u32).to_le_bytes());
+    buf.extend_from_slice(&thread_id.to_le_bytes());
+    for word in args {
+        buf.extend_from_slice(&word.to_le_bytes());
+    }
+    buf.extend_from_slice(data);
+    buf
+}
+
+pub fn read_packet<R: Read + ?Sized>(r: &mut R) -> io::Result<Packet> {
+    let body_len = read_u32(r)? as usize;
+    if body_len < HEADER_WORDS * size_of::<usize>() || body_len > MAX_PACKET_SIZE {
+        return Err(invalid_data("packet length is out of range"));
+    }
+    let mut body = vec![0u8; body_len];
+    r.read_exact(&mut body)?;
+
+    let mut words = body
+        .chunks_exact(size_of::<usize>())
+        .take(HEADER_WORDS)
+        .map(|bytes| usize::from_le_bytes(bytes.try_into().unwrap()));
+    let thread_id = words.next().unwrap();
+    let mut args = [0usize; 8];
+    for (arg, word) in args.iter_mut().zip(words) {
+        *arg = word;
+    }
+    let data = body.split_off(HEADER_WORDS * size_of::<usize>());
+    Ok(Packet {
+        thread_id,
+        args,
+        data,
+    })
+}
@@ -0,0 +1,423 @@
+//! Transports that carry syscalls between a hosted process and the kernel.
+//!
+//! The kernel listens on an address, and each process opens a connection to it.
+//! Three kinds of address are supported:
+//!
+//! * `127.0.0.1:1234` -- a TCP socket, which is the default
+//! * `unix:/path/to/socket` -- a Unix domain socket (Unix hosts only)
+//! * `local:name` -- an in-process channel, which is only reachable from threads
+//!   within the same executable. This lets an entire system run inside a single
+//!   test binary.
+//!
+//! Binding to `127.0.0.1:0` or `local:` picks a free port or name. The kernel
+//! then reports the address it actually bound to.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long a nonblocking socket listener sleeps between checks for a new client.
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A bidirectional byte stream between a process and the kernel.
+pub trait Transport: Read + Write + Send {
+    /// Create a new handle to the same underlying stream, analogous to
+    /// `TcpStream::try_clone()`.
+    fn duplicate(&self) -> io::Result<Box<dyn Transport>>;
+
+    /// Shut down both halves of the stream. Any thread blocked reading from
+    /// either end will see end-of-file.
+    fn close(&self) -> io::Result<()>;
+
+    /// Make reads fail with `TimedOut` or `WouldBlock` if nothing arrives
+    /// within `timeout`. `None` lets reads block forever, which is the default.
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+}
+
+impl fmt::Debug for dyn Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Transport")
+    }
+}
+
+/// Accepts new connections from processes.
+pub trait Listener: Send {
+    /// Wait up to `timeout` for a new connection. Returns `Ok(None)` if no
+    /// client connected in that time.
+    fn accept_timeout(&self, timeout: Duration) -> io::Result<Option<Box<dyn Transport>>>;
+
+    /// The address that clients should connect to in order to reach this listener.
+    fn local_address(&self) -> io::Result<XousAddress>;
+}
+
+/// The address of a Xous kernel.
+#[derive(Clone, Debug, PartialEq)]
+pub enum XousAddress {
+    Tcp(SocketAddr),
+    #[cfg(unix)]
+    Unix(std::path::PathBuf),
+    Local(String),
+}
+
+impl fmt::Display for XousAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            XousAddress::Tcp(addr) => write!(f, "{}", addr),
+            #[cfg(unix)]
+            XousAddress::Unix(path) => write!(f, "unix:{}", path.display()),
+            XousAddress::Local(name) => write!(f, "local:{}", name),
+        }
+    }
+}
+
+impl FromStr for XousAddress {
+    type Err = io::Error;
+    fn from_str(s: &str) -> io::Result<XousAddress> {
+        if let Some(name) = s.strip_prefix("local:") {
+            return Ok(XousAddress::Local(name.to_owned()));
+        }
+        if let Some(path) = s.strip_prefix("unix:") {
+            #[cfg(unix)]
+            return Ok(XousAddress::Unix(path.into()));
+            #[cfg(not(unix))]
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unix sockets are not supported on this platform: {}", path),
+            ));
+        }
+        s.to_socket_addrs()?
+            .next()
+            .map(XousAddress::Tcp)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address found"))
+    }
+}
+
+impl From<SocketAddr> for XousAddress {
+    fn from(addr: SocketAddr) -> XousAddress {
+        XousAddress::Tcp(addr)
+    }
+}
+
+/// Open a connection to the kernel listening at `addr`.
+pub fn connect(addr: &XousAddress) -> io::Result<Box<dyn Transport>> {
+    match addr {
+        XousAddress::Tcp(addr) => {
+            let conn = TcpStream::connect(addr)?;
+            conn.set_nodelay(true)?;
+            Ok(Box::new(conn))
+        }
+        #[cfg(unix)]
+        XousAddress::Unix(path) => Ok(Box::new(std::os::unix::net::UnixStream::connect(path)?)),
+        XousAddress::Local(name) => {
+            let listeners = LOCAL_LISTENERS.lock().unwrap();
+            let acceptor = listeners.get(name).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::ConnectionRefused, "no such local listener")
+            })?;
+            let (client, server) = LocalStream::pair();
+            acceptor
+                .send(server)
+                .map_err(|_| io::Error::new(io::ErrorKind::ConnectionRefused, "listener closed"))?;
+            Ok(Box::new(client))
+        }
+    }
+}
+
+/// Start listening for processes on `addr`.
+pub fn bind(addr: &XousAddress) -> io::Result<Box<dyn Listener>> {
+    match addr {
+        XousAddress::Tcp(addr) => {
+            let listener = TcpListener::bind(addr)?;
+            // `accept()` has no way to break, so put the listener in nonblocking mode.
+            listener.set_nonblocking(true)?;
+            Ok(Box::new(listener))
+        }
+        #[cfg(unix)]
+        XousAddress::Unix(path) => {
+            let listener = std::os::unix::net::UnixListener::bind(path)?;
+            listener.set_nonblocking(true)?;
+            Ok(Box::new(UnixTransportListener {
+                listener,
+                path: path.clone(),
+            }))
+        }
+        XousAddress::Local(name) => {
+            let mut listeners = LOCAL_LISTENERS.lock().unwrap();
+            let name = if name.is_empty() {
+                format!(
+                    "xous-{}",
+                    NEXT_LOCAL_LISTENER.fetch_add(1, Ordering::Relaxed)
+                )
+            } else {
+                name.clone()
+            };
+            if listeners.contains_key(&name) {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    "local listener already exists",
+                ));
+            }
+            let (sender, receiver) = channel();
+            listeners.insert(name.clone(), sender);
+            Ok(Box::new(LocalListener { name, receiver }))
+        }
+    }
+}
+
+/// Repeatedly call `accept` on a nonblocking listener until it yields a connection
+/// or `timeout` elapses.
+fn poll_accept<T, F>(timeout: Duration, mut accept: F) -> io::Result<Option<T>>
+where
+    F: FnMut() -> io::Result<T>,
+{
+    let deadline = Instant::now() + timeout;
+    loop {
+        match accept() {
+            Ok(conn) => return Ok(Some(conn)),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return Ok(None);
+                }
+                std::thread::sleep(ACCEPT_POLL_INTERVAL.min(deadline - now));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+impl Transport for TcpStream {
+    fn duplicate(&self) -> io::Result<Box<dyn Transport>> {
+        Ok(Box::new(self.try_clone()?))
+    }
+
+    fn close(&self) -> io::Result<()> {
+        self.shutdown(std::net::Shutdown::Both)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+}
+
+impl Listener for TcpListener {
+    fn accept_timeout(&self, timeout: Duration) -> io::Result<Option<Box<dyn Transport>>> {
+        poll_accept(timeout, || self.accept()).and_then(|conn| match conn {
+            Some((conn, _addr)) => {
+                conn.set_nonblocking(false)?;
+                conn.set_nodelay(true)?;
+                Ok(Some(Box::new(conn) as Box<dyn Transport>))
+            }
+            None => Ok(None),
+        })
+    }
+
+    fn local_address(&self) -> io::Result<XousAddress> {
+        self.local_addr().map(XousAddress::Tcp)
+    }
+}
+
+#[cfg(unix)]
+impl Transport for std::os::unix::net::UnixStream {
+    fn duplicate(&self) -> io::Result<Box<dyn Transport>> {
+        Ok(Box::new(self.try_clone()?))
+    }
+
+    fn close(&self) -> io::Result<()> {
+        self.shutdown(std::net::Shutdown::Both)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        std::os::unix::net::UnixStream::set_read_timeout(self, timeout)
+    }
+}
+
+/// A Unix socket listener that removes its socket file when it is dropped.
+#[cfg(unix)]
+struct UnixTransportListener {
+    listener: std::os::unix::net::UnixListener,
+    path: std::path::PathBuf,
+}
+
+#[cfg(unix)]
+impl Listener for UnixTransportListener {
+    fn accept_timeout(&self, timeout: Duration) -> io::Result<Option<Box<dyn Transport>>> {
+        poll_accept(timeout, || self.listener.accept()).and_then(|conn| match conn {
+            Some((conn, _addr)) => {
+                conn.set_nonblocking(false)?;
+                Ok(Some(Box::new(conn) as Box<dyn Transport>))
+            }
+            None => Ok(None),
+        })
+    }
+
+    fn local_address(&self) -> io::Result<XousAddress> {
+        Ok(XousAddress::Unix(self.path.clone()))
+    }
+}
+
+#[cfg(unix)]
+impl Drop for UnixTransportListener {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.path).ok();
+    }
+}
+
+lazy_static::lazy_static! {
+    /// All in-process listeners, indexed by name.
+    static ref LOCAL_LISTENERS: Mutex<HashMap<String, Sender<LocalStream>>> =
+        Mutex::new(HashMap::new());
+}
+static NEXT_LOCAL_LISTENER: AtomicUsize = AtomicUsize::new(1);
+
+/// A listener for in-process connections.
+struct LocalListener {
+    name: String,
+    receiver: Receiver<LocalStream>,
+}
+
+impl Listener for LocalListener {
+    fn accept_timeout(&self, timeout: Duration) -> io::Result<Option<Box<dyn Transport>>> {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(conn) => Ok(Some(Box::new(conn))),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(io::ErrorKind::NotConnected.into()),
+        }
+    }
+
+    fn local_address(&self) -> io::Result<XousAddress> {
+        Ok(XousAddress::Local(self.name.clone()))
+    }
+}
+
+impl Drop for LocalListener {
+    fn drop(&mut self) {
+        LOCAL_LISTENERS.lock().unwrap().remove(&self.name);
+    }
+}
+
+/// One direction of an in-process stream.
+#[derive(Default)]
+struct Pipe {
+    /// Bytes that have been written but not yet read, and whether the pipe is closed.
+    state: Mutex<(VecDeque<u8>, bool)>,
+    readable: Condvar,
+}
+
+impl Pipe {
+    fn close(&self) {
+        self.state.lock().unwrap().1 = true;
+        self.readable.notify_all();
+    }
+}
+
+/// Both directions of an in-process stream, as seen from one end. Both pipes
+/// are closed when the last handle to this end is dropped, which mirrors
+/// what happens when a socket is closed.
+struct LocalEndpoint {
+    incoming: Arc<Pipe>,
+    outgoing: Arc<Pipe>,
+    read_timeout: Mutex<Option<Duration>>,
+}
+
+impl Drop for LocalEndpoint {
+    fn drop(&mut self) {
+        self.incoming.close();
+        self.outgoing.close();
+    }
+}
+
+/// An in-process stream, which behaves like a connected socket.
+pub struct LocalStream(Arc<LocalEndpoint>);
+
+impl LocalStream {
+    /// Create a connected pair of streams.
+    pub fn pair() -> (LocalStream, LocalStream) {
+        let a_to_b = Arc::new(Pipe::default());
+        let b_to_a = Arc::new(Pipe::default());
+        (
+            LocalStream(Arc::new(LocalEndpoint {
+                incoming: b_to_a.clone(),
+                outgoing: a_to_b.clone(),
+                read_timeout: Mutex::new(None),
+            })),
+            LocalStream(Arc::new(LocalEndpoint {
+                incoming: a_to_b,
+                outgoing: b_to_a,
+                read_timeout: Mutex::new(None),
+            })),
+        )
+    }
+}
+
+impl Read for LocalStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let deadline = self
+            .0
+            .read_timeout
+            .lock()
+            .unwrap()
+            .map(|timeout| Instant::now() + timeout);
+        let pipe = &self.0.incoming;
+        let mut state = pipe.state.lock().unwrap();
+        while state.0.is_empty() && !state.1 {
+            state = match deadline {
+                None => pipe.readable.wait(state).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(io::ErrorKind::TimedOut.into());
+                    }
+                    pipe.readable.wait_timeout(state, deadline - now).unwrap().0
+                }
+            };
+        }
+        let count = buf.len().min(state.0.len());
+        for (dest, src) in buf.iter_mut().zip(state.0.drain(..count)) {
+            *dest = src;
+        }
+        Ok(count)
+    }
+}
+
+impl Write for LocalStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let pipe = &self.0.outgoing;
+        let mut state = pipe.state.lock().unwrap();
+        if state.1 {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+        state.0.extend(buf);
+        pipe.readable.notify_all();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for LocalStream {
+    fn duplicate(&self) -> io::Result<Box<dyn Transport>> {
+        Ok(Box::new(LocalStream(self.0.clone())))
+    }
+
+    fn close(&self) -> io::Result<()> {
+        self.0.incoming.close();
+        self.0.outgoing.close();
+        Ok(())
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        *self.0.read_timeout.lock().unwrap() = timeout;
+        Ok(())
+    }
+}
@@ -1,5 +1,4 @@
 use crate::{MemoryAddress, MemoryRange, PID, TID};
-use core::convert::TryInto;
 
 mod mem;
 pub use mem::*;
@@ -52,9 +51,27 @@ impl ProcessKey {
     }
 }
 
+/// Describes a new process.  The program is passed as page-aligned regions
+/// of the caller's memory, which are moved into the new process and
+/// removed from the caller.
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct ProcessInit {
-    pub key: ProcessKey,
+    /// Program text and read-only data.  These pages are mapped into the
+    /// new process as read-only and executable.
+    pub text: MemoryRange,
+
+    /// Where `text` is mapped in the new process
+    pub text_destination: MemoryAddress,
+
+    /// Program data and bss, if any.  These pages are mapped into the new
+    /// process as read-write.
+    pub data: Option<MemoryRange>,
+
+    /// Where `data` is mapped in the new process
+    pub data_destination: Option<MemoryAddress>,
+
+    /// Virtual address of the first instruction to run
+    pub start: MemoryAddress,
 }
 
 pub struct WaitHandle<T>(core::marker::PhantomData<T>);
@@ -144,26 +161,49 @@ pub fn wait_thread<T>(_joiner: WaitHandle<T>) -> crate::SysCallResult {
 pub fn process_to_args(call: usize, init: &ProcessInit) -> [usize; 8] {
     [
         call,
-        u32::from_le_bytes(init.key.0[0..4].try_into().unwrap()) as _,
-        u32::from_le_bytes(init.key.0[4..8].try_into().unwrap()) as _,
-        u32::from_le_bytes(init.key.0[8..12].try_into().unwrap()) as _,
-        u32::from_le_bytes(init.key.0[12..16].try_into().unwrap()) as _,
-        0,
-        0,
-        0,
+        init.text.as_ptr() as usize,
+        init.text.len(),
+        init.text_destination.get(),
+        init.data.map(|x| x.as_ptr() as usize).unwrap_or_default(),
+        init.data.map(|x| x.len()).unwrap_or_default(),
+        init.data_destination.map(|x| x.get()).unwrap_or_default(),
+        init.start.get(),
     ]
 }
 
+/// This code is executed inside the kernel. It takes the list of args
+/// that were passed via registers and converts them into a `ProcessInit`
+/// struct describing the program to load.
 pub fn args_to_process(
-    _a1: usize,
-    _a2: usize,
-    _a3: usize,
-    _a4: usize,
-    _a5: usize,
-    _a6: usize,
-    _a7: usize,
+    a1: usize,
+    a2: usize,
+    a3: usize,
+    a4: usize,
+    a5: usize,
+    a6: usize,
+    a7: usize,
 ) -> core::result::Result<ProcessInit, crate::Error> {
-    todo!()
+    if a1 == 0 || a2 == 0 {
+        return Err(crate::Error::InvalidSyscall);
+    }
+    let text = MemoryRange::new(a1, a2)?;
+    let text_destination = MemoryAddress::new(a3).ok_or(crate::Error::BadAddress)?;
+    let (data, data_destination) = if a4 != 0 && a5 != 0 {
+        (
+            Some(MemoryRange::new(a4, a5)?),
+            Some(MemoryAddress::new(a6).ok_or(crate::Error::BadAddress)?),
+        )
+    } else {
+        (None, None)
+    };
+    let start = MemoryAddress::new(a7).ok_or(crate::Error::BadAddress)?;
+    Ok(ProcessInit {
+        text,
+        text_destination,
+        data,
+        data_destination,
+        start,
+    })
 }
 
 pub fn create_thread_simple_pre<T, U>(
@@ -293,6 +293,34 @@ pub enum SysCall {
     /// disclosing SIDs.
     ConnectForProcess(PID, SID),
 
+    /// Map the structured log the loader wrote while booting into this
+    /// process, read-only.  The pages remain owned by the kernel.
+    ///
+    /// # Returns
+    ///
+    /// The MemoryRange the log was mapped at.
+    ///
+    /// # Errors
+    ///
+    /// * **ShareViolation**: Only the process named `bootlog` may map the log
+    /// * **BadAddress**: The loader didn't provide a boot log
+    MapBootLog,
+
+    /// Map the page holding the `confirmed` word of the boot slot the loader
+    /// started into this process, read-write.  Writing `BtOK` to the word
+    /// stops the loader counting boots of this image as failed attempts.
+    ///
+    /// # Returns
+    ///
+    /// The MemoryRange of the `confirmed` word, within the mapped page.
+    ///
+    /// # Errors
+    ///
+    /// * **ShareViolation**: Only the process named `bootlog` may map the word
+    /// * **BadAddress**: The image didn't come from a boot slot, or the slot
+    ///   record isn't in a region the kernel knows about
+    MapBootSlot,
+
     /// This syscall does not exist. It captures all possible
     /// arguments so detailed analysis can be performed.
     Invalid(usize, usize, usize, usize, usize, usize, usize),
@@ -329,6 +357,8 @@ pub enum SysCallNumber {
     TryReceiveMessage = 28,
     CreateServer = 29,
     ConnectForProcess = 30,
+    MapBootLog = 31,
+    MapBootSlot = 32,
     Invalid,
 }
 
@@ -365,6 +395,8 @@ impl SysCallNumber {
             28 => TryReceiveMessage,
             29 => CreateServer,
             30 => ConnectForProcess,
+            31 => MapBootLog,
+            32 => MapBootSlot,
             _ => Invalid,
         }
     }
@@ -603,6 +635,8 @@ impl SysCall {
                 0,
             ],
             SysCall::Shutdown => [SysCallNumber::Shutdown as usize, 0, 0, 0, 0, 0, 0, 0],
+            SysCall::MapBootLog => [SysCallNumber::MapBootLog as usize, 0, 0, 0, 0, 0, 0, 0],
+            SysCall::MapBootSlot => [SysCallNumber::MapBootSlot as usize, 0, 0, 0, 0, 0, 0, 0],
             SysCall::TryConnect(sid) => {
                 let s = sid.to_u32();
                 [
@@ -793,6 +827,8 @@ impl SysCall {
             }
             SysCallNumber::TerminateProcess => SysCall::TerminateProcess,
             SysCallNumber::Shutdown => SysCall::Shutdown,
+            SysCallNumber::MapBootLog => SysCall::MapBootLog,
+            SysCallNumber::MapBootSlot => SysCall::MapBootSlot,
             SysCallNumber::TryConnect => {
                 SysCall::TryConnect(SID::from_u32(a1 as _, a2 as _, a3 as _, a4 as _))
             }
@@ -1203,6 +1239,41 @@ pub fn connect_for_process(pid: PID, sid: SID) -> core::result::Result<Result, E
     }
 }
 
+/// Map the loader's boot log into this process, read-only.
+///
+/// # Errors
+///
+/// * **ShareViolation**: This process isn't the `bootlog` service
+/// * **BadAddress**: The loader didn't provide a boot log
+pub fn map_boot_log() -> core::result::Result<MemoryRange, Error> {
+    let result = rsyscall(SysCall::MapBootLog)?;
+    if let Result::MemoryRange(range) = result {
+        Ok(range)
+    } else if let Result::Error(e) = result {
+        Err(e)
+    } else {
+        Err(Error::InternalError)
+    }
+}
+
+/// Map the `confirmed` word of the boot slot the loader started into this
+/// process, read-write.
+///
+/// # Errors
+///
+/// * **ShareViolation**: This process isn't the `bootlog` service
+/// * **BadAddress**: The image didn't come from a boot slot
+pub fn map_boot_slot() -> core::result::Result<MemoryRange, Error> {
+    let result = rsyscall(SysCall::MapBootSlot)?;
+    if let Result::MemoryRange(range) = result {
+        Ok(range)
+    } else if let Result::Error(e) = result {
+        Err(e)
+    } else {
+        Err(Error::InternalError)
+    }
+}
+
 /// Send a message to a server.  Depending on the mesage type (move or borrow), it
 /// will either block (borrow) or return immediately (move).
 /// If the message type is `borrow`, then the memory addresses pointed to will be
@@ -1318,6 +1389,20 @@ pub fn create_process(
     })
 }
 
+/// Create a new process from an already-prepared `ProcessInit`, returning
+/// its PID.  On hardware, the memory described by `init` is moved into the
+/// new process, or left where it was if this fails.  See the `elf-loader`
+/// crate for a way to build this from an ELF file.
+pub fn create_process_raw(init: ProcessInit) -> core::result::Result<PID, Error> {
+    rsyscall(SysCall::CreateProcess(init)).and_then(|result| {
+        if let Result::ProcessID(pid) = result {
+            Ok(pid)
+        } else {
+            Err(Error::InternalError)
+        }
+    })
+}
+
 /// Wait for a thread to finish
 pub fn wait_process(joiner: crate::arch::ProcessHandle) -> SysCallResult {
     crate::arch::wait_process(joiner)
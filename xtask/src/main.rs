@@ -2,13 +2,31 @@ use std::{
     env,
     io::{Read, Write},
     path::{Path, PathBuf, MAIN_SEPARATOR},
-    process::Command,
+    process::{Command, Stdio},
+    time::{Duration, Instant},
 };
 
 type DynError = Box<dyn std::error::Error>;
 
 const TARGET: &str = "riscv32imac-unknown-none-elf";
 
+/// Where `xtask qemu` loads the image arguments.  This must match
+/// `_xous_args` in loader/memory-qemu-virt.x.
+const QEMU_ARGS_ADDR: u32 = 0x8080_0000;
+
+/// How long `xtask test-system` and `xtask qemu-test` wait for the system to shut itself down
+const SYSTEM_TEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Prefix of the lines that services/system-test reports its scenarios with
+const SYSTEM_TEST_MARKER: &str = "INFO - SYSTEM-TEST: ";
+
+/// The graphics server writes its frame buffer here instead of opening a window
+const FRAMEBUFFER_DUMP_VAR: &str = "XOUS_FRAMEBUFFER_DUMP";
+
+/// Size of the frame buffer in 32-bit words, which is 11 per line
+const FRAMEBUFFER_WORDS_PER_LINE: usize = 11;
+const FRAMEBUFFER_LINES: usize = 536;
+
 enum MemorySpec {
     SvdFile(String),
 }
@@ -41,8 +59,15 @@ fn try_main() -> Result<(), DynError> {
         Some("renode-image") => renode_image(false)?,
         Some("renode-image-debug") => renode_image(true)?,
         Some("run") => run(false)?,
-        Some("hw-image") => build_hw_image(false, env::args().nth(2))?,
+        Some("hw-image") => {
+            build_hw_image(false, env::args().nth(2))?;
+        }
+        Some("size-report") => size_report(env::args().nth(2), env::args().nth(3))?,
         Some("debug") => run(true)?,
+        Some("qemu") => run_qemu(false)?,
+        Some("qemu-debug") => run_qemu(true)?,
+        Some("qemu-test") => test_qemu()?,
+        Some("test-system") => test_system()?,
         _ => print_help(),
     }
     Ok(())
@@ -54,13 +79,20 @@ fn print_help() {
 renode-image            builds a test image for renode
 renode-image-debug      builds a test image for renode in debug mode
 hw-image [soc.svd]      builds an image for real hardware
+size-report [soc.svd] [baseline.toml]
+                        builds an image for real hardware and reports its size,
+                        failing if it exceeds any budget in the baseline
 run                     runs a release build using a hosted environment
 debug                   runs a debug build using a hosted environment
+qemu                    builds an image for the QEMU virt machine and boots it headless
+qemu-debug              builds a debug image for the QEMU virt machine and boots it headless
+qemu-test               boots services/system-test on the QEMU virt machine and checks the results
+test-system             runs scripted scenarios in a hosted environment and checks the results
 "
     )
 }
 
-fn build_hw_image(debug: bool, svd: Option<String>) -> Result<(), DynError> {
+fn build_hw_image(debug: bool, svd: Option<String>) -> Result<PathBuf, DynError> {
     let svd_file = match svd {
         Some(s) => s,
         None => return Err("svd file not specified".into()),
@@ -76,7 +108,14 @@ fn build_hw_image(debug: bool, svd: Option<String>) -> Result<(), DynError> {
 
     let kernel = build_kernel(debug)?;
     let mut init = vec![];
-    for pkg in &["shell", "graphics-server", "ticktimer-server", "log-server", "com"] {
+    for pkg in &[
+        "shell",
+        "graphics-server",
+        "ticktimer-server",
+        "log-server",
+        "com",
+        "bootlog",
+    ] {
         init.push(build(pkg, debug, Some(TARGET), None)?);
     }
     let loader = build("loader", debug, Some(TARGET), Some("loader".into()))?;
@@ -137,6 +176,43 @@ fn build_hw_image(debug: bool, svd: Option<String>) -> Result<(), DynError> {
     println!();
     println!("Bundled image file created at {}", xous_img_path.display());
 
+    Ok(xous_img_path)
+}
+
+fn size_report(svd: Option<String>, baseline: Option<String>) -> Result<(), DynError> {
+    // Resolve the baseline now, since the report runs from the project root.
+    let baseline = match baseline {
+        Some(b) => Some(Path::new(&b).canonicalize()?),
+        None => None,
+    };
+    let image = build_hw_image(false, svd)?;
+    let manifest = image.with_file_name("image.toml");
+
+    let mut args = vec![
+        "run",
+        "--package",
+        "tools",
+        "--bin",
+        "size-report",
+        "--",
+        "--manifest",
+        manifest.to_str().ok_or(BuildError::PathConversionError)?,
+        "--image",
+        image.to_str().ok_or(BuildError::PathConversionError)?,
+    ];
+    if let Some(baseline) = &baseline {
+        args.push("--baseline");
+        args.push(baseline.to_str().ok_or(BuildError::PathConversionError)?);
+    }
+
+    println!();
+    let status = Command::new(cargo())
+        .current_dir(project_root())
+        .args(&args)
+        .status()?;
+    if !status.success() {
+        return Err("size report failed".into());
+    }
     Ok(())
 }
 
@@ -145,7 +221,14 @@ fn renode_image(debug: bool) -> Result<(), DynError> {
     std::env::set_var("XOUS_SVD_FILE", path.canonicalize().unwrap());
     let kernel = build_kernel(debug)?;
     let mut init = vec![];
-    for pkg in &["shell", "log-server", "graphics-server", "ticktimer-server", "com"] {
+    for pkg in &[
+        "shell",
+        "log-server",
+        "graphics-server",
+        "ticktimer-server",
+        "com",
+        "bootlog",
+    ] {
         init.push(build(pkg, debug, Some(TARGET), None)?);
     }
     build("loader", debug, Some(TARGET), Some("loader".into()))?;
@@ -160,9 +243,157 @@ fn renode_image(debug: bool) -> Result<(), DynError> {
     Ok(())
 }
 
+/// Build an image for the QEMU `virt` machine, and return the command that
+/// boots it.  A `test` image runs services/system-test, which shuts the
+/// machine down once it's done.
+fn qemu_command(debug: bool, test: bool) -> Result<Command, DynError> {
+    let svd_file = "emulation/qemu-virt.svd";
+    std::env::set_var(
+        "XOUS_SVD_FILE",
+        project_root().join(svd_file).canonicalize()?,
+    );
+
+    // Only the services that don't need LiteX peripherals run on QEMU.
+    let features = ["qemu-virt"];
+    let kernel = build_with_features(
+        "kernel",
+        debug,
+        Some(TARGET),
+        Some("kernel".into()),
+        &features,
+    )?;
+    let mut init = vec![
+        build_with_features("log-server", debug, Some(TARGET), None, &features)?,
+        build("bootlog", debug, Some(TARGET), None)?,
+    ];
+    if test {
+        init.push(build_with_features(
+            "system-test",
+            debug,
+            Some(TARGET),
+            None,
+            &features,
+        )?);
+    }
+    let loader = build_with_features(
+        "loader",
+        debug,
+        Some(TARGET),
+        Some("loader".into()),
+        &features,
+    )?;
+
+    let args_bin = create_image(&kernel, &init, debug, MemorySpec::SvdFile(svd_file.into()))?;
+
+    // QEMU starts the loader in Machine mode at the bottom of RAM, and
+    // the loader finds its arguments at a fixed address.
+    let qemu = env::var("QEMU").unwrap_or_else(|_| "qemu-system-riscv32".to_string());
+    let device = format!(
+        "loader,file={},addr=0x{:08x}",
+        args_bin.to_str().ok_or(BuildError::PathConversionError)?,
+        QEMU_ARGS_ADDR
+    );
+    let mut command = Command::new(qemu);
+    command.args(&[
+        "-machine",
+        "virt",
+        "-m",
+        "16M",
+        "-nographic",
+        "-bios",
+        "none",
+        "-kernel",
+        loader.to_str().ok_or(BuildError::PathConversionError)?,
+        "-device",
+        &device,
+    ]);
+    Ok(command)
+}
+
+fn run_qemu(debug: bool) -> Result<(), DynError> {
+    let mut qemu = qemu_command(debug, false)?;
+    println!("Starting QEMU.  Press Ctrl-A X to exit.");
+    let status = qemu.status()?;
+    if !status.success() {
+        return Err("qemu exited with an error".into());
+    }
+
+    Ok(())
+}
+
+/// Boot the QEMU image with services/system-test and check what it
+/// reported.  The kernel stops QEMU with a status of 0 when the test shuts
+/// the system down, so anything else is a failure.
+fn test_qemu() -> Result<(), DynError> {
+    let mut qemu = qemu_command(false, true)?;
+
+    let log_path = project_root().join(format!("target/{}/release/qemu-test.log", TARGET));
+    println!(
+        "Running system test in QEMU, logging to {}",
+        log_path.display()
+    );
+    let mut system = qemu
+        .stdin(Stdio::null())
+        .stdout(std::fs::File::create(&log_path)?)
+        .spawn()?;
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = system.try_wait()? {
+            break status;
+        }
+        if started.elapsed() > SYSTEM_TEST_TIMEOUT {
+            system.kill()?;
+            return Err(format!(
+                "system did not shut down within {} seconds, see {}",
+                SYSTEM_TEST_TIMEOUT.as_secs(),
+                log_path.display()
+            )
+            .into());
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    };
+    if !status.success() {
+        return Err(format!("qemu exited with {}, see {}", status, log_path.display()).into());
+    }
+
+    let log = std::fs::read_to_string(&log_path)?;
+    let mut failures = vec![];
+    find_reports(
+        &log,
+        &[
+            "starting",
+            "log line 1 of 3",
+            "log line 2 of 3",
+            "log line 3 of 3",
+            "done",
+        ],
+        &mut failures,
+    );
+    if !failures.is_empty() {
+        for failure in &failures {
+            eprintln!("FAIL: {}", failure);
+        }
+        return Err(format!(
+            "{} system test check(s) failed, see {}",
+            failures.len(),
+            log_path.display()
+        )
+        .into());
+    }
+    println!("System test passed");
+    Ok(())
+}
+
 fn run(debug: bool) -> Result<(), DynError> {
     let stream = if debug { "debug" } else { "release" };
-    let init = ["shell", "log-server", "graphics-server", "ticktimer-server", "com"];
+    let init = [
+        "shell",
+        "log-server",
+        "graphics-server",
+        "ticktimer-server",
+        "com",
+        "bootlog",
+    ];
 
     // let mut init_paths = vec![];
     for pkg in &init {
@@ -208,6 +439,214 @@ fn run(debug: bool) -> Result<(), DynError> {
     Ok(())
 }
 
+fn test_system() -> Result<(), DynError> {
+    // The system test program goes last, once everything it talks to is up.
+    let init = [
+        "shell",
+        "log-server",
+        "graphics-server",
+        "ticktimer-server",
+        "com",
+        "bootlog",
+        "system-test",
+    ];
+    let mut paths = vec![];
+    for pkg in &init {
+        paths.push(build(pkg, false, None, None)?);
+    }
+    let kernel = build("kernel", false, None, Some("kernel".into()))?;
+
+    // Remove the results of any earlier run so they can't be mistaken for
+    // this one.
+    let log_path = project_root().join("target/release/system-test.log");
+    let framebuffer_path = project_root().join("target/release/system-test.fb");
+    for path in &[&log_path, &framebuffer_path] {
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+    }
+
+    println!("Running system test, logging to {}", log_path.display());
+    let mut system = Command::new(&kernel)
+        .current_dir(project_root().join("kernel"))
+        .args(&paths)
+        .env(FRAMEBUFFER_DUMP_VAR, &framebuffer_path)
+        .stdout(std::fs::File::create(&log_path)?)
+        .spawn()?;
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = system.try_wait()? {
+            break status;
+        }
+        if started.elapsed() > SYSTEM_TEST_TIMEOUT {
+            system.kill()?;
+            return Err(format!(
+                "system did not shut down within {} seconds, see {}",
+                SYSTEM_TEST_TIMEOUT.as_secs(),
+                log_path.display()
+            )
+            .into());
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    };
+    if !status.success() {
+        return Err(format!("kernel exited with {}, see {}", status, log_path.display()).into());
+    }
+
+    // Services notice the kernel has gone the next time they make a call.
+    // Give them a moment to do so before looking at what they left behind.
+    std::thread::sleep(Duration::from_millis(500));
+
+    let log = std::fs::read_to_string(&log_path)?;
+    let framebuffer = if framebuffer_path.exists() {
+        std::fs::read(&framebuffer_path)?
+    } else {
+        vec![]
+    };
+    let failures = check_system_test(&log, &framebuffer);
+    if !failures.is_empty() {
+        for failure in &failures {
+            eprintln!("FAIL: {}", failure);
+        }
+        return Err(format!(
+            "{} system test check(s) failed, see {}",
+            failures.len(),
+            log_path.display()
+        )
+        .into());
+    }
+    println!("System test passed");
+    Ok(())
+}
+
+/// Find each of the `expected` reports from services/system-test in `log`,
+/// in order.  Returns the matching report for each one, and adds a failure
+/// for each report that's missing.
+fn find_reports<'a>(
+    log: &'a str,
+    expected: &[&str],
+    failures: &mut Vec<String>,
+) -> Vec<Option<&'a str>> {
+    let reports: Vec<&str> = log
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix(SYSTEM_TEST_MARKER))
+        .collect();
+
+    let mut next = 0;
+    let mut found = vec![];
+    for expect in expected {
+        match reports[next..]
+            .iter()
+            .position(|report| report.starts_with(expect))
+        {
+            Some(offset) => {
+                next += offset + 1;
+                found.push(Some(reports[next - 1]));
+            }
+            None => {
+                failures.push(format!("missing report \"{}\"", expect.trim_end()));
+                found.push(None);
+            }
+        }
+    }
+    found
+}
+
+/// Compare what services/system-test reported, and what it drew, against
+/// what it was asked to do.  Returns a description of each mismatch.
+fn check_system_test(log: &str, framebuffer: &[u8]) -> Vec<String> {
+    let mut failures = vec![];
+
+    // Reports must appear in this order.  The battery values are the ones
+    // the hosted COM server makes up.
+    let expected = [
+        "starting",
+        "log line 1 of 3",
+        "log line 2 of 3",
+        "log line 3 of 3",
+        "rectangle ",
+        "battery 3700 mV -150 mA 50% 750 mAh",
+        "done",
+    ];
+    let found = find_reports(log, &expected, &mut failures);
+
+    // The rectangle report says where to look in the frame buffer
+    if let Some(report) = found[4] {
+        let coords: Vec<usize> = report
+            .split_whitespace()
+            .skip(1)
+            .filter_map(|c| c.parse().ok())
+            .collect();
+        match coords.as_slice() {
+            &[x0, y0, x1, y1] => {
+                failures.extend(check_rectangle(framebuffer, (x0, y0, x1, y1)));
+            }
+            _ => failures.push(format!("unable to parse \"{}\"", report)),
+        }
+    }
+    failures
+}
+
+/// Check that the rectangle is filled in and that the two pixels around it
+/// are clear.
+fn check_rectangle(
+    framebuffer: &[u8],
+    (x0, y0, x1, y1): (usize, usize, usize, usize),
+) -> Vec<String> {
+    let words_per_frame = FRAMEBUFFER_WORDS_PER_LINE * FRAMEBUFFER_LINES;
+    if framebuffer.len() != words_per_frame * 4 {
+        return vec![format!(
+            "frame buffer dump is {} bytes, expected {}",
+            framebuffer.len(),
+            words_per_frame * 4
+        )];
+    }
+    if x0 < 2 || y0 < 2 || x1 + 2 >= FRAMEBUFFER_WORDS_PER_LINE * 32 || y1 + 2 >= FRAMEBUFFER_LINES
+    {
+        return vec![format!(
+            "rectangle {} {} {} {} is off the screen",
+            x0, y0, x1, y1
+        )];
+    }
+    let dark = |x: usize, y: usize| {
+        let word = (x + y * FRAMEBUFFER_WORDS_PER_LINE * 32) / 32;
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(&framebuffer[word * 4..word * 4 + 4]);
+        (u32::from_le_bytes(bytes) >> (x % 32)) & 1 != 0
+    };
+
+    let mut light_inside = 0;
+    for y in y0 + 1..y1 {
+        for x in x0 + 1..x1 {
+            if !dark(x, y) {
+                light_inside += 1;
+            }
+        }
+    }
+    let mut dark_outside = 0;
+    for x in x0 - 2..=x1 + 2 {
+        dark_outside += dark(x, y0 - 2) as usize + dark(x, y1 + 2) as usize;
+    }
+    for y in y0 - 1..=y1 + 1 {
+        dark_outside += dark(x0 - 2, y) as usize + dark(x1 + 2, y) as usize;
+    }
+
+    let mut failures = vec![];
+    if light_inside != 0 {
+        failures.push(format!(
+            "{} pixels inside the rectangle are not filled",
+            light_inside
+        ));
+    }
+    if dark_outside != 0 {
+        failures.push(format!(
+            "{} pixels around the rectangle are filled",
+            dark_outside
+        ));
+    }
+    failures
+}
+
 fn build_kernel(debug: bool) -> Result<PathBuf, DynError> {
     build("kernel", debug, Some(TARGET), Some("kernel".into()))
 }
@@ -217,10 +656,45 @@ fn build(
     debug: bool,
     target: Option<&str>,
     directory: Option<PathBuf>,
+) -> Result<PathBuf, DynError> {
+    build_with_features(project, debug, target, directory, &[])
+}
+
+/// The loader refuses to build for release without a public key, so accept
+/// images signed with the developer key when that's what `create_image()`
+/// is going to sign with.  Returns `None` if a key was already given.
+fn loader_public_key() -> Result<Option<PathBuf>, DynError> {
+    if env::var_os("XOUS_LOADER_PUBLIC_KEY").is_some() {
+        return Ok(None);
+    }
+    if env::var_os("XOUS_SIGN_KEY").is_some() {
+        return Err("XOUS_SIGN_KEY is set, so XOUS_LOADER_PUBLIC_KEY must be too".into());
+    }
+    println!("Loader will accept images signed with the developer key");
+    Ok(Some(project_root().join("keys/developer.pub")))
+}
+
+fn build_with_features(
+    project: &str,
+    debug: bool,
+    target: Option<&str>,
+    directory: Option<PathBuf>,
+    features: &[&str],
 ) -> Result<PathBuf, DynError> {
     println!("Building {}...", project);
     let stream = if debug { "debug" } else { "release" };
     let mut args = vec!["build", "--package", project];
+    // Name the package explicitly, since cargo only accepts bare feature
+    // names for the package in the current directory.
+    let features: Vec<String> = features
+        .iter()
+        .map(|f| format!("{}/{}", project, f))
+        .collect();
+    let features = features.join(",");
+    if !features.is_empty() {
+        args.push("--features");
+        args.push(&features);
+    }
     let mut target_path = "".to_owned();
     if let Some(t) = target {
         args.push("--target");
@@ -237,10 +711,13 @@ fn build(
         dir.push(subdir);
     }
 
-    let status = Command::new(cargo())
-        .current_dir(dir)
-        .args(&args)
-        .status()?;
+    let mut command = Command::new(cargo());
+    if project == "loader" {
+        if let Some(key) = loader_public_key()? {
+            command.env("XOUS_LOADER_PUBLIC_KEY", key);
+        }
+    }
+    let status = command.current_dir(dir).args(&args).status()?;
 
     if !status.success() {
         return Err("cargo build failed".into());
@@ -266,25 +743,44 @@ fn create_image(
     memory_spec: MemorySpec,
 ) -> Result<PathBuf, DynError> {
     let stream = if debug { "debug" } else { "release" };
-    let mut args = vec!["run", "--package", "tools", "--bin", "create-image", "--"];
 
-    let output_file = format!("target/{}/{}/args.bin", TARGET, stream);
-    args.push(&output_file);
+    // Describe the image in a manifest alongside it, so the exact contents
+    // can be inspected or rebuilt later.
+    let mut manifest = format!("kernel = {}\n", toml_path(kernel)?);
+    match memory_spec {
+        MemorySpec::SvdFile(ref s) => {
+            manifest.push_str(&format!("svd = {}\n", toml_path(&project_root().join(s))?));
+        }
+    }
 
-    args.push("--kernel");
-    args.push(kernel.to_str().ok_or(BuildError::PathConversionError)?);
+    // The loader refuses unsigned images.  Sign with the developer key
+    // unless another key was specified.
+    let sign_key =
+        env::var("XOUS_SIGN_KEY").unwrap_or_else(|_| "keys/developer.key".to_string());
+    manifest.push_str(&format!(
+        "sign_key = {}\n",
+        toml_path(&project_root().join(sign_key))?
+    ));
 
     for i in init {
-        args.push("--init");
-        args.push(i.to_str().ok_or(BuildError::PathConversionError)?);
+        manifest.push_str(&format!("\n[[init]]\npath = {}\n", toml_path(i)?));
     }
 
-    match memory_spec {
-        MemorySpec::SvdFile(ref s) => {
-            args.push("--svd");
-            args.push(s);
-        }
-    }
+    let manifest_file = format!("target/{}/{}/image.toml", TARGET, stream);
+    std::fs::write(project_root().join(&manifest_file), manifest)?;
+
+    let output_file = format!("target/{}/{}/args.bin", TARGET, stream);
+    let args = [
+        "run",
+        "--package",
+        "tools",
+        "--bin",
+        "create-image",
+        "--",
+        &output_file,
+        "--manifest",
+        &manifest_file,
+    ];
 
     let status = Command::new(cargo())
         .current_dir(project_root())
@@ -297,6 +793,15 @@ fn create_image(
     Ok(project_root().join(&format!("target/{}/{}/args.bin", TARGET, stream)))
 }
 
+/// Quote a path as a TOML literal string, which has no escape sequences.
+fn toml_path(path: &Path) -> Result<String, DynError> {
+    let path = path.to_str().ok_or(BuildError::PathConversionError)?;
+    if path.contains('\'') || path.contains('\n') {
+        return Err(format!("path {} cannot be stored in a manifest", path).into());
+    }
+    Ok(format!("'{}'", path))
+}
+
 fn cargo() -> String {
     env::var("CARGO").unwrap_or_else(|_| "cargo".to_string())
 }